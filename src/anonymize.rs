@@ -0,0 +1,202 @@
+/*
+Anonymized copy of the database for bug reports.
+Replaces every user-written string with a placeholder and keeps
+everything the planner looks at. Pure, independent from HTTP / Axum.
+
+- Titles become task-0001, task-0002, ...; titles that compare equal
+  for duplicate detection share one placeholder, and numbers follow
+  the sort_locale order of the originals, so score ties still break
+  the same way
+- Tags become tag-a, tag-b, ... (case-insensitive: "Work" and "work"
+  share one), notes become note-0001, ...
+- Ids, timestamps, durations, priorities, statuses and settings are
+  kept as they are
+- Webhook and push URLs and the push token are replaced; the delivery
+  log (which holds sent payloads) is dropped
+*/
+
+use std::collections::HashMap;
+
+use crate::collate::TitleOrder;
+use crate::logic;
+use crate::models::{Db, Task};
+
+// Stand-in for a configured URL.
+const PLACEHOLDER_URL: &str = "https://example.invalid/";
+
+// Assigns one placeholder per distinct key, in first-seen order.
+struct Placeholders {
+    names: HashMap<String, String>,
+    make: fn(usize) -> String,
+}
+
+impl Placeholders {
+    fn new(make: fn(usize) -> String) -> Self {
+        Self { names: HashMap::new(), make }
+    }
+
+    fn get(&mut self, key: String) -> String {
+        let n = self.names.len();
+        let make = self.make;
+        self.names.entry(key).or_insert_with(|| make(n)).clone()
+    }
+}
+
+// 0 -> "a", 25 -> "z", 26 -> "aa", ...
+fn letters(mut n: usize) -> String {
+    let mut out = Vec::new();
+    loop {
+        out.push(b'a' + (n % 26) as u8);
+        if n < 26 {
+            break;
+        }
+        n = n / 26 - 1;
+    }
+    out.reverse();
+    String::from_utf8(out).unwrap_or_default()
+}
+
+fn redact_url(url: &str) -> String {
+    if url.is_empty() { String::new() } else { PLACEHOLDER_URL.to_string() }
+}
+
+pub fn anonymize(db: &Db) -> Db {
+    let mut out = db.clone();
+
+    // Number titles in collation order of a representative original
+    let order = TitleOrder::new(&db.settings.sort_locale);
+    let mut keys: Vec<(String, &str)> = Vec::new();
+    for t in db.tasks.iter().chain(&db.archived).chain(db.trash.iter().map(|t| &t.task)) {
+        let key = logic::normalize_title(&t.title);
+        if !keys.iter().any(|(k, _)| *k == key) {
+            keys.push((key, &t.title));
+        }
+    }
+    keys.sort_by(|a, b| order.compare_titles(a.1, b.1));
+    let mut titles = Placeholders::new(|n| format!("task-{:04}", n + 1));
+    for (key, _) in keys {
+        titles.get(key);
+    }
+
+    let mut tags = Placeholders::new(|n| format!("tag-{}", letters(n)));
+    let mut notes = Placeholders::new(|n| format!("note-{:04}", n + 1));
+    // Unknown keys may hold anything, so none are shared
+    out.extra.clear();
+    out.settings.extra.clear();
+    let mut scrub = |t: &mut Task| {
+        t.extra.clear();
+        t.title = titles.get(logic::normalize_title(&t.title));
+        if let Some(list) = t.tags.as_mut() {
+            for tag in list.iter_mut() {
+                *tag = tags.get(tag.to_lowercase());
+            }
+        }
+        if let Some(note) = t.notes.as_mut().filter(|n| !n.is_empty()) {
+            *note = notes.get(note.clone());
+        }
+    };
+    out.tasks.iter_mut().for_each(&mut scrub);
+    out.archived.iter_mut().for_each(&mut scrub);
+    out.trash.iter_mut().for_each(|t| scrub(&mut t.task));
+
+    out.notifications.webhook_url = redact_url(&db.notifications.webhook_url);
+    out.notifications.push.url = redact_url(&db.notifications.push.url);
+    if !out.notifications.push.token.is_empty() {
+        out.notifications.push.token = "***".to_string();
+    }
+    out.deliveries.clear();
+
+    out.reindex();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock;
+    use crate::models::{TrashedTask, test_task};
+    use crate::store;
+
+    // Every user-written string in the fixture.
+    const SECRETS: &[&str] = &[
+        "Call Dr. Kim", "call dr. kim", "Pay rent", "Renew passport", "Old report", "Gone task",
+        "Clinic", "clinic", "Finance", "bring insurance card", "door code 4471",
+        "hooks.example.com", "push.example.com", "s3cret-token", "unknown-key-value",
+    ];
+
+    fn fixture() -> Db {
+        let mut db = Db::default();
+        let task = |title: &str, due: &str, min: i64, tags: &[&str], notes: Option<&str>| {
+            let mut t = test_task(title, clock::local(due));
+            t.duration_min = min;
+            t.tags = Some(tags.iter().map(|t| t.to_string()).collect()).filter(|t: &Vec<String>| !t.is_empty());
+            t.notes = notes.map(str::to_string);
+            t
+        };
+        db.tasks = vec![
+            task("Call Dr. Kim", "2024-06-03 11:00", 30, &["Clinic"], Some("bring insurance card")),
+            task("call dr. kim", "2024-06-03 16:00", 30, &["clinic", "Finance"], None),
+            task("Pay rent", "2024-06-02 17:00", 45, &["Finance"], Some("door code 4471")),
+            task("Renew passport", "2024-06-05 12:00", 120, &[], None),
+        ];
+        db.tasks[1].priority = 5;
+        db.tasks[0].extra.insert("secret".to_string(), "unknown-key-value".into());
+        db.archived = vec![task("Old report", "2024-05-01 09:00", 60, &["Finance"], None)];
+        let gone = task("Gone task", "2024-06-03 10:00", 15, &["Clinic"], None);
+        db.trash = vec![TrashedTask { task: gone, deleted_at: clock::local("2024-06-01 10:00") }];
+        db.notifications.webhook_url = "https://hooks.example.com/abc".to_string();
+        db.notifications.push.url = "https://push.example.com/topic".to_string();
+        db.notifications.push.token = "s3cret-token".to_string();
+        db.reindex();
+        db
+    }
+
+    #[test]
+    fn no_original_string_survives() {
+        let db = fixture();
+        let out = anonymize(&db);
+        let text = serde_json::to_string(&out).unwrap();
+        for secret in SECRETS {
+            assert!(!text.contains(secret), "{secret} in {text}");
+        }
+
+        // Same title (by duplicate rules) and same tag (any case) share a placeholder
+        let titles: Vec<&str> = out.tasks.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles[0], titles[1]);
+        assert_ne!(titles[0], titles[2]);
+        assert_eq!(out.tasks[0].tags.as_ref().unwrap()[0], out.tasks[1].tags.as_ref().unwrap()[0]);
+        assert_eq!(out.tasks[1].tags.as_ref().unwrap()[1], out.tasks[2].tags.as_ref().unwrap()[0]);
+        assert_eq!(out.archived[0].tags, out.tasks[2].tags);
+        assert_ne!(out.tasks[0].notes, out.tasks[2].notes);
+
+        // Everything else is kept
+        for (a, b) in db.tasks.iter().zip(&out.tasks) {
+            assert_eq!((a.id, a.due_at, a.duration_min, a.priority, &a.status), (b.id, b.due_at, b.duration_min, b.priority, &b.status));
+            assert_eq!(a.tags.as_ref().map(Vec::len), b.tags.as_ref().map(Vec::len));
+        }
+        assert_eq!(serde_json::to_value(&out.settings).unwrap(), serde_json::to_value(&db.settings).unwrap());
+        assert_eq!(out.trash[0].deleted_at, db.trash[0].deleted_at);
+    }
+
+    #[test]
+    fn anonymized_export_plans_the_same_shape() {
+        let db = fixture();
+        let text = serde_json::to_string_pretty(&anonymize(&db)).unwrap();
+        let mut loaded = store::decode(&text).map_err(|e| e.describe()).unwrap().db;
+        store::upgrade(&mut loaded);
+
+        let now = clock::local("2024-06-03 08:00");
+        let day = logic::plan_date(&db.settings, now);
+        let shape = |db: &Db| {
+            let relevant = logic::relevant_tasks(&db.tasks, &db.settings, day, now);
+            let scored = logic::score_and_sort(relevant, now, &db.settings, logic::scorer_for(db.settings.scorer));
+            let (plan, unplanned, _) = logic::build_today_plan(scored, day, now, &db.settings, 90, true);
+            let plan: Vec<_> = plan.iter().map(|p| (p.task_id.clone(), p.start, p.end)).collect();
+            let unplanned: Vec<_> = unplanned.iter().map(|u| (u.task_id.clone(), u.reason)).collect();
+            (plan, unplanned)
+        };
+        let (plan, unplanned) = shape(&db);
+        assert!(!plan.is_empty() && !unplanned.is_empty());
+        assert_eq!(shape(&loaded), (plan, unplanned));
+    }
+}
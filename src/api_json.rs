@@ -0,0 +1,191 @@
+// --------------------------------------------------
+// JSON request bodies for the API.
+//
+// `ApiJson<T>` replaces axum's `Json<T>` extractor on handlers
+// that take a request DTO:
+// - Malformed JSON and wrong content types are rejected the
+//   same way `Json` rejects them
+// - With server.strict_requests (the default), fields the DTO
+//   does not know are rejected with 422
+//   {"errors":[{"field": "<path>", "code": "unknown_field", "message"}]}
+//   instead of being silently dropped, so a misspelled
+//   "durration_min" is not saved as the default duration
+// - With it off, unknown fields are ignored as before
+//
+// serde's deny_unknown_fields cannot be used for this: it is
+// fixed at compile time and does not work with the flattened
+// `extra` maps on the models. Keys captured by a flattened map
+// are checked by the handler with `unknown_extra`.
+// --------------------------------------------------
+
+use axum::{
+    async_trait,
+    extract::{FromRequest, Request},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::config;
+use crate::i18n::{self, Lang};
+
+pub struct ApiJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ApiJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Response> {
+        let lang = Lang::from_headers(req.headers());
+        let Json(value) = Json::<Value>::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        let mut unknown = Vec::new();
+        let parsed: Result<T, _> = serde_ignored::deserialize(value, |path| unknown.push(field_path(&path)));
+
+        // Unknown fields are reported first: a misspelled required
+        // field also shows up as "missing field", which hides the typo
+        if config::get().server.strict_requests && !unknown.is_empty() {
+            return Err(unknown_fields(&unknown, lang));
+        }
+        match parsed {
+            Ok(parsed) => Ok(ApiJson(parsed)),
+            Err(e) => {
+                let msg = format!("Failed to deserialize the JSON body into the target type: {e}");
+                Err((StatusCode::UNPROCESSABLE_ENTITY, msg).into_response())
+            }
+        }
+    }
+}
+
+// Dotted path of an ignored field ("settings.day_strat",
+// "profiles.0.nmae"); Option and newtype wrappers add nothing.
+fn field_path(path: &serde_ignored::Path) -> String {
+    use serde_ignored::Path;
+    match path {
+        Path::Root => String::new(),
+        Path::Seq { parent, index } => join(field_path(parent), &index.to_string()),
+        Path::Map { parent, key } => join(field_path(parent), key),
+        Path::Some { parent } | Path::NewtypeStruct { parent } | Path::NewtypeVariant { parent } => {
+            field_path(parent)
+        }
+    }
+}
+
+fn join(parent: String, name: &str) -> String {
+    if parent.is_empty() {
+        name.to_string()
+    } else {
+        format!("{parent}.{name}")
+    }
+}
+
+// 422 listing every unknown field.
+fn unknown_fields(paths: &[String], lang: Lang) -> Response {
+    let errors: Vec<Value> = paths
+        .iter()
+        .map(|p| json!({ "field": p, "code": "unknown_field", "message": i18n::unknown_field(lang, p) }))
+        .collect();
+    (StatusCode::UNPROCESSABLE_ENTITY, Json(json!({ "errors": errors }))).into_response()
+}
+
+// Strict-mode check of keys a flattened `extra` map took in
+// (under `prefix`, "" at the top level). None when they are
+// allowed: no such keys, or strict_requests is off.
+pub fn unknown_extra<'a>(
+    prefix: &str,
+    keys: impl IntoIterator<Item = &'a String>,
+    lang: Lang,
+) -> Option<Response> {
+    if !config::get().server.strict_requests {
+        return None;
+    }
+    let paths: Vec<String> = keys.into_iter().map(|k| join(prefix.to_string(), k)).collect();
+    (!paths.is_empty()).then(|| unknown_fields(&paths, lang))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request as HttpRequest};
+    use serde::Deserialize;
+
+    async fn send(method: &str, uri: &str, body: Value) -> (StatusCode, Value) {
+        let req = HttpRequest::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let (status, _, body) = crate::test_request(req).await;
+        (status, body)
+    }
+
+    fn unknown(body: &Value) -> Vec<String> {
+        let errors = body["errors"].as_array().unwrap();
+        assert!(errors.iter().all(|e| e["code"] == "unknown_field"));
+        assert!(errors.iter().all(|e| e["message"].as_str().unwrap().contains(e["field"].as_str().unwrap())));
+        errors.iter().map(|e| e["field"].as_str().unwrap().to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn a_misspelled_task_field_is_named_in_the_422() {
+        let task = json!({ "title": "typo 490", "due_at": "2031-06-01T12:00:00-04:00", "durration_min": 90, "priority": 3 });
+        let (status, body) = send("POST", "/api/tasks", task).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(unknown(&body), ["durration_min"]);
+
+        let task = json!({ "title": "extra 490", "due_at": "2031-06-01T12:00:00-04:00", "duration_min": 90, "priority": 3, "colour": "red", "estimate": 2 });
+        let (status, body) = send("POST", "/api/tasks", task).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(unknown(&body), ["colour", "estimate"]);
+    }
+
+    #[tokio::test]
+    async fn unknown_settings_keys_are_refused_where_extra_would_take_them() {
+        let (_, mut settings) = send("GET", "/api/settings", Value::Null).await;
+        settings["day_strat"] = "08:00".into();
+        let (status, body) = send("PUT", "/api/settings", settings).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(unknown(&body), ["day_strat"]);
+
+        let compare = json!({
+            "date": "2031-06-01",
+            "available_min": 60,
+            "profiles": [{ "name": "a" }, { "name": "b", "settings": { "urgncy_mode": "calendar" } }]
+        });
+        let (status, body) = send("POST", "/api/plan/compare", compare).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(unknown(&body), ["profiles.1.settings.urgncy_mode"]);
+        let misnamed = json!({ "date": "2031-06-01", "available_min": 60, "profiles": [{ "name": "a" }, { "nmae": "b" }] });
+        let (status, body) = send("POST", "/api/plan/compare", misnamed).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(unknown(&body), ["profiles.1.nmae"]);
+    }
+
+    #[test]
+    fn ignored_fields_get_dotted_paths() {
+        #[derive(Deserialize)]
+        struct Inner {
+            name: String,
+        }
+        #[derive(Deserialize)]
+        struct Outer {
+            inner: Option<Inner>,
+            list: Vec<Inner>,
+        }
+        let value = json!({ "inner": { "name": "a", "nmae": "b" }, "list": [{ "name": "c" }, { "name": "d", "x": 1 }], "top": true });
+        let mut paths = Vec::new();
+        let outer: Outer = serde_ignored::deserialize(value, |p| paths.push(field_path(&p))).unwrap();
+        assert_eq!(paths, ["inner.nmae", "list.1.x", "top"]);
+        let names: Vec<String> = outer.inner.into_iter().chain(outer.list).map(|i| i.name).collect();
+        assert_eq!(names, ["a", "c", "d"]);
+    }
+}
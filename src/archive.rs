@@ -0,0 +1,136 @@
+// --------------------------------------------------
+// Auto-archive of finished tasks.
+//
+// When settings.auto_archive_days is set, tasks that have
+// been Done for longer than that many days are moved from
+// db.tasks to db.archived. "Done since" is completed_at,
+// or due_at for tasks finished before completed_at existed.
+//
+// The pass runs at startup and then once a day. Running it
+// again right away archives nothing, so it is safe to repeat.
+// --------------------------------------------------
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+
+use crate::clock;
+use crate::models::{Db, Task, TaskStatus};
+use crate::writer;
+
+// How often the pass runs after the one at startup.
+const ARCHIVE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+// Outcome of the most recent pass, reported by GET /api/health.
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveRun {
+    pub at: DateTime<FixedOffset>,
+    pub archived: usize,
+}
+
+static LAST_RUN: Mutex<Option<ArchiveRun>> = Mutex::new(None);
+
+pub fn last_run() -> Option<ArchiveRun> {
+    LAST_RUN.lock().ok()?.clone()
+}
+
+// Whether a task has been Done for more than `days` days at `now`.
+fn is_stale(task: &Task, days: i64, now: DateTime<FixedOffset>) -> bool {
+    if task.status != TaskStatus::Done {
+        return false;
+    }
+    let done_at = task.completed_at.unwrap_or(task.due_at);
+    let age = chrono::Duration::try_days(days).unwrap_or(chrono::Duration::MAX);
+    done_at.checked_add_signed(age).is_some_and(|limit| limit < now)
+}
+
+// Move stale Done tasks into db.archived. Returns how many moved.
+pub fn archive_done(db: &mut Db, days: i64, now: DateTime<FixedOffset>) -> usize {
+    let (stale, keep): (Vec<Task>, Vec<Task>) =
+        std::mem::take(&mut db.tasks).into_iter().partition(|t| is_stale(t, days, now));
+    db.tasks = keep;
+    db.reindex();
+    let count = stale.len();
+    db.archived.extend(stale);
+    count
+}
+
+// Background loop started from main.rs.
+//
+// The first tick fires immediately, so the pass also runs at startup.
+pub async fn run_auto_archive() {
+    let mut ticker = tokio::time::interval(ARCHIVE_INTERVAL);
+
+    loop {
+        ticker.tick().await;
+
+        let now = clock::now();
+        let result = writer::apply(move |db| match db.settings.auto_archive_days {
+            Some(days) => {
+                let n = archive_done(db, days, now);
+                (Some(n), n > 0)
+            }
+            None => (None, false),
+        })
+        .await;
+
+        // Setting is off: nothing to record
+        let Ok(Some(archived)) = result else {
+            continue;
+        };
+        if archived > 0 {
+            println!("  Archived {archived} done task(s)");
+        }
+        if let Ok(mut last) = LAST_RUN.lock() {
+            *last = Some(ArchiveRun { at: now, archived });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_task;
+
+    fn done(title: &str, completed_at: Option<&str>, due_at: &str) -> Task {
+        let mut t = test_task(title, clock::local(due_at));
+        t.status = TaskStatus::Done;
+        t.completed_at = completed_at.map(clock::local);
+        t
+    }
+
+    fn titles(tasks: &[Task]) -> Vec<&str> {
+        tasks.iter().map(|t| t.title.as_str()).collect()
+    }
+
+    #[test]
+    fn archives_only_tasks_done_longer_than_the_threshold() {
+        let now = clock::local("2024-06-10 12:00");
+        let mut db = Db::default();
+        db.insert(done("exactly 7 days", Some("2024-06-03 12:00"), "2024-06-01 09:00"));
+        db.insert(done("7 days and a minute", Some("2024-06-03 11:59"), "2024-06-09 09:00"));
+        db.insert(done("old due, recent completion", Some("2024-06-09 08:00"), "2024-01-01 09:00"));
+        db.insert(done("no completed_at, old due", None, "2024-06-01 09:00"));
+        db.insert(done("no completed_at, recent due", None, "2024-06-05 09:00"));
+        db.insert(test_task("open and old", clock::local("2024-01-01 09:00")));
+
+        assert_eq!(archive_done(&mut db, 7, now), 2);
+        assert_eq!(titles(&db.archived), ["7 days and a minute", "no completed_at, old due"]);
+        assert_eq!(titles(&db.tasks), ["exactly 7 days", "old due, recent completion", "no completed_at, recent due", "open and old"]);
+        assert!(db.tasks.iter().all(|t| db.get(t.id).is_some_and(|found| found.id == t.id)));
+    }
+
+    #[test]
+    fn a_second_pass_archives_nothing() {
+        let now = clock::local("2024-06-10 12:00");
+        let mut db = Db::default();
+        db.insert(done("old", Some("2024-05-01 12:00"), "2024-05-01 09:00"));
+        db.insert(done("recent", Some("2024-06-09 12:00"), "2024-06-09 09:00"));
+        assert_eq!(archive_done(&mut db, 7, now), 1);
+        let after_first = serde_json::to_value(&db).unwrap();
+        assert_eq!(archive_done(&mut db, 7, now), 0);
+        assert_eq!(serde_json::to_value(&db).unwrap(), after_first);
+    }
+}
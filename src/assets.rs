@@ -0,0 +1,254 @@
+// --------------------------------------------------
+// Static frontend embedded into the binary.
+//
+// static/ is compiled in at build time, so the server works
+// from any working directory. `--static-dir` switches back to
+// serving from disk (see main.rs) for frontend development.
+//
+// Caching, the same in both modes:
+// - File names carrying a content hash ("app.3f9a1c2b.js")
+//   never change content: cached for a year, immutable
+// - Everything else, index.html included, is revalidated on
+//   every load (no-cache); embedded files answer with an ETag
+//   and 304, ServeDir with Last-Modified
+// - A precompressed sibling (".br", ".gz") is served instead
+//   when the client accepts that encoding
+// --------------------------------------------------
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use axum::{
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use include_dir::{include_dir, Dir};
+
+static STATIC_DIR: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/static");
+
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+const REVALIDATE: &str = "no-cache";
+
+// Precompressed variants, preferred first: (encoding, file suffix).
+const ENCODINGS: [(&str, &str); 2] = [("br", ".br"), ("gzip", ".gz")];
+
+// Whether the file name has a content hash before its extension:
+// at least 8 hex digits as the second-to-last dot-separated part.
+fn is_hashed(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let parts: Vec<&str> = name.split('.').collect();
+    parts.len() >= 3 && {
+        let hash = parts[parts.len() - 2];
+        hash.len() >= 8 && hash.chars().all(|c| c.is_ascii_hexdigit())
+    }
+}
+
+// Cache-Control for a static file path.
+pub fn cache_control(path: &str) -> &'static str {
+    if is_hashed(path) { IMMUTABLE } else { REVALIDATE }
+}
+
+// Whether Accept-Encoding allows `encoding` (listed, and not with q=0).
+fn accepts(headers: &HeaderMap, encoding: &str) -> bool {
+    headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|part| {
+            let mut it = part.split(';').map(str::trim);
+            it.next().is_some_and(|name| name.eq_ignore_ascii_case(encoding))
+                && !it.any(|p| matches!(p, "q=0" | "q=0.0" | "q=0.00" | "q=0.000"))
+        })
+}
+
+// Strong ETag of embedded content.
+fn etag(contents: &[u8]) -> String {
+    let mut h = DefaultHasher::new();
+    contents.hash(&mut h);
+    format!("\"{:016x}\"", h.finish())
+}
+
+// Serve an embedded file by request path.
+// "/" and directory paths resolve to their index.html.
+pub async fn serve_embedded(uri: Uri, headers: HeaderMap) -> Response {
+    let mut path = uri.path().trim_start_matches('/').to_string();
+    if path.is_empty() || path.ends_with('/') {
+        path.push_str("index.html");
+    }
+
+    let Some(file) = STATIC_DIR.get_file(&path) else {
+        return (StatusCode::NOT_FOUND, "not found").into_response();
+    };
+
+    // Precompressed sibling the client accepts, if one was embedded
+    let (contents, encoding) = ENCODINGS
+        .iter()
+        .filter(|(enc, _)| accepts(&headers, enc))
+        .find_map(|(enc, suffix)| {
+            STATIC_DIR.get_file(format!("{path}{suffix}")).map(|f| (f.contents(), Some(*enc)))
+        })
+        .unwrap_or((file.contents(), None));
+
+    let tag = etag(contents);
+    let mime = mime_guess::from_path(&path).first_or_octet_stream();
+    let mut out = if headers.get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok()) == Some(tag.as_str()) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        ([(header::CONTENT_TYPE, mime.as_ref())], contents).into_response()
+    };
+
+    let h = out.headers_mut();
+    h.insert(header::CACHE_CONTROL, HeaderValue::from_static(cache_control(&path)));
+    h.insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+    if let Ok(v) = HeaderValue::from_str(&tag) {
+        h.insert(header::ETAG, v);
+    }
+    if let Some(enc) = encoding {
+        h.insert(header::CONTENT_ENCODING, HeaderValue::from_static(enc));
+    }
+    out
+}
+
+// Cache-Control for files served from --static-dir (ServeDir), by path.
+// Only successful and 304 responses are marked; errors stay uncached.
+pub async fn cache_headers(req: Request, next: Next) -> Response {
+    let mut path = req.uri().path().to_string();
+    if path.ends_with('/') {
+        path.push_str("index.html");
+    }
+    let mut res = next.run(req).await;
+    if res.status().is_success() || res.status() == StatusCode::NOT_MODIFIED {
+        res.headers_mut().insert(header::CACHE_CONTROL, HeaderValue::from_static(cache_control(&path)));
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The response body is read from the binary: these tests pass in
+    // any working directory, with or without static/ next to it.
+    async fn get(path: &str, headers: HeaderMap) -> (Response, Vec<u8>) {
+        let res = serve_embedded(path.parse().unwrap(), headers).await;
+        let (parts, body) = res.into_parts();
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap().to_vec();
+        (Response::from_parts(parts, axum::body::Body::empty()), bytes)
+    }
+
+    fn header_of(res: &Response, name: header::HeaderName) -> &str {
+        res.headers().get(name).and_then(|v| v.to_str().ok()).unwrap_or_default()
+    }
+
+    #[tokio::test]
+    async fn root_serves_the_embedded_index() {
+        let (res, body) = get("/", HeaderMap::new()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(header_of(&res, header::CONTENT_TYPE), "text/html");
+        assert_eq!(body, include_bytes!("../static/index.html"));
+        assert_eq!(header_of(&res, header::CACHE_CONTROL), REVALIDATE);
+    }
+
+    #[tokio::test]
+    async fn css_and_js_get_their_content_types() {
+        let (res, body) = get("/styles.css", HeaderMap::new()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(header_of(&res, header::CONTENT_TYPE), "text/css");
+        assert_eq!(body, include_bytes!("../static/styles.css"));
+
+        let (res, _) = get("/app.js", HeaderMap::new()).await;
+        assert_eq!(header_of(&res, header::CONTENT_TYPE), "text/javascript");
+    }
+
+    #[tokio::test]
+    async fn unknown_paths_are_404() {
+        let (res, _) = get("/missing.css", HeaderMap::new()).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn matching_etag_gives_304() {
+        let (res, _) = get("/styles.css", HeaderMap::new()).await;
+        let tag = res.headers().get(header::ETAG).unwrap().clone();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, tag);
+        let (res, body) = get("/styles.css", headers).await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert!(body.is_empty());
+    }
+
+    #[test]
+    fn only_hashed_names_are_immutable() {
+        assert_eq!(cache_control("assets/app.3f9a1c2b.js"), IMMUTABLE);
+        assert_eq!(cache_control("app.js"), REVALIDATE);
+        assert_eq!(cache_control("app.v2.js"), REVALIDATE);
+        assert_eq!(cache_control("index.html"), REVALIDATE);
+    }
+
+    #[test]
+    fn accept_encoding_respects_q_zero() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT_ENCODING, HeaderValue::from_static("gzip;q=0, br"));
+        assert!(accepts(&headers, "br"));
+        assert!(!accepts(&headers, "gzip"));
+    }
+
+    // --static-dir: ServeDir behind cache_headers, over a temp dir with a
+    // hashed asset and its gzip sibling.
+    #[tokio::test]
+    async fn disk_mode_caches_hashed_assets_and_serves_gzip() {
+        use tower::ServiceExt as _;
+        let dir = std::env::temp_dir().join(format!("scheduler-static-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("index.html"), "<html></html>").unwrap();
+        std::fs::write(dir.join("app.1a2b3c4d.js"), "plain").unwrap();
+        std::fs::write(dir.join("app.1a2b3c4d.js.gz"), "gzipped").unwrap();
+        let app = crate::router(Some(dir.to_str().unwrap()), false);
+
+        let get = |path: &str, encoding: Option<&'static str>| {
+            let mut req = Request::builder().uri(path);
+            if let Some(enc) = encoding {
+                req = req.header(header::ACCEPT_ENCODING, enc);
+            }
+            let app = app.clone();
+            let req = req.body(axum::body::Body::empty()).unwrap();
+            async move {
+                let res = app.oneshot(req).await.unwrap();
+                let (parts, body) = res.into_parts();
+                let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap().to_vec();
+                (Response::from_parts(parts, axum::body::Body::empty()), bytes)
+            }
+        };
+
+        let (res, _) = get("/", None).await;
+        assert_eq!((res.status(), header_of(&res, header::CACHE_CONTROL)), (StatusCode::OK, REVALIDATE));
+
+        let (res, body) = get("/app.1a2b3c4d.js", None).await;
+        assert_eq!(header_of(&res, header::CACHE_CONTROL), IMMUTABLE);
+        assert_eq!(body, b"plain");
+
+        let (res, body) = get("/app.1a2b3c4d.js", Some("gzip")).await;
+        assert_eq!((header_of(&res, header::CONTENT_ENCODING), body.as_slice()), ("gzip", &b"gzipped"[..]));
+        assert_eq!(header_of(&res, header::CACHE_CONTROL), IMMUTABLE);
+        let (res, body) = get("/app.1a2b3c4d.js", Some("br")).await;
+        assert_eq!((header_of(&res, header::CONTENT_ENCODING), body.as_slice()), ("", &b"plain"[..]));
+
+        let (res, _) = get("/missing.js", None).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert!(res.headers().get(header::CACHE_CONTROL).is_none());
+    }
+
+    #[tokio::test]
+    async fn embedded_files_without_a_precompressed_copy_are_sent_as_is() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT_ENCODING, HeaderValue::from_static("br, gzip"));
+        let (res, body) = get("/app.js", headers).await;
+        assert_eq!(body, include_bytes!("../static/app.js"));
+        assert!(res.headers().get(header::CONTENT_ENCODING).is_none());
+        assert_eq!(header_of(&res, header::VARY), "accept-encoding");
+        assert_eq!(header_of(&res, header::CACHE_CONTROL), REVALIDATE);
+    }
+}
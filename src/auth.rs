@@ -0,0 +1,32 @@
+// --------------------------------------------------
+// Optional API key check for /api routes.
+//
+// Enabled only when auth.api_key is configured.
+// Clients then send "Authorization: Bearer <api_key>".
+// --------------------------------------------------
+
+use axum::{
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::config;
+
+pub async fn require_api_key(req: Request, next: Next) -> Response {
+    let Some(key) = config::get().auth.api_key.as_deref() else {
+        return next.run(req).await;
+    };
+
+    let provided = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    if provided != Some(key) {
+        return (StatusCode::UNAUTHORIZED, "missing or invalid api key").into_response();
+    }
+    next.run(req).await
+}
@@ -0,0 +1,182 @@
+// --------------------------------------------------
+// Local time handling.
+//
+// All "now" values and local date/time conversions go through
+// the configured IANA zone (server.timezone, default: the system
+// zone, falling back to UTC) instead of a frozen UTC offset, so
+// date math stays correct across DST transitions.
+//
+// Tasks keep storing DateTime<FixedOffset>; values produced here
+// carry the zone's offset in effect at that instant.
+//
+// Local times that do not map to exactly one instant:
+// - Ambiguous (clocks fall back, time occurs twice): earliest instant
+// - Nonexistent (clocks spring forward, time is skipped): shifted
+//   forward by the length of the gap, e.g. 02:30 -> 03:30
+// --------------------------------------------------
+
+use std::sync::OnceLock;
+
+use chrono::{
+    DateTime, Duration, FixedOffset, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, Offset,
+    TimeZone, Utc,
+};
+use chrono_tz::Tz;
+
+use crate::config;
+use crate::models::TimeFormat;
+
+static ZONE: OnceLock<Tz> = OnceLock::new();
+
+// Parse an IANA zone name such as "America/Chicago".
+pub fn parse_zone(name: &str) -> Result<Tz, String> {
+    name.trim()
+        .parse::<Tz>()
+        .map_err(|_| format!("unknown timezone \"{name}\""))
+}
+
+// The system zone, or UTC when it cannot be detected.
+fn system_zone() -> Tz {
+    iana_time_zone::get_timezone()
+        .ok()
+        .and_then(|name| parse_zone(&name).ok())
+        .unwrap_or(Tz::UTC)
+}
+
+// Configured zone (resolved once).
+pub fn zone() -> Tz {
+    *ZONE.get_or_init(|| {
+        config::get()
+            .server
+            .timezone
+            .as_deref()
+            .and_then(|name| parse_zone(name).ok())
+            .unwrap_or_else(system_zone)
+    })
+}
+
+// Current time in the configured zone.
+pub fn now() -> DateTime<FixedOffset> {
+    Utc::now().with_timezone(&zone()).fixed_offset()
+}
+
+// Express an instant in the configured zone.
+pub fn to_local<T: TimeZone>(dt: &DateTime<T>) -> DateTime<FixedOffset> {
+    dt.with_timezone(&zone()).fixed_offset()
+}
+
+// Resolve a local wall-clock time in `tz` (see policy above).
+pub fn resolve_in(tz: &Tz, naive: NaiveDateTime) -> DateTime<FixedOffset> {
+    match tz.from_local_datetime(&naive) {
+        LocalResult::Single(dt) => dt.fixed_offset(),
+        LocalResult::Ambiguous(earliest, _) => earliest.fixed_offset(),
+        LocalResult::None => {
+            // Gap length = offset after the transition - offset before it
+            let before = tz.offset_from_utc_datetime(&(naive - Duration::days(1))).fix();
+            let after = tz.offset_from_utc_datetime(&(naive + Duration::days(1))).fix();
+            let gap = i64::from(after.local_minus_utc() - before.local_minus_utc());
+            let shifted = naive + Duration::seconds(gap.max(0));
+            match tz.from_local_datetime(&shifted) {
+                LocalResult::Single(dt) | LocalResult::Ambiguous(dt, _) => dt.fixed_offset(),
+                // Should not happen; read the wall time in the later offset
+                LocalResult::None => after.from_local_datetime(&naive).unwrap(),
+            }
+        }
+    }
+}
+
+// Resolve a local wall-clock time in the configured zone.
+pub fn resolve_local(naive: NaiveDateTime) -> DateTime<FixedOffset> {
+    resolve_in(&zone(), naive)
+}
+
+// Local date + time of day in the configured zone.
+pub fn at(date: NaiveDate, time: NaiveTime) -> DateTime<FixedOffset> {
+    resolve_local(date.and_time(time))
+}
+
+// Local time of day for people to read ("14:30" / "2:30 PM").
+// The one place human-facing output formats times.
+pub fn format_time(dt: &DateTime<FixedOffset>, fmt: TimeFormat) -> String {
+    let local = to_local(dt);
+    match fmt {
+        TimeFormat::H24 => local.format("%H:%M").to_string(),
+        TimeFormat::H12 => local.format("%-I:%M %p").to_string(),
+    }
+}
+
+// Local date and time of day for people to read ("2024-06-01 14:30").
+pub fn format_date_time(dt: &DateTime<FixedOffset>, fmt: TimeFormat) -> String {
+    format!("{} {}", to_local(dt).format("%Y-%m-%d"), format_time(dt, fmt))
+}
+
+// Calendar date of an instant in the configured zone.
+pub fn local_date(dt: &DateTime<FixedOffset>) -> NaiveDate {
+    to_local(dt).date_naive()
+}
+
+// Local wall-clock time "YYYY-MM-DD HH:MM" in the configured zone, for tests.
+#[cfg(test)]
+pub fn local(text: &str) -> DateTime<FixedOffset> {
+    resolve_local(NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").expect("YYYY-MM-DD HH:MM"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive(text: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn rfc(dt: DateTime<FixedOffset>) -> String {
+        dt.to_rfc3339()
+    }
+
+    // Tests run in America/New_York (config::get under cfg(test)).
+    #[test]
+    fn tests_use_a_zone_with_dst() {
+        assert_eq!(zone(), chrono_tz::America::New_York);
+    }
+
+    #[test]
+    fn us_transitions() {
+        let ny = chrono_tz::America::New_York;
+        // Spring forward: 02:00-03:00 does not exist, skipped forward
+        assert_eq!(rfc(resolve_in(&ny, naive("2024-03-10 02:30"))), "2024-03-10T03:30:00-04:00");
+        assert_eq!(rfc(resolve_in(&ny, naive("2024-03-10 01:59"))), "2024-03-10T01:59:00-05:00");
+        assert_eq!(rfc(resolve_in(&ny, naive("2024-03-10 03:00"))), "2024-03-10T03:00:00-04:00");
+        // Fall back: 01:00-02:00 happens twice, earliest wins
+        assert_eq!(rfc(resolve_in(&ny, naive("2024-11-03 01:30"))), "2024-11-03T01:30:00-04:00");
+        assert_eq!(rfc(resolve_in(&ny, naive("2024-11-03 02:00"))), "2024-11-03T02:00:00-05:00");
+    }
+
+    #[test]
+    fn eu_transitions() {
+        let berlin = chrono_tz::Europe::Berlin;
+        assert_eq!(rfc(resolve_in(&berlin, naive("2024-03-31 02:30"))), "2024-03-31T03:30:00+02:00");
+        assert_eq!(rfc(resolve_in(&berlin, naive("2024-10-27 02:30"))), "2024-10-27T02:30:00+02:00");
+        assert_eq!(rfc(resolve_in(&berlin, naive("2024-10-27 03:00"))), "2024-10-27T03:00:00+01:00");
+    }
+
+    #[test]
+    fn tomorrow_morning_created_the_evening_before_spring_forward() {
+        // Evening of the 9th is still on -05:00
+        let now = local("2024-03-09 20:00");
+        assert_eq!(now.offset().local_minus_utc(), -5 * 3600);
+        let due = at(now.date_naive() + Duration::days(1), NaiveTime::from_hms_opt(9, 0, 0).unwrap());
+        assert_eq!(rfc(due), "2024-03-10T09:00:00-04:00");
+        assert_eq!(format_time(&due, TimeFormat::H24), "09:00");
+        // A frozen offset would have been an hour off
+        let frozen = now.offset().from_local_datetime(&naive("2024-03-10 09:00")).unwrap();
+        assert_eq!(format_time(&frozen, TimeFormat::H24), "10:00");
+    }
+
+    #[test]
+    fn local_date_follows_the_offset_in_effect() {
+        // 23:30 on the 10th is 03:30Z on the 11th after the switch
+        let late = local("2024-03-10 23:30");
+        assert_eq!(local_date(&late), NaiveDate::from_ymd_opt(2024, 3, 10).unwrap());
+        assert_eq!(local_date(&late.with_timezone(&Utc).fixed_offset()), NaiveDate::from_ymd_opt(2024, 3, 10).unwrap());
+    }
+}
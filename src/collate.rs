@@ -0,0 +1,102 @@
+// --------------------------------------------------
+// Locale-aware title ordering.
+//
+// Uses the ICU4X collator with its built-in data, so the
+// order is the same on every platform and does not depend
+// on the OS locale:
+// - Accents sort with their base letter ("Éplucher" < "Zebra")
+// - Case differences only break otherwise equal titles
+// - Non-Latin scripts follow the locale's tailoring (e.g. "ko")
+//
+// Titles the collator considers equal are ordered by their
+// bytes so sorting stays total and deterministic.
+// --------------------------------------------------
+
+use std::cmp::Ordering;
+
+use icu_collator::{Collator, CollatorOptions, Strength};
+use icu_locid::Locale;
+
+// Used when no locale is configured.
+pub const DEFAULT_LOCALE: &str = "und";
+
+// Parse a BCP 47 locale tag such as "fr", "de-DE" or "ko".
+pub fn parse_locale(tag: &str) -> Result<Locale, String> {
+    tag.trim()
+        .parse::<Locale>()
+        .map_err(|_| format!("invalid locale \"{tag}\""))
+}
+
+// Compares titles for one locale; build once per sort.
+pub struct TitleOrder {
+    collator: Collator,
+}
+
+impl TitleOrder {
+    // Collator for `tag`; the root collation if the tag is invalid.
+    pub fn new(tag: &str) -> Self {
+        let locale = parse_locale(tag).unwrap_or_default();
+        let mut options = CollatorOptions::new();
+        options.strength = Some(Strength::Tertiary);
+        let collator = Collator::try_new(&locale.into(), options)
+            .or_else(|_| Collator::try_new(&Default::default(), options))
+            .expect("root collation data is compiled in");
+        Self { collator }
+    }
+
+    pub fn compare_titles(&self, a: &str, b: &str) -> Ordering {
+        self.collator.compare(a, b).then_with(|| a.cmp(b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(locale: &str, titles: &[&str]) -> Vec<String> {
+        let order = TitleOrder::new(locale);
+        let mut out: Vec<String> = titles.iter().map(|t| t.to_string()).collect();
+        out.sort_by(|a, b| order.compare_titles(a, b));
+        out
+    }
+
+    #[test]
+    fn french_accents_sort_with_their_base_letter() {
+        assert_eq!(
+            sorted("fr", &["Zebra", "Éplucher", "eplucher", "Ecrire", "élan"]),
+            ["Ecrire", "élan", "eplucher", "Éplucher", "Zebra"]
+        );
+    }
+
+    #[test]
+    fn german_eszett_sorts_as_ss() {
+        assert_eq!(sorted("de", &["Strauss", "Straße", "Strasse", "Strand"]), ["Strand", "Strasse", "Straße", "Strauss"]);
+    }
+
+    #[test]
+    fn korean_titles_follow_hangul_order() {
+        // The "ko" tailoring puts Hangul before Latin
+        assert_eq!(sorted("ko", &["하늘", "가방", "나무", "다리", "Apple"]), ["가방", "나무", "다리", "하늘", "Apple"]);
+        assert_eq!(sorted("en", &["하늘", "Apple"]), ["Apple", "하늘"]);
+    }
+
+    #[test]
+    fn order_is_total_and_independent_of_input_order() {
+        let titles = ["résumé", "resume", "Resume", "RESUME", "résume"];
+        let first = sorted("fr", &titles);
+        let mut reversed = titles;
+        reversed.reverse();
+        assert_eq!(sorted("fr", &reversed), first);
+        // No two distinct titles compare equal
+        let order = TitleOrder::new("fr");
+        for (a, b) in first.iter().zip(first.iter().skip(1)) {
+            assert_eq!(order.compare_titles(a, b), Ordering::Less, "{a} / {b}");
+        }
+    }
+
+    #[test]
+    fn invalid_locale_falls_back_to_root() {
+        assert!(parse_locale("not a locale!").is_err());
+        assert_eq!(sorted("not a locale!", &["Zebra", "Éplucher"]), ["Éplucher", "Zebra"]);
+    }
+}
@@ -0,0 +1,128 @@
+// --------------------------------------------------
+// Compaction of long-finished tasks out of the database.
+//
+// Tasks that are Done and were due more than `days` days ago
+// (in tasks or archived) move to monthly files
+// archive/<year>-<month>.json next to db.json, by the local
+// month of due_at. The db then no longer loads or saves them;
+// GET /api/archive?month= reads them back.
+//
+// The archive files are written first and the tasks removed
+// from the db only after that succeeded, so a failure loses
+// nothing. A task left in both places (the db save failed, or
+// it was edited in between) is merged again by the next run.
+//
+// Runs from POST /api/maintenance/compact and --compact.
+// --------------------------------------------------
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+
+use crate::clock;
+use crate::models::{Db, Task, TaskStatus};
+
+// Age in days used when none is given.
+pub const DEFAULT_DAYS: u32 = 90;
+
+// What a compaction moved (or would move).
+#[derive(Debug, Clone, Serialize)]
+pub struct Outcome {
+    pub days: u32,
+    pub dry_run: bool,
+    pub compacted: usize,
+    pub months: Vec<MonthCount>, // oldest first
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MonthCount {
+    pub month: String, // "YYYY-MM"
+    pub tasks: usize,
+}
+
+// Whether a task is Done and was due more than `days` days before `now`.
+fn is_old(task: &Task, days: u32, now: DateTime<FixedOffset>) -> bool {
+    let age = chrono::Duration::days(i64::from(days));
+    task.status == TaskStatus::Done && task.due_at.checked_add_signed(age).is_some_and(|limit| limit < now)
+}
+
+// Month file a task belongs in, "YYYY-MM" of its local due date.
+pub fn month_of(task: &Task) -> String {
+    clock::local_date(&task.due_at).format("%Y-%m").to_string()
+}
+
+// Copies of the tasks to compact, from tasks and archived.
+pub fn select(db: &Db, days: u32, now: DateTime<FixedOffset>) -> Vec<Task> {
+    db.tasks.iter().chain(&db.archived).filter(|t| is_old(t, days, now)).cloned().collect()
+}
+
+// Tasks per month file, oldest month first.
+pub fn months(tasks: &[Task]) -> Vec<MonthCount> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for t in tasks {
+        *counts.entry(month_of(t)).or_default() += 1;
+    }
+    counts.into_iter().map(|(month, tasks)| MonthCount { month, tasks }).collect()
+}
+
+// Remove the compacted tasks from the db. A task whose rev changed
+// since it was selected stays. Returns how many were removed.
+pub fn remove(db: &mut Db, moved: &[Task]) -> usize {
+    let revs: HashMap<_, _> = moved.iter().map(|t| (t.id, t.rev)).collect();
+    let gone = |t: &Task| revs.get(&t.id) == Some(&t.rev);
+    let before = db.tasks.len() + db.archived.len();
+    db.tasks.retain(|t| !gone(t));
+    db.archived.retain(|t| !gone(t));
+    db.reindex();
+    before - db.tasks.len() - db.archived.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_task;
+
+    fn done(title: &str, due_at: &str) -> Task {
+        let mut t = test_task(title, clock::local(due_at));
+        t.status = TaskStatus::Done;
+        t
+    }
+
+    #[test]
+    fn a_task_is_old_only_past_the_full_age() {
+        let t = done("done", "2024-04-01 09:00");
+        assert!(!is_old(&t, 90, clock::local("2024-06-30 09:00")));
+        assert!(is_old(&t, 90, clock::local("2024-06-30 09:01")));
+        assert!(!is_old(&Task { status: TaskStatus::Todo, ..t }, 90, clock::local("2025-01-01 09:00")));
+    }
+
+    #[test]
+    fn the_month_is_that_of_the_local_due_date() {
+        // 02:00 UTC on July 1st is still June 30th in New York
+        let mut t = done("late evening", "2024-06-30 22:00");
+        t.due_at = DateTime::parse_from_rfc3339("2024-07-01T02:00:00+00:00").unwrap();
+        assert_eq!(month_of(&t), "2024-06");
+        t.due_at = DateTime::parse_from_rfc3339("2024-07-01T04:00:00+00:00").unwrap();
+        assert_eq!(month_of(&t), "2024-07");
+    }
+
+    #[test]
+    fn a_task_edited_since_it_was_selected_stays() {
+        let mut db = Db::default();
+        db.insert(done("untouched", "2024-01-10 09:00"));
+        db.insert(done("edited meanwhile", "2024-01-11 09:00"));
+        db.archived.push(done("archived", "2024-02-01 09:00"));
+        db.insert(done("recent", "2024-06-01 09:00"));
+        let moved = select(&db, 90, clock::local("2024-06-10 12:00"));
+        assert_eq!(moved.len(), 3);
+
+        let edited = db.tasks[1].id;
+        db.get_mut(edited).unwrap().rev += 1;
+        assert_eq!(remove(&mut db, &moved), 2);
+        let titles: Vec<&str> = db.tasks.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, ["edited meanwhile", "recent"]);
+        assert!(db.archived.is_empty());
+        assert_eq!(db.get(edited).map(|t| t.rev), Some(1));
+    }
+}
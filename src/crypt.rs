@@ -0,0 +1,185 @@
+/*
+Encryption of db.json at rest, with the passphrase from
+SCHEDULER_DB_KEY or --db-key-file (storage.db_key).
+
+An encrypted file is:
+
+    MAGIC (8 bytes) | salt (16) | nonce (12) | ciphertext
+
+- the key is Argon2id(passphrase, salt)
+- the ciphertext is the JSON text sealed with ChaCha20-Poly1305
+  under that key and the nonce, with MAGIC and the salt as
+  associated data, so any change to the file fails to open
+- every save draws a new random nonce; the salt, and so the key,
+  stays the one of the file last opened (deriving a key is slow
+  on purpose)
+
+Plain files are told apart by MAGIC, which valid JSON cannot
+start with. Independent from HTTP and the file system;
+store.rs reads and writes the bytes.
+*/
+
+use std::sync::Mutex;
+
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+use crate::config;
+
+const MAGIC: &[u8; 8] = b"SCHEDENC";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = MAGIC.len() + SALT_LEN + NONCE_LEN;
+
+// Key derived for a salt, kept so saves and reloads do not derive it again.
+static KEY: Mutex<Option<([u8; SALT_LEN], Key)>> = Mutex::new(None);
+
+// Why an encrypted file could not be opened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CryptError {
+    NoKey,           // encrypted, but no passphrase is configured
+    WrongKey,        // the passphrase does not open it (or the file was altered)
+    Damaged(String), // too short to hold the header, or the key cannot be derived
+}
+
+impl CryptError {
+    pub fn describe(&self) -> String {
+        match self {
+            CryptError::NoKey => "no db key is configured".to_string(),
+            CryptError::WrongKey => "wrong db key, or the file was altered".to_string(),
+            CryptError::Damaged(message) => message.clone(),
+        }
+    }
+}
+
+// Whether db files are encrypted on save.
+pub fn enabled() -> bool {
+    config::get().storage.db_key.is_some()
+}
+
+pub fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+// Encrypt `plain` with the configured passphrase.
+pub fn seal(plain: &[u8]) -> Result<Vec<u8>, CryptError> {
+    let passphrase = config::get().storage.db_key.as_deref().ok_or(CryptError::NoKey)?;
+    seal_with(passphrase, plain, &KEY)
+}
+
+// Decrypt a file written by `seal`.
+pub fn open(bytes: &[u8]) -> Result<Vec<u8>, CryptError> {
+    let passphrase = config::get().storage.db_key.as_deref().ok_or(CryptError::NoKey)?;
+    open_with(passphrase, bytes, &KEY)
+}
+
+// `seal` with an explicit passphrase and key cache (which must only
+// ever hold keys of that passphrase).
+fn seal_with(passphrase: &str, plain: &[u8], cache: &Mutex<Option<([u8; SALT_LEN], Key)>>) -> Result<Vec<u8>, CryptError> {
+    let mut slot = cache.lock().unwrap_or_else(|e| e.into_inner());
+    let (salt, key) = match *slot {
+        Some(cached) => cached,
+        None => {
+            let mut salt = [0u8; SALT_LEN];
+            OsRng.fill_bytes(&mut salt);
+            let key = derive(passphrase, &salt)?;
+            *slot = Some((salt, key));
+            (salt, key)
+        }
+    };
+    drop(slot);
+
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    let mut out = Vec::with_capacity(HEADER_LEN + plain.len() + 16);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+    let aad = &out[..MAGIC.len() + SALT_LEN];
+    let sealed = ChaCha20Poly1305::new(&key)
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: plain, aad })
+        .map_err(|_| CryptError::Damaged("encryption failed".to_string()))?;
+    out.extend_from_slice(&sealed);
+    Ok(out)
+}
+
+fn open_with(passphrase: &str, bytes: &[u8], cache: &Mutex<Option<([u8; SALT_LEN], Key)>>) -> Result<Vec<u8>, CryptError> {
+    if bytes.len() < HEADER_LEN {
+        return Err(CryptError::Damaged("encrypted file is truncated".to_string()));
+    }
+    let (aad, rest) = bytes.split_at(MAGIC.len() + SALT_LEN);
+    let (nonce, sealed) = rest.split_at(NONCE_LEN);
+    let mut salt = [0u8; SALT_LEN];
+    salt.copy_from_slice(&aad[MAGIC.len()..]);
+
+    let mut slot = cache.lock().unwrap_or_else(|e| e.into_inner());
+    let key = match *slot {
+        Some((cached, key)) if cached == salt => key,
+        _ => derive(passphrase, &salt)?,
+    };
+    let plain = ChaCha20Poly1305::new(&key)
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: sealed, aad })
+        .map_err(|_| CryptError::WrongKey)?;
+    // Later saves keep this file's salt
+    *slot = Some((salt, key));
+    Ok(plain)
+}
+
+fn derive(passphrase: &str, salt: &[u8; SALT_LEN]) -> Result<Key, CryptError> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| CryptError::Damaged(format!("cannot derive the key: {e}")))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{self, StoreError};
+
+    type Cache = Mutex<Option<([u8; SALT_LEN], Key)>>;
+
+    const PLAIN: &[u8] = br#"{"tasks": [{"title": "Call Acme Corp"}]}"#;
+
+    #[test]
+    fn a_sealed_file_round_trips_and_hides_its_text() {
+        let cache = Cache::new(None);
+        let first = seal_with("correct horse", PLAIN, &cache).unwrap();
+        let second = seal_with("correct horse", PLAIN, &cache).unwrap();
+        assert!(is_encrypted(&first) && !is_encrypted(PLAIN));
+        assert!(!first.windows(4).any(|w| w == b"Acme"));
+        // Same salt (and key) for the process, a fresh nonce per save
+        assert_eq!(first[..HEADER_LEN - NONCE_LEN], second[..HEADER_LEN - NONCE_LEN]);
+        assert_ne!(first[HEADER_LEN - NONCE_LEN..HEADER_LEN], second[HEADER_LEN - NONCE_LEN..HEADER_LEN]);
+
+        // Opened by a process that has not derived the key yet
+        assert_eq!(open_with("correct horse", &first, &Cache::new(None)).unwrap(), PLAIN);
+        assert_eq!(open_with("correct horse", &second, &cache).unwrap(), PLAIN);
+    }
+
+    #[test]
+    fn a_wrong_key_an_altered_file_and_a_short_one_do_not_open() {
+        let sealed = seal_with("correct horse", PLAIN, &Cache::new(None)).unwrap();
+        assert_eq!(open_with("battery staple", &sealed, &Cache::new(None)), Err(CryptError::WrongKey));
+
+        for at in [MAGIC.len(), HEADER_LEN - 1, sealed.len() - 1] {
+            let mut altered = sealed.clone();
+            altered[at] ^= 1;
+            assert_eq!(open_with("correct horse", &altered, &Cache::new(None)), Err(CryptError::WrongKey), "byte {at}");
+        }
+        assert!(matches!(open_with("correct horse", &sealed[..HEADER_LEN - 1], &Cache::new(None)), Err(CryptError::Damaged(_))));
+    }
+
+    #[test]
+    fn without_a_key_plain_files_read_and_encrypted_ones_are_refused() {
+        // The test config has no db key
+        assert!(!enabled());
+        assert_eq!(store::db_text(PLAIN.to_vec()).unwrap().as_bytes(), PLAIN);
+        let sealed = seal_with("correct horse", PLAIN, &Cache::new(None)).unwrap();
+        assert_eq!(store::db_text(sealed).err(), Some(StoreError::Encrypted));
+        assert_eq!(seal(PLAIN), Err(CryptError::NoKey));
+    }
+}
@@ -0,0 +1,345 @@
+/*
+Daily digest composition.
+Collects overdue tasks, the plan, unplanned items and tomorrow's
+due tasks into one structure, and renders it as plain text
+(72 columns, no markup) for piping into mail(1).
+Kept free of HTTP / Axum like logic.rs.
+*/
+
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate};
+use serde::Serialize;
+
+use crate::clock;
+use crate::i18n::{self, DigestLabel, Lang};
+use crate::logic::{self, PlanItem, UnplannedItem, UnplannedReason};
+use crate::models::{DaySettings, Task, TaskStatus, TimeFormat};
+
+// Maximum line width of the text digest.
+const WIDTH: usize = 72;
+
+#[derive(Debug, Serialize)]
+pub struct Digest {
+    pub date: String,
+    pub now: String,
+    pub overdue: Vec<DigestTask>,
+    pub plan: Vec<DigestPlanItem>,
+    pub unplanned: Vec<DigestUnplanned>,
+    pub due_tomorrow: Vec<DigestTask>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DigestTask {
+    pub title: String,
+    pub due_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DigestPlanItem {
+    pub start: String,
+    pub end: String,
+    pub title: String,
+    pub priority_label: String, // settings.priority_labels
+    pub is_overdue: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DigestUnplanned {
+    pub title: String,
+    pub reason: UnplannedReason,
+}
+
+fn digest_task(t: &Task) -> DigestTask {
+    DigestTask {
+        title: t.title.clone(),
+        due_at: clock::to_local(&t.due_at).to_rfc3339(),
+    }
+}
+
+// Assemble the digest from an already built plan.
+//
+// - overdue: open tasks past due_at + overdue_grace_min (logic::is_overdue)
+// - due_tomorrow: open tasks due on date + 1
+pub fn build_digest(
+    tasks: &[Task],
+    settings: &DaySettings,
+    date: NaiveDate,
+    now: DateTime<FixedOffset>,
+    plan: &[PlanItem],
+    unplanned: &[UnplannedItem],
+) -> Digest {
+    let open = || tasks.iter().filter(|t| t.status != TaskStatus::Done);
+    let tomorrow = date + Duration::days(1);
+
+    let mut overdue: Vec<&Task> = open().filter(|t| logic::is_overdue(t.due_at, now, settings)).collect();
+    overdue.sort_by_key(|t| t.due_at);
+    let mut due_tomorrow: Vec<&Task> =
+        open().filter(|t| logic::due_date(&t.due_at, settings) == tomorrow).collect();
+    due_tomorrow.sort_by_key(|t| t.due_at);
+
+    let title_of = |id: &str| {
+        tasks
+            .iter()
+            .find(|t| t.id.to_string() == id)
+            .map(|t| t.title.clone())
+            .unwrap_or_else(|| id.to_string())
+    };
+
+    Digest {
+        date: date.to_string(),
+        now: now.to_rfc3339(),
+        overdue: overdue.into_iter().map(digest_task).collect(),
+        plan: plan
+            .iter()
+            .map(|p| DigestPlanItem {
+                start: p.start.to_rfc3339(),
+                end: p.end.to_rfc3339(),
+                title: p.title.clone(),
+                priority_label: settings.priority_meta(p.score_breakdown.priority).label,
+                is_overdue: p.is_overdue,
+            })
+            .collect(),
+        unplanned: unplanned
+            .iter()
+            .map(|u| DigestUnplanned {
+                title: title_of(&u.task_id),
+                reason: u.reason,
+            })
+            .collect(),
+        due_tomorrow: due_tomorrow.into_iter().map(digest_task).collect(),
+    }
+}
+
+// Word-wrap `text` to WIDTH columns.
+// The first line starts with `first`, continuation lines with `rest`.
+fn wrap(text: &str, first: &str, rest: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = first.to_string();
+    let mut empty = true;
+
+    for word in text.split_whitespace() {
+        let sep = if empty { 0 } else { 1 };
+        if !empty && line.chars().count() + sep + word.chars().count() > WIDTH {
+            lines.push(line);
+            line = rest.to_string();
+            empty = true;
+        }
+        if !empty {
+            line.push(' ');
+        }
+        line.push_str(word);
+        empty = false;
+    }
+    lines.push(line);
+    lines
+}
+
+// Time of day of an RFC3339 timestamp, or the raw string if unparsable.
+fn time_of(rfc: &str, fmt: TimeFormat) -> String {
+    DateTime::parse_from_rfc3339(rfc)
+        .map(|d| clock::format_time(&d, fmt))
+        .unwrap_or_else(|_| rfc.to_string())
+}
+
+// Date and time of day of an RFC3339 timestamp.
+fn date_time_of(rfc: &str, fmt: TimeFormat) -> String {
+    DateTime::parse_from_rfc3339(rfc)
+        .map(|d| clock::format_date_time(&d, fmt))
+        .unwrap_or_else(|_| rfc.to_string())
+}
+
+// Render the digest as plain text in `lang`, times in `fmt`.
+pub fn format_text(d: &Digest, lang: Lang, fmt: TimeFormat) -> String {
+    let label = |l| i18n::digest_label(lang, l);
+    let none = format!("  {}", label(DigestLabel::None));
+    let due = label(DigestLabel::Due);
+
+    let mut out: Vec<String> = Vec::new();
+    let title = format!("{} {}", label(DigestLabel::Title), d.date);
+    out.push(title.clone());
+    out.push("=".repeat(title.chars().count()));
+
+    out.push(String::new());
+    out.push(format!("{} ({})", label(DigestLabel::Overdue), d.overdue.len()));
+    if d.overdue.is_empty() {
+        out.push(none.clone());
+    }
+    for t in &d.overdue {
+        out.extend(wrap(&format!("{} ({due} {})", t.title, date_time_of(&t.due_at, fmt)), "  - ", "    "));
+    }
+
+    out.push(String::new());
+    out.push(format!("{} ({})", label(DigestLabel::Plan), d.plan.len()));
+    if d.plan.is_empty() {
+        out.push(format!("  {}", label(DigestLabel::NothingPlanned)));
+    }
+    for p in &d.plan {
+        let head = format!("  {}-{}  ", time_of(&p.start, fmt), time_of(&p.end, fmt));
+        let indent = " ".repeat(head.len());
+        let text = if p.is_overdue {
+            format!("[{}] {} ({})", p.priority_label, p.title, label(DigestLabel::OverdueMark))
+        } else {
+            format!("[{}] {}", p.priority_label, p.title)
+        };
+        out.extend(wrap(&text, &head, &indent));
+    }
+
+    out.push(String::new());
+    out.push(format!("{} ({})", label(DigestLabel::Unplanned), d.unplanned.len()));
+    if d.unplanned.is_empty() {
+        out.push(none.clone());
+    }
+    for u in &d.unplanned {
+        let reason = i18n::unplanned_reason(lang, u.reason);
+        out.extend(wrap(&format!("{} [{reason}]", u.title), "  - ", "    "));
+    }
+
+    out.push(String::new());
+    out.push(format!("{} ({})", label(DigestLabel::DueTomorrow), d.due_tomorrow.len()));
+    if d.due_tomorrow.is_empty() {
+        out.push(none);
+    }
+    for t in &d.due_tomorrow {
+        out.extend(wrap(&format!("{} ({due} {})", t.title, time_of(&t.due_at, fmt)), "  - ", "    "));
+    }
+
+    out.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_task;
+
+    fn task(title: &str, due: &str, duration_min: i64, priority: i64) -> Task {
+        Task { duration_min, priority, ..test_task(title, clock::local(due)) }
+    }
+
+    // Monday 2024-06-03 at 08:00 with the default 09:00-18:00 window.
+    fn fixture() -> Digest {
+        let mut done = task("Renew passport", "2024-06-01 12:00", 30, 3);
+        done.status = TaskStatus::Done;
+        let tasks = vec![
+            task("Pay invoice", "2024-06-01 17:00", 30, 5),
+            task("Write quarterly report", "2024-06-03 12:00", 120, 4),
+            task("Call plumber", "2024-06-03 16:00", 60, 2),
+            task("Move the photo archive to the new server", "2024-06-03 18:00", 600, 3),
+            task("Team retro", "2024-06-04 10:00", 60, 3),
+            done,
+        ];
+        let settings = DaySettings::default();
+        let date = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let now = clock::local("2024-06-03 08:00");
+        let available = logic::day_window_min(&settings);
+        let (plan, unplanned, _) = logic::plan_for_date(&tasks, &settings, date, now, available, true);
+        build_digest(&tasks, &settings, date, now, &plan, &unplanned)
+    }
+
+    #[test]
+    fn text_digest_snapshot() {
+        let expected = "\
+Daily digest for 2024-06-03
+===========================
+
+Overdue (1)
+  - Pay invoice (due 2024-06-01 17:00)
+
+Plan (3)
+  09:00-09:30  [Highest] Pay invoice (overdue)
+  09:30-11:30  [High] Write quarterly report
+  11:30-12:30  [Low] Call plumber
+
+Unplanned (1)
+  - Move the photo archive to the new server [Longer than the whole day;
+    split it into smaller tasks]
+
+Due tomorrow (1)
+  - Team retro (due 10:00)
+";
+        assert_eq!(format_text(&fixture(), Lang::En, TimeFormat::H24), expected);
+    }
+
+    #[test]
+    fn empty_digest_snapshot() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let d = build_digest(&[], &DaySettings::default(), date, clock::local("2024-06-03 08:00"), &[], &[]);
+        let expected = "\
+Daily digest for 2024-06-03
+===========================
+
+Overdue (0)
+  none
+
+Plan (0)
+  nothing planned
+
+Unplanned (0)
+  none
+
+Due tomorrow (0)
+  none
+";
+        assert_eq!(format_text(&d, Lang::En, TimeFormat::H24), expected);
+    }
+
+    #[test]
+    fn twelve_hour_digest_snapshot() {
+        let expected = "\
+Daily digest for 2024-06-03
+===========================
+
+Overdue (1)
+  - Pay invoice (due 2024-06-01 5:00 PM)
+
+Plan (3)
+  9:00 AM-9:30 AM  [Highest] Pay invoice (overdue)
+  9:30 AM-11:30 AM  [High] Write quarterly report
+  11:30 AM-12:30 PM  [Low] Call plumber
+
+Unplanned (1)
+  - Move the photo archive to the new server [Longer than the whole day;
+    split it into smaller tasks]
+
+Due tomorrow (1)
+  - Team retro (due 10:00 AM)
+";
+        assert_eq!(format_text(&fixture(), Lang::En, TimeFormat::H12), expected);
+    }
+
+    #[test]
+    fn long_titles_wrap_at_72_columns() {
+        let mut d = fixture();
+        d.overdue[0].title = "word ".repeat(40);
+        let text = format_text(&d, Lang::En, TimeFormat::H24);
+        assert!(text.lines().all(|l| l.chars().count() <= WIDTH), "{text}");
+        assert!(text.lines().any(|l| l.starts_with("    word")));
+    }
+
+    #[test]
+    fn structured_digest_keeps_the_same_sections() {
+        let d = fixture();
+        assert_eq!(d.date, "2024-06-03");
+        let titles = |items: &[DigestTask]| items.iter().map(|t| t.title.clone()).collect::<Vec<_>>();
+        assert_eq!(titles(&d.overdue), ["Pay invoice"]);
+        assert_eq!(titles(&d.due_tomorrow), ["Team retro"]);
+        assert_eq!(d.plan.len(), 3);
+        assert_eq!(d.plan[0].priority_label, "Highest");
+        assert_eq!(d.unplanned[0].reason, UnplannedReason::ExceedsDayWindow);
+    }
+
+    #[test]
+    fn custom_priority_labels_snapshot() {
+        let labels = ["Someday", "Minor", "Normal", "Major", "Critical"]
+            .map(|label| crate::models::PriorityMeta { label: label.to_string(), color: "#123456".to_string() });
+        let settings = DaySettings { priority_labels: Some(labels), ..DaySettings::default() };
+        let tasks = vec![
+            task("Fix outage", "2024-06-03 10:00", 60, 5),
+            task("Update docs", "2024-06-03 17:00", 30, 2),
+        ];
+        let date = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let now = clock::local("2024-06-03 08:00");
+        let (plan, unplanned, _) = logic::plan_for_date(&tasks, &settings, date, now, 480, true);
+        let d = build_digest(&tasks, &settings, date, now, &plan, &unplanned);
+        let text = format_text(&d, Lang::En, TimeFormat::H24);
+        assert!(text.contains("\nPlan (2)\n  09:00-10:00  [Critical] Fix outage\n  10:00-10:30  [Minor] Update docs\n"), "{text}");
+    }
+}
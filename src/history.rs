@@ -0,0 +1,278 @@
+// --------------------------------------------------
+// Daily plan-vs-reality snapshots.
+//
+// Once a day window has ended, one DaySnapshot for that date
+// is appended to history.jsonl (store::append_history):
+// - planned: the plan for the full window as it stood at
+//   day_start (tasks finished that day or later count as open,
+//   tasks created later are left out)
+// - completed: tasks whose completed_at falls on the date
+// - carried over: planned tasks not completed by the date's end
+// - adherence: share of planned minutes done, plus what was
+//   dropped and what was done without being planned
+// - the settings in force
+//
+// A date already in the history is never written again, so
+// the job can run any number of times (and after restarts).
+// --------------------------------------------------
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use chrono::{DateTime, FixedOffset, NaiveDate};
+
+use crate::clock;
+use crate::logic;
+use crate::logic::PlanItem;
+use crate::models::{Adherence, DaySnapshot, Db, Task, TaskStatus};
+use crate::store;
+use crate::writer;
+
+// How often the job checks for a finished day.
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+fn completed_on(task: &Task, date: NaiveDate) -> bool {
+    task.completed_at.is_some_and(|at| clock::local_date(&at) == date)
+}
+
+// Compare the morning plan for `date` with what was completed on it.
+//
+// - A planned task counts as done when its completed_at falls on the
+//   date, whether before, during or after its block; item minutes count
+// - Planned tasks that are gone from `tasks` count as dropped
+// - Ad hoc: tasks completed on the date that were not in the plan,
+//   counted with their duration_min
+// - No score when nothing was planned (reason "nothing_planned")
+pub fn adherence(plan: &[PlanItem], tasks: &[&Task], date: NaiveDate) -> Adherence {
+    let find = |id: &str| tasks.iter().find(|t| t.id.to_string() == id);
+    let minutes = |p: &PlanItem| (p.end - p.start).num_minutes();
+
+    let (done, dropped): (Vec<&PlanItem>, Vec<&PlanItem>) =
+        plan.iter().partition(|p| find(&p.task_id).is_some_and(|t| completed_on(t, date)));
+    let ad_hoc: Vec<&&Task> = tasks
+        .iter()
+        .filter(|t| completed_on(t, date))
+        .filter(|t| !plan.iter().any(|p| p.task_id == t.id.to_string()))
+        .collect();
+
+    let planned_min: i64 = plan.iter().map(minutes).sum();
+    let done_min: i64 = done.iter().map(|p| minutes(p)).sum();
+    let score = (planned_min > 0)
+        .then(|| (done_min as f64 / planned_min as f64 * 1000.0).round() / 1000.0);
+    Adherence {
+        score,
+        reason: score.is_none().then(|| "nothing_planned".to_string()),
+        planned_min,
+        done_count: done.len(),
+        done_min,
+        dropped_count: dropped.len(),
+        dropped_min: dropped.iter().map(|p| minutes(p)).sum(),
+        ad_hoc_count: ad_hoc.len(),
+        ad_hoc_min: ad_hoc.iter().map(|t| t.duration_min).sum(),
+    }
+}
+
+// Snapshot of `date` as seen at `now`; None if the settings give no day window.
+pub fn snapshot_day(db: &Db, date: NaiveDate, now: DateTime<FixedOffset>) -> Option<DaySnapshot> {
+    let (day_start, _) = logic::day_window(date, &db.settings)?;
+
+    // Tasks as they were at day_start
+    let morning: Vec<Task> = db
+        .tasks
+        .iter()
+        .chain(&db.archived)
+        .filter(|t| t.created_at <= day_start)
+        .filter(|t| t.status != TaskStatus::Done || t.completed_at.is_some_and(|at| clock::local_date(&at) >= date))
+        .map(|t| {
+            let mut t = t.clone();
+            if t.completed_at.is_some() {
+                t.status = TaskStatus::Todo;
+                t.completed_at = None;
+            }
+            t
+        })
+        .collect();
+    let available = logic::day_window_min(&db.settings);
+    let (plan, _, _) = logic::plan_for_date(&morning, &db.settings, date, day_start, available, true);
+
+    let all: Vec<&Task> = db.tasks.iter().chain(&db.archived).collect();
+    let find = |id: &str| all.iter().find(|t| t.id.to_string() == id);
+    let completed: Vec<&&Task> = all.iter().filter(|t| completed_on(t, date)).collect();
+    let carried: Vec<&Task> = plan
+        .iter()
+        .filter_map(|p| find(&p.task_id))
+        .filter(|t| t.completed_at.is_none_or(|at| clock::local_date(&at) > date))
+        .copied()
+        .collect();
+
+    Some(DaySnapshot {
+        date,
+        recorded_at: now,
+        planned_count: plan.len(),
+        planned_min: plan.iter().map(|p| (p.end - p.start).num_minutes()).sum(),
+        completed_count: completed.len(),
+        completed_min: completed.iter().map(|t| t.duration_min).sum(),
+        carried_over_count: carried.len(),
+        carried_over_min: carried.iter().map(|t| t.duration_min).sum(),
+        settings: db.settings.without_extra(),
+        adherence: Some(adherence(&plan, &all, date)),
+    })
+}
+
+// Background loop started from main.rs.
+//
+// Checks the current plan date and the one before, so a day that
+// ended while the server was down is still recorded at startup.
+pub async fn run_daily_snapshot() {
+    let mut recorded: HashSet<NaiveDate> = match store::load_history().await {
+        Ok(days) => days.into_iter().map(|d| d.date).collect(),
+        Err(e) => {
+            eprintln!("  History disabled: cannot read {}: {e}", store::history_path());
+            return;
+        }
+    };
+    let mut ticker = tokio::time::interval(SNAPSHOT_INTERVAL);
+
+    loop {
+        ticker.tick().await;
+
+        let db = writer::snapshot();
+        for day in days_to_record(&db, clock::now(), &recorded) {
+            match store::append_history(&day).await {
+                Ok(()) => {
+                    recorded.insert(day.date);
+                }
+                Err(e) => eprintln!("  Failed to record history for {}: {e}", day.date),
+            }
+        }
+    }
+}
+
+// Snapshots due at `now`: the current plan date and the one before,
+// once their window has ended, unless already in `recorded`.
+fn days_to_record(db: &Db, now: DateTime<FixedOffset>, recorded: &HashSet<NaiveDate>) -> Vec<DaySnapshot> {
+    let today = logic::plan_date(&db.settings, now);
+    [today - chrono::Duration::days(1), today]
+        .into_iter()
+        .filter(|date| !recorded.contains(date))
+        .filter(|date| logic::day_window(*date, &db.settings).is_some_and(|(_, end)| end <= now))
+        .filter_map(|date| snapshot_day(db, date, now))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_task;
+
+    fn task(title: &str, due: &str, created: &str, completed: Option<&str>) -> Task {
+        let mut t = test_task(title, clock::local(due));
+        t.created_at = clock::local(created);
+        if let Some(at) = completed {
+            t.status = TaskStatus::Done;
+            t.completed_at = Some(clock::local(at));
+        }
+        t
+    }
+
+    // June 3rd: two planned tasks (one done that day), one planned and
+    // done the next day, one created after day_start and done anyway.
+    fn db() -> Db {
+        let mut db = Db::default();
+        db.tasks = vec![
+            task("done on time", "2024-06-03 12:00", "2024-06-01 10:00", Some("2024-06-03 11:00")),
+            task("done late", "2024-06-03 12:00", "2024-06-01 10:00", Some("2024-06-04 09:30")),
+            task("still open", "2024-06-03 15:00", "2024-06-02 10:00", None),
+            task("ad hoc", "2024-06-03 17:00", "2024-06-03 13:00", Some("2024-06-03 16:00")),
+            task("done before", "2024-06-03 12:00", "2024-06-01 10:00", Some("2024-06-02 18:00")),
+        ];
+        db.reindex();
+        db
+    }
+
+    #[test]
+    fn snapshot_compares_the_morning_plan_with_the_day() {
+        let now = clock::local("2024-06-03 19:00");
+        let day = snapshot_day(&db(), NaiveDate::from_ymd_opt(2024, 6, 3).unwrap(), now).unwrap();
+        assert_eq!((day.planned_count, day.planned_min), (3, 180));
+        assert_eq!((day.completed_count, day.completed_min), (2, 120));
+        assert_eq!((day.carried_over_count, day.carried_over_min), (2, 120));
+        let adherence = day.adherence.unwrap();
+        assert_eq!((adherence.done_count, adherence.ad_hoc_count), (1, 1));
+        assert_eq!(adherence.score, Some(0.333));
+        assert_eq!(day.recorded_at, now);
+    }
+
+    #[test]
+    fn each_day_is_recorded_once() {
+        let db = db();
+        let mut recorded = HashSet::new();
+
+        // Before day_end nothing is due for today; the day before has ended
+        let due = days_to_record(&db, clock::local("2024-06-03 17:59"), &recorded);
+        assert_eq!(due.iter().map(|d| d.date.to_string()).collect::<Vec<_>>(), ["2024-06-02"]);
+        recorded.extend(due.iter().map(|d| d.date));
+
+        let now = clock::local("2024-06-03 18:00");
+        let due = days_to_record(&db, now, &recorded);
+        assert_eq!(due.iter().map(|d| d.date.to_string()).collect::<Vec<_>>(), ["2024-06-03"]);
+        recorded.extend(due.iter().map(|d| d.date));
+        assert!(days_to_record(&db, now, &recorded).is_empty());
+        assert!(days_to_record(&db, clock::local("2024-06-03 23:00"), &recorded).is_empty());
+
+        // The same day snapshotted again later gives the same record
+        let date = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let again = snapshot_day(&db, date, clock::local("2024-06-03 23:00")).unwrap();
+        let json = |d: &DaySnapshot| serde_json::to_value(DaySnapshot { recorded_at: now, ..d.clone() }).unwrap();
+        assert_eq!(json(&again), json(&due[0]));
+    }
+
+    // A messy day: one task done before its block, one in it, one left
+    // open, one done a day late, one deleted, and one done unplanned.
+    #[test]
+    fn adherence_over_a_messy_day() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let morning = clock::local("2024-06-03 09:00");
+        let mut tasks = vec![
+            task("early", "2024-06-03 10:00", "2024-06-01 10:00", None),
+            task("in block", "2024-06-03 12:00", "2024-06-01 10:00", None),
+            task("left open", "2024-06-03 14:00", "2024-06-01 10:00", None),
+            task("a day late", "2024-06-03 15:00", "2024-06-01 10:00", None),
+            task("deleted", "2024-06-03 16:00", "2024-06-01 10:00", None),
+        ];
+        tasks[4].duration_min = 30;
+        let (plan, _, _) = logic::plan_for_date(&tasks, &db().settings, date, morning, 540, true);
+        assert_eq!(plan.len(), 5);
+        let block = |title: &str| plan.iter().find(|p| p.title == title).unwrap().clone();
+
+        let complete = |t: &mut Task, at: &str| {
+            t.status = TaskStatus::Done;
+            t.completed_at = Some(clock::local(at));
+        };
+        complete(&mut tasks[0], "2024-06-03 08:30");
+        assert!(tasks[0].completed_at.unwrap() < block("early").start);
+        let at = (block("in block").start + chrono::Duration::minutes(45)).format("%Y-%m-%d %H:%M").to_string();
+        complete(&mut tasks[1], &at);
+        complete(&mut tasks[3], "2024-06-04 09:00");
+        tasks.remove(4);
+        let mut ad_hoc = task("ad hoc", "2024-06-05 12:00", "2024-06-03 13:00", Some("2024-06-03 16:00"));
+        ad_hoc.duration_min = 45;
+        tasks.push(ad_hoc);
+
+        let a = adherence(&plan, &tasks.iter().collect::<Vec<_>>(), date);
+        assert_eq!(a.planned_min, 270);
+        assert_eq!((a.done_count, a.done_min), (2, 120));
+        assert_eq!((a.dropped_count, a.dropped_min), (3, 150));
+        assert_eq!((a.ad_hoc_count, a.ad_hoc_min), (1, 45));
+        assert_eq!((a.score, a.reason), (Some(0.444), None));
+    }
+
+    #[test]
+    fn adherence_without_a_plan_has_a_reason_instead_of_a_score() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let done = task("ad hoc", "2024-06-03 12:00", "2024-06-03 10:00", Some("2024-06-03 11:00"));
+        let a = adherence(&[], &[&done], date);
+        assert_eq!((a.score, a.reason.as_deref()), (None, Some("nothing_planned")));
+        assert_eq!((a.planned_min, a.ad_hoc_count, a.ad_hoc_min), (0, 1, 60));
+    }
+}
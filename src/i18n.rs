@@ -0,0 +1,211 @@
+// --------------------------------------------------
+// User-facing message text in English and Korean.
+//
+// Machine-readable codes (unplanned reasons, validation
+// codes) never change; only the human text next to them
+// is localized. The language is picked per request from
+// Accept-Language, falling back to English.
+//
+// Every table is a `match` so adding a reason, problem or
+// digest label without translating it fails to compile.
+// --------------------------------------------------
+
+use axum::http::{header, HeaderMap};
+
+use crate::logic::{PlanWarning, UnplannedReason};
+use crate::validation::Problem;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lang {
+    #[default]
+    En,
+    Ko,
+}
+
+impl Lang {
+    // Language for a primary subtag such as "ko" in "ko-KR".
+    fn from_tag(tag: &str) -> Option<Lang> {
+        let primary = tag.split(['-', '_']).next()?.trim().to_ascii_lowercase();
+        match primary.as_str() {
+            "en" => Some(Lang::En),
+            "ko" => Some(Lang::Ko),
+            _ => None,
+        }
+    }
+
+    // Best supported language from an Accept-Language value,
+    // honoring q-values ("ko-KR,ko;q=0.9,en;q=0.8").
+    pub fn from_accept_language(value: &str) -> Lang {
+        let mut ranked: Vec<(f32, Lang)> = value
+            .split(',')
+            .filter_map(|part| {
+                let mut it = part.split(';');
+                let lang = Lang::from_tag(it.next()?)?;
+                let q = it
+                    .find_map(|p| p.trim().strip_prefix("q="))
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                (q > 0.0).then_some((q, lang))
+            })
+            .collect();
+        // Stable sort keeps header order among equal q-values
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+        ranked.first().map(|(_, l)| *l).unwrap_or_default()
+    }
+
+    // Language requested by the client, English if none is supported.
+    pub fn from_headers(headers: &HeaderMap) -> Lang {
+        headers
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|v| v.to_str().ok())
+            .map(Lang::from_accept_language)
+            .unwrap_or_default()
+    }
+}
+
+// Why a task was left out of the plan.
+pub fn unplanned_reason(lang: Lang, reason: UnplannedReason) -> &'static str {
+    match (lang, reason) {
+        (Lang::En, UnplannedReason::InsufficientTime) => "Not enough time left today",
+        (Lang::En, UnplannedReason::InvalidDuration) => "Duration is invalid",
+        (Lang::En, UnplannedReason::ExceedsDayWindow) => "Longer than the whole day; split it into smaller tasks",
+        (Lang::En, UnplannedReason::CrossesMidnight) => "Would run past midnight into the next day",
+        (Lang::Ko, UnplannedReason::InsufficientTime) => "오늘 남은 시간이 부족합니다",
+        (Lang::Ko, UnplannedReason::InvalidDuration) => "소요 시간이 올바르지 않습니다",
+        (Lang::Ko, UnplannedReason::ExceedsDayWindow) => "하루 전체보다 깁니다. 더 작은 작업으로 나누세요",
+        (Lang::Ko, UnplannedReason::CrossesMidnight) => "자정을 넘겨 다음 날로 이어집니다",
+    }
+}
+
+// Warning text for a plan.
+pub fn plan_warning(lang: Lang, warning: &PlanWarning) -> String {
+    match (lang, warning) {
+        (Lang::En, PlanWarning::DeadlineMissed { .. }) => {
+            "Due before the end of the day but not in the plan".to_string()
+        }
+        (Lang::En, PlanWarning::SettingsFallback) => {
+            "Day start/end settings are invalid; planned the next 8 hours instead".to_string()
+        }
+        (Lang::En, PlanWarning::Overcommitted { short_min }) => {
+            format!("Unplanned work exceeds the day by {short_min} minutes")
+        }
+        (Lang::En, PlanWarning::LockConflict { .. }) => {
+            "Locked item is outside the day or overlaps another locked item; it was kept where it is".to_string()
+        }
+        (Lang::Ko, PlanWarning::DeadlineMissed { .. }) => {
+            "오늘 마감이지만 계획에 포함되지 않았습니다".to_string()
+        }
+        (Lang::Ko, PlanWarning::SettingsFallback) => {
+            "하루 시작/종료 설정이 올바르지 않아 지금부터 8시간으로 계획했습니다".to_string()
+        }
+        (Lang::Ko, PlanWarning::Overcommitted { short_min }) => {
+            format!("미배정 작업이 하루를 {short_min}분 초과합니다")
+        }
+        (Lang::Ko, PlanWarning::LockConflict { .. }) => {
+            "고정된 항목이 하루 범위를 벗어나거나 다른 고정 항목과 겹칩니다. 위치는 유지했습니다".to_string()
+        }
+    }
+}
+
+// Validation message for one field.
+pub fn field_problem(lang: Lang, field: &str, problem: &Problem) -> String {
+    match (lang, problem) {
+        (Lang::En, Problem::Required) => format!("{field} required"),
+        (Lang::En, Problem::TooLong { max }) => format!("{field} exceeds {max} characters"),
+        (Lang::En, Problem::OutOfRange { min, max: None }) => format!("{field} must be >= {min}"),
+        (Lang::En, Problem::OutOfRange { min, max: Some(max) }) => {
+            format!("{field} must be {min}..={max}")
+        }
+        (Lang::En, Problem::InvalidFormat { expected }) => format!("{field} must be {expected}"),
+        (Lang::En, Problem::NotOneOf { value, allowed }) => {
+            format!("{field}: unknown \"{value}\", expected one of {}", allowed.join(", "))
+        }
+        (Lang::Ko, Problem::Required) => format!("{field} 항목은 필수입니다"),
+        (Lang::Ko, Problem::TooLong { max }) => format!("{field} 항목은 {max}자를 넘을 수 없습니다"),
+        (Lang::Ko, Problem::OutOfRange { min, max: None }) => {
+            format!("{field} 항목은 {min} 이상이어야 합니다")
+        }
+        (Lang::Ko, Problem::OutOfRange { min, max: Some(max) }) => {
+            format!("{field} 항목은 {min}~{max} 범위여야 합니다")
+        }
+        (Lang::Ko, Problem::InvalidFormat { expected }) => {
+            format!("{field} 항목은 {expected} 형식이어야 합니다")
+        }
+        (Lang::Ko, Problem::NotOneOf { value, allowed }) => {
+            format!("{field}: 알 수 없는 값 \"{value}\", 가능한 값: {}", allowed.join(", "))
+        }
+    }
+}
+
+// Request body field the endpoint does not accept (strict_requests).
+pub fn unknown_field(lang: Lang, field: &str) -> String {
+    match lang {
+        Lang::En => format!("{field} is not a known field"),
+        Lang::Ko => format!("{field} 항목은 알 수 없는 필드입니다"),
+    }
+}
+
+// Task id used more than once in an imported db (POST /api/import).
+pub fn duplicate_id(lang: Lang, id: &str, first: &str) -> String {
+    match lang {
+        Lang::En => format!("id {id} is already used by {first}"),
+        Lang::Ko => format!("id {id}는 이미 {first}에서 사용 중입니다"),
+    }
+}
+
+// Fixed labels of the plain-text digest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestLabel {
+    Title, // followed by the date
+    Overdue,
+    Plan,
+    Unplanned,
+    DueTomorrow,
+    None,
+    NothingPlanned,
+    OverdueMark,
+    Due, // followed by a time
+}
+
+pub fn digest_label(lang: Lang, label: DigestLabel) -> &'static str {
+    match (lang, label) {
+        (Lang::En, DigestLabel::Title) => "Daily digest for",
+        (Lang::En, DigestLabel::Overdue) => "Overdue",
+        (Lang::En, DigestLabel::Plan) => "Plan",
+        (Lang::En, DigestLabel::Unplanned) => "Unplanned",
+        (Lang::En, DigestLabel::DueTomorrow) => "Due tomorrow",
+        (Lang::En, DigestLabel::None) => "none",
+        (Lang::En, DigestLabel::NothingPlanned) => "nothing planned",
+        (Lang::En, DigestLabel::OverdueMark) => "overdue",
+        (Lang::En, DigestLabel::Due) => "due",
+        (Lang::Ko, DigestLabel::Title) => "일일 요약",
+        (Lang::Ko, DigestLabel::Overdue) => "기한 지남",
+        (Lang::Ko, DigestLabel::Plan) => "계획",
+        (Lang::Ko, DigestLabel::Unplanned) => "미배정",
+        (Lang::Ko, DigestLabel::DueTomorrow) => "내일 마감",
+        (Lang::Ko, DigestLabel::None) => "없음",
+        (Lang::Ko, DigestLabel::NothingPlanned) => "계획 없음",
+        (Lang::Ko, DigestLabel::OverdueMark) => "기한 지남",
+        (Lang::Ko, DigestLabel::Due) => "마감",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_language_picks_the_best_supported_language() {
+        let cases = [
+            ("ko-KR,ko;q=0.9,en;q=0.8", Lang::Ko),
+            ("en-US,ko;q=0.5", Lang::En),
+            ("fr-FR,ko;q=0.3,en;q=0.2", Lang::Ko),
+            ("ko;q=0,en;q=0.1", Lang::En),
+            ("de, fr", Lang::En),
+            ("", Lang::En),
+        ];
+        for (header, lang) in cases {
+            assert_eq!(Lang::from_accept_language(header), lang, "{header:?}");
+        }
+    }
+}
@@ -0,0 +1,184 @@
+/*
+iCalendar (RFC 5545) formatting.
+Turns tasks into a VCALENDAR document for calendar subscriptions.
+Pure formatting, independent from HTTP / Axum.
+*/
+
+use chrono::{DateTime, Duration, FixedOffset, NaiveTime, Utc};
+
+use crate::clock;
+use crate::config;
+use crate::models::Task;
+
+// Escape TEXT values: backslash, semicolon, comma and newlines.
+fn escape_text(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+// Fold a content line to 75 octets, continuation lines start with a space.
+fn fold(line: &str) -> String {
+    let mut out = String::new();
+    let mut len = 0;
+    for c in line.chars() {
+        let w = c.len_utf8();
+        if len + w > 75 {
+            out.push_str("\r\n ");
+            len = 1;
+        }
+        out.push(c);
+        len += w;
+    }
+    out.push_str("\r\n");
+    out
+}
+
+fn utc_stamp(dt: DateTime<FixedOffset>) -> String {
+    dt.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+// Stable per-task UID so clients update events instead of duplicating them.
+pub fn task_uid(task: &Task) -> String {
+    format!("{}@project-scheduler", task.id)
+}
+
+// Build a VCALENDAR with one VEVENT per task, placed at due_at.
+//
+// - Tasks due exactly at `all_day_time` become all-day events
+// - Other tasks span due_at .. due_at + duration_min
+// - With `app_url`, each event links back to its task (URL property;
+//   a URI value, so it is not TEXT-escaped)
+pub fn format_calendar(
+    name: &str,
+    tasks: &[Task],
+    all_day_time: Option<NaiveTime>,
+    now: DateTime<FixedOffset>,
+    app_url: Option<&str>,
+) -> String {
+    let mut out = String::new();
+    let mut line = |l: String| out.push_str(&fold(&l));
+
+    line("BEGIN:VCALENDAR".to_string());
+    line("VERSION:2.0".to_string());
+    line("PRODID:-//Project_Scheduler//Tasks//EN".to_string());
+    line("CALSCALE:GREGORIAN".to_string());
+    line(format!("X-WR-CALNAME:{}", escape_text(name)));
+
+    for t in tasks {
+        line("BEGIN:VEVENT".to_string());
+        line(format!("UID:{}", task_uid(t)));
+        line(format!("DTSTAMP:{}", utc_stamp(now)));
+        let local_due = clock::to_local(&t.due_at);
+        if Some(local_due.time()) == all_day_time {
+            let day = local_due.date_naive();
+            line(format!("DTSTART;VALUE=DATE:{}", day.format("%Y%m%d")));
+            line(format!("DTEND;VALUE=DATE:{}", (day + Duration::days(1)).format("%Y%m%d")));
+        } else {
+            let end = Duration::try_minutes(t.duration_min.max(0))
+                .and_then(|d| t.due_at.checked_add_signed(d))
+                .unwrap_or(t.due_at);
+            line(format!("DTSTART:{}", utc_stamp(t.due_at)));
+            line(format!("DTEND:{}", utc_stamp(end)));
+        }
+        line(format!("SUMMARY:{}", escape_text(&t.title)));
+        if let Some(notes) = t.notes.as_deref().filter(|n| !n.is_empty()) {
+            line(format!("DESCRIPTION:{}", escape_text(notes)));
+        }
+        if let Some(tags) = t.tags.as_ref().filter(|t| !t.is_empty()) {
+            let cats: Vec<String> = tags.iter().map(|c| escape_text(c)).collect();
+            line(format!("CATEGORIES:{}", cats.join(",")));
+        }
+        if let Some(app_url) = app_url {
+            line(format!("URL:{}", config::task_link(app_url, t.id)));
+        }
+        line("END:VEVENT".to_string());
+    }
+
+    line("END:VCALENDAR".to_string());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_task;
+    use std::collections::HashMap;
+
+    // Unfold the feed and return each VEVENT as property -> value.
+    fn parse_events(feed: &str) -> Vec<HashMap<String, String>> {
+        let unfolded = feed.replace("\r\n ", "");
+        let mut events = Vec::new();
+        let mut current: Option<HashMap<String, String>> = None;
+        for line in unfolded.split("\r\n").filter(|l| !l.is_empty()) {
+            match line {
+                "BEGIN:VEVENT" => current = Some(HashMap::new()),
+                "END:VEVENT" => events.extend(current.take()),
+                _ => {
+                    if let (Some(event), Some((name, value))) = (current.as_mut(), line.split_once(':')) {
+                        event.insert(name.to_string(), value.to_string());
+                    }
+                }
+            }
+        }
+        events
+    }
+
+    fn nine() -> Option<NaiveTime> {
+        NaiveTime::from_hms_opt(9, 0, 0)
+    }
+
+    #[test]
+    fn uids_are_stable_across_generations_after_an_edit() {
+        let now = clock::local("2024-06-03 08:00");
+        let mut tasks = vec![
+            test_task("Write report", clock::local("2024-06-03 14:00")),
+            test_task("Call bank", clock::local("2024-06-04 10:30")),
+        ];
+        let first = parse_events(&format_calendar("Tasks: work", &tasks, nine(), now, None));
+
+        tasks[0].title = "Write quarterly report".to_string();
+        tasks[0].due_at = clock::local("2024-06-05 16:00");
+        let second = parse_events(&format_calendar("Tasks: work", &tasks, nine(), now, None));
+
+        assert_eq!(first.len(), 2);
+        assert_eq!(second.len(), 2);
+        for (a, b) in first.iter().zip(&second) {
+            assert_eq!(a["UID"], b["UID"]);
+        }
+        assert_eq!(second[0]["UID"], format!("{}@project-scheduler", tasks[0].id));
+        assert_eq!(second[0]["SUMMARY"], "Write quarterly report");
+        assert_ne!(first[0]["DTSTART"], second[0]["DTSTART"]);
+    }
+
+    #[test]
+    fn default_due_time_becomes_an_all_day_event() {
+        let now = clock::local("2024-06-03 08:00");
+        let tasks = vec![
+            test_task("All day", clock::local("2024-06-03 09:00")),
+            test_task("Timed", clock::local("2024-06-03 14:00")),
+        ];
+        let events = parse_events(&format_calendar("Tasks", &tasks, nine(), now, None));
+        assert_eq!(events[0]["DTSTART;VALUE=DATE"], "20240603");
+        assert_eq!(events[0]["DTEND;VALUE=DATE"], "20240604");
+        assert!(events[1].contains_key("DTSTART"));
+        let start = utc_stamp(tasks[1].due_at);
+        let end = utc_stamp(tasks[1].due_at + Duration::minutes(60));
+        assert_eq!((events[1]["DTSTART"].as_str(), events[1]["DTEND"].as_str()), (start.as_str(), end.as_str()));
+    }
+
+    #[test]
+    fn text_is_escaped_and_long_lines_folded() {
+        let now = clock::local("2024-06-03 08:00");
+        let mut task = test_task("Plan; review, ship\nnext", clock::local("2024-06-03 14:00"));
+        task.notes = Some("x".repeat(200));
+        let feed = format_calendar("Tasks", &[task], None, now, Some("https://example.com/s"));
+        assert!(feed.lines().all(|l| l.trim_end_matches('\r').len() <= 75));
+        let events = parse_events(&feed);
+        assert_eq!(events[0]["SUMMARY"], r"Plan\; review\, ship\nnext");
+        assert_eq!(events[0]["DESCRIPTION"].len(), 200);
+        assert!(events[0]["URL"].starts_with("https://example.com/s"));
+    }
+}
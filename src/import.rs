@@ -0,0 +1,1010 @@
+/*
+Import of tasks exported from other task managers.
+Parsing and field mapping live here, independent from HTTP / Axum,
+so every source format produces plain `Task` values.
+routes_import.rs wires these into the API.
+*/
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::Serialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::clock;
+use crate::logic;
+use crate::models::{DaySettings, Extra, Task, TaskStatus};
+use crate::validation::{self, TaskDraft};
+
+// Duration used when the source format carries no estimate.
+const DEFAULT_DURATION_MIN: i64 = 60;
+
+// Tasks parsed out of an export file, before deduplication.
+#[derive(Debug, Default)]
+pub struct ParsedImport {
+    pub tasks: Vec<Task>,
+    pub skipped: usize,
+    pub warnings: Vec<String>,
+}
+
+// Summary returned to the client after an import (or a dry run).
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub imported: usize,
+    pub skipped: usize,
+    pub warnings: Vec<String>,
+    pub tasks: Vec<Task>, // tasks that were (or would be) created
+    // Incoming tasks resembling an open task (logic::find_duplicate);
+    // skipped with dedupe / skip_duplicates, otherwise created anyway
+    pub possible_duplicates: Vec<PossibleDuplicate>,
+    // Incoming tasks whose id already exists; always skipped
+    pub id_conflicts: Vec<PossibleDuplicate>,
+}
+
+// An incoming task next to the existing one it matches.
+#[derive(Debug, Serialize)]
+pub struct PossibleDuplicate {
+    pub incoming_id: Uuid,
+    pub incoming_title: String,
+    pub existing_id: Uuid,
+    pub existing_title: String,
+}
+
+impl PossibleDuplicate {
+    fn new(incoming: &Task, existing: &Task) -> Self {
+        Self {
+            incoming_id: incoming.id,
+            incoming_title: incoming.title.clone(),
+            existing_id: existing.id,
+            existing_title: existing.title.clone(),
+        }
+    }
+}
+
+// Merge parsed tasks into the existing task list.
+//
+// Rules:
+// - Tasks are validated like POST /api/tasks (validation.rs); invalid ones are skipped
+// - A task whose exact title + due_at already exists is skipped
+// - A task whose id already exists is skipped (formats that carry ids)
+// - Duplicates inside the same file are skipped as well
+// - A task duplicating an open task due the same day (logic::find_duplicate,
+//   same check as POST /api/tasks) is listed in possible_duplicates,
+//   and skipped with `dedupe`
+// - On dry run nothing is appended, but the report is identical
+pub fn merge_into(
+    existing: &mut Vec<Task>,
+    parsed: ParsedImport,
+    dry_run: bool,
+    dedupe: bool,
+) -> ImportReport {
+    let mut warnings = parsed.warnings;
+    let mut skipped = parsed.skipped;
+    let mut accepted: Vec<Task> = Vec::new();
+    let mut possible_duplicates = Vec::new();
+    let mut id_conflicts = Vec::new();
+
+    for mut t in parsed.tasks {
+        // Same validation and sanitizing as the task API
+        let due_at = t.due_at.to_rfc3339();
+        let checked = validation::validate_task(TaskDraft {
+            title: &t.title,
+            due_at: &due_at,
+            duration_min: t.duration_min,
+            priority: t.priority,
+            notes: t.notes.as_deref(),
+        });
+        match checked {
+            Ok(v) => {
+                t.title = v.title;
+                t.notes = v.notes;
+                t.tags = t.tags.as_deref().and_then(validation::coerce_tags);
+            }
+            Err(errors) => {
+                let shown: String = t.title.chars().take(40).collect();
+                warnings.push(format!("skipped \"{shown}\": {}", validation::summary(&errors)));
+                skipped += 1;
+                continue;
+            }
+        }
+
+        if let Some(e) = existing.iter().chain(accepted.iter()).find(|e| e.id == t.id) {
+            id_conflicts.push(PossibleDuplicate::new(&t, e));
+            warnings.push(format!("skipped existing id {}: \"{}\"", t.id, t.title));
+            skipped += 1;
+            continue;
+        }
+        let duplicate = existing
+            .iter()
+            .chain(accepted.iter())
+            .any(|e| e.title == t.title && e.due_at == t.due_at);
+        if duplicate {
+            warnings.push(format!("skipped duplicate: \"{}\" due {}", t.title, t.due_at.to_rfc3339()));
+            skipped += 1;
+            continue;
+        }
+        let similar = logic::find_duplicate(existing, &t.title, t.due_at)
+            .or_else(|| logic::find_duplicate(&accepted, &t.title, t.due_at));
+        if let Some(e) = similar {
+            possible_duplicates.push(PossibleDuplicate::new(&t, e));
+            if dedupe {
+                warnings.push(format!(
+                    "skipped duplicate of open task \"{}\" ({}): \"{}\"",
+                    e.title,
+                    e.id,
+                    t.title
+                ));
+                skipped += 1;
+                continue;
+            }
+        }
+        accepted.push(t);
+    }
+
+    if !dry_run {
+        existing.extend(accepted.iter().cloned());
+    }
+
+    ImportReport {
+        dry_run,
+        imported: accepted.len(),
+        skipped,
+        warnings,
+        tasks: accepted,
+        possible_duplicates,
+        id_conflicts,
+    }
+}
+
+// Parse the "HH:MM" default due time from settings.
+// Falls back to end of day when the setting is malformed.
+fn default_time(settings: &DaySettings) -> NaiveTime {
+    NaiveTime::parse_from_str(&settings.default_due_time, "%H:%M")
+        .unwrap_or_else(|_| NaiveTime::from_hms_opt(23, 59, 0).unwrap())
+}
+
+// Resolve a due string into a local DateTime.
+//
+// Accepted forms:
+// - RFC3339 ("2024-06-01T10:00:00Z"), converted to the local time zone
+// - Naive date-time ("2024-06-01T10:00:00"), taken as local time
+// - Date only ("2024-06-01"), completed with the default due time
+pub fn resolve_due(raw: &str, default_time: NaiveTime) -> Option<DateTime<FixedOffset>> {
+    let raw = raw.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(raw) {
+        return Some(clock::to_local(&dt));
+    }
+    for fmt in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(raw, fmt) {
+            return Some(clock::resolve_local(naive));
+        }
+    }
+    let date = NaiveDate::parse_from_str(raw, "%Y-%m-%d").ok()?;
+    Some(clock::at(date, default_time))
+}
+
+fn new_task(title: String, due_at: DateTime<FixedOffset>, now: DateTime<FixedOffset>) -> Task {
+    Task {
+        id: Uuid::new_v4(),
+        title,
+        due_at,
+        duration_min: DEFAULT_DURATION_MIN,
+        priority: 3,
+        status: TaskStatus::Todo,
+        created_at: now,
+        tags: None,
+        notes: None,
+        completed_at: None,
+        postpone_count: 0,
+        rev: 0,
+        updated_at: now,
+        deadline_notified_at: None,
+        locked: None,
+        extra: Extra::new(),
+    }
+}
+
+// Append a line to optional notes, creating them if needed.
+fn push_note(notes: &mut Option<String>, line: &str) {
+    match notes {
+        Some(n) if !n.is_empty() => {
+            n.push('\n');
+            n.push_str(line);
+        }
+        _ => *notes = Some(line.to_string()),
+    }
+}
+
+// --------------------------------------------------
+// Todoist
+// --------------------------------------------------
+
+// Todoist ranks priority 4 (shown as p1, urgent) down to 1 (p4, normal).
+// Shift onto our 1..=5 scale so p1 -> 5 and p4 -> 2.
+fn todoist_priority(p: i64) -> i64 {
+    p.clamp(1, 4) + 1
+}
+
+// Parse a Todoist export.
+//
+// Both export flavors are accepted:
+// - JSON: an array of tasks, or an object with "items" / "tasks"
+//   (REST and Sync API shapes)
+// - CSV: the project template export (TYPE, CONTENT, DESCRIPTION, PRIORITY, DATE, ...)
+//
+// Recurring tasks are imported once as plain tasks with a note.
+pub fn parse_todoist(
+    text: &str,
+    settings: &DaySettings,
+    now: DateTime<FixedOffset>,
+) -> Result<ParsedImport, String> {
+    let trimmed = text.trim_start();
+    if trimmed.starts_with('[') || trimmed.starts_with('{') {
+        let value: Value =
+            serde_json::from_str(trimmed).map_err(|e| format!("invalid todoist json: {e}"))?;
+        parse_todoist_json(&value, settings, now)
+    } else {
+        parse_todoist_csv(text, settings, now)
+    }
+}
+
+fn parse_todoist_json(
+    value: &Value,
+    settings: &DaySettings,
+    now: DateTime<FixedOffset>,
+) -> Result<ParsedImport, String> {
+    let items = match value {
+        Value::Array(items) => items,
+        Value::Object(obj) => obj
+            .get("items")
+            .or_else(|| obj.get("tasks"))
+            .and_then(Value::as_array)
+            .ok_or("todoist json must be an array or contain \"items\"")?,
+        _ => return Err("todoist json must be an array or contain \"items\"".to_string()),
+    };
+
+    let default_time = default_time(settings);
+    let mut out = ParsedImport::default();
+
+    for (i, item) in items.iter().enumerate() {
+        let title = item["content"].as_str().unwrap_or("").trim().to_string();
+        if title.is_empty() {
+            out.warnings.push(format!("item {i}: missing content"));
+            out.skipped += 1;
+            continue;
+        }
+
+        let due = &item["due"];
+        let raw_due = due["datetime"].as_str().or_else(|| due["date"].as_str());
+        let Some(due_at) = raw_due.and_then(|d| resolve_due(d, default_time)) else {
+            out.warnings.push(format!("item {i} (\"{title}\"): missing or unreadable due date"));
+            out.skipped += 1;
+            continue;
+        };
+
+        let mut task = new_task(title, due_at, now);
+        if let Some(p) = item["priority"].as_i64() {
+            task.priority = todoist_priority(p);
+        }
+        if let Some(amount) = item["duration"]["amount"].as_i64() {
+            match item["duration"]["unit"].as_str() {
+                Some("minute") => task.duration_min = amount,
+                Some("day") => task.duration_min = amount.saturating_mul(24 * 60),
+                _ => {}
+            }
+        }
+        if let Some(labels) = item["labels"].as_array() {
+            let tags: Vec<String> = labels
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect();
+            if !tags.is_empty() {
+                task.tags = Some(tags);
+            }
+        }
+        if let Some(desc) = item["description"].as_str().filter(|d| !d.trim().is_empty()) {
+            task.notes = Some(desc.to_string());
+        }
+        if due["is_recurring"].as_bool().unwrap_or(false) {
+            let rule = due["string"].as_str().unwrap_or("recurring");
+            push_note(&mut task.notes, &format!("imported from recurring todoist task ({rule})"));
+        }
+        let completed = item["checked"].as_bool().or_else(|| item["is_completed"].as_bool());
+        if completed.unwrap_or(false) {
+            task.status = TaskStatus::Done;
+        }
+
+        out.tasks.push(task);
+    }
+
+    Ok(out)
+}
+
+fn parse_todoist_csv(
+    text: &str,
+    settings: &DaySettings,
+    now: DateTime<FixedOffset>,
+) -> Result<ParsedImport, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(text.as_bytes());
+
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| format!("invalid todoist csv: {e}"))?
+        .iter()
+        .map(|h| h.trim().to_uppercase())
+        .collect();
+    let col = |name: &str| headers.iter().position(|h| h == name);
+    let (Some(type_col), Some(content_col)) = (col("TYPE"), col("CONTENT")) else {
+        return Err("todoist csv must have TYPE and CONTENT columns".to_string());
+    };
+    let desc_col = col("DESCRIPTION");
+    let priority_col = col("PRIORITY");
+    let date_col = col("DATE");
+    let duration_col = col("DURATION");
+    let duration_unit_col = col("DURATION_UNIT");
+
+    let default_time = default_time(settings);
+    let mut out = ParsedImport::default();
+    // Index of the last imported task, so "note" rows can attach to it
+    let mut last_task: Option<usize> = None;
+
+    for (i, record) in reader.records().enumerate() {
+        let line = i + 2; // header is line 1
+        let record = record.map_err(|e| format!("invalid todoist csv at line {line}: {e}"))?;
+        let field = |c: Option<usize>| c.and_then(|c| record.get(c)).unwrap_or("").trim();
+
+        match field(Some(type_col)) {
+            "task" => {}
+            "note" => {
+                let content = field(Some(content_col));
+                if let (Some(idx), false) = (last_task, content.is_empty()) {
+                    push_note(&mut out.tasks[idx].notes, content);
+                }
+                continue;
+            }
+            // sections, blank separator rows, etc.
+            _ => {
+                last_task = None;
+                continue;
+            }
+        }
+
+        // Labels are embedded in the content as "@label" words.
+        let (words, labels): (Vec<&str>, Vec<&str>) = field(Some(content_col))
+            .split_whitespace()
+            .partition(|w| !(w.len() > 1 && w.starts_with('@')));
+        let title = words.join(" ");
+        if title.is_empty() {
+            out.warnings.push(format!("line {line}: missing content"));
+            out.skipped += 1;
+            last_task = None;
+            continue;
+        }
+
+        // The CSV only carries Todoist's human due string ("2024-06-01", "every monday").
+        let raw_due = field(date_col);
+        let recurring = raw_due.to_lowercase().starts_with("every");
+        let due_at = match resolve_due(raw_due, default_time) {
+            Some(d) => d,
+            None if recurring => {
+                clock::at(clock::local_date(&now), default_time)
+            }
+            None => {
+                out.warnings.push(format!("line {line} (\"{title}\"): missing or unreadable date \"{raw_due}\""));
+                out.skipped += 1;
+                last_task = None;
+                continue;
+            }
+        };
+
+        let mut task = new_task(title, due_at, now);
+        if let Ok(p) = field(priority_col).parse::<i64>() {
+            task.priority = todoist_priority(p);
+        }
+        if let Ok(amount) = field(duration_col).parse::<i64>() {
+            match field(duration_unit_col) {
+                "minute" | "" => task.duration_min = amount,
+                "day" => task.duration_min = amount.saturating_mul(24 * 60),
+                _ => {}
+            }
+        }
+        if !labels.is_empty() {
+            task.tags = Some(labels.iter().map(|l| l[1..].to_string()).collect());
+        }
+        let desc = field(desc_col);
+        if !desc.is_empty() {
+            task.notes = Some(desc.to_string());
+        }
+        if recurring {
+            push_note(&mut task.notes, &format!("imported from recurring todoist task ({raw_due})"));
+        }
+
+        out.tasks.push(task);
+        last_task = Some(out.tasks.len() - 1);
+    }
+
+    Ok(out)
+}
+
+// --------------------------------------------------
+// Google Tasks (Takeout)
+// --------------------------------------------------
+
+// Days added to today for tasks exported without a due date.
+const UNDATED_DUE_DAYS: i64 = 7;
+
+// Parse a Google Takeout "Tasks.json" export.
+//
+// Mapping:
+// - Each task list title becomes a tag on its tasks
+// - "completed" status -> Done, completed_at from "completed" (else now)
+// - Tasks without a due date get today + 7 days and a warning
+// - Subtasks are flattened, with "parent: <title>" added to notes
+//
+// Google only stores the due date (time is always midnight UTC),
+// so the default due time is applied.
+pub fn parse_google_tasks(
+    text: &str,
+    settings: &DaySettings,
+    now: DateTime<FixedOffset>,
+) -> Result<ParsedImport, String> {
+    let value: Value =
+        serde_json::from_str(text).map_err(|e| format!("invalid google tasks json: {e}"))?;
+    let lists = value["items"]
+        .as_array()
+        .ok_or("google tasks json must contain \"items\" (task lists)")?;
+
+    let default_time = default_time(settings);
+    let undated_due = clock::at(
+        clock::local_date(&now) + chrono::Duration::days(UNDATED_DUE_DAYS),
+        default_time,
+    );
+    let mut out = ParsedImport::default();
+
+    for list in lists {
+        let list_title = list["title"].as_str().unwrap_or("").trim();
+        let Some(items) = list["items"].as_array() else {
+            continue;
+        };
+
+        // Titles by Google id, used to describe subtask parents
+        let titles: std::collections::HashMap<&str, &str> = items
+            .iter()
+            .filter_map(|t| Some((t["id"].as_str()?, t["title"].as_str()?)))
+            .collect();
+
+        for (i, item) in items.iter().enumerate() {
+            if item["deleted"].as_bool().unwrap_or(false) {
+                continue;
+            }
+            let title = item["title"].as_str().unwrap_or("").trim().to_string();
+            if title.is_empty() {
+                out.warnings.push(format!("list \"{list_title}\" item {i}: missing title"));
+                out.skipped += 1;
+                continue;
+            }
+
+            let due_at = match item["due"].as_str() {
+                Some(raw) => {
+                    let date_part = raw.get(..10).unwrap_or(raw);
+                    match resolve_due(date_part, default_time) {
+                        Some(d) => d,
+                        None => {
+                            out.warnings.push(format!("\"{title}\": unreadable due date \"{raw}\""));
+                            out.skipped += 1;
+                            continue;
+                        }
+                    }
+                }
+                None => {
+                    out.warnings.push(format!(
+                        "\"{title}\": no due date, set to {}",
+                        undated_due.date_naive()
+                    ));
+                    undated_due
+                }
+            };
+
+            let mut task = new_task(title, due_at, now);
+            if !list_title.is_empty() {
+                task.tags = Some(vec![list_title.to_string()]);
+            }
+            if let Some(notes) = item["notes"].as_str().filter(|n| !n.trim().is_empty()) {
+                task.notes = Some(notes.to_string());
+            }
+            if let Some(parent) = item["parent"].as_str() {
+                let parent_title = titles.get(parent).copied().unwrap_or(parent);
+                push_note(&mut task.notes, &format!("parent: {parent_title}"));
+            }
+            if item["status"].as_str() == Some("completed") {
+                task.status = TaskStatus::Done;
+                let completed = item["completed"].as_str().and_then(|c| DateTime::parse_from_rfc3339(c).ok());
+                task.completed_at = Some(completed.map_or(now, |c| clock::to_local(&c)));
+            }
+
+            out.tasks.push(task);
+        }
+    }
+
+    Ok(out)
+}
+
+// --------------------------------------------------
+// Taskwarrior
+// --------------------------------------------------
+
+// Attributes Taskwarrior itself defines; anything else is a UDA.
+const TASKWARRIOR_FIELDS: &[&str] = &[
+    "id", "uuid", "description", "status", "entry", "modified", "due", "end", "start",
+    "wait", "scheduled", "until", "recur", "mask", "imask", "parent", "project",
+    "priority", "tags", "annotations", "depends", "urgency",
+];
+
+// Taskwarrior timestamps look like "20240601T120000Z" (always UTC).
+fn parse_tw_date(raw: &str) -> Option<DateTime<FixedOffset>> {
+    let naive = NaiveDateTime::parse_from_str(raw, "%Y%m%dT%H%M%SZ").ok()?;
+    Some(clock::to_local(&naive.and_utc()))
+}
+
+fn format_tw_date(dt: DateTime<FixedOffset>) -> String {
+    dt.with_timezone(&chrono::Utc).format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+// Taskwarrior priority H/M/L mapped onto 5/3/1 (none -> 3).
+fn taskwarrior_priority(p: Option<&str>) -> i64 {
+    match p {
+        Some("H") => 5,
+        Some("L") => 1,
+        _ => 3,
+    }
+}
+
+// Inverse of `taskwarrior_priority`: 4..=5 -> H, 3 -> M, 1..=2 -> L.
+fn taskwarrior_priority_code(p: i64) -> &'static str {
+    match p {
+        4.. => "H",
+        3 => "M",
+        _ => "L",
+    }
+}
+
+// Parse `task export` output.
+//
+// Accepts the JSON array form and the older one-object-per-line form.
+// Mapping:
+// - uuid is kept as the task id
+// - description -> title, due -> due_at, priority H/M/L -> 5/3/1
+// - annotations -> notes (one line each)
+// - completed -> Done with end -> completed_at (else now),
+//   started (has "start") -> InProgress
+// - deleted and recurrence templates are skipped
+// Unknown (UDA) fields are ignored and counted in a warning.
+pub fn parse_taskwarrior(
+    text: &str,
+    settings: &DaySettings,
+    now: DateTime<FixedOffset>,
+) -> Result<ParsedImport, String> {
+    let items: Vec<Value> = if text.trim_start().starts_with('[') {
+        serde_json::from_str(text).map_err(|e| format!("invalid taskwarrior json: {e}"))?
+    } else {
+        text.lines()
+            .filter(|l| !l.trim().is_empty())
+            .enumerate()
+            .map(|(i, l)| {
+                serde_json::from_str(l.trim().trim_end_matches(','))
+                    .map_err(|e| format!("invalid taskwarrior json on line {}: {e}", i + 1))
+            })
+            .collect::<Result<_, _>>()?
+    };
+
+    let default_time = default_time(settings);
+    let mut out = ParsedImport::default();
+    let mut uda_count = 0;
+
+    for (i, item) in items.iter().enumerate() {
+        let Some(obj) = item.as_object() else {
+            out.warnings.push(format!("item {i}: not an object"));
+            out.skipped += 1;
+            continue;
+        };
+        uda_count += obj.keys().filter(|k| !TASKWARRIOR_FIELDS.contains(&k.as_str())).count();
+
+        let title = item["description"].as_str().unwrap_or("").trim().to_string();
+        if title.is_empty() {
+            out.warnings.push(format!("item {i}: missing description"));
+            out.skipped += 1;
+            continue;
+        }
+
+        let status = item["status"].as_str().unwrap_or("pending");
+        if status == "deleted" || status == "recurring" {
+            out.skipped += 1;
+            continue;
+        }
+
+        let Some(due_at) = item["due"]
+            .as_str()
+            .and_then(|d| parse_tw_date(d).or_else(|| resolve_due(d, default_time)))
+        else {
+            out.warnings.push(format!("item {i} (\"{title}\"): missing or unreadable due date"));
+            out.skipped += 1;
+            continue;
+        };
+
+        let created_at = item["entry"].as_str().and_then(parse_tw_date);
+        let mut task = new_task(title, due_at, created_at.unwrap_or(now));
+
+        if let Some(id) = item["uuid"].as_str().and_then(|u| Uuid::parse_str(u).ok()) {
+            task.id = id;
+        }
+        task.priority = taskwarrior_priority(item["priority"].as_str());
+        if let Some(tags) = item["tags"].as_array() {
+            let tags: Vec<String> = tags.iter().filter_map(Value::as_str).map(str::to_string).collect();
+            if !tags.is_empty() {
+                task.tags = Some(tags);
+            }
+        }
+        for a in item["annotations"].as_array().into_iter().flatten() {
+            if let Some(desc) = a["description"].as_str() {
+                push_note(&mut task.notes, desc);
+            }
+        }
+        task.status = match status {
+            "completed" => TaskStatus::Done,
+            _ if item.get("start").is_some() => TaskStatus::InProgress,
+            _ => TaskStatus::Todo,
+        };
+        if task.status == TaskStatus::Done {
+            task.completed_at = Some(item["end"].as_str().and_then(parse_tw_date).unwrap_or(now));
+        }
+
+        out.tasks.push(task);
+    }
+
+    if uda_count > 0 {
+        out.warnings.push(format!("ignored {uda_count} unknown (UDA) field(s)"));
+    }
+    Ok(out)
+}
+
+// Export tasks in `task import` JSON format (inverse of `parse_taskwarrior`).
+pub fn export_taskwarrior(tasks: &[Task]) -> Vec<Value> {
+    tasks
+        .iter()
+        .map(|t| {
+            let mut obj = serde_json::Map::new();
+            obj.insert("uuid".into(), Value::from(t.id.to_string()));
+            obj.insert("description".into(), Value::from(t.title.clone()));
+            let status = if t.status == TaskStatus::Done { "completed" } else { "pending" };
+            obj.insert("status".into(), Value::from(status));
+            obj.insert("entry".into(), Value::from(format_tw_date(t.created_at)));
+            obj.insert("due".into(), Value::from(format_tw_date(t.due_at)));
+            obj.insert("priority".into(), Value::from(taskwarrior_priority_code(t.priority)));
+            match t.status {
+                // Start times are not tracked; fall back to the entry time
+                TaskStatus::InProgress => {
+                    obj.insert("start".into(), Value::from(format_tw_date(t.created_at)));
+                }
+                TaskStatus::Done => {
+                    let end = t.completed_at.unwrap_or(t.created_at);
+                    obj.insert("end".into(), Value::from(format_tw_date(end)));
+                }
+                TaskStatus::Todo => {}
+            }
+            if let Some(tags) = t.tags.as_ref().filter(|t| !t.is_empty()) {
+                obj.insert("tags".into(), Value::from(tags.clone()));
+            }
+            if let Some(notes) = t.notes.as_deref() {
+                let annotations: Vec<Value> = notes
+                    .lines()
+                    .filter(|l| !l.trim().is_empty())
+                    .map(|l| {
+                        serde_json::json!({
+                            "entry": format_tw_date(t.created_at),
+                            "description": l,
+                        })
+                    })
+                    .collect();
+                if !annotations.is_empty() {
+                    obj.insert("annotations".into(), Value::Array(annotations));
+                }
+            }
+            Value::Object(obj)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_task;
+
+    fn now() -> DateTime<FixedOffset> {
+        clock::local("2024-05-20 08:00")
+    }
+
+    fn find<'a>(tasks: &'a [Task], title: &str) -> &'a Task {
+        tasks.iter().find(|t| t.title == title).unwrap_or_else(|| panic!("no task \"{title}\""))
+    }
+
+    #[test]
+    fn todoist_json_export_maps_every_field() {
+        let text = include_str!("../testdata/todoist.json");
+        let parsed = parse_todoist(text, &DaySettings::default(), now()).unwrap();
+
+        assert_eq!(parsed.tasks.len(), 3);
+        assert_eq!(parsed.skipped, 2); // no due date, blank content
+        assert_eq!(parsed.warnings.len(), 2);
+
+        let milk = find(&parsed.tasks, "Buy milk");
+        assert_eq!(milk.priority, 5); // p1
+        assert_eq!(milk.due_at, clock::local("2024-06-01 23:59")); // default_due_time
+        assert_eq!(milk.tags.as_deref(), Some(&["errands".to_string(), "home".to_string()][..]));
+        assert_eq!(milk.notes.as_deref(), Some("2% please"));
+        assert_eq!(milk.status, TaskStatus::Todo);
+
+        let review = find(&parsed.tasks, "Weekly review");
+        assert_eq!(review.priority, 2); // p4
+        assert_eq!(review.duration_min, 45);
+        assert_eq!(review.due_at, clock::local("2024-06-03 09:00"));
+        assert!(review.notes.as_deref().unwrap().contains("recurring todoist task (every monday 9am)"));
+
+        assert_eq!(find(&parsed.tasks, "File taxes").status, TaskStatus::Done);
+    }
+
+    #[test]
+    fn todoist_json_accepts_the_sync_api_shape() {
+        let text = r#"{"items": [{"content": "Pay rent", "due": {"date": "2024-06-01"}}]}"#;
+        let parsed = parse_todoist(text, &DaySettings::default(), now()).unwrap();
+        assert_eq!(parsed.tasks.len(), 1);
+        assert_eq!(parsed.tasks[0].duration_min, DEFAULT_DURATION_MIN);
+    }
+
+    #[test]
+    fn todoist_csv_template_maps_labels_notes_and_recurrence() {
+        let text = include_str!("../testdata/todoist.csv");
+        let parsed = parse_todoist(text, &DaySettings::default(), now()).unwrap();
+
+        assert_eq!(parsed.tasks.len(), 3);
+        assert_eq!(parsed.skipped, 1);
+        assert!(parsed.warnings[0].contains("when I feel like it"));
+
+        let milk = find(&parsed.tasks, "Buy milk");
+        assert_eq!(milk.tags.as_deref(), Some(&["errands".to_string(), "home".to_string()][..]));
+        assert_eq!(milk.notes.as_deref(), Some("2% please\nLactose free"));
+        assert_eq!(milk.priority, 2);
+
+        // A recurring date string has no date: due today, with a note
+        let review = find(&parsed.tasks, "Weekly review");
+        assert_eq!(review.due_at, clock::local("2024-05-20 23:59"));
+        assert_eq!(review.duration_min, 45);
+        assert_eq!(review.priority, 5);
+        assert!(review.notes.as_deref().unwrap().contains("(every monday)"));
+
+        let plumber = find(&parsed.tasks, "Call plumber");
+        assert_eq!(plumber.due_at, clock::local("2024-06-02 14:30"));
+        assert_eq!(plumber.duration_min, 24 * 60);
+    }
+
+    #[test]
+    fn todoist_csv_without_type_column_is_rejected() {
+        let err = parse_todoist("CONTENT,DATE\nBuy milk,2024-06-01\n", &DaySettings::default(), now()).unwrap_err();
+        assert!(err.contains("TYPE"));
+    }
+
+    #[test]
+    fn import_skips_exact_title_and_due_matches() {
+        let text = include_str!("../testdata/todoist.json");
+        let parsed = || parse_todoist(text, &DaySettings::default(), now()).unwrap();
+        let mut existing = vec![test_task("Buy milk", clock::local("2024-06-01 23:59"))];
+
+        let preview = merge_into(&mut existing, parsed(), true, false);
+        assert_eq!(existing.len(), 1, "a dry run adds nothing");
+        assert_eq!(preview.imported, 2);
+        assert_eq!(preview.skipped, 3);
+        assert!(preview.warnings.iter().any(|w| w.contains("skipped duplicate: \"Buy milk\"")));
+
+        let report = merge_into(&mut existing, parsed(), false, false);
+        assert_eq!((report.imported, report.skipped), (preview.imported, preview.skipped));
+        assert_eq!(existing.len(), 3);
+
+        // Importing the same file again changes nothing
+        let again = merge_into(&mut existing, parsed(), false, false);
+        assert_eq!(again.imported, 0);
+        assert_eq!(existing.len(), 3);
+    }
+
+    #[test]
+    fn google_takeout_maps_lists_status_and_subtasks() {
+        let text = include_str!("../testdata/google-tasks.json");
+        let parsed = parse_google_tasks(text, &DaySettings::default(), now()).unwrap();
+
+        // The deleted task is left out silently, the untitled one is skipped
+        assert_eq!(parsed.tasks.len(), 4);
+        assert_eq!(parsed.skipped, 1);
+
+        let kitchen = find(&parsed.tasks, "Renovate kitchen");
+        assert_eq!(kitchen.tags.as_deref(), Some(&["Home".to_string()][..]));
+        assert_eq!(kitchen.notes.as_deref(), Some("Get three quotes"));
+        // Only the date counts; the time is the default due time
+        assert_eq!(kitchen.due_at, clock::local("2024-06-10 23:59"));
+
+        let tiles = find(&parsed.tasks, "Pick tiles");
+        assert_eq!(tiles.notes.as_deref(), Some("parent: Renovate kitchen"));
+
+        let books = find(&parsed.tasks, "Return library books");
+        assert_eq!(books.status, TaskStatus::Done);
+        let completed = DateTime::parse_from_rfc3339("2024-05-18T09:12:00Z").unwrap();
+        assert_eq!(books.completed_at, Some(completed));
+        assert_eq!(kitchen.completed_at, None);
+    }
+
+    #[test]
+    fn google_tasks_without_a_due_date_are_due_in_a_week_and_reported() {
+        let text = include_str!("../testdata/google-tasks.json");
+        let parsed = parse_google_tasks(text, &DaySettings::default(), now()).unwrap();
+
+        let report = find(&parsed.tasks, "Write quarterly report");
+        assert_eq!(report.due_at, clock::local("2024-05-27 23:59"));
+        assert_eq!(report.tags.as_deref(), Some(&["Work".to_string()][..]));
+        assert!(
+            parsed.warnings.iter().any(|w| w == "\"Write quarterly report\": no due date, set to 2024-05-27"),
+            "{:?}",
+            parsed.warnings
+        );
+    }
+
+    #[test]
+    fn google_tasks_dry_run_reports_without_adding() {
+        let text = include_str!("../testdata/google-tasks.json");
+        let parsed = parse_google_tasks(text, &DaySettings::default(), now()).unwrap();
+        let mut existing = Vec::new();
+
+        let report = merge_into(&mut existing, parsed, true, false);
+        assert!(report.dry_run);
+        assert_eq!(report.imported, 4);
+        assert!(existing.is_empty());
+        // List names pass through the same tag cleanup as the task API
+        let kitchen = find(&report.tasks, "Renovate kitchen");
+        assert_eq!(kitchen.tags.as_deref(), Some(&["home".to_string()][..]));
+    }
+
+    #[test]
+    fn google_tasks_without_lists_are_rejected() {
+        assert!(parse_google_tasks("{}", &DaySettings::default(), now()).is_err());
+    }
+
+    fn tw_fixture() -> ParsedImport {
+        parse_taskwarrior(include_str!("../testdata/taskwarrior.json"), &DaySettings::default(), now()).unwrap()
+    }
+
+    fn utc(text: &str) -> DateTime<FixedOffset> {
+        parse_tw_date(text).unwrap()
+    }
+
+    #[test]
+    fn taskwarrior_export_maps_every_field() {
+        let parsed = tw_fixture();
+        assert_eq!(parsed.tasks.len(), 3);
+        // deleted and recurring are skipped silently, the undated one with a warning
+        assert_eq!(parsed.skipped, 3);
+        assert!(parsed.warnings.iter().any(|w| w.contains("\"No due date\"")));
+        assert!(parsed.warnings.iter().any(|w| w == "ignored 1 unknown (UDA) field(s)"));
+
+        let passport = find(&parsed.tasks, "Renew passport");
+        assert_eq!(passport.id.to_string(), "5f1d6c3e-2a7b-4c1e-9d3a-8b6f0e2c4a11");
+        assert_eq!(passport.due_at, utc("20240610T070000Z"));
+        assert_eq!(passport.created_at, utc("20240501T090000Z"));
+        assert_eq!(passport.priority, 5);
+        assert_eq!(passport.status, TaskStatus::Todo);
+        assert_eq!(passport.tags, Some(vec!["errands".to_string(), "admin".to_string()]));
+        assert_eq!(passport.notes.as_deref(), Some("bring old passport\nphoto booth at station"));
+
+        let review = find(&parsed.tasks, "Review PR");
+        assert_eq!((review.priority, review.status.clone()), (3, TaskStatus::InProgress));
+
+        let taxes = find(&parsed.tasks, "File taxes");
+        assert_eq!((taxes.priority, taxes.status.clone()), (1, TaskStatus::Done));
+        assert_eq!(taxes.completed_at, Some(utc("20240412T180000Z")));
+    }
+
+    #[test]
+    fn taskwarrior_line_format_is_accepted() {
+        let text = "{\"description\":\"A\",\"due\":\"20240610T070000Z\",\"status\":\"pending\"},\n\
+                    {\"description\":\"B\",\"due\":\"2024-06-11\",\"status\":\"pending\"}\n";
+        let parsed = parse_taskwarrior(text, &DaySettings::default(), now()).unwrap();
+        assert_eq!(parsed.tasks.len(), 2);
+        assert!(parse_taskwarrior("{nope", &DaySettings::default(), now()).unwrap_err().contains("line 1"));
+    }
+
+    #[test]
+    fn taskwarrior_round_trip_preserves_tasks() {
+        let tasks = tw_fixture().tasks;
+        let exported = serde_json::to_string(&export_taskwarrior(&tasks)).unwrap();
+        let back = parse_taskwarrior(&exported, &DaySettings::default(), now()).unwrap();
+        assert!(back.warnings.is_empty(), "{:?}", back.warnings);
+        assert_eq!(back.tasks.len(), tasks.len());
+        for (a, b) in tasks.iter().zip(&back.tasks) {
+            assert_eq!(a.id, b.id);
+            assert_eq!(a.title, b.title);
+            assert_eq!(a.due_at, b.due_at);
+            assert_eq!(a.created_at, b.created_at);
+            assert_eq!(a.priority, b.priority);
+            assert_eq!(a.status, b.status);
+            assert_eq!(a.completed_at, b.completed_at);
+            assert_eq!(a.tags, b.tags);
+            assert_eq!(a.notes, b.notes);
+        }
+    }
+
+    #[test]
+    fn taskwarrior_reimport_after_export_is_a_no_op() {
+        let mut existing = Vec::new();
+        merge_into(&mut existing, tw_fixture(), false, false);
+        assert_eq!(existing.len(), 3);
+
+        let exported = serde_json::to_string(&export_taskwarrior(&existing)).unwrap();
+        let parsed = parse_taskwarrior(&exported, &DaySettings::default(), now()).unwrap();
+        let report = merge_into(&mut existing, parsed, false, false);
+        assert_eq!(report.imported, 0);
+        assert_eq!(report.id_conflicts.len(), 3);
+        assert_eq!(existing.len(), 3);
+    }
+
+    #[test]
+    fn imports_report_possible_duplicates_per_item() {
+        let due = clock::local("2024-06-03 09:00");
+        let existing_task = test_task("Pay rent", due);
+        let incoming = || ParsedImport {
+            tasks: vec![test_task("pay  RENT", due + chrono::Duration::hours(3)), test_task("Pay rent", due + chrono::Duration::days(1))],
+            ..ParsedImport::default()
+        };
+
+        let mut existing = vec![existing_task.clone()];
+        let listed = merge_into(&mut existing, incoming(), true, false);
+        assert_eq!(listed.imported, 2);
+        assert_eq!(listed.possible_duplicates.len(), 1);
+        assert_eq!(listed.possible_duplicates[0].existing_id, existing_task.id);
+        assert_eq!(listed.possible_duplicates[0].incoming_title, "pay  RENT");
+
+        let deduped = merge_into(&mut existing, incoming(), false, true);
+        assert_eq!((deduped.imported, deduped.skipped), (1, 1));
+        assert_eq!(existing.len(), 2);
+    }
+
+    #[test]
+    fn dry_run_groups_new_tasks_id_conflicts_and_possible_duplicates() {
+        let due = clock::local("2024-06-03 09:00");
+        let rent = test_task("Pay rent", due);
+        let mut existing = vec![rent.clone()];
+
+        let mut same_id = test_task("Renamed elsewhere", due);
+        same_id.id = rent.id;
+        let parsed = ParsedImport {
+            tasks: vec![
+                same_id.clone(),
+                test_task("PAY RENT", due + chrono::Duration::hours(8)),
+                test_task("Pay rent", clock::local("2025-06-03 09:00")),
+                test_task("Call bank", due),
+            ],
+            ..ParsedImport::default()
+        };
+        let report = merge_into(&mut existing, parsed, true, false);
+
+        assert_eq!(existing.len(), 1, "dry run writes nothing");
+        let created: Vec<&str> = report.tasks.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(created, ["PAY RENT", "Pay rent", "Call bank"]);
+        let conflicts: Vec<(Uuid, &str)> = report.id_conflicts.iter().map(|c| (c.existing_id, c.incoming_title.as_str())).collect();
+        assert_eq!(conflicts, [(rent.id, "Renamed elsewhere")]);
+        let similar: Vec<(&str, &str)> = report
+            .possible_duplicates
+            .iter()
+            .map(|d| (d.incoming_title.as_str(), d.existing_title.as_str()))
+            .collect();
+        assert_eq!(similar, [("PAY RENT", "Pay rent")]);
+        assert_eq!((report.imported, report.skipped), (3, 1));
+    }
+}
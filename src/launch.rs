@@ -0,0 +1,101 @@
+// --------------------------------------------------
+// Startup conveniences for the server binary.
+//
+// - --open: launch the system browser at the app URL
+// - Non-loopback host: print the URL as a terminal QR code
+//   so it can be opened from a phone on the same network
+//
+// Both are best-effort: a failure prints a warning and the
+// server keeps starting.
+// --------------------------------------------------
+
+use std::io;
+use std::net::{IpAddr, UdpSocket};
+
+use qrcode::render::unicode::Dense1x2;
+use qrcode::QrCode;
+
+// Open `url` with `spawn` (open::that_detached in main.rs).
+// Passed in so the launcher can be swapped out.
+pub fn open_browser(url: &str, spawn: impl FnOnce(&str) -> io::Result<()>) {
+    if let Err(e) = spawn(url) {
+        eprintln!("  Warning: could not open a browser ({e}); visit {url}");
+    }
+}
+
+// Whether `host` only accepts connections from this machine.
+pub fn is_loopback(host: &str) -> bool {
+    match host.parse::<IpAddr>() {
+        Ok(ip) => ip.is_loopback(),
+        Err(_) => host.eq_ignore_ascii_case("localhost"),
+    }
+}
+
+// Host other devices can reach: for 0.0.0.0 / :: the address of
+// the interface with the default route, otherwise `host` itself.
+pub fn reachable_host(host: &str) -> String {
+    let unspecified = host.parse::<IpAddr>().is_ok_and(|ip| ip.is_unspecified());
+    if !unspecified {
+        return host.to_string();
+    }
+    // Connecting a UDP socket sends nothing; it only selects a route
+    let local = UdpSocket::bind("0.0.0.0:0")
+        .and_then(|s| s.connect("192.0.2.1:80").map(|_| s))
+        .and_then(|s| s.local_addr());
+    match local {
+        Ok(addr) => addr.ip().to_string(),
+        Err(_) => host.to_string(),
+    }
+}
+
+// Print `url` as a QR code made of half-block characters.
+pub fn print_qr(url: &str) {
+    match QrCode::new(url.as_bytes()) {
+        Ok(code) => {
+            let art = code.render::<Dense1x2>().quiet_zone(true).build();
+            println!("  Scan to open {url}:\n{art}");
+        }
+        Err(e) => eprintln!("  Warning: could not render QR code ({e})"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn open_browser_passes_the_url_to_the_launcher() {
+        let opened = RefCell::new(Vec::new());
+        open_browser("http://127.0.0.1:3000/", |u| {
+            opened.borrow_mut().push(u.to_string());
+            Ok(())
+        });
+        assert_eq!(opened.into_inner(), ["http://127.0.0.1:3000/"]);
+    }
+
+    #[test]
+    fn open_browser_survives_an_unsupported_platform() {
+        // Returns normally: the failure is only a warning
+        open_browser("http://127.0.0.1:3000/", |_| {
+            Err(io::Error::new(io::ErrorKind::Unsupported, "no browser on this platform"))
+        });
+    }
+
+    #[test]
+    fn loopback_hosts() {
+        for host in ["127.0.0.1", "127.1.2.3", "::1", "localhost", "LOCALHOST"] {
+            assert!(is_loopback(host), "{host}");
+        }
+        for host in ["0.0.0.0", "::", "192.168.1.20", "example.com"] {
+            assert!(!is_loopback(host), "{host}");
+        }
+    }
+
+    #[test]
+    fn specific_hosts_are_reachable_as_given() {
+        assert_eq!(reachable_host("192.168.1.20"), "192.168.1.20");
+        assert_eq!(reachable_host("example.com"), "example.com");
+        assert!(!reachable_host("0.0.0.0").is_empty());
+    }
+}
@@ -4,19 +4,27 @@ Module was independently written from HTTP / Axum for testing
 */
 
 
-use chrono::{DateTime, Duration, FixedOffset, NaiveDate, TimeZone};
-use crate::models::{Task, TaskStatus, DaySettings};
+use std::collections::HashMap;
+
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveTime, Weekday};
+use serde::Serialize;
+use crate::clock;
+use icu_normalizer::ComposingNormalizer;
+
+use crate::collate::TitleOrder;
+use crate::models::{DueBoundary, PlanLock, Task, TaskStatus, DaySettings, ScorerKind, UrgencyMode};
+use crate::validation;
 
 
 // Internal representation of single task after scoring
 //     not exposed through API directly
+//     borrows the task from the caller's list instead of cloning it
 #[derive(Debug, Clone)]
-pub struct ScoredTask {
-    pub task: Task,
+pub struct ScoredTask<'a> {
+    pub task: &'a Task,
     pub is_overdue: bool,   // determine whether the task is overdue
-    pub urgency: i64,        // 0..5
-    pub duration_score: i64, // 1..5
-    pub total: i64,          // urgency + priority + duration_score
+    pub is_due_now: bool,   // past due_at but still inside the grace window
+    pub score: ScoreBreakdown, // from the Scorer in use; total decides the order
 }
 
 // Scheduled item placed on today's timeline
@@ -29,42 +37,225 @@ pub struct PlanItem {
     pub end: DateTime<FixedOffset>,     // end time of task
     pub score_breakdown: ScoreBreakdown,    // scoring info
     pub is_overdue: bool,   // whether the task is overdue
+    pub is_due_now: bool,   // past due_at, within settings.overdue_grace_min
+    pub placed_by: PlacedBy, // which build_today_plan pass placed it
+}
+
+// Which pass of build_today_plan placed an item
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlacedBy {
+    ScorePass,    // score order
+    DeadlinePass, // due on the plan date, given reserved time after losing on score
+    Locked,       // kept at its PlanLock time, never moved
+}
+
+impl PlacedBy {
+    // Stable code returned by the API.
+    pub fn code(self) -> &'static str {
+        match self {
+            PlacedBy::ScorePass => "score_pass",
+            PlacedBy::DeadlinePass => "deadline_pass",
+            PlacedBy::Locked => "locked",
+        }
+    }
+}
+
+// A ScorePass item for tests; times are "YYYY-MM-DD HH:MM" local.
+#[cfg(test)]
+pub fn test_item(title: &str, start: &str, end: &str) -> PlanItem {
+    PlanItem {
+        task_id: uuid::Uuid::new_v4().to_string(),
+        title: title.to_string(),
+        start: clock::local(start),
+        end: clock::local(end),
+        score_breakdown: ScoreBreakdown {
+            urgency: 5,
+            urgency_mode: UrgencyBasis::Rolling,
+            priority: 3,
+            duration_score: 5,
+            total: 13,
+        },
+        is_overdue: false,
+        is_due_now: false,
+        placed_by: PlacedBy::ScorePass,
+    }
 }
 
 // Logic of how a task's score is calculated
 #[derive(Debug, Clone)]
 pub struct ScoreBreakdown {
     pub urgency: i64,
+    pub urgency_mode: UrgencyBasis, // how urgency was computed
     pub priority: i64,
     pub duration_score: i64,
     pub total: i64,
 }
 
-// Task that cannot be scheduled today
+// How a ScoreBreakdown's urgency was computed: the urgency_mode
+// setting for DefaultScorer, or the scorer's own method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UrgencyBasis {
+    Rolling,
+    Calendar,
+    DeadlinePressure, // DeadlinePressureScorer ignores urgency_mode
+}
+
+impl From<UrgencyMode> for UrgencyBasis {
+    fn from(mode: UrgencyMode) -> Self {
+        match mode {
+            UrgencyMode::Rolling => UrgencyBasis::Rolling,
+            UrgencyMode::Calendar => UrgencyBasis::Calendar,
+        }
+    }
+}
+
+// Why a task could not be scheduled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnplannedReason {
+    InsufficientTime,
+    InvalidDuration,
+    ExceedsDayWindow, // longer than day_end - day_start, never fits whole
+    CrossesMidnight,  // would end on the next date (clamp_to_date, see resolve_window)
+}
+
+impl UnplannedReason {
+    // Stable code returned by the API.
+    pub fn code(self) -> &'static str {
+        match self {
+            UnplannedReason::InsufficientTime => "insufficient_time",
+            UnplannedReason::InvalidDuration => "invalid_duration",
+            UnplannedReason::ExceedsDayWindow => "exceeds_day_window",
+            UnplannedReason::CrossesMidnight => "crosses_midnight",
+        }
+    }
+}
+
+// Task that cannot be scheduled today, with the scoring it lost on
 #[derive(Debug, Clone)]
 pub struct UnplannedItem {
     pub task_id: String,
-    pub reason: String, // "insufficient_time" / "invalid_duration"
+    pub title: String,
+    pub duration_min: i64,
+    pub score_breakdown: ScoreBreakdown,
+    pub is_overdue: bool,
+    pub is_due_now: bool,
+    pub reason: UnplannedReason,
+    // InsufficientTime only: minutes beyond the time that was left when
+    // the task was tried, and when it would have ended if placed anyway
+    pub minutes_short: Option<i64>,
+    pub would_end_at: Option<DateTime<FixedOffset>>,
 }
 
 // Select tasks that are relevant for today's plan.
+// Overdue: now is past due_at + settings.overdue_grace_min.
+// The single overdue predicate; use it instead of comparing due_at.
+pub fn is_overdue(due_at: DateTime<FixedOffset>, now: DateTime<FixedOffset>, settings: &DaySettings) -> bool {
+    let grace = Duration::try_minutes(settings.overdue_grace_min.max(0)).unwrap_or(Duration::MAX);
+    due_at.checked_add_signed(grace).is_some_and(|limit| now > limit)
+}
+
+// Due now: past due_at but still inside the grace window.
+pub fn is_due_now(due_at: DateTime<FixedOffset>, now: DateTime<FixedOffset>, settings: &DaySettings) -> bool {
+    now > due_at && !is_overdue(due_at, now, settings)
+}
+
+// Calendar date a task is due on, in the configured time zone.
+// A due_at at exactly 00:00:00 counts for the date before under
+// DueBoundary::InclusiveNextMidnight, for its own date under Strict.
+pub fn due_date(due_at: &DateTime<FixedOffset>, settings: &DaySettings) -> NaiveDate {
+    let date = clock::local_date(due_at);
+    let at_midnight = clock::to_local(due_at).time() == NaiveTime::MIN;
+    match settings.due_boundary {
+        DueBoundary::InclusiveNextMidnight if at_midnight => date - Duration::days(1),
+        DueBoundary::InclusiveNextMidnight | DueBoundary::Strict => date,
+    }
+}
+
+// Whether a task is due on `date` (the single due-on-date predicate):
+// - due_date equals `date`
+// - with an overnight window, also due after midnight but not after
+//   day_end
+pub fn is_due_on(due_at: DateTime<FixedOffset>, date: NaiveDate, settings: &DaySettings) -> bool {
+    let due = due_date(&due_at, settings);
+    let due_tonight = || {
+        day_window(date, settings)
+            .filter(|_| is_overnight(settings))
+            .is_some_and(|(_, end)| due == date + Duration::days(1) && due_at <= end)
+    };
+    due == date || due_tonight()
+}
+
+// Shared by the planner and GET /api/tasks so both agree.
 //
 // Rules:
 // - Task status must not be Done
-// - Task must be either overdue OR due today
-pub fn relevant_tasks(tasks: &[Task], date: NaiveDate, now: DateTime<FixedOffset>) -> Vec<Task> {
+// - Task must be either past due (overdue or due now) OR due on
+//   `date` (is_due_on; past due means strictly after due_at)
+// - A task locked to a time on `date` (plan_date of the lock start)
+//   is relevant whatever its due date
+pub fn relevant_tasks<'a>(
+    tasks: &'a [Task],
+    settings: &DaySettings,
+    date: NaiveDate,
+    now: DateTime<FixedOffset>,
+) -> Vec<&'a Task> {
     tasks
         .iter()
         .filter(|t| t.status != TaskStatus::Done)
         .filter(|t| {
-            let overdue = now > t.due_at;
-            let due_today = t.due_at.date_naive() == date;
-            overdue || due_today
+            let past_due = is_overdue(t.due_at, now, settings) || is_due_now(t.due_at, now, settings);
+            let locked_here = t.locked.is_some_and(|l| plan_date(settings, l.start) == date);
+            past_due || is_due_on(t.due_at, date, settings) || locked_here
         })
-        .cloned()
         .collect()
 }
 
+// Title compared for duplicates: Unicode NFKC (so "é" typed either way,
+// or full-width letters, compare equal), case-insensitive, whitespace collapsed.
+pub fn normalize_title(title: &str) -> String {
+    let nfkc = ComposingNormalizer::new_nfkc().normalize(title);
+    nfkc.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+// Open task that `title` + `due_at` would duplicate:
+// equal normalized title and due on the same local calendar date.
+pub fn find_duplicate<'a>(
+    tasks: &'a [Task],
+    title: &str,
+    due_at: DateTime<FixedOffset>,
+) -> Option<&'a Task> {
+    let key = normalize_title(title);
+    let date = clock::local_date(&due_at);
+    tasks.iter().find(|t| {
+        t.status != TaskStatus::Done
+            && clock::local_date(&t.due_at) == date
+            && normalize_title(&t.title) == key
+    })
+}
+
+// Tag list after removing `remove` and appending `add`.
+//
+// - Matching is by validation::tag_key (any case, Unicode included);
+//   kept tags keep their original spelling
+// - A tag in both lists is removed (remove wins)
+// - Duplicates collapse to the first occurrence, order is preserved
+// - An empty result is None
+pub fn edit_tags(tags: Option<&[String]>, add: &[String], remove: &[String]) -> Option<Vec<String>> {
+    let removed: Vec<String> = remove.iter().map(|r| validation::tag_key(r)).collect();
+    let mut keys: Vec<String> = Vec::new();
+    let mut out: Vec<String> = Vec::new();
+    for t in tags.unwrap_or_default().iter().chain(add) {
+        let key = validation::tag_key(t);
+        if !removed.contains(&key) && !keys.contains(&key) {
+            keys.push(key);
+            out.push(t.clone());
+        }
+    }
+    (!out.is_empty()).then_some(out)
+}
+
 // urgency (0..5):
 // overdue -> 5
 // 0-1 day:5, 1-2:4, 2-3:3, 3-4:2, 4-5:1, >=5:0
@@ -78,7 +269,7 @@ pub fn urgency_score(due_at: DateTime<FixedOffset>, now: DateTime<FixedOffset>)
         return 5;
     }
     let day = 24 * 60 * 60;
-    if secs < 1 * day { 5 }
+    if secs < day { 5 }
     else if secs < 2 * day { 4 }
     else if secs < 3 * day { 3 }
     else if secs < 4 * day { 2 }
@@ -86,6 +277,31 @@ pub fn urgency_score(due_at: DateTime<FixedOffset>, now: DateTime<FixedOffset>)
     else { 0 }
 }
 
+// Calendar-day urgency (0..5):
+// overdue -> 5
+// due today:5, tomorrow:4, in 2 days:3, 3:2, 4:1, >=5:0
+// Days are counted between calendar dates in the configured
+// time zone, so the time of day does not matter.
+pub fn calendar_urgency_score(due_at: DateTime<FixedOffset>, now: DateTime<FixedOffset>) -> i64 {
+    if now > due_at {
+        return 5;
+    }
+    let days = (clock::local_date(&due_at) - clock::local_date(&now)).num_days();
+    (5 - days).clamp(0, 5)
+}
+
+// Urgency according to the configured mode.
+pub fn urgency_for_mode(
+    mode: UrgencyMode,
+    due_at: DateTime<FixedOffset>,
+    now: DateTime<FixedOffset>,
+) -> i64 {
+    match mode {
+        UrgencyMode::Rolling => urgency_score(due_at, now),
+        UrgencyMode::Calendar => calendar_urgency_score(due_at, now),
+    }
+}
+
 // Compute duration score based on estimated task length.
 //
 // Shorter tasks are prioritized:
@@ -105,48 +321,121 @@ pub fn duration_score(duration_min: i64) -> i64 {
     6 - bucket
 }
 
-// Score all tasks and sort them by priority.
+// Ranks one task for the plan. Higher total is planned first.
+//
+// Only the score is pluggable: filtering, tie-breaking and
+// placement are the same whichever scorer is used.
+pub trait Scorer: Sync {
+    // Stable code returned by the API (matches ScorerKind in settings).
+    fn name(&self) -> &'static str;
+    fn score(&self, task: &Task, now: DateTime<FixedOffset>, settings: &DaySettings) -> ScoreBreakdown;
+}
+
+// urgency (settings.urgency_mode) + priority + duration score
+pub struct DefaultScorer;
+
+impl Scorer for DefaultScorer {
+    fn name(&self) -> &'static str {
+        "default"
+    }
+
+    fn score(&self, task: &Task, now: DateTime<FixedOffset>, settings: &DaySettings) -> ScoreBreakdown {
+        let urgency = urgency_for_mode(settings.urgency_mode, task.due_at, now);
+        let duration_score = duration_score(task.duration_min);
+        ScoreBreakdown {
+            urgency,
+            urgency_mode: settings.urgency_mode.into(),
+            priority: task.priority,
+            duration_score,
+            total: urgency + task.priority + duration_score,
+        }
+    }
+}
+
+// Like DefaultScorer, but urgency follows how tight the deadline is for
+// the work left instead of whole-day buckets:
+//     slack = hours until due / hours of work (duration_min, at least 1 min)
+//     urgency = 5 / slack, rounded and clamped to 0..5
+// A task that cannot be finished before due_at (slack <= 1), or is
+// overdue, gets 5; a 1h task due in 2h gets 3; in 10h, 1; in 11h, 0.
+pub struct DeadlinePressureScorer;
+
+impl DeadlinePressureScorer {
+    pub fn urgency(due_at: DateTime<FixedOffset>, now: DateTime<FixedOffset>, duration_min: i64) -> i64 {
+        let left_min = (due_at - now).num_seconds() as f64 / 60.0;
+        let work_min = duration_min.max(1) as f64;
+        let slack = left_min / work_min;
+        if slack <= 1.0 {
+            return 5;
+        }
+        ((5.0 / slack).round() as i64).clamp(0, 5)
+    }
+}
+
+impl Scorer for DeadlinePressureScorer {
+    fn name(&self) -> &'static str {
+        "deadline_pressure"
+    }
+
+    fn score(&self, task: &Task, now: DateTime<FixedOffset>, _settings: &DaySettings) -> ScoreBreakdown {
+        let urgency = Self::urgency(task.due_at, now, task.duration_min);
+        let duration_score = duration_score(task.duration_min);
+        ScoreBreakdown {
+            urgency,
+            urgency_mode: UrgencyBasis::DeadlinePressure,
+            priority: task.priority,
+            duration_score,
+            total: urgency + task.priority + duration_score,
+        }
+    }
+}
+
+// The scorer selected by settings.scorer.
+pub fn scorer_for(kind: ScorerKind) -> &'static dyn Scorer {
+    match kind {
+        ScorerKind::Default => &DefaultScorer,
+        ScorerKind::DeadlinePressure => &DeadlinePressureScorer,
+    }
+}
+
+// Score all tasks with `scorer` and sort them by priority.
 //
 // Sorting rules:
 // 1) Higher total score first
-// 2) If tied, alphabetical order by title
-pub fn score_and_sort(tasks: Vec<Task>, now: DateTime<FixedOffset>) -> Vec<ScoredTask> {
+// 2) If tied, alphabetical order by title (settings.sort_locale collation)
+// 3) Then by task id, so the order never depends on storage order
+pub fn score_and_sort<'a>(
+    tasks: Vec<&'a Task>,
+    now: DateTime<FixedOffset>,
+    settings: &DaySettings,
+    scorer: &dyn Scorer,
+) -> Vec<ScoredTask<'a>> {
     let mut scored: Vec<ScoredTask> = tasks
         .into_iter()
-        .map(|t| {
-            let is_overdue = now > t.due_at;
-            let u = urgency_score(t.due_at, now);
-            let d = duration_score(t.duration_min);
-            let p = t.priority;
-            let total = u + p + d;
-
-            ScoredTask {
-                task: t,
-                is_overdue,
-                urgency: u,
-                duration_score: d,
-                total,
-            }
+        .map(|t| ScoredTask {
+            task: t,
+            is_overdue: is_overdue(t.due_at, now, settings),
+            is_due_now: is_due_now(t.due_at, now, settings),
+            score: scorer.score(t, now, settings),
         })
         .collect();
 
-    // sort: total desc, tie -> title alphabetical asc
+    // sort: total desc, tie -> title alphabetical asc -> id
+    let titles = TitleOrder::new(&settings.sort_locale);
     scored.sort_by(|a, b| {
-        b.total
-            .cmp(&a.total)
-            .then_with(|| a.task.title.to_lowercase().cmp(&b.task.title.to_lowercase()))
+        b.score.total
+            .cmp(&a.score.total)
+            .then_with(|| titles.compare_titles(&a.task.title, &b.task.title))
+            .then_with(|| a.task.id.cmp(&b.task.id))
     });
 
     scored
 }
 
 
-// Parse a "HH:MM" string into a DateTime on the given date.
-fn parse_hhmm_to_today(
-    date: NaiveDate,
-    hhmm: &str,
-    offset: FixedOffset,
-) -> Option<DateTime<FixedOffset>> {
+// Parse a "HH:MM" string into a DateTime on the given date,
+// in the configured time zone (DST gaps/overlaps resolved by clock.rs).
+fn parse_hhmm_to_today(date: NaiveDate, hhmm: &str) -> Option<DateTime<FixedOffset>> {
     let parts: Vec<&str> = hhmm.split(':').collect();
     if parts.len() != 2 {
         return None;
@@ -154,86 +443,2044 @@ fn parse_hhmm_to_today(
     let h: u32 = parts[0].parse().ok()?;
     let m: u32 = parts[1].parse().ok()?;
     let naive = date.and_hms_opt(h, m, 0)?;
-    offset.from_local_datetime(&naive).single()
+    Some(clock::resolve_local(naive))
+}
+
+
+
+// Whether day_end falls on the next calendar date (e.g. 18:00-02:00).
+// A day_end equal to day_start means a full 24 hour window.
+pub fn is_overnight(settings: &DaySettings) -> bool {
+    let parse = |s: &str| NaiveTime::parse_from_str(s, "%H:%M").ok();
+    match (parse(&settings.day_start), parse(&settings.day_end)) {
+        (Some(start), Some(end)) => end <= start,
+        _ => false,
+    }
+}
+
+// Start and end of the day window belonging to `date`.
+// An overnight day_end is placed on the following date.
+pub fn day_window(
+    date: NaiveDate,
+    settings: &DaySettings,
+) -> Option<(DateTime<FixedOffset>, DateTime<FixedOffset>)> {
+    let start = parse_hhmm_to_today(date, &settings.day_start)?;
+    let end_date = if is_overnight(settings) { date + Duration::days(1) } else { date };
+    let end = parse_hhmm_to_today(end_date, &settings.day_end)?;
+    Some((start, end))
+}
+
+// Date whose day window `now` belongs to.
+// Between midnight and an overnight day_end this is still the previous date.
+pub fn plan_date(settings: &DaySettings, now: DateTime<FixedOffset>) -> NaiveDate {
+    let today = clock::local_date(&now);
+    let yesterday = today - Duration::days(1);
+    match day_window(yesterday, settings) {
+        Some((_, end)) if is_overnight(settings) && now < end => yesterday,
+        _ => today,
+    }
+}
+
+// Length of the configured day window in minutes.
+// Used when no explicit available_min is given (e.g. scheduled notifications).
+pub fn day_window_min(settings: &DaySettings) -> i64 {
+    let parse = |s: &str| NaiveTime::parse_from_str(s, "%H:%M").ok();
+    match (parse(&settings.day_start), parse(&settings.day_end)) {
+        (Some(start), Some(end)) if end > start => (end - start).num_minutes(),
+        // Overnight: the window wraps past midnight
+        (Some(start), Some(end)) => (end - start).num_minutes() + 24 * 60,
+        _ => 0,
+    }
+}
+
+// Whether a task is longer than the configured day window, so no
+// plan can ever hold it (unusable settings: never).
+pub fn exceeds_day_window(duration_min: i64, settings: &DaySettings) -> bool {
+    let window = day_window_min(settings);
+    window > 0 && duration_min > window
+}
+
+// Where a resolved window value came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowSource {
+    Settings, // day_start / day_end
+    Now,      // now is past day_start, so placement starts at now
+    Fallback, // settings unusable: now..now+8h
+    Request,  // available_min as requested
+    Window,   // available_min clamped to the time left in the window
+}
+
+impl WindowSource {
+    pub fn code(self) -> &'static str {
+        match self {
+            WindowSource::Settings => "settings",
+            WindowSource::Now => "now",
+            WindowSource::Fallback => "fallback",
+            WindowSource::Request => "request",
+            WindowSource::Window => "window",
+        }
+    }
+}
+
+// The window build_today_plan actually placed tasks in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlanWindow {
+    pub start: DateTime<FixedOffset>, // first possible start, max(now, day_start)
+    pub end: DateTime<FixedOffset>,   // day_end (next date for overnight windows)
+    pub available_min: i64,           // usable minutes: min(requested, end - start), >= 0
+    pub now: DateTime<FixedOffset>,
+    pub start_source: WindowSource,
+    pub end_source: WindowSource,
+    pub available_source: WindowSource,
+    pub date_end: Option<DateTime<FixedOffset>>, // no item may end after this (clamp_to_date)
+}
+
+// Resolve the placement window for `date` at `now`.
+//
+// clamp_to_date keeps items on `date` (midnight at the end of it is
+// the last possible end), for callers that key plans by date:
+// - clamp, same-day window: items past midnight -> CrossesMidnight.
+//   Only the fallback window (now..now+8h) can reach past midnight.
+// - clamp, overnight window: no clamp. day_end on the next date is
+//   what the user configured, so the overnight window owns those items.
+// - no clamp, either window: items may end on the next date.
+pub fn resolve_window(
+    date: NaiveDate,
+    now: DateTime<FixedOffset>,
+    settings: &DaySettings,
+    available_min: i64,
+    clamp_to_date: bool,
+) -> PlanWindow {
+    let (day_start, end, source) = match day_window(date, settings) {
+        Some((start, end)) => (start, end, WindowSource::Settings),
+        None => (now, now + Duration::hours(8), WindowSource::Fallback),
+    };
+    let overnight = source == WindowSource::Settings && is_overnight(settings);
+    let date_end = match (clamp_to_date, overnight) {
+        (true, false) => Some(clock::at(date + Duration::days(1), NaiveTime::MIN)),
+        (true, true) | (false, _) => None,
+    };
+    let (start, start_source) = if now > day_start { (now, WindowSource::Now) } else { (day_start, source) };
+
+    let left = (end - start).num_minutes().max(0);
+    let (available_min, available_source) = if available_min > left {
+        (left, WindowSource::Window)
+    } else {
+        (available_min.max(0), WindowSource::Request)
+    };
+
+    PlanWindow {
+        start,
+        end,
+        available_min,
+        now,
+        start_source,
+        end_source: source,
+        available_source,
+        date_end,
+    }
+}
+
+// Place one task at `cursor` if it fits before `day_end` and within `remaining`.
+fn place(
+    st: &ScoredTask<'_>,
+    cursor: DateTime<FixedOffset>,
+    day_end: DateTime<FixedOffset>,
+    remaining: i64,
+    date_end: Option<DateTime<FixedOffset>>,
+    settings: &DaySettings,
+    placed_by: PlacedBy,
+) -> Result<PlanItem, Box<UnplannedItem>> {
+    let dur = st.task.duration_min;
+    // Non-positive, or too large to add to the cursor (hand-edited db.json)
+    let end = Duration::try_minutes(dur)
+        .and_then(|d| cursor.checked_add_signed(d))
+        .filter(|_| dur > 0)
+        // Re-express in the local zone so the offset is right past midnight / DST
+        .map(|end| clock::to_local(&end));
+
+    let unplanned = |reason| {
+        let short_of_time = reason == UnplannedReason::InsufficientTime;
+        let room = remaining.min((day_end - cursor).num_minutes()).max(0);
+        Box::new(UnplannedItem {
+            task_id: st.task.id.to_string(),
+            title: st.task.title.clone(),
+            duration_min: dur,
+            score_breakdown: st.score.clone(),
+            is_overdue: st.is_overdue,
+            is_due_now: st.is_due_now,
+            reason,
+            minutes_short: short_of_time.then(|| (dur - room).max(1)),
+            would_end_at: end.filter(|_| short_of_time),
+        })
+    };
+
+    let Some(end) = end else {
+        return Err(unplanned(UnplannedReason::InvalidDuration));
+    };
+    if exceeds_day_window(dur, settings) {
+        return Err(unplanned(UnplannedReason::ExceedsDayWindow));
+    }
+    if remaining <= 0 {
+        return Err(unplanned(UnplannedReason::InsufficientTime));
+    }
+    if end > day_end || dur > remaining {
+        return Err(unplanned(UnplannedReason::InsufficientTime));
+    }
+    if date_end.is_some_and(|limit| end > limit) {
+        return Err(unplanned(UnplannedReason::CrossesMidnight));
+    }
+
+    Ok(PlanItem {
+        task_id: st.task.id.to_string(),
+        title: st.task.title.clone(),
+        start: cursor,
+        end,
+        score_breakdown: st.score.clone(),
+        is_overdue: st.is_overdue,
+        is_due_now: st.is_due_now,
+        placed_by,
+    })
+}
+
+// Free stretch of the window between locked items.
+#[derive(Debug, Clone, Copy)]
+struct Gap {
+    cursor: DateTime<FixedOffset>, // next free start
+    end: DateTime<FixedOffset>,
+}
+
+// Gaps of start..end left free by the locked intervals (sorted by start).
+// Never empty: without locks it is the one gap start..end.
+fn free_gaps(
+    start: DateTime<FixedOffset>,
+    end: DateTime<FixedOffset>,
+    locked: &[(DateTime<FixedOffset>, DateTime<FixedOffset>)],
+) -> Vec<Gap> {
+    let mut gaps = Vec::new();
+    let mut cursor = start;
+    for &(lock_start, lock_end) in locked {
+        if lock_start > cursor && cursor < end {
+            gaps.push(Gap { cursor, end: lock_start.min(end) });
+        }
+        cursor = cursor.max(lock_end);
+    }
+    gaps.push(Gap { cursor, end });
+    gaps
+}
+
+// Whole minutes of start..end covered by the locked intervals (sorted
+// by start). Overlapping locks count once, time outside start..end not at all.
+fn locked_min_within(
+    start: DateTime<FixedOffset>,
+    end: DateTime<FixedOffset>,
+    locked: &[(DateTime<FixedOffset>, DateTime<FixedOffset>)],
+) -> i64 {
+    let mut covered = Duration::zero();
+    let mut cursor = start;
+    for &(lock_start, lock_end) in locked {
+        let from = lock_start.max(cursor);
+        let to = lock_end.min(end);
+        if to > from {
+            covered += to - from;
+        }
+        cursor = cursor.max(lock_end);
+    }
+    covered.num_minutes()
+}
+
+// The gaps with `min` minutes held back at their ends, latest gap first.
+fn reserve_tail(gaps: &[Gap], min: i64) -> Vec<Gap> {
+    let mut left = min;
+    let mut out = gaps.to_vec();
+    for g in out.iter_mut().rev() {
+        let take = left.min((g.end - g.cursor).num_minutes().max(0));
+        g.end -= Duration::minutes(take);
+        left -= take;
+    }
+    out
 }
 
+// Place one task in the first gap it fits, moving that gap's cursor.
+// If none has room, the refusal from the roomiest gap is returned.
+fn place_in_gaps(
+    st: &ScoredTask<'_>,
+    gaps: &mut [Gap],
+    remaining: i64,
+    date_end: Option<DateTime<FixedOffset>>,
+    settings: &DaySettings,
+    placed_by: PlacedBy,
+) -> Result<PlanItem, Box<UnplannedItem>> {
+    let mut refusal: Option<(i64, Box<UnplannedItem>)> = None;
+    for gap in gaps.iter_mut() {
+        match place(st, gap.cursor, gap.end, remaining, date_end, settings, placed_by) {
+            Ok(item) => {
+                gap.cursor = item.end;
+                return Ok(item);
+            }
+            Err(item) => {
+                let room = (gap.end - gap.cursor).num_minutes();
+                if refusal.as_ref().is_none_or(|(best, _)| room > *best) {
+                    refusal = Some((room, item));
+                }
+            }
+        }
+    }
+    Err(refusal.map(|(_, item)| item).expect("free_gaps returns at least one gap"))
+}
 
+// Place tasks in the given order, each in the first gap it fits,
+// skipping ones that do not fit. Also returns the tasks skipped for lack of time.
+fn score_pass<'a>(
+    scored_sorted: Vec<ScoredTask<'a>>,
+    gaps: &mut [Gap],
+    available_min: i64,
+    date_end: Option<DateTime<FixedOffset>>,
+    settings: &DaySettings,
+) -> (Vec<PlanItem>, Vec<UnplannedItem>, Vec<ScoredTask<'a>>) {
+    let mut remaining = available_min;
+    let mut plan: Vec<PlanItem> = Vec::new();
+    let mut unplanned: Vec<UnplannedItem> = Vec::new();
+    let mut short_of_time: Vec<ScoredTask> = Vec::new();
+
+    for st in scored_sorted {
+        match place_in_gaps(&st, gaps, remaining, date_end, settings, PlacedBy::ScorePass) {
+            Ok(item) => {
+                remaining -= st.task.duration_min;
+                plan.push(item);
+            }
+            Err(item) => {
+                let short = item.reason == UnplannedReason::InsufficientTime;
+                unplanned.push(*item);
+                if short {
+                    short_of_time.push(st);
+                }
+            }
+        }
+    }
+    (plan, unplanned, short_of_time)
+}
 
 /// Build today's schedule by placing tasks on a timeline.
 ///
 /// Process:
 /// - Start at max(now, day_start)
-/// - Respect day_end and available minutes
+/// - Respect day_end (next date for overnight windows) and available minutes
 /// - Place tasks sequentially in sorted order
 /// - Tasks that do not fit are marked as unplanned
+///
+/// With settings.prefer_due_today, tasks due on `date` that the score
+/// order leaves out get a second chance: time for them is reserved,
+/// earliest due_at first, the score order fills what is left, and they
+/// are placed at the end. Placement is greedy, so a task skipped once
+/// would never fit later; reserving the time is what lets it in, at the
+/// cost of score-order tasks that no longer fit. Time held back for a
+/// due-today task that then does not fit (the gaps between locks can
+/// be too short) goes back to the score-order tasks left out.
+///
+/// Tasks locked to a time on `date` (Task::locked) are kept exactly
+/// there, like time that is not free: the other tasks fill the gaps
+/// before, between and after them, and the locked minutes still to
+/// come inside the window count against available_min. Locked items are never moved or
+/// dropped, even past the budget or outside the day window
+/// (summarize_plan warns). Tasks locked to another date are left out.
+///
+/// clamp_to_date: see resolve_window.
+///
+/// Items are returned in start order.
+/// Also returns the resolved window, so responses can show what was used.
 pub fn build_today_plan(
-    scored_sorted: Vec<ScoredTask>,
+    scored_sorted: Vec<ScoredTask<'_>>,
     date: NaiveDate,
     now: DateTime<FixedOffset>,
     settings: &DaySettings,
     available_min: i64,
-) -> (Vec<PlanItem>, Vec<UnplannedItem>) {
-    let offset = *now.offset();
+    clamp_to_date: bool,
+) -> (Vec<PlanItem>, Vec<UnplannedItem>, PlanWindow) {
+    let window = resolve_window(date, now, settings, available_min, clamp_to_date);
 
-    let day_start_dt =
-        parse_hhmm_to_today(date, &settings.day_start, offset).unwrap_or(now);
-    let day_end_dt =
-        parse_hhmm_to_today(date, &settings.day_end, offset).unwrap_or(now + Duration::hours(8));
+    let (locked, scored_sorted): (Vec<ScoredTask>, Vec<ScoredTask>) = scored_sorted
+        .into_iter()
+        .filter(|st| st.task.locked.is_none_or(|l| plan_date(settings, l.start) == date))
+        .partition(|st| st.task.locked.is_some());
+    let mut pinned: Vec<PlanItem> = locked
+        .iter()
+        .filter_map(|st| {
+            let lock = st.task.locked?;
+            Some(PlanItem {
+                task_id: st.task.id.to_string(),
+                title: st.task.title.clone(),
+                start: lock.start,
+                end: lock.end,
+                score_breakdown: st.score.clone(),
+                is_overdue: st.is_overdue,
+                is_due_now: st.is_due_now,
+                placed_by: PlacedBy::Locked,
+            })
+        })
+        .collect();
+    pinned.sort_by_key(|p| p.start);
+    let blocked: Vec<_> = pinned.iter().map(|p| (p.start, p.end)).collect();
+    // Only the part still ahead, inside the window, uses up the budget
+    let locked_min = locked_min_within(window.start, window.end, &blocked);
+    let gaps = free_gaps(window.start, window.end, &blocked);
+    let available_min = window.available_min - locked_min;
+    let with_pinned = |mut plan: Vec<PlanItem>| {
+        plan.extend(pinned.iter().cloned());
+        plan.sort_by_key(|p| p.start);
+        plan
+    };
 
-    // start = max(now, day_start)
-    let mut cursor = if now > day_start_dt { now } else { day_start_dt };
+    // First pass: score order from max(now, day_start)
+    let (plan, unplanned, short_of_time) =
+        score_pass(scored_sorted.clone(), &mut gaps.clone(), available_min, window.date_end, settings);
+    if !settings.prefer_due_today {
+        return (with_pinned(plan), unplanned, window);
+    }
 
-    let mut remaining = available_min;
-    let mut plan: Vec<PlanItem> = Vec::new();
-    let mut unplanned: Vec<UnplannedItem> = Vec::new();
+    let mut due_today: Vec<ScoredTask> = short_of_time
+        .into_iter()
+        .filter(|st| is_due_on(st.task.due_at, date, settings))
+        .collect();
+    due_today.sort_by(|a, b| a.task.due_at.cmp(&b.task.due_at).then_with(|| a.task.id.cmp(&b.task.id)));
 
-    for st in scored_sorted {
-        if remaining <= 0 {
-            unplanned.push(UnplannedItem {
-                task_id: st.task.id.to_string(),
-                reason: "insufficient_time".to_string(),
-            });
-            continue;
+    // Reserve time for as many as fit, earliest deadline first
+    let mut reserved_min = 0;
+    let mut reserved: Vec<ScoredTask> = Vec::new();
+    for st in due_today {
+        if st.task.duration_min <= available_min - reserved_min {
+            reserved_min += st.task.duration_min;
+            reserved.push(st);
         }
+    }
+    if reserved.is_empty() {
+        return (with_pinned(plan), unplanned, window);
+    }
 
-        let dur = st.task.duration_min;
-        if dur <= 0 {
-            unplanned.push(UnplannedItem {
-                task_id: st.task.id.to_string(),
-                reason: "invalid_duration".to_string(),
-            });
-            continue;
+    let others: Vec<ScoredTask> = scored_sorted
+        .into_iter()
+        .filter(|st| !reserved.iter().any(|r| r.task.id == st.task.id))
+        .collect();
+    let mut held_back = reserve_tail(&gaps, reserved_min);
+    let (mut plan, mut unplanned, left_out) =
+        score_pass(others, &mut held_back, available_min - reserved_min, window.date_end, settings);
+
+    // The reserved tasks go after the others, into the time held back
+    let mut gaps: Vec<Gap> =
+        gaps.iter().zip(&held_back).map(|(g, h)| Gap { cursor: h.cursor, end: g.end }).collect();
+    let mut remaining = available_min - plan.iter().map(|p| (p.end - p.start).num_minutes()).sum::<i64>();
+    for st in reserved {
+        match place_in_gaps(&st, &mut gaps, remaining, window.date_end, settings, PlacedBy::DeadlinePass) {
+            Ok(item) => {
+                remaining -= st.task.duration_min;
+                plan.push(item);
+            }
+            Err(item) => unplanned.push(*item),
+        }
+    }
+
+    // Whatever the reserved tasks left free is offered back, in score order
+    for st in left_out {
+        if let Ok(item) = place_in_gaps(&st, &mut gaps, remaining, window.date_end, settings, PlacedBy::ScorePass) {
+            remaining -= st.task.duration_min;
+            unplanned.retain(|u| u.task_id != item.task_id);
+            plan.push(item);
         }
+    }
 
-        let end = cursor + Duration::minutes(dur);
+    (with_pinned(plan), unplanned, window)
+}
 
-        if end > day_end_dt || dur > remaining {
-            unplanned.push(UnplannedItem {
-                task_id: st.task.id.to_string(),
-                reason: "insufficient_time".to_string(),
-            });
+// Where every minute of a plan's window went.
+//
+// gross_min = task_min + locked_min + idle_min + unbudgeted_min,
+// each >= 0:
+// - task_min:       placed tasks
+// - locked_min:     locked items inside the window (overlaps once)
+// - idle_min:       within available_min, but nothing left fit there
+// - unbudgeted_min: past available_min (less was requested than the
+//                   window holds); locks past the budget shrink it
+//
+// There are no breaks, padding or rounding between items: tasks are
+// placed back to back for their exact duration_min.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BudgetLedger {
+    pub gross_min: i64, // window end - start, whole minutes
+    pub task_min: i64,
+    pub locked_min: i64,
+    pub idle_min: i64,
+    pub unbudgeted_min: i64,
+}
+
+// Ledger of a plan built by build_today_plan in `window`.
+pub fn budget_ledger(plan: &[PlanItem], window: &PlanWindow) -> BudgetLedger {
+    let gross_min = (window.end - window.start).num_minutes().max(0);
+    let blocked: Vec<_> =
+        plan.iter().filter(|p| p.placed_by == PlacedBy::Locked).map(|p| (p.start, p.end)).collect();
+    let locked_min = locked_min_within(window.start, window.end, &blocked);
+    let task_min: i64 = plan
+        .iter()
+        .filter(|p| p.placed_by != PlacedBy::Locked)
+        .map(|p| (p.end.min(window.end) - p.start.max(window.start)).num_minutes().max(0))
+        .sum();
+    let used = task_min + locked_min;
+    let budget = window.available_min.min(gross_min).max(used);
+    BudgetLedger {
+        gross_min,
+        task_min,
+        locked_min,
+        idle_min: budget - used,
+        unbudgeted_min: gross_min - budget,
+    }
+}
+
+
+// Unplanned work beyond this many minutes past day_end is warned about.
+pub const OVERCOMMIT_WARN_MIN: i64 = 60;
+
+// Something the user should notice about a plan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlanWarning {
+    // Unplanned task due before day_end that is not overdue yet: it will be missed
+    DeadlineMissed { task_id: String },
+    // day_start/day_end could not be used; the plan assumed now..now+8h
+    SettingsFallback,
+    // Unplanned work exceeds the day by more than OVERCOMMIT_WARN_MIN
+    Overcommitted { short_min: i64 },
+    // Locked item outside the day window or overlapping another locked
+    // item; it stays where it was locked
+    LockConflict { task_id: String },
+}
+
+impl PlanWarning {
+    // Stable, machine-readable code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            PlanWarning::DeadlineMissed { .. } => "deadline_missed",
+            PlanWarning::SettingsFallback => "settings_fallback",
+            PlanWarning::Overcommitted { .. } => "overcommitted",
+            PlanWarning::LockConflict { .. } => "lock_conflict",
+        }
+    }
+}
+
+// Headline numbers for a built plan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanSummary {
+    pub finish_at: Option<DateTime<FixedOffset>>, // end of the last item; None if empty
+    pub slack_min: i64,                            // minutes left before day_end
+    pub slack_after_unplanned_min: i64,            // minus unplanned work; < 0 = overcommitted
+    pub completed_today_min: i64,                  // durations of tasks completed on the date
+    pub planned_min: i64,                          // durations of the plan items
+    pub utilization_pct: Option<i64>,              // (completed + planned) / whole day window; None if unusable
+    pub warnings: Vec<PlanWarning>,                // empty for a plan that covers everything
+}
+
+// Summarize a plan built by build_today_plan for `date`.
+//
+// Slack counts from the finish time, or from max(now, day_start)
+// when nothing is planned (never past day_end). Unplanned items
+// with an invalid duration are not counted as missing work.
+//
+// Utilization is over the whole day window, not the part left at
+// `now`: tasks completed on `date` (plan_date of completed_at, by
+// duration_min) count with the plan, so an evening view of a full day
+// is not near 0%.
+// It can pass 100 when finished tasks were quicker than estimated.
+// Warnings are listed in a fixed order: settings, locks, deadlines,
+// overcommitment.
+pub fn summarize_plan(
+    plan: &[PlanItem],
+    unplanned: &[UnplannedItem],
+    tasks: &[Task],
+    date: NaiveDate,
+    now: DateTime<FixedOffset>,
+    settings: &DaySettings,
+) -> PlanSummary {
+    let mut warnings = Vec::new();
+    let window = day_window(date, settings);
+    if window.is_none() {
+        warnings.push(PlanWarning::SettingsFallback);
+    }
+
+    if let Some((day_start, day_end)) = window {
+        let pinned: Vec<&PlanItem> = plan.iter().filter(|p| p.placed_by == PlacedBy::Locked).collect();
+        for p in &pinned {
+            let outside = p.start < day_start || p.end > day_end;
+            let overlaps = pinned.iter().any(|o| o.task_id != p.task_id && o.start < p.end && p.start < o.end);
+            if outside || overlaps {
+                warnings.push(PlanWarning::LockConflict { task_id: p.task_id.clone() });
+            }
+        }
+    }
+
+    let finish_at = plan.last().map(|p| p.end);
+    let slack_min = match window {
+        Some((day_start, day_end)) => {
+            let from = finish_at.unwrap_or_else(|| now.max(day_start).min(day_end));
+            (day_end - from).num_minutes()
+        }
+        None => 0,
+    };
+
+    let short_of_time: Vec<&UnplannedItem> =
+        unplanned.iter().filter(|u| u.reason == UnplannedReason::InsufficientTime).collect();
+    let unplanned_min = short_of_time.iter().fold(0i64, |sum, u| sum.saturating_add(u.duration_min));
+    let slack_after_unplanned_min = slack_min.saturating_sub(unplanned_min);
+
+    if let Some((_, day_end)) = window {
+        let by_id: HashMap<String, &Task> = tasks.iter().map(|t| (t.id.to_string(), t)).collect();
+        for u in short_of_time {
+            if by_id.get(&u.task_id).is_some_and(|t| now <= t.due_at && t.due_at <= day_end) {
+                warnings.push(PlanWarning::DeadlineMissed { task_id: u.task_id.clone() });
+            }
+        }
+    }
+    if slack_after_unplanned_min < -OVERCOMMIT_WARN_MIN {
+        warnings.push(PlanWarning::Overcommitted { short_min: -slack_after_unplanned_min });
+    }
+
+    let completed_today_min = tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::Done)
+        .filter(|t| t.completed_at.is_some_and(|c| plan_date(settings, c) == date))
+        .fold(0i64, |sum, t| sum.saturating_add(t.duration_min.max(0)));
+    let planned_min = plan.iter().map(|p| (p.end - p.start).num_minutes()).sum::<i64>();
+    let window_min = window.map_or(0, |(start, end)| (end - start).num_minutes());
+    let utilization_pct = (window_min > 0).then(|| {
+        let used = completed_today_min.saturating_add(planned_min) as f64;
+        (used * 100.0 / window_min as f64).round() as i64
+    });
+
+    PlanSummary {
+        finish_at,
+        slack_min,
+        slack_after_unplanned_min,
+        completed_today_min,
+        planned_min,
+        utilization_pct,
+        warnings,
+    }
+}
+
+// How completed work compares to the plan at an instant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pace {
+    Ahead,
+    OnTrack,
+    Behind,
+}
+
+impl Pace {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Pace::Ahead => "ahead",
+            Pace::OnTrack => "on_track",
+            Pace::Behind => "behind",
+        }
+    }
+}
+
+// The block running at `now` in a plan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveBlock {
+    pub index: usize,      // into the plan
+    pub elapsed_min: i64,  // whole minutes since start
+    pub remaining_min: i64, // block length minus elapsed_min
+}
+
+// The block coming after `now`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NextBlock {
+    pub index: usize,
+    pub starts_in_min: i64, // whole minutes, rounded down
+}
+
+// Where `now` falls in a plan (see plan_position).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlanPosition {
+    pub active: Option<ActiveBlock>, // None between items, before the first or after the last
+    pub next: Option<NextBlock>,     // first item starting after now
+    pub drift_min: i64,              // ahead_min - behind_min
+    pub pace: Pace,                  // sign of drift_min
+}
+
+// Locate `now` in a plan and compare it with completed tasks.
+//
+// - An item is active from its start (inclusive) to its end (exclusive),
+//   so at an item's start it is active, and at its end the next one is
+// - behind: minutes of items already over whose task is not done
+// - ahead: minutes of done tasks whose items have not started, plus
+//   the rest of the active item if its task is done
+pub fn plan_position(
+    plan: &[PlanItem],
+    now: DateTime<FixedOffset>,
+    is_done: impl Fn(&str) -> bool,
+) -> PlanPosition {
+    let active = plan.iter().position(|p| p.start <= now && now < p.end).map(|index| {
+        let p = &plan[index];
+        let elapsed_min = (now - p.start).num_minutes();
+        ActiveBlock { index, elapsed_min, remaining_min: (p.end - p.start).num_minutes() - elapsed_min }
+    });
+    let next = plan.iter().position(|p| p.start > now).map(|index| NextBlock {
+        index,
+        starts_in_min: (plan[index].start - now).num_minutes(),
+    });
+
+    let minutes = |p: &PlanItem| (p.end - p.start).num_minutes();
+    let behind_min: i64 = plan
+        .iter()
+        .filter(|p| p.end <= now && !is_done(&p.task_id))
+        .map(minutes)
+        .sum();
+    let mut ahead_min: i64 = plan
+        .iter()
+        .filter(|p| p.start > now && is_done(&p.task_id))
+        .map(minutes)
+        .sum();
+    if let Some(a) = active
+        && is_done(&plan[a.index].task_id)
+    {
+        ahead_min += a.remaining_min;
+    }
+
+    let drift_min = ahead_min - behind_min;
+    let pace = match drift_min {
+        d if d > 0 => Pace::Ahead,
+        0 => Pace::OnTrack,
+        _ => Pace::Behind,
+    };
+    PlanPosition { active, next, drift_min, pace }
+}
+
+// Plan size for one available_min value (see simulate_budgets).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BudgetResult {
+    pub available_min: i64,
+    pub scheduled_count: usize,
+    pub scheduled_min: i64,
+    pub unplanned_count: usize,
+}
+
+// Build the plan once per budget over the same scored list,
+// smallest budget first; results come back in that order.
+//
+// Scoring happens once (by the caller); only placement is repeated.
+// Each plan keeps every item the previous (smaller) budget placed,
+// pinned where it was like a locked item, and fills the extra time
+// in score order. Plain greedy placement could let a long task in
+// ahead of several short ones at a larger budget; nesting the plans
+// makes scheduled_count and scheduled_min grow with the budget and
+// unplanned_count shrink.
+pub fn simulate_budgets(
+    scored_sorted: &[ScoredTask<'_>],
+    date: NaiveDate,
+    now: DateTime<FixedOffset>,
+    settings: &DaySettings,
+    budgets: impl IntoIterator<Item = i64>,
+) -> Vec<BudgetResult> {
+    let mut budgets: Vec<i64> = budgets.into_iter().collect();
+    budgets.sort_unstable();
+
+    // task id -> where the previous budget placed it
+    let mut kept: HashMap<String, PlanLock> = HashMap::new();
+    let mut results = Vec::with_capacity(budgets.len());
+    for available_min in budgets {
+        let (pinned, free): (Vec<&ScoredTask>, Vec<&ScoredTask>) =
+            scored_sorted.iter().partition(|st| kept.contains_key(&st.task.id.to_string()));
+        // Earlier items as locked copies of their tasks
+        let locked: Vec<Task> = pinned
+            .iter()
+            .map(|st| Task { locked: kept.get(&st.task.id.to_string()).copied(), ..st.task.clone() })
+            .collect();
+        let scored: Vec<ScoredTask> = free
+            .into_iter()
+            .cloned()
+            .chain(pinned.iter().zip(&locked).map(|(st, task)| ScoredTask { task, ..(*st).clone() }))
+            .collect();
+
+        let (plan, unplanned, _) = build_today_plan(scored, date, now, settings, available_min, true);
+        results.push(BudgetResult {
+            available_min,
+            scheduled_count: plan.len(),
+            scheduled_min: plan.iter().map(|p| (p.end - p.start).num_minutes()).sum(),
+            unplanned_count: unplanned.len(),
+        });
+        kept = plan.into_iter().map(|p| (p.task_id, PlanLock { start: p.start, end: p.end })).collect();
+    }
+    results
+}
+
+// Time an item takes up in one side of a plan diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: DateTime<FixedOffset>,
+    pub end: DateTime<FixedOffset>,
+}
+
+impl Span {
+    pub fn minutes(&self) -> i64 {
+        (self.end - self.start).num_minutes()
+    }
+}
+
+// One item that differs between two plans.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemDiff {
+    pub task_id: String,              // right side's task, or left side's when removed
+    pub left_task_id: Option<String>, // matched by title to a different task
+    pub title: String,
+    pub left: Option<Span>,  // None = added
+    pub right: Option<Span>, // None = removed
+    pub uncertain: bool,     // matched by title only, not by task id
+}
+
+// Headline changes from the left plan to the right one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlanDiffSummary {
+    pub left_count: usize,
+    pub right_count: usize,
+    pub left_min: i64,
+    pub right_min: i64,
+    pub unchanged: usize,
+    pub finish_delta_min: Option<i64>, // right finish - left finish; None if either is empty
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanDiff {
+    pub added: Vec<ItemDiff>,
+    pub removed: Vec<ItemDiff>,
+    pub moved: Vec<ItemDiff>,   // start changed
+    pub resized: Vec<ItemDiff>, // duration changed (may also be in moved)
+    pub summary: PlanDiffSummary,
+}
+
+// Compare two plans item by item.
+//
+// Items are aligned by task id. Left-over items on both sides are
+// then paired by normalized title (e.g. a recurring task replaced by
+// its successor) and flagged uncertain; what is still unpaired is
+// added or removed. Lists follow the right plan's order (removed:
+// the left plan's).
+pub fn diff_plans(left: &[PlanItem], right: &[PlanItem]) -> PlanDiff {
+    let span = |p: &PlanItem| Span { start: p.start, end: p.end };
+    let mut left_open: Vec<&PlanItem> = left.iter().collect();
+    let mut pairs: Vec<(Option<&PlanItem>, &PlanItem, bool)> = Vec::new();
+
+    for r in right {
+        match left_open.iter().position(|l| l.task_id == r.task_id) {
+            Some(i) => pairs.push((Some(left_open.remove(i)), r, false)),
+            None => pairs.push((None, r, false)),
+        }
+    }
+    for pair in pairs.iter_mut().filter(|(l, _, _)| l.is_none()) {
+        let title = normalize_title(&pair.1.title);
+        if let Some(i) = left_open.iter().position(|l| normalize_title(&l.title) == title) {
+            *pair = (Some(left_open.remove(i)), pair.1, true);
+        }
+    }
+
+    let mut out = PlanDiff {
+        added: Vec::new(),
+        removed: Vec::new(),
+        moved: Vec::new(),
+        resized: Vec::new(),
+        summary: PlanDiffSummary {
+            left_count: left.len(),
+            right_count: right.len(),
+            left_min: left.iter().map(|p| span(p).minutes()).sum(),
+            right_min: right.iter().map(|p| span(p).minutes()).sum(),
+            unchanged: 0,
+            finish_delta_min: left
+                .iter()
+                .map(|p| p.end)
+                .max()
+                .zip(right.iter().map(|p| p.end).max())
+                .map(|(l, r)| (r - l).num_minutes()),
+        },
+    };
+    for (l, r, uncertain) in pairs {
+        let entry = ItemDiff {
+            task_id: r.task_id.clone(),
+            left_task_id: l.filter(|l| l.task_id != r.task_id).map(|l| l.task_id.clone()),
+            title: r.title.clone(),
+            left: l.map(span),
+            right: Some(span(r)),
+            uncertain,
+        };
+        let Some(l) = l else {
+            out.added.push(entry);
             continue;
+        };
+        let moved = l.start != r.start;
+        let resized = span(l).minutes() != span(r).minutes();
+        if !moved && !resized {
+            out.summary.unchanged += 1;
+        }
+        if resized {
+            out.resized.push(entry.clone());
+        }
+        if moved {
+            out.moved.push(entry);
+        }
+    }
+    out.removed = left_open
+        .into_iter()
+        .map(|l| ItemDiff {
+            task_id: l.task_id.clone(),
+            left_task_id: None,
+            title: l.title.clone(),
+            left: Some(span(l)),
+            right: None,
+            uncertain: false,
+        })
+        .collect();
+    out
+}
+
+// Run the full planning pipeline for one date:
+// filter relevant tasks -> score and sort -> place on the timeline.
+// clamp_to_date: see resolve_window.
+pub fn plan_for_date(
+    tasks: &[Task],
+    settings: &DaySettings,
+    date: NaiveDate,
+    now: DateTime<FixedOffset>,
+    available_min: i64,
+    clamp_to_date: bool,
+) -> (Vec<PlanItem>, Vec<UnplannedItem>, PlanWindow) {
+    let relevant = relevant_tasks(tasks, settings, date, now);
+    let scored_sorted = score_and_sort(relevant, now, settings, scorer_for(settings.scorer));
+    build_today_plan(scored_sorted, date, now, settings, available_min, clamp_to_date)
+}
+
+// First and last date of the week containing `date`.
+// All week bucketing goes through here so it agrees with settings.week_start.
+pub fn week_bounds(date: NaiveDate, week_start: Weekday) -> (NaiveDate, NaiveDate) {
+    let offset = date.weekday().days_since(week_start);
+    let start = date - Duration::days(i64::from(offset));
+    (start, start + Duration::days(6))
+}
+
+// Plan several consecutive days starting at `start`.
+//
+// Each day uses the full day window as available time, unclamped:
+// a range may run an item into the next day.
+// Tasks placed on an earlier day are removed from the pool,
+// so every task appears at most once across the week.
+pub fn plan_days(
+    tasks: &[Task],
+    settings: &DaySettings,
+    start: NaiveDate,
+    days: i64,
+    now: DateTime<FixedOffset>,
+) -> Vec<(NaiveDate, Vec<PlanItem>)> {
+    let available_min = day_window_min(settings);
+    let mut pool: Vec<Task> = tasks.to_vec();
+    let mut out = Vec::new();
+
+    for i in 0..days {
+        let date = start + Duration::days(i);
+        let (plan, _, _) = plan_for_date(&pool, settings, date, now, available_min, false);
+        pool.retain(|t| !plan.iter().any(|p| p.task_id == t.id.to_string()));
+        out.push((date, plan));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{TestRng, test_task};
+    use std::collections::BTreeSet;
+
+    fn date(text: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
+
+    fn titles(tasks: &[&Task]) -> BTreeSet<String> {
+        tasks.iter().map(|t| t.title.clone()).collect()
+    }
+
+    // Titles the planner placed or reported for `date`.
+    fn planned_titles(tasks: &[Task], settings: &DaySettings, date: NaiveDate, now: DateTime<FixedOffset>) -> BTreeSet<String> {
+        let (plan, unplanned, _) = plan_for_date(tasks, settings, date, now, 24 * 60, false);
+        plan.iter().map(|p| p.title.clone()).chain(unplanned.iter().map(|u| u.title.clone())).collect()
+    }
+
+    #[test]
+    fn relevant_tasks_boundary_at_exactly_now() {
+        let now = clock::local("2024-06-03 10:00");
+        let tomorrow = date("2024-06-04");
+        let tasks = vec![
+            test_task("due at now", now),
+            test_task("due a minute ago", now - Duration::minutes(1)),
+            test_task("due in a minute", now + Duration::minutes(1)),
+        ];
+        let settings = DaySettings::default();
+        let relevant = relevant_tasks(&tasks, &settings, tomorrow, now);
+        // Past due means strictly after due_at
+        assert_eq!(titles(&relevant), BTreeSet::from(["due a minute ago".to_string()]));
+        assert_eq!(planned_titles(&tasks, &settings, tomorrow, now), titles(&relevant));
+    }
+
+    #[test]
+    fn relevant_tasks_boundary_at_midnight_of_the_date() {
+        let now = clock::local("2024-06-03 06:00");
+        let tasks = vec![
+            test_task("midnight starting the 4th", clock::local("2024-06-04 00:00")),
+            test_task("last minute of the 3rd", clock::local("2024-06-03 23:59")),
+            test_task("first minute of the 4th", clock::local("2024-06-04 00:01")),
+        ];
+
+        let strict = DaySettings { due_boundary: DueBoundary::Strict, ..DaySettings::default() };
+        let inclusive = DaySettings { due_boundary: DueBoundary::InclusiveNextMidnight, ..DaySettings::default() };
+        let cases = [
+            (&strict, "2024-06-03", vec!["last minute of the 3rd"]),
+            (&strict, "2024-06-04", vec!["first minute of the 4th", "midnight starting the 4th"]),
+            (&inclusive, "2024-06-03", vec!["last minute of the 3rd", "midnight starting the 4th"]),
+            (&inclusive, "2024-06-04", vec!["first minute of the 4th"]),
+        ];
+        for (settings, day, expected) in cases {
+            let expected: BTreeSet<String> = expected.into_iter().map(str::to_string).collect();
+            let relevant = relevant_tasks(&tasks, settings, date(day), now);
+            assert_eq!(titles(&relevant), expected, "{:?} {day}", settings.due_boundary);
+            assert_eq!(planned_titles(&tasks, settings, date(day), now), expected, "{:?} {day}", settings.due_boundary);
+        }
+    }
+
+    #[test]
+    fn relevant_tasks_skips_done_and_borrows() {
+        let now = clock::local("2024-06-03 10:00");
+        let mut done = test_task("done", now - Duration::hours(2));
+        done.status = TaskStatus::Done;
+        let tasks = vec![done, test_task("open", now - Duration::hours(2))];
+        let relevant = relevant_tasks(&tasks, &DaySettings::default(), date("2024-06-03"), now);
+        assert_eq!(relevant.len(), 1);
+        assert!(std::ptr::eq(relevant[0], &tasks[1]));
+    }
+
+    #[test]
+    fn urgency_modes_around_midnight() {
+        // (now, due_at, rolling, calendar)
+        let table = [
+            ("2024-06-03 23:50", "2024-06-04 00:30", 5, 4),
+            ("2024-06-03 23:50", "2024-06-04 23:00", 5, 4),
+            ("2024-06-03 23:50", "2024-06-05 00:00", 4, 3),
+            ("2024-06-03 23:50", "2024-06-03 23:59", 5, 5),
+            ("2024-06-04 00:10", "2024-06-04 23:59", 5, 5),
+            ("2024-06-04 00:10", "2024-06-05 00:05", 5, 4),
+            ("2024-06-03 23:50", "2024-06-05 00:10", 4, 3),
+            ("2024-06-03 00:00", "2024-06-07 23:59", 1, 1),
+            ("2024-06-03 23:59", "2024-06-08 00:01", 1, 0),
+            ("2024-06-03 12:00", "2024-06-03 11:00", 5, 5), // overdue
+        ];
+        for (now, due, rolling, calendar) in table {
+            let (now, due) = (clock::local(now), clock::local(due));
+            assert_eq!(urgency_for_mode(UrgencyMode::Rolling, due, now), rolling, "rolling {now} -> {due}");
+            assert_eq!(urgency_for_mode(UrgencyMode::Calendar, due, now), calendar, "calendar {now} -> {due}");
         }
+    }
+
+    #[test]
+    fn score_breakdown_reports_the_urgency_mode_used() {
+        let now = clock::local("2024-06-03 23:50");
+        let task = test_task("soon", clock::local("2024-06-04 00:30"));
+        for (mode, urgency) in [(UrgencyMode::Rolling, 5), (UrgencyMode::Calendar, 4)] {
+            let settings = DaySettings { urgency_mode: mode, ..DaySettings::default() };
+            let scored = score_and_sort(vec![&task], now, &settings, &DefaultScorer);
+            assert_eq!(scored[0].score.urgency_mode, UrgencyBasis::from(mode));
+            assert_eq!(scored[0].score.urgency, urgency);
+        }
+    }
+
+    // Tests run in America/New_York: DST starts 2024-03-10, ends 2024-11-03.
+    #[test]
+    fn relevant_tasks_across_spring_forward() {
+        let now = clock::local("2024-03-09 20:00");
+        let tasks = vec![
+            test_task("late on the 10th", clock::local("2024-03-10 23:30")),
+            test_task("midnight starting the 11th", clock::local("2024-03-11 00:00")),
+        ];
+        let settings = DaySettings::default();
+        let on_10th = relevant_tasks(&tasks, &settings, date("2024-03-10"), now);
+        assert_eq!(titles(&on_10th), BTreeSet::from(["late on the 10th".to_string()]));
+        let on_11th = relevant_tasks(&tasks, &settings, date("2024-03-11"), now);
+        assert_eq!(titles(&on_11th), BTreeSet::from(["midnight starting the 11th".to_string()]));
+    }
+
+    #[test]
+    fn parse_hhmm_to_today_resolves_dst_gaps_and_overlaps() {
+        let at = |day: &str, hhmm: &str| parse_hhmm_to_today(date(day), hhmm).map(|dt| dt.to_rfc3339());
+        assert_eq!(at("2024-03-10", "02:30").as_deref(), Some("2024-03-10T03:30:00-04:00"));
+        assert_eq!(at("2024-03-10", "09:00").as_deref(), Some("2024-03-10T09:00:00-04:00"));
+        assert_eq!(at("2024-11-03", "01:30").as_deref(), Some("2024-11-03T01:30:00-04:00"));
+        assert_eq!(at("2024-11-03", "09:00").as_deref(), Some("2024-11-03T09:00:00-05:00"));
+        assert_eq!(at("2024-03-10", "9"), None);
+        assert_eq!(at("2024-03-10", "25:00"), None);
+    }
+
+    #[test]
+    fn day_window_on_dst_days_spans_real_hours() {
+        let settings = DaySettings { day_start: "00:00".to_string(), day_end: "06:00".to_string(), ..DaySettings::default() };
+        let (start, end) = day_window(date("2024-03-10"), &settings).unwrap();
+        assert_eq!((end - start).num_hours(), 5);
+        let (start, end) = day_window(date("2024-11-03"), &settings).unwrap();
+        assert_eq!((end - start).num_hours(), 7);
+    }
+
+    fn night_owl() -> DaySettings {
+        DaySettings { day_start: "18:00".to_string(), day_end: "02:00".to_string(), ..DaySettings::default() }
+    }
+
+    #[test]
+    fn overnight_window_spans_midnight() {
+        let settings = night_owl();
+        assert!(is_overnight(&settings));
+        assert!(crate::validation::validate_settings(&settings).is_ok());
+        assert_eq!(day_window_min(&settings), 8 * 60);
+        let (start, end) = day_window(date("2024-06-03"), &settings).unwrap();
+        assert_eq!(start.to_rfc3339(), "2024-06-03T18:00:00-04:00");
+        assert_eq!(end.to_rfc3339(), "2024-06-04T02:00:00-04:00");
+        // A full-day window is overnight too
+        let full = DaySettings { day_start: "06:00".to_string(), day_end: "06:00".to_string(), ..DaySettings::default() };
+        assert_eq!(day_window_min(&full), 24 * 60);
+    }
+
+    #[test]
+    fn overnight_plan_with_now_before_midnight() {
+        let settings = night_owl();
+        let now = clock::local("2024-06-03 23:00");
+        assert_eq!(plan_date(&settings, now), date("2024-06-03"));
+        let mut long = test_task("across midnight", clock::local("2024-06-03 20:00"));
+        long.duration_min = 120;
+        let (plan, unplanned, window) = plan_for_date(&[long], &settings, date("2024-06-03"), now, 24 * 60, true);
+        assert!(unplanned.is_empty());
+        assert_eq!(window.available_min, 180);
+        assert_eq!(plan[0].start.to_rfc3339(), "2024-06-03T23:00:00-04:00");
+        assert_eq!(plan[0].end.to_rfc3339(), "2024-06-04T01:00:00-04:00");
+    }
 
-        let breakdown = ScoreBreakdown {
-            urgency: st.urgency,
-            priority: st.task.priority,
-            duration_score: st.duration_score,
-            total: st.total,
+    #[test]
+    fn overnight_plan_with_now_after_midnight() {
+        let settings = night_owl();
+        let now = clock::local("2024-06-04 01:00");
+        // Still the night of the 3rd until day_end
+        assert_eq!(plan_date(&settings, now), date("2024-06-03"));
+        assert_eq!(plan_date(&settings, clock::local("2024-06-04 02:00")), date("2024-06-04"));
+
+        let mut tonight = test_task("due tonight", clock::local("2024-06-04 01:30"));
+        tonight.duration_min = 30;
+        let mut too_long = test_task("too long", clock::local("2024-06-04 01:45"));
+        too_long.duration_min = 90;
+        let later = test_task("due after day_end", clock::local("2024-06-04 03:00"));
+        let (plan, unplanned, window) =
+            plan_for_date(&[tonight, too_long, later], &settings, date("2024-06-03"), now, 24 * 60, true);
+
+        assert_eq!(window.available_min, 60);
+        assert_eq!(plan.len(), 1);
+        assert_eq!(plan[0].title, "due tonight");
+        assert_eq!(plan[0].start.to_rfc3339(), "2024-06-04T01:00:00-04:00");
+        assert_eq!(plan[0].end.to_rfc3339(), "2024-06-04T01:30:00-04:00");
+        let unplanned: Vec<_> = unplanned.iter().map(|u| (u.title.as_str(), u.reason)).collect();
+        assert_eq!(unplanned, [("too long", UnplannedReason::InsufficientTime)]);
+    }
+
+    #[test]
+    fn absurd_durations_in_a_crafted_db_are_unplanned_not_a_panic() {
+        let due = clock::local("2024-06-03 12:00");
+        let durations = [("zero", 0), ("negative", -30), ("too long", 1441), ("max", i64::MAX), ("min", i64::MIN)];
+        let tasks: Vec<serde_json::Value> = durations
+            .iter()
+            .map(|&(title, d)| {
+                let mut v = serde_json::to_value(test_task(title, due)).unwrap();
+                v["duration_min"] = serde_json::json!(d);
+                v
+            })
+            .collect();
+        let text = serde_json::json!({ "settings": DaySettings::default(), "tasks": tasks }).to_string();
+        let mut db = crate::store::decode(&text).unwrap().db;
+        db.reindex();
+
+        let now = clock::local("2024-06-03 08:00");
+        let (plan, unplanned, _) = plan_for_date(&db.tasks, &db.settings, date("2024-06-03"), now, 24 * 60, true);
+        assert!(plan.is_empty());
+        let mut reasons: Vec<(&str, UnplannedReason)> = unplanned.iter().map(|u| (u.title.as_str(), u.reason)).collect();
+        reasons.sort_by_key(|(title, _)| *title);
+        assert_eq!(
+            reasons,
+            [
+                ("max", UnplannedReason::InvalidDuration),
+                ("min", UnplannedReason::InvalidDuration),
+                ("negative", UnplannedReason::InvalidDuration),
+                ("too long", UnplannedReason::ExceedsDayWindow),
+                ("zero", UnplannedReason::InvalidDuration),
+            ]
+        );
+        // The summary copes with the same durations
+        let summary = summarize_plan(&plan, &unplanned, &db.tasks, date("2024-06-03"), now, &db.settings);
+        assert_eq!(summary.planned_min, 0);
+    }
+
+    #[test]
+    fn score_ties_are_broken_by_collated_title() {
+        let now = clock::local("2024-06-03 08:00");
+        let due = clock::local("2024-06-03 17:00");
+        let tasks = [test_task("Zebra", due), test_task("Éplucher", due), test_task("eplucher", due)];
+        let settings = DaySettings { sort_locale: "fr".to_string(), ..DaySettings::default() };
+        let scored = score_and_sort(tasks.iter().collect(), now, &settings, &DefaultScorer);
+        let titles: Vec<&str> = scored.iter().map(|st| st.task.title.as_str()).collect();
+        assert_eq!(titles, ["eplucher", "Éplucher", "Zebra"]);
+    }
+
+    #[test]
+    fn find_duplicate_matches_open_tasks_on_the_same_local_date() {
+        let mut done = test_task("Done twin", clock::local("2024-06-03 09:00"));
+        done.status = TaskStatus::Done;
+        let tasks = [test_task("Pay  Rent", clock::local("2024-06-03 09:00")), done];
+        let found = |title: &str, due: &str| find_duplicate(&tasks, title, clock::local(due)).map(|t| t.title.as_str());
+        assert_eq!(found(" pay rent", "2024-06-03 23:59"), Some("Pay  Rent"));
+        assert_eq!(found("ＰＡＹ ＲＥＮＴ", "2024-06-03 00:00"), Some("Pay  Rent"));
+        assert_eq!(found("pay rent", "2024-06-04 00:00"), None);
+        assert_eq!(found("pay rent!", "2024-06-03 09:00"), None);
+        assert_eq!(found("done twin", "2024-06-03 09:00"), None);
+    }
+
+    #[test]
+    fn find_duplicate_tricky_cases() {
+        let tasks = [
+            test_task("Café meeting", clock::local("2024-06-03 09:00")),
+            test_task("회의 준비", clock::local("2024-06-03 10:00")),
+        ];
+        let found = |title: &str, due: DateTime<FixedOffset>| find_duplicate(&tasks, title, due).map(|t| t.title.as_str());
+        // Same day and month, another year
+        assert_eq!(found("Café meeting", clock::local("2025-06-03 09:00")), None);
+        // Decomposed "e" + combining acute, and conjoining Hangul jamo
+        assert_eq!(found("Cafe\u{301} meeting", clock::local("2024-06-03 18:00")), Some("Café meeting"));
+        assert_eq!(found("\u{1112}\u{116C}\u{110B}\u{1174} \u{110C}\u{116E}\u{11AB}\u{1107}\u{1175}", clock::local("2024-06-03 11:00")), Some("회의 준비"));
+        // Same local date written with another offset
+        let utc = DateTime::parse_from_rfc3339("2024-06-04T03:30:00Z").unwrap();
+        assert_eq!(found("café MEETING", utc), Some("Café meeting"));
+        let next_day = DateTime::parse_from_rfc3339("2024-06-04T04:30:00Z").unwrap();
+        assert_eq!(found("café meeting", next_day), None);
+    }
+
+    // Many ties on score, title, due and created_at, plus tasks that
+    // cannot fit, so the tie-break and both result lists are exercised.
+    fn tie_heavy_tasks(rng: &mut TestRng) -> Vec<Task> {
+        let dues = ["2024-06-03 09:00", "2024-06-03 12:00", "2024-06-03 18:00", "2024-06-02 17:00"];
+        (0..40)
+            .map(|i| {
+                let mut t = test_task(["Pay rent", "Call bank", "Review"][rng.below(3)], clock::local(dues[rng.below(dues.len())]));
+                t.duration_min = [15, 30, 60, 90][rng.below(4)];
+                t.priority = 1 + rng.below(5) as i64;
+                if i % 7 == 0 {
+                    t.duration_min = 0;
+                }
+                t
+            })
+            .collect()
+    }
+
+    #[test]
+    fn plan_is_independent_of_storage_order() {
+        let now = clock::local("2024-06-03 08:30");
+        let day = date("2024-06-03");
+        let settings = DaySettings::default();
+        let key = |(plan, unplanned, _): (Vec<PlanItem>, Vec<UnplannedItem>, PlanWindow)| {
+            let plan: Vec<_> = plan.into_iter().map(|p| (p.task_id, p.start, p.end)).collect();
+            let unplanned: Vec<_> = unplanned.into_iter().map(|u| (u.task_id, u.reason)).collect();
+            (plan, unplanned)
         };
+        for seed in 1..=25u64 {
+            let mut rng = TestRng::new(seed);
+            let mut tasks = tie_heavy_tasks(&mut rng);
+            let expected = key(plan_for_date(&tasks, &settings, day, now, 8 * 60, false));
+            for _ in 0..5 {
+                rng.shuffle(&mut tasks);
+                let got = key(plan_for_date(&tasks, &settings, day, now, 8 * 60, false));
+                assert_eq!(got, expected, "seed {seed}");
+            }
+        }
+    }
 
-        plan.push(PlanItem {
-            task_id: st.task.id.to_string(),
-            title: st.task.title.clone(),
-            start: cursor,
-            end,
-            score_breakdown: breakdown,
-            is_overdue: st.is_overdue,
-        });
+    #[test]
+    fn plan_invariants_hold_for_random_input() {
+        let now = clock::local("2024-06-03 08:30");
+        let day = date("2024-06-03");
+        let settings = DaySettings::default();
+        let (day_start, day_end) = day_window(day, &settings).unwrap();
+        for seed in 1..=25u64 {
+            let mut rng = TestRng::new(seed);
+            let tasks = tie_heavy_tasks(&mut rng);
+            let available = [60, 4 * 60, 24 * 60][rng.below(3)];
+            let (plan, unplanned, _) = plan_for_date(&tasks, &settings, day, now, available, false);
+
+            for pair in plan.windows(2) {
+                assert!(pair[0].end <= pair[1].start, "seed {seed}: {} overlaps {}", pair[0].title, pair[1].title);
+            }
+            for p in &plan {
+                assert!(day_start <= p.start && p.start < p.end && p.end <= day_end, "seed {seed}: {} outside the day", p.title);
+            }
+            let mut seen: Vec<&str> = plan.iter().map(|p| p.task_id.as_str()).chain(unplanned.iter().map(|u| u.task_id.as_str())).collect();
+            seen.sort_unstable();
+            let mut relevant: Vec<String> = relevant_tasks(&tasks, &settings, day, now).iter().map(|t| t.id.to_string()).collect();
+            relevant.sort_unstable();
+            assert_eq!(seen, relevant, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn week_bounds_around_a_month_boundary() {
+        // 2024-05-31 is a Friday; June starts on a Saturday
+        let cases = [
+            ("2024-05-31", Weekday::Mon, "2024-05-27", "2024-06-02"),
+            ("2024-06-02", Weekday::Mon, "2024-05-27", "2024-06-02"),
+            ("2024-06-03", Weekday::Mon, "2024-06-03", "2024-06-09"),
+            ("2024-05-31", Weekday::Sun, "2024-05-26", "2024-06-01"),
+            ("2024-06-01", Weekday::Sun, "2024-05-26", "2024-06-01"),
+            ("2024-06-02", Weekday::Sun, "2024-06-02", "2024-06-08"),
+        ];
+        for (day, week_start, first, last) in cases {
+            assert_eq!(week_bounds(date(day), week_start), (date(first), date(last)), "{day} {week_start}");
+        }
+    }
+
+    fn simulate(tasks: &[Task], settings: &DaySettings, budgets: Vec<i64>) -> Vec<BudgetResult> {
+        let now = clock::local("2024-06-03 07:00");
+        let scored = score_and_sort(tasks.iter().collect(), now, settings, &DefaultScorer);
+        simulate_budgets(&scored, date("2024-06-03"), now, settings, budgets)
+    }
+
+    #[test]
+    fn simulation_keeps_what_a_smaller_budget_scheduled() {
+        // Overdue, so the deadline pass does not reorder them
+        let due = clock::local("2024-06-02 17:00");
+        let mut long = test_task("long and important", due);
+        long.duration_min = 120;
+        long.priority = 5;
+        let mut short = [test_task("short a", due), test_task("short b", due)];
+        for t in &mut short {
+            t.duration_min = 30;
+            t.priority = 1;
+        }
+        let tasks = vec![long, short[0].clone(), short[1].clone()];
+        let settings = DaySettings::default();
+
+        // Plain greedy placement drops from two tasks to one at 120 minutes
+        let now = clock::local("2024-06-03 07:00");
+        let plain = |budget| plan_for_date(&tasks, &settings, date("2024-06-03"), now, budget, true).0.len();
+        assert_eq!((plain(60), plain(120)), (2, 1));
+
+        let counts: Vec<(i64, usize, i64, usize)> = simulate(&tasks, &settings, vec![180, 60, 120])
+            .into_iter()
+            .map(|r| (r.available_min, r.scheduled_count, r.scheduled_min, r.unplanned_count))
+            .collect();
+        assert_eq!(counts, [(60, 2, 60, 1), (120, 2, 60, 1), (180, 3, 180, 0)]);
+    }
+
+    #[test]
+    fn simulation_is_monotonic_for_random_input() {
+        let settings = DaySettings::default();
+        for seed in 1..=25u64 {
+            let mut rng = TestRng::new(seed);
+            let tasks = tie_heavy_tasks(&mut rng);
+            let results = simulate(&tasks, &settings, (30..=600).step_by(30).collect());
+            for pair in results.windows(2) {
+                let (a, b) = (&pair[0], &pair[1]);
+                assert!(a.available_min < b.available_min);
+                assert!(a.scheduled_count <= b.scheduled_count, "seed {seed}: {a:?} then {b:?}");
+                assert!(a.scheduled_min <= b.scheduled_min, "seed {seed}: {a:?} then {b:?}");
+                assert!(a.unplanned_count >= b.unplanned_count, "seed {seed}: {a:?} then {b:?}");
+            }
+            assert!(results.iter().all(|r| r.scheduled_min <= r.available_min));
+        }
+    }
+
+    #[test]
+    fn budget_freed_by_an_exclusion_goes_to_the_next_task() {
+        let now = clock::local("2024-06-03 07:00");
+        let due = clock::local("2024-06-03 17:00");
+        let mut first = test_task("first", due);
+        first.priority = 5;
+        let mut second = test_task("second", due);
+        second.priority = 4;
+        let third = test_task("third", due);
+        let tasks = vec![first.clone(), second, third];
+        let settings = DaySettings { prefer_due_today: false, ..DaySettings::default() };
+
+        let planned = |tasks: &[Task]| -> Vec<String> {
+            let (plan, _, _) = plan_for_date(tasks, &settings, date("2024-06-03"), now, 120, true);
+            plan.into_iter().map(|p| p.title).collect()
+        };
+        assert_eq!(planned(&tasks), ["first", "second"]);
+        // What the handler does with exclude_ids: drop them after relevant_tasks
+        let rest: Vec<Task> = tasks.into_iter().filter(|t| t.id != first.id).collect();
+        assert_eq!(planned(&rest), ["second", "third"]);
+    }
+
+    fn summary_for(tasks: &[Task], now: &str, available_min: i64) -> PlanSummary {
+        let (day, now) = (date("2024-06-03"), clock::local(now));
+        let settings = DaySettings::default();
+        let (plan, unplanned, _) = plan_for_date(tasks, &settings, day, now, available_min, true);
+        summarize_plan(&plan, &unplanned, tasks, day, now, &settings)
+    }
+
+    #[test]
+    fn empty_plan_has_no_finish_and_counts_slack_from_the_window_start() {
+        let early = summary_for(&[], "2024-06-03 07:00", 600);
+        assert_eq!((early.finish_at, early.slack_min, early.slack_after_unplanned_min), (None, 540, 540));
+        let midday = summary_for(&[], "2024-06-03 13:30", 600);
+        assert_eq!((midday.slack_min, midday.slack_after_unplanned_min), (270, 270));
+        let late = summary_for(&[], "2024-06-03 20:00", 600);
+        assert_eq!(late.slack_min, 0);
+        assert!(early.warnings.is_empty());
+    }
+
+    #[test]
+    fn plan_ending_exactly_at_day_end_has_zero_slack() {
+        let due = clock::local("2024-06-03 17:00");
+        let tasks: Vec<Task> = (0..3)
+            .map(|i| Task { duration_min: 180, ..test_task(&format!("block {i}"), due) })
+            .collect();
+        let s = summary_for(&tasks, "2024-06-03 07:00", 600);
+        assert_eq!(s.finish_at, Some(clock::local("2024-06-03 18:00")));
+        assert_eq!((s.slack_min, s.slack_after_unplanned_min), (0, 0));
+        assert_eq!(s.planned_min, 540);
+    }
+
+    #[test]
+    fn overcommitted_plan_has_negative_slack_after_unplanned() {
+        let due = clock::local("2024-06-03 17:00");
+        let tasks: Vec<Task> = (0..6)
+            .map(|i| Task { duration_min: 120, ..test_task(&format!("chunk {i}"), due) })
+            .collect();
+        let s = summary_for(&tasks, "2024-06-03 07:00", 600);
+        // Four fit (09:00-17:00), two do not
+        assert_eq!(s.finish_at, Some(clock::local("2024-06-03 17:00")));
+        assert_eq!((s.slack_min, s.slack_after_unplanned_min), (60, -180));
+        assert!(s.warnings.contains(&PlanWarning::Overcommitted { short_min: 180 }));
+    }
+
+    fn warnings_for(tasks: &[Task], settings: &DaySettings, available_min: i64) -> Vec<PlanWarning> {
+        let (day, now) = (date("2024-06-03"), clock::local("2024-06-03 07:00"));
+        let (plan, unplanned, _) = plan_for_date(tasks, settings, day, now, available_min, true);
+        summarize_plan(&plan, &unplanned, tasks, day, now, settings).warnings
+    }
+
+    fn overdue_hours(n: usize, prefix: &str) -> Vec<Task> {
+        (0..n).map(|i| test_task(&format!("{prefix} {i}"), clock::local("2024-06-02 17:00"))).collect()
+    }
+
+    #[test]
+    fn a_plan_that_covers_everything_has_no_warnings() {
+        let tasks = overdue_hours(3, "fits");
+        assert_eq!(warnings_for(&tasks, &DaySettings::default(), 600), []);
+    }
+
+    #[test]
+    fn unplanned_task_due_later_today_is_a_missed_deadline() {
+        let tasks = vec![
+            Task { priority: 5, ..test_task("due at noon", clock::local("2024-06-03 12:00")) },
+            test_task("due at three", clock::local("2024-06-03 15:00")),
+        ];
+        let score_order = DaySettings { prefer_due_today: false, ..DaySettings::default() };
+        let missed = warnings_for(&tasks, &score_order, 60);
+        assert_eq!(missed, [PlanWarning::DeadlineMissed { task_id: tasks[1].id.to_string() }]);
+        // Overdue work that does not fit is not a missed deadline
+        assert_eq!(warnings_for(&overdue_hours(2, "late"), &DaySettings::default(), 60), []);
+    }
+
+    #[test]
+    fn unusable_day_window_warns_about_the_fallback() {
+        let settings = DaySettings { day_start: "9am".to_string(), ..DaySettings::default() };
+        assert!(warnings_for(&overdue_hours(1, "any"), &settings, 600).contains(&PlanWarning::SettingsFallback));
+    }
+
+    #[test]
+    fn overcommitment_warns_only_past_the_threshold() {
+        // Nine hours fill 09:00-18:00; the rest is unplanned
+        let mut at_threshold = overdue_hours(10, "hour");
+        assert_eq!(warnings_for(&at_threshold, &DaySettings::default(), 600), []);
+        at_threshold[9].duration_min = OVERCOMMIT_WARN_MIN + 1;
+        assert_eq!(
+            warnings_for(&at_threshold, &DaySettings::default(), 600),
+            [PlanWarning::Overcommitted { short_min: OVERCOMMIT_WARN_MIN + 1 }]
+        );
+    }
+
+    #[test]
+    fn conflicting_locks_are_warned_about() {
+        let lock = |title: &str, start: &str, end: &str| Task {
+            locked: Some(PlanLock { start: clock::local(start), end: clock::local(end) }),
+            ..test_task(title, clock::local(start))
+        };
+        let a = lock("standup", "2024-06-03 10:00", "2024-06-03 11:00");
+        let b = lock("review", "2024-06-03 10:30", "2024-06-03 11:30");
+        let c = lock("lunch", "2024-06-03 12:00", "2024-06-03 13:00");
+        assert_eq!(
+            warnings_for(&[a.clone(), b.clone(), c.clone()], &DaySettings::default(), 600),
+            [PlanWarning::LockConflict { task_id: a.id.to_string() }, PlanWarning::LockConflict { task_id: b.id.to_string() }]
+        );
+
+        let evening = lock("evening call", "2024-06-03 19:00", "2024-06-03 20:00");
+        let warnings = warnings_for(&[evening.clone(), c], &DaySettings::default(), 600);
+        assert!(warnings.contains(&PlanWarning::LockConflict { task_id: evening.id.to_string() }), "{warnings:?}");
+    }
+
+    #[test]
+    fn overdue_only_after_the_grace_window() {
+        let due = clock::local("2024-06-03 14:00");
+        let second = Duration::seconds(1);
+        // (grace, now, overdue, due now)
+        let table = [
+            (0, due, false, false),
+            (0, due + second, true, false),
+            (15, due - second, false, false),
+            (15, due, false, false),
+            (15, due + second, false, true),
+            (15, due + Duration::minutes(15), false, true),
+            (15, due + Duration::minutes(15) + second, true, false),
+        ];
+        for (grace, now, overdue, due_now) in table {
+            let settings = DaySettings { overdue_grace_min: grace, ..DaySettings::default() };
+            assert_eq!(is_overdue(due, now, &settings), overdue, "grace {grace} at {now}");
+            assert_eq!(is_due_now(due, now, &settings), due_now, "grace {grace} at {now}");
+        }
+    }
+
+    #[test]
+    fn plan_flags_due_now_apart_from_overdue() {
+        let now = clock::local("2024-06-03 14:10");
+        let settings = DaySettings { overdue_grace_min: 15, ..DaySettings::default() };
+        let tasks = vec![
+            test_task("inside grace", clock::local("2024-06-03 14:00")),
+            test_task("past grace", clock::local("2024-06-03 13:50")),
+            test_task("not yet due", clock::local("2024-06-03 16:00")),
+        ];
+        let (plan, _, _) = plan_for_date(&tasks, &settings, date("2024-06-03"), now, 600, true);
+        let mut flags: Vec<(&str, bool, bool)> =
+            plan.iter().map(|p| (p.title.as_str(), p.is_overdue, p.is_due_now)).collect();
+        flags.sort_unstable();
+        assert_eq!(
+            flags,
+            [("inside grace", false, true), ("not yet due", false, false), ("past grace", true, false)]
+        );
+    }
+
+    #[test]
+    fn window_sources_are_labeled() {
+        use WindowSource::*;
+        let day = date("2024-06-03");
+        let broken = DaySettings { day_end: "6pm".to_string(), ..DaySettings::default() };
+        // (settings, now, requested, start, end, available, sources)
+        let cases = [
+            (DaySettings::default(), "07:00", 120, "09:00", "18:00", 120, (Settings, Settings, Request)),
+            (DaySettings::default(), "07:00", 900, "09:00", "18:00", 540, (Settings, Settings, Window)),
+            (DaySettings::default(), "13:30", 600, "13:30", "18:00", 270, (Now, Settings, Window)),
+            (DaySettings::default(), "13:30", -5, "13:30", "18:00", 0, (Now, Settings, Request)),
+            (DaySettings::default(), "19:00", 60, "19:00", "18:00", 0, (Now, Settings, Window)),
+            (broken.clone(), "10:00", 60, "10:00", "18:00", 60, (Fallback, Fallback, Request)),
+            (broken, "10:00", 600, "10:00", "18:00", 480, (Fallback, Fallback, Window)),
+        ];
+        for (settings, now, requested, start, end, available, (start_source, end_source, available_source)) in cases {
+            let now = clock::local(&format!("2024-06-03 {now}"));
+            let w = resolve_window(day, now, &settings, requested, true);
+            let label = format!("{now} {requested}");
+            assert_eq!(w.start, clock::local(&format!("2024-06-03 {start}")), "{label}");
+            assert_eq!(w.end, clock::local(&format!("2024-06-03 {end}")), "{label}");
+            assert_eq!(w.available_min, available, "{label}");
+            assert_eq!((w.start_source, w.end_source, w.available_source), (start_source, end_source, available_source), "{label}");
+            assert_eq!(w.now, now);
+        }
+    }
+
+    #[test]
+    fn build_today_plan_returns_the_window_it_used() {
+        let now = clock::local("2024-06-03 13:30");
+        let tasks = [test_task("one", clock::local("2024-06-03 17:00"))];
+        let (plan, _, window) = plan_for_date(&tasks, &DaySettings::default(), date("2024-06-03"), now, 600, true);
+        assert_eq!(window, resolve_window(date("2024-06-03"), now, &DaySettings::default(), 600, true));
+        assert_eq!(plan[0].start, window.start);
+    }
+
+    #[test]
+    fn tag_edits_match_any_case_and_remove_wins() {
+        let tags = |list: &[&str]| list.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        let stored = tags(&["Über", "work", "Deep-Work"]);
+        type Case<'a> = (&'a [&'a str], &'a [&'a str], Option<Vec<String>>);
+        let cases: [Case; 6] = [
+            // A tag in both lists is removed
+            (&["urgent"], &["urgent"], Some(tags(&["Über", "work", "Deep-Work"]))),
+            (&["WORK"], &["work"], Some(tags(&["Über", "Deep-Work"]))),
+            // Removal ignores case, beyond ASCII too
+            (&[], &["über"], Some(tags(&["work", "Deep-Work"]))),
+            (&[], &["deep work"], Some(tags(&["Über", "work"]))),
+            // Adding a spelling already there keeps the stored one
+            (&["über", "Work", "new", "NEW"], &[], Some(tags(&["Über", "work", "Deep-Work", "new"]))),
+            (&[], &["ÜBER", "Work", "deep-work"], None),
+        ];
+        for (add, remove, expected) in cases {
+            assert_eq!(edit_tags(Some(&stored), &tags(add), &tags(remove)), expected, "+{add:?} -{remove:?}");
+        }
+    }
+
+    #[test]
+    fn task_due_today_gets_in_ahead_of_higher_scores() {
+        let now = clock::local("2024-06-03 08:00");
+        let day = date("2024-06-03");
+        let mut tasks = overdue_hours(2, "important");
+        for t in &mut tasks {
+            t.priority = 5;
+        }
+        tasks.push(Task { priority: 1, ..test_task("due today", clock::local("2024-06-03 16:00")) });
+        tasks.push(Task { priority: 1, ..test_task("due tomorrow", clock::local("2024-06-04 16:00")) });
+        let run = |settings: &DaySettings| {
+            let scored = score_and_sort(tasks.iter().collect(), now, settings, scorer_for(settings.scorer));
+            build_today_plan(scored, day, now, settings, 120, true)
+        };
+
+        // Score order alone spends the budget on the overdue tasks
+        let score_order = DaySettings { prefer_due_today: false, ..DaySettings::default() };
+        let (plan, _, _) = run(&score_order);
+        assert_eq!(plan.iter().map(|p| p.title.as_str()).collect::<Vec<_>>(), ["important 0", "important 1"]);
+
+        let (plan, unplanned, _) = run(&DaySettings::default());
+        let placed: Vec<(&str, PlacedBy, DateTime<FixedOffset>)> =
+            plan.iter().map(|p| (p.title.as_str(), p.placed_by, p.start)).collect();
+        assert_eq!(
+            placed,
+            [
+                ("important 0", PlacedBy::ScorePass, clock::local("2024-06-03 09:00")),
+                ("due today", PlacedBy::DeadlinePass, clock::local("2024-06-03 10:00")),
+            ]
+        );
+        let mut left: Vec<&str> = unplanned.iter().map(|u| u.title.as_str()).collect();
+        left.sort();
+        assert_eq!(left, ["due tomorrow", "important 1"]);
+    }
+
+    #[test]
+    fn deadline_pass_skips_what_cannot_fit() {
+        let now = clock::local("2024-06-03 08:00");
+        let mut tasks = overdue_hours(1, "important");
+        tasks[0].priority = 5;
+        tasks.push(Task { priority: 1, duration_min: 90, ..test_task("too long", clock::local("2024-06-03 16:00")) });
+        let scored = score_and_sort(tasks.iter().collect(), now, &DaySettings::default(), scorer_for(ScorerKind::default()));
+        let (plan, unplanned, _) = build_today_plan(scored, date("2024-06-03"), now, &DaySettings::default(), 60, true);
+        assert_eq!(plan.iter().map(|p| p.title.as_str()).collect::<Vec<_>>(), ["important 0"]);
+        assert_eq!(unplanned[0].title, "too long");
+        assert_eq!(unplanned[0].reason, UnplannedReason::InsufficientTime);
+    }
+
+    // 09:00-10:00 "a", 10:00-10:30 "b", break, 10:45-11:45 "c".
+    fn timeline() -> Vec<PlanItem> {
+        let now = clock::local("2024-06-03 08:00");
+        let mut tasks = overdue_hours(3, "block");
+        tasks[1].duration_min = 30;
+        let (mut plan, _, _) = plan_for_date(&tasks, &DaySettings::default(), date("2024-06-03"), now, 600, true);
+        plan.sort_by_key(|p| p.title.clone());
+        plan[1].start = clock::local("2024-06-03 10:00");
+        plan[1].end = clock::local("2024-06-03 10:30");
+        plan[2].start = clock::local("2024-06-03 10:45");
+        plan[2].end = clock::local("2024-06-03 11:45");
+        plan[0].start = clock::local("2024-06-03 09:00");
+        plan[0].end = clock::local("2024-06-03 10:00");
+        plan
+    }
+
+    #[test]
+    fn plan_position_at_the_boundaries() {
+        let plan = timeline();
+        let nothing_done = |_: &str| false;
+        let at = |time: &str| plan_position(&plan, clock::local(&format!("2024-06-03 {time}")), nothing_done);
+        let active = |index, elapsed_min, remaining_min| Some(ActiveBlock { index, elapsed_min, remaining_min });
+        let next = |index, starts_in_min| Some(NextBlock { index, starts_in_min });
+
+        // Before the first item
+        let p = at("08:30");
+        assert_eq!((p.active, p.next, p.pace), (None, next(0, 30), Pace::OnTrack));
+        // Exactly at a start: that item is active; at its end, the next one is
+        assert_eq!((at("09:00").active, at("09:00").next), (active(0, 0, 60), next(1, 60)));
+        assert_eq!(at("10:00").active, active(1, 0, 30));
+        assert_eq!(at("10:29").active, active(1, 29, 1));
+        // Inside the break
+        let p = at("10:35");
+        assert_eq!((p.active, p.next), (None, next(2, 10)));
+        assert_eq!((p.drift_min, p.pace), (-90, Pace::Behind));
+        // After the last item
+        let p = at("11:45");
+        assert_eq!((p.active, p.next, p.drift_min), (None, None, -150));
+        assert_eq!(plan_position(&[], clock::local("2024-06-03 11:00"), nothing_done).pace, Pace::OnTrack);
+    }
+
+    #[test]
+    fn plan_position_pace_follows_completed_items() {
+        let plan = timeline();
+        let now = clock::local("2024-06-03 09:20");
+        let done = |ids: &[usize]| {
+            let ids: Vec<String> = ids.iter().map(|i| plan[*i].task_id.clone()).collect();
+            plan_position(&plan, now, move |id| ids.iter().any(|d| d == id))
+        };
+        // Active item done early: its remaining 40 minutes are ahead
+        assert_eq!((done(&[0]).drift_min, done(&[0]).pace), (40, Pace::Ahead));
+        // A later item done too
+        assert_eq!(done(&[0, 2]).drift_min, 100);
+        assert_eq!(done(&[]).pace, Pace::OnTrack);
+
+        // Past items not done count against it, done ones do not
+        let later = clock::local("2024-06-03 10:40");
+        let ids = [plan[0].task_id.clone(), plan[2].task_id.clone()];
+        let p = plan_position(&plan, later, |id| ids.iter().any(|d| d == id));
+        assert_eq!((p.drift_min, p.pace), (60 - 30, Pace::Ahead));
+    }
+
+    #[test]
+    fn unplanned_items_say_how_close_they_came() {
+        let now = clock::local("2024-06-03 08:00");
+        let settings = DaySettings::default();
+        let mut tasks = overdue_hours(2, "hour");
+        tasks[0].priority = 5;
+        tasks.push(Task { duration_min: 0, ..test_task("no length", clock::local("2024-06-02 17:00")) });
+        tasks.push(Task { duration_min: 600, ..test_task("whole day", clock::local("2024-06-02 17:00")) });
+        let scored = score_and_sort(tasks.iter().collect(), now, &settings, scorer_for(settings.scorer));
+        let expected_scores: Vec<(String, ScoreBreakdown)> =
+            scored.iter().map(|st| (st.task.title.clone(), st.score.clone())).collect();
+        let (plan, unplanned, _) = build_today_plan(scored, date("2024-06-03"), now, &settings, 100, true);
+        assert_eq!(plan.iter().map(|p| p.title.as_str()).collect::<Vec<_>>(), ["hour 0"]);
+
+        let by_title = |title: &str| unplanned.iter().find(|u| u.title == title).unwrap();
+        // 40 minutes were left for a 60 minute task starting at 10:00
+        let short = by_title("hour 1");
+        assert_eq!((short.reason, short.duration_min), (UnplannedReason::InsufficientTime, 60));
+        assert_eq!(short.minutes_short, Some(20));
+        assert_eq!(short.would_end_at, Some(clock::local("2024-06-03 11:00")));
+        assert!(short.is_overdue);
+
+        for (title, reason) in [("no length", UnplannedReason::InvalidDuration), ("whole day", UnplannedReason::ExceedsDayWindow)] {
+            let u = by_title(title);
+            assert_eq!((u.reason, u.minutes_short, u.would_end_at), (reason, None, None), "{title}");
+        }
+        for u in &unplanned {
+            let (_, score) = expected_scores.iter().find(|(title, _)| *title == u.title).unwrap();
+            assert_eq!(format!("{:?}", u.score_breakdown), format!("{score:?}"), "{}", u.title);
+            assert_eq!(u.task_id, tasks.iter().find(|t| t.title == u.title).unwrap().id.to_string());
+        }
+    }
+
+    #[test]
+    fn deadline_pressure_follows_time_left_per_minute_of_work() {
+        let now = clock::local("2024-06-03 08:00");
+        // (due_at, duration_min, urgency)
+        let table = [
+            ("2024-06-03 10:00", 60, 3),
+            ("2024-06-03 18:00", 60, 1),
+            ("2024-06-03 19:00", 60, 0),
+            ("2024-06-03 08:45", 60, 5), // cannot be finished in time
+            ("2024-06-03 07:00", 60, 5), // overdue
+            ("2024-06-03 12:00", 0, 0),  // counted as one minute of work
+        ];
+        for (due, duration, urgency) in table {
+            assert_eq!(DeadlinePressureScorer::urgency(clock::local(due), now, duration), urgency, "{due} {duration}");
+        }
+    }
+
+    #[test]
+    fn deadline_pressure_reports_itself_whatever_the_urgency_mode() {
+        let now = clock::local("2024-06-03 08:00");
+        let task = Task { duration_min: 120, ..test_task("tight", clock::local("2024-06-03 11:00")) };
+        for mode in [UrgencyMode::Rolling, UrgencyMode::Calendar] {
+            let settings = DaySettings { urgency_mode: mode, ..DaySettings::default() };
+            let score = DeadlinePressureScorer.score(&task, now, &settings);
+            assert_eq!(score.urgency_mode, UrgencyBasis::DeadlinePressure);
+            assert_eq!((score.urgency, score.total), (3, 3 + task.priority + duration_score(120)));
+        }
+        assert_eq!(serde_json::to_value(UrgencyBasis::DeadlinePressure).unwrap(), "deadline_pressure");
+    }
+
+    #[test]
+    fn scorers_are_picked_by_kind_and_can_rank_differently() {
+        let now = clock::local("2024-06-03 08:00");
+        assert_eq!(scorer_for(ScorerKind::Default).name(), "default");
+        assert_eq!(scorer_for(ScorerKind::DeadlinePressure).name(), "deadline_pressure");
+
+        // Same deadline, but the long one needs all the time that is left
+        let long = Task { duration_min: 240, ..test_task("long", clock::local("2024-06-03 12:00")) };
+        let short = Task { duration_min: 30, ..test_task("short", clock::local("2024-06-03 12:00")) };
+        let settings = DaySettings::default();
+        let order = |kind| {
+            score_and_sort(vec![&long, &short], now, &settings, scorer_for(kind))
+                .iter()
+                .map(|st| st.task.title.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(order(ScorerKind::Default), ["short", "long"]);
+        assert_eq!(order(ScorerKind::DeadlinePressure), ["long", "short"]);
+    }
+
+    #[test]
+    fn tasks_longer_than_the_window_get_their_own_reason() {
+        let now = clock::local("2024-06-03 08:00");
+        let settings = DaySettings::default();
+        let long = Task { duration_min: 541, ..test_task("long", clock::local("2024-06-03 12:00")) };
+        let whole = Task { duration_min: 540, ..test_task("whole", clock::local("2024-06-03 17:00")) };
+        let (plan, unplanned, _) = plan_for_date(&[long, whole], &settings, date("2024-06-03"), now, 540, true);
+        assert_eq!(plan.iter().map(|p| p.title.as_str()).collect::<Vec<_>>(), ["whole"]);
+        assert_eq!(unplanned.len(), 1);
+        assert_eq!((unplanned[0].title.as_str(), unplanned[0].reason), ("long", UnplannedReason::ExceedsDayWindow));
+        assert_eq!(UnplannedReason::ExceedsDayWindow.code(), "exceeds_day_window");
+    }
+
+    #[test]
+    fn clamp_to_date_against_same_day_and_overnight_windows() {
+        let now = clock::local("2024-06-03 08:00");
+        let same_day = DaySettings::default();
+        let overnight = DaySettings { day_start: "22:00".into(), day_end: "03:00".into(), ..DaySettings::default() };
+        let midnight = Some(clock::local("2024-06-04 00:00"));
+        // (settings, clamp_to_date, date_end)
+        let cases = [(&same_day, true, midnight), (&same_day, false, None), (&overnight, true, None), (&overnight, false, None)];
+        for (settings, clamp, date_end) in cases {
+            let window = resolve_window(date("2024-06-03"), now, settings, 24 * 60, clamp);
+            assert_eq!(window.date_end, date_end, "{}-{} clamp {clamp}", settings.day_start, settings.day_end);
+        }
+    }
+
+    #[test]
+    fn clamped_items_past_midnight_are_unplanned_unless_the_window_is_overnight() {
+        let tasks = vec![
+            Task { duration_min: 180, priority: 5, ..test_task("evening", clock::local("2024-06-03 23:59")) },
+            Task { duration_min: 120, ..test_task("late", clock::local("2024-06-03 23:59")) },
+        ];
+        let placed = |settings: &DaySettings, now: &str, clamp: bool| {
+            let (plan, unplanned, _) = plan_for_date(&tasks, settings, date("2024-06-03"), clock::local(now), 24 * 60, clamp);
+            let plan: Vec<(String, String)> =
+                plan.iter().map(|p| (p.title.clone(), p.end.format("%d %H:%M").to_string())).collect();
+            (plan, unplanned.iter().map(|u| (u.title.clone(), u.reason)).collect::<Vec<_>>())
+        };
+        let both = |late_end: &str| vec![("evening".to_string(), "03 23:00".to_string()), ("late".to_string(), late_end.to_string())];
 
-        cursor = end;
-        remaining -= dur;
+        // Unusable settings: the fallback window (now..now+8h) runs past midnight
+        let fallback = DaySettings { day_start: "soon".into(), ..DaySettings::default() };
+        let (plan, unplanned) = placed(&fallback, "2024-06-03 20:00", true);
+        assert_eq!(plan, both("")[..1]);
+        assert_eq!(unplanned, [("late".to_string(), UnplannedReason::CrossesMidnight)]);
+        assert_eq!(placed(&fallback, "2024-06-03 20:00", false), (both("04 01:00"), vec![]));
+
+        // An overnight window owns its items after midnight, clamped or not
+        let overnight = DaySettings { day_start: "20:00".into(), day_end: "03:00".into(), ..DaySettings::default() };
+        for clamp in [true, false] {
+            assert_eq!(placed(&overnight, "2024-06-03 08:00", clamp), (both("04 01:00"), vec![]), "clamp {clamp}");
+        }
     }
 
-    (plan, unplanned)
+    // One fixture day seen at 08:00, 12:00 and 18:00: 240 minutes of
+    // work in a 540 minute window, finished as the day goes on.
+    #[test]
+    fn utilization_credits_work_completed_earlier_in_the_day() {
+        let settings = DaySettings::default();
+        let day = date("2024-06-03");
+        let mut tasks = vec![
+            Task { duration_min: 120, priority: 5, ..test_task("report", clock::local("2024-06-03 17:00")) },
+            Task { priority: 4, ..test_task("review", clock::local("2024-06-03 17:00")) },
+            test_task("email", clock::local("2024-06-03 17:00")),
+        ];
+        let mut yesterday = test_task("done yesterday", clock::local("2024-06-02 17:00"));
+        yesterday.status = TaskStatus::Done;
+        yesterday.completed_at = Some(clock::local("2024-06-02 16:00"));
+        tasks.push(yesterday);
+        let complete = |tasks: &mut Vec<Task>, i: usize, at: &str| {
+            tasks[i].status = TaskStatus::Done;
+            tasks[i].completed_at = Some(clock::local(at));
+        };
+        let summary_at = |tasks: &[Task], now: &str| {
+            let now = clock::local(now);
+            let (plan, unplanned, _) = plan_for_date(tasks, &settings, day, now, 24 * 60, true);
+            let s = summarize_plan(&plan, &unplanned, tasks, day, now, &settings);
+            (s.completed_today_min, s.planned_min, s.utilization_pct)
+        };
+
+        assert_eq!(summary_at(&tasks, "2024-06-03 08:00"), (0, 240, Some(44)));
+        complete(&mut tasks, 0, "2024-06-03 11:00");
+        assert_eq!(summary_at(&tasks, "2024-06-03 12:00"), (120, 120, Some(44)));
+        complete(&mut tasks, 1, "2024-06-03 13:00");
+        complete(&mut tasks, 2, "2024-06-03 15:00");
+        assert_eq!(summary_at(&tasks, "2024-06-03 18:00"), (240, 0, Some(44)));
+
+        let unusable = DaySettings { day_end: "late".into(), ..DaySettings::default() };
+        let now = clock::local("2024-06-03 18:00");
+        let s = summarize_plan(&[], &[], &tasks, day, now, &unusable);
+        assert_eq!((s.completed_today_min, s.utilization_pct), (240, None));
+    }
+
+    fn locked_at(title: &str, start: &str, end: &str) -> Task {
+        Task {
+            locked: Some(PlanLock { start: clock::local(start), end: clock::local(end) }),
+            ..test_task(title, clock::local(start))
+        }
+    }
+
+    #[test]
+    fn work_is_planned_on_both_sides_of_a_locked_mid_day_item() {
+        let now = clock::local("2024-06-03 08:00");
+        let mut tasks = overdue_hours(10, "work");
+        let review = locked_at("review", "2024-06-03 13:00", "2024-06-03 14:00");
+        tasks.push(review.clone());
+        let (plan, unplanned, _) = plan_for_date(&tasks, &DaySettings::default(), date("2024-06-03"), now, 24 * 60, true);
+
+        let pinned = plan.iter().find(|p| p.task_id == review.id.to_string()).unwrap();
+        assert_eq!((pinned.start, pinned.end, pinned.placed_by), (clock::local("2024-06-03 13:00"), clock::local("2024-06-03 14:00"), PlacedBy::Locked));
+        let before = plan.iter().filter(|p| p.end <= pinned.start).count();
+        let after = plan.iter().filter(|p| p.start >= pinned.end).count();
+        assert_eq!((before, after), (4, 4));
+        // In start order, never overlapping, and the rest did not fit
+        assert!(plan.windows(2).all(|w| w[0].end <= w[1].start));
+        assert_eq!(plan.len(), 9);
+        assert_eq!(unplanned.len(), 2);
+        assert!(unplanned.iter().all(|u| u.reason == UnplannedReason::InsufficientTime));
+    }
+
+    #[test]
+    fn a_lock_outside_a_narrowed_window_is_warned_about_not_moved() {
+        // Locked at 16:00, then the day was cut short at 15:00
+        let now = clock::local("2024-06-03 07:00");
+        let mut tasks = overdue_hours(2, "work");
+        let call = locked_at("call", "2024-06-03 16:00", "2024-06-03 17:00");
+        tasks.push(call.clone());
+        let settings = DaySettings { day_end: "15:00".to_string(), ..DaySettings::default() };
+        let (plan, unplanned, _) = plan_for_date(&tasks, &settings, date("2024-06-03"), now, 24 * 60, true);
+
+        let pinned = plan.iter().find(|p| p.task_id == call.id.to_string()).unwrap();
+        assert_eq!((pinned.start, pinned.end), (clock::local("2024-06-03 16:00"), clock::local("2024-06-03 17:00")));
+        assert!(unplanned.iter().all(|u| u.task_id != call.id.to_string()));
+        // The plan now ends at 17:00, two hours past day_end
+        assert_eq!(
+            summarize_plan(&plan, &unplanned, &tasks, date("2024-06-03"), now, &settings).warnings,
+            [PlanWarning::LockConflict { task_id: call.id.to_string() }, PlanWarning::Overcommitted { short_min: 120 }]
+        );
+    }
+
+    #[test]
+    fn due_date_at_the_edges_of_a_day_under_both_boundaries() {
+        let strict = DaySettings { due_boundary: DueBoundary::Strict, ..DaySettings::default() };
+        let inclusive = DaySettings { due_boundary: DueBoundary::InclusiveNextMidnight, ..DaySettings::default() };
+        let start_of_3rd = clock::local("2024-06-03 00:00");
+        let end_of_3rd = clock::local("2024-06-03 23:59") + Duration::seconds(59);
+        let start_of_4th = clock::local("2024-06-04 00:00");
+        // (due_at, strict, inclusive_next_midnight)
+        let table = [
+            (start_of_3rd, "2024-06-03", "2024-06-02"),
+            (start_of_3rd + Duration::seconds(1), "2024-06-03", "2024-06-03"),
+            (end_of_3rd, "2024-06-03", "2024-06-03"),
+            (start_of_4th, "2024-06-04", "2024-06-03"),
+        ];
+        for (due_at, on_strict, on_inclusive) in table {
+            assert_eq!(due_date(&due_at, &strict), date(on_strict), "strict {due_at}");
+            assert_eq!(due_date(&due_at, &inclusive), date(on_inclusive), "inclusive {due_at}");
+            for (settings, on) in [(&strict, on_strict), (&inclusive, on_inclusive)] {
+                assert!(is_due_on(due_at, date(on), settings));
+                assert!(!is_due_on(due_at, date(on) + Duration::days(1), settings));
+            }
+        }
+    }
+
+    fn diff_titles(items: &[ItemDiff]) -> Vec<&str> {
+        items.iter().map(|d| d.title.as_str()).collect()
+    }
+
+    #[test]
+    fn a_reordered_plan_only_moves_items() {
+        let left = vec![test_item("a", "2024-06-03 09:00", "2024-06-03 10:00"), test_item("b", "2024-06-03 10:00", "2024-06-03 11:00")];
+        let mut right = vec![left[1].clone(), left[0].clone()];
+        (right[0].start, right[0].end) = (left[0].start, left[0].end);
+        (right[1].start, right[1].end) = (left[1].start, left[1].end);
+
+        let diff = diff_plans(&left, &right);
+        assert_eq!(diff_titles(&diff.moved), ["b", "a"]);
+        assert!(diff.added.is_empty() && diff.removed.is_empty() && diff.resized.is_empty());
+        assert!(diff.moved.iter().all(|d| !d.uncertain && d.left_task_id.is_none()));
+        let s = diff.summary;
+        assert_eq!((s.left_count, s.right_count, s.left_min, s.right_min, s.unchanged, s.finish_delta_min), (2, 2, 120, 120, 0, Some(0)));
+        assert_eq!(diff_plans(&left, &left).summary.unchanged, 2);
+    }
+
+    #[test]
+    fn a_recurrence_successor_is_matched_by_title_and_flagged() {
+        let left = vec![
+            test_item("Weekly review", "2024-06-03 09:00", "2024-06-03 10:00"),
+            test_item("standup", "2024-06-03 10:00", "2024-06-03 10:15"),
+            test_item("dropped", "2024-06-03 10:15", "2024-06-03 11:00"),
+        ];
+        let mut standup = left[1].clone();
+        standup.end = clock::local("2024-06-03 10:30");
+        let right = vec![
+            standup,
+            test_item("weekly  REVIEW", "2024-06-03 14:00", "2024-06-03 15:00"), // the successor, a new task
+            test_item("new", "2024-06-03 15:00", "2024-06-03 16:00"),
+        ];
+
+        let diff = diff_plans(&left, &right);
+        assert_eq!(diff.moved.len(), 1);
+        let successor = &diff.moved[0];
+        assert!(successor.uncertain);
+        assert_eq!(successor.left_task_id.as_deref(), Some(left[0].task_id.as_str()));
+        assert_eq!(successor.task_id, right[1].task_id);
+        assert_eq!(successor.left.unwrap().start, left[0].start);
+        assert_eq!(diff_titles(&diff.resized), ["standup"]);
+        assert_eq!(diff_titles(&diff.added), ["new"]);
+        assert_eq!(diff_titles(&diff.removed), ["dropped"]);
+        assert_eq!(diff.summary.finish_delta_min, Some(300));
+        assert_eq!(diff_plans(&left, &[]).summary.finish_delta_min, None);
+    }
+
+    #[test]
+    fn locks_are_charged_once_and_only_inside_the_window() {
+        let now = clock::local("2024-06-03 07:00");
+        let mut tasks = overdue_hours(3, "work");
+        tasks.push(locked_at("early", "2024-06-03 08:00", "2024-06-03 09:30"));
+        tasks.push(locked_at("overlap", "2024-06-03 09:00", "2024-06-03 10:00"));
+        let (plan, _, window) = plan_for_date(&tasks, &DaySettings::default(), date("2024-06-03"), now, 120, true);
+        let ledger = budget_ledger(&plan, &window);
+        assert_eq!(
+            ledger,
+            BudgetLedger { gross_min: 540, task_min: 60, locked_min: 60, idle_min: 0, unbudgeted_min: 420 }
+        );
+    }
+
+    // Property: every minute of the window is in exactly one column,
+    // for randomized tasks, locks, settings, now and budgets.
+    #[test]
+    fn the_ledger_always_sums_to_the_gross_window() {
+        let windows = [("09:00", "18:00"), ("06:30", "22:15"), ("22:00", "03:00"), ("12:00", "12:45")];
+        for seed in 0..300 {
+            let mut rng = TestRng::new(seed);
+            let (day_start, day_end) = windows[rng.below(windows.len())];
+            let settings = DaySettings { day_start: day_start.into(), day_end: day_end.into(), ..DaySettings::default() };
+            let day = date("2024-06-03");
+            let base = clock::local("2024-06-03 00:00");
+            let now = base + Duration::minutes(rng.below(24 * 60) as i64);
+            let available = [0, 30, 240, 600, 2000][rng.below(5)] + rng.below(30) as i64;
+
+            let tasks: Vec<Task> = (0..rng.below(16))
+                .map(|i| {
+                    let due = base + Duration::minutes(rng.below(2 * 24 * 60) as i64 - 12 * 60);
+                    let mut t = test_task(&format!("t{i}"), due);
+                    t.duration_min = 1 + rng.below(300) as i64;
+                    t.priority = 1 + rng.below(5) as i64;
+                    if rng.below(4) == 0 {
+                        let start = base + Duration::minutes(rng.below(26 * 60) as i64);
+                        t.locked = Some(PlanLock { start, end: start + Duration::minutes(1 + rng.below(180) as i64) });
+                    }
+                    t
+                })
+                .collect();
+
+            let (plan, _, window) = plan_for_date(&tasks, &settings, day, now, available, rng.below(2) == 0);
+            let l = budget_ledger(&plan, &window);
+            let case = format!("seed {seed}: {day_start}-{day_end} at {now}, {available} min: {l:?}");
+            assert!([l.task_min, l.locked_min, l.idle_min, l.unbudgeted_min].iter().all(|m| *m >= 0), "{case}");
+            assert_eq!(l.task_min + l.locked_min + l.idle_min + l.unbudgeted_min, l.gross_min, "{case}");
+            assert_eq!(l.gross_min, (window.end - window.start).num_minutes().max(0), "{case}");
+            assert!(l.task_min <= window.available_min.max(0), "{case}");
+        }
+    }
 }
@@ -1,36 +1,173 @@
 // Define data modules
+mod config; // Server configuration (CLI > env > scheduler.toml > defaults)
+mod auth;   // Optional API key middleware
+mod read_only; // --read-only: refuse API writes
+mod clock;  // Configured time zone: "now" and local date/time conversion
 mod models; // Data structures (Task, Settings, Db, etc.)
-mod store;  // Persistent storage (load/save db.json)
+mod store;  // Persistent storage: Store trait, JSON backend
+mod crypt;  // Passphrase encryption of db.json at rest
+#[cfg(feature = "sqlite")]
+mod store_sqlite; // SQLite backend (--storage sqlite)
+#[cfg(feature = "postgres")]
+mod store_postgres; // PostgreSQL backend (--storage postgres)
+mod writer; // Single task that applies and saves all db mutations
+mod validation; // Title/notes sanitization and length limits
 mod logic;  // Core scheduling and scoring logic
+mod collate; // Locale-aware title ordering
 mod routes_tasks;   // HTTP handlers for task & settings APIs
+mod routes_trash;   // HTTP handlers for listing, restoring and purging deleted tasks
 mod routes_plan;    // HTTP handlers for today plan API
+mod sticky;         // Cached plans reused while their inputs are unchanged
+mod digest;         // Plain-text daily digest formatting
+mod i18n;           // English / Korean message text
+mod import;         // Parsers for task exports from other tools
+mod routes_import;  // HTTP handlers for import APIs
+mod notify;         // Daily plan delivery to chat webhooks
+mod routes_notifications; // HTTP handlers for notification settings
+mod routes_settings; // HTTP handlers for settings export/import
+mod routes_health;  // HTTP handler for health/config reporting
+mod assets;         // Static frontend embedded into the binary
+mod ics;            // iCalendar formatting
+mod routes_feeds;   // HTTP handlers for calendar subscription feeds
+mod obsidian;       // Obsidian Tasks Markdown formatting
+mod org;            // Org-mode formatting
+mod tasks_csv;      // Tasks as CSV for spreadsheets (export and import)
+mod routes_export;  // HTTP handlers for export APIs
+mod anonymize;      // Placeholder-only copy of the db for bug reports
+mod transfer;       // Whole-db export documents: checks, replace and merge
+mod archive;        // Auto-archive of long-finished tasks
+mod compact;        // Compaction of old Done tasks into month files
+mod stale;          // Aging report of long-open tasks
+mod history;        // Daily plan-vs-reality snapshots
+mod routes_history; // HTTP handler for reading snapshots
+mod routes_audit;   // HTTP handler for reading the audit log
+mod routes_admin;   // HTTP handlers for integrity check and repair
+mod routes_backups; // HTTP handlers for listing and restoring backups
+mod launch;         // --open and startup QR code
+mod preflight;      // Startup checks of config, storage and static files (--check)
+mod migrate;        // Schema version steps on load; --migrate-check/-only report and apply them
+mod request_id;     // X-Request-Id correlation for logs and errors
+mod api_json;       // JSON request extractor that rejects unknown fields
 
 // Import axum routing utilities and Router
 use axum::{
-    routing::{get, post, put}, // HTTP method helpers
+    extract::{DefaultBodyLimit, Request, State},
+    middleware::{self, Next},
+    http::{StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    routing::{delete, get, post}, // HTTP method helpers
     Router, // Main router type
+    ServiceExt,
 };
+use tower::Layer;
 use tower_http::services::ServeDir; // Used to serve static files (HTML/CSS/JS)
-use std::net::SocketAddr;   // ServeDir is used to serve static files (HTML/CSS/JS)
+
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::config::StorageBackend;
+use crate::models::Db;
+use crate::store::StoreError;
 
 
 #[tokio::main]
 async fn main() {
-    let api = Router::new()
-        // plan
-        .route("/plan/today", get(routes_plan::get_today_plan))
-        // tasks
-        .route("/tasks", get(routes_tasks::get_tasks).post(routes_tasks::create_task))
-        .route("/tasks/:id", put(routes_tasks::update_task).delete(routes_tasks::delete_task))
-        .route("/tasks/:id/toggle", post(routes_tasks::toggle_task))
-        // settings
-        .route("/settings", get(routes_tasks::get_settings).put(routes_tasks::put_settings));
+    // --check: validate the environment and exit 0/1 without serving
+    // --migrate-check / --migrate-only: report / apply the db.json upgrade and exit 0/1
+    // --recover: back up an unparsable db.json and start with an empty one
+    // --repair: apply the safe integrity fixes (store::repair) to db.json, then start
+    // --import-json PATH: copy a db.json into an empty database of the configured backend and exit 0/1
+    // --compact [--compact-days N]: move old Done tasks into archive/<month>.json and exit 0/1
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let check_only = args.iter().any(|a| a == "--check");
+    let migrate_check = args.iter().any(|a| a == "--migrate-check");
+    let migrate_only = args.iter().any(|a| a == "--migrate-only");
+    let recover = args.iter().any(|a| a == "--recover");
+    let repair = args.iter().any(|a| a == "--repair");
+    let compact_only = args.iter().any(|a| a == "--compact");
+    args.retain(|a| {
+        !matches!(a.as_str(), "--check" | "--migrate-check" | "--migrate-only" | "--recover" | "--repair" | "--compact")
+    });
+    let import_json = match take_value(&mut args, "--import-json") {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("  {e}");
+            std::process::exit(1);
+        }
+    };
+    let compact_days = match take_value(&mut args, "--compact-days").map(|days| days.map(|d| d.parse::<u32>())) {
+        Ok(None) => compact::DEFAULT_DAYS,
+        Ok(Some(Ok(days))) => days,
+        Ok(Some(Err(_))) => {
+            eprintln!("  --compact-days must be a whole number of days");
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("  {e}");
+            std::process::exit(1);
+        }
+    };
+
+    // Resolve configuration before anything reads it
+    let cfg = match config::init(&args) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            eprintln!("  Config error: {e}");
+            let one_shot = check_only || migrate_check || migrate_only || compact_only || import_json.is_some();
+            std::process::exit(if one_shot { 1 } else { 2 });
+        }
+    };
+
+    if let Some(path) = import_json {
+        std::process::exit(run_import_json(&path).await);
+    }
+    if migrate_check || migrate_only {
+        std::process::exit(run_migrate(migrate_only).await);
+    }
+    if compact_only {
+        std::process::exit(run_compact(compact_days).await);
+    }
+    if recover && !check_only && !run_recover().await {
+        std::process::exit(2);
+    }
+    if repair && !check_only && !run_repair().await {
+        std::process::exit(2);
+    }
+
+    // Stop on a broken setup, listing every problem at once
+    let problems = preflight::run(cfg);
+    if !problems.is_empty() {
+        eprint!("{}", preflight::report(&problems));
+        std::process::exit(if check_only { 1 } else { 2 });
+    }
+    if check_only {
+        println!("  Startup check passed");
+        return;
+    }
+
+    // One server per database file; held until the process exits
+    let _db_lock = match store::lock_db() {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("  {}", e.describe());
+            std::process::exit(2);
+        }
+    };
+
+    let app = router(cfg.server.static_dir.as_deref(), cfg.server.read_only);
+
+    // Serve everything under base_path; unprefixed paths are 404.
+    // Wrapped around the router so the prefix is stripped before routing.
+    let base = config::base_path();
+    let app = middleware::from_fn_with_state(base, strip_base_path).layer(app);
 
-    let app = Router::new()
-        .nest("/api", api)
-        .nest_service("/", ServeDir::new("static"));
+    let addr = format!("{}:{}{}", cfg.server.host, cfg.server.port, base);
 
-    let addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+    // Print the effective config (secrets redacted)
+    println!("  Effective config:");
+    for line in toml::to_string(&cfg.redacted()).unwrap_or_default().lines() {
+        println!("    {line}");
+    }
 
     // Print the link to the server 
     println!("  Server running at http://{}", addr);
@@ -38,13 +175,444 @@ async fn main() {
     println!("  API base:     http://{}/api", addr);
 
     // Define listener with an address
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000")
+    let listener = tokio::net::TcpListener::bind((cfg.server.host.as_str(), cfg.server.port))
         .await
         .expect("bind failed");
 
+    // The db is read once; from here on it lives in memory (writer.rs)
+    let db = match store::load_db().await {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("  {}", e.describe());
+            std::process::exit(2);
+        }
+    };
+
+    // Report inconsistencies in a hand-edited db.json (fixed by POST /api/admin/repair or --repair)
+    for issue in store::check_integrity(&db) {
+        eprintln!("  Integrity: {}", issue.describe());
+    }
+
+    // All db mutations are applied by one writer task
+    writer::spawn(db);
+
+    // Post the daily plan to the configured webhook in the background
+    tokio::spawn(notify::run_daily_webhook());
+    // Push ntfy/Gotify notifications for tasks coming due
+    tokio::spawn(notify::run_deadline_pusher());
+    // Archive long-finished tasks at startup and once a day
+    tokio::spawn(archive::run_auto_archive());
+    // Record plan vs reality once each day window has ended
+    tokio::spawn(history::run_daily_snapshot());
+
+    // Phone-friendly QR code when reachable from other devices; shown
+    // only once the db has loaded, so a failed load prints no link
+    if !launch::is_loopback(&cfg.server.host) {
+        let host = launch::reachable_host(&cfg.server.host);
+        launch::print_qr(&format!("http://{}:{}{}/", host, cfg.server.port, base));
+    }
+    // --open: the browser runs on this machine, so loopback always works
+    if cfg.server.open_browser {
+        let url = format!("http://localhost:{}{}/", cfg.server.port, base);
+        launch::open_browser(&url, |u| open::that_detached(u));
+    }
 
     // Start HTTP server
-    axum::serve(listener, app)
+    axum::serve(listener, ServiceExt::<Request>::into_make_service(app))
         .await
         .expect("server error");
 }
+
+// The API under /api and the frontend at /, without base_path.
+// static_dir: serve the frontend from disk instead of the binary.
+// read_only: refuse API writes (read_only.rs).
+fn router(static_dir: Option<&str>, read_only: bool) -> Router {
+    let api = Router::new()
+        // plan
+        .route("/plan/today", get(routes_plan::get_today_plan))
+        .route("/plan/week", get(routes_plan::get_week_plan))
+        .route("/plan/simulate", get(routes_plan::simulate_plan))
+        .route("/plan/compare", post(routes_plan::compare_plans))
+        .route("/plan/now", get(routes_plan::get_plan_now))
+        .route("/plan/scores", get(routes_plan::get_scores))
+        .route("/plan/diff", get(routes_plan::get_plan_diff))
+        .route("/plan/items/:task_id/lock", post(routes_plan::lock_item))
+        .route("/plan/items/:task_id/unlock", post(routes_plan::unlock_item))
+        .route("/digest", get(routes_plan::get_digest))
+        .route("/history", get(routes_history::get_history))
+        .route("/audit", get(routes_audit::get_audit))
+        // tasks
+        .route("/tasks", get(routes_tasks::get_tasks).post(routes_tasks::create_task))
+        .route("/tasks/bulk-tags", post(routes_tasks::bulk_tags))
+        .route("/tasks/stale", get(routes_tasks::get_stale_tasks))
+        .route("/tasks/export.csv", get(routes_export::export_tasks_csv))
+        .route("/tasks/import", post(routes_import::import_tasks_csv))
+        .route(
+            "/tasks/:id",
+            get(routes_tasks::get_task)
+                .put(routes_tasks::update_task)
+                .delete(routes_tasks::delete_task),
+        )
+        .route("/tasks/:id/toggle", post(routes_tasks::toggle_task))
+        // trash
+        .route("/trash", get(routes_trash::get_trash))
+        .route("/trash/:id", delete(routes_trash::purge_task))
+        .route("/trash/:id/restore", post(routes_trash::restore_task))
+        // settings
+        .route("/settings", get(routes_tasks::get_settings).put(routes_tasks::put_settings))
+        .route("/settings/export", get(routes_settings::export_settings))
+        .route("/settings/import", post(routes_settings::import_settings))
+        // import
+        .route(
+            "/import",
+            post(routes_import::import_db).layer(DefaultBodyLimit::max(routes_import::MAX_DB_IMPORT_BYTES)),
+        )
+        .route("/import/todoist", post(routes_import::import_todoist))
+        .route("/import/google-tasks", post(routes_import::import_google_tasks))
+        .route("/import/taskwarrior", post(routes_import::import_taskwarrior))
+        // export
+        .route("/export", get(routes_export::export_db))
+        .route("/export/obsidian", get(routes_export::export_obsidian))
+        .route("/export/taskwarrior", get(routes_export::export_taskwarrior))
+        .route("/export/org", get(routes_export::export_org))
+        .route("/export/anonymized", get(routes_export::export_anonymized))
+        // notifications
+        .route(
+            "/notifications",
+            get(routes_notifications::get_notifications).put(routes_notifications::put_notifications),
+        )
+        .route("/notifications/test", post(routes_notifications::test_notification))
+        .route("/notifications/test-push", post(routes_notifications::test_push))
+        .route("/notifications/deliveries", get(routes_notifications::get_deliveries))
+        .route(
+            "/notifications/deliveries/:id/redeliver",
+            post(routes_notifications::redeliver),
+        )
+        // maintenance
+        .route("/admin/integrity", get(routes_admin::get_integrity))
+        .route("/admin/repair", post(routes_admin::repair))
+        .route("/maintenance/validate", get(routes_admin::get_integrity))
+        .route("/maintenance/compact", post(routes_admin::compact))
+        .route("/archive", get(routes_admin::get_archive))
+        .route("/backups", get(routes_backups::get_backups))
+        .route("/backups/:name/restore", post(routes_backups::restore_backup))
+        // with --read-only, everything above only serves reads
+        .route_layer(middleware::from_fn_with_state(read_only, read_only::reject_writes))
+        // everything above requires the api key when one is configured
+        .route_layer(middleware::from_fn(auth::require_api_key))
+        // health
+        .route("/health", get(routes_health::get_health))
+        // calendar feeds (authenticate with ?token= instead of a header)
+        .route("/feeds/:file", get(routes_feeds::get_tag_feed))
+        // X-Request-Id on every /api response and error body
+        .layer(middleware::from_fn(request_id::assign));
+
+    // Frontend: embedded copy by default, on-disk directory with --static-dir
+    match static_dir {
+        Some(dir) => Router::new()
+            .nest("/api", api)
+            .nest_service(
+                "/",
+                middleware::from_fn(assets::cache_headers)
+                    .layer(ServeDir::new(dir).precompressed_br().precompressed_gzip()),
+            ),
+        None => Router::new()
+            .nest("/api", api)
+            .fallback(assets::serve_embedded),
+    }
+}
+
+// Send a request through the full router (test db, no base path)
+// and return the status, headers and body as JSON (Null if it is not).
+#[cfg(test)]
+async fn test_request(req: Request) -> (StatusCode, axum::http::HeaderMap, serde_json::Value) {
+    use tower::ServiceExt as _;
+    writer::spawn_for_tests();
+    let res = router(None, false).oneshot(req).await.unwrap();
+    let (parts, body) = res.into_parts();
+    let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+    (parts.status, parts.headers, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+}
+
+// Map "/prefix/rest" to "/rest" when a base_path is configured.
+//
+// - "/prefix" redirects to "/prefix/" so the frontend's relative
+//   asset and API URLs resolve inside the prefix
+// - Paths outside the prefix are 404
+async fn strip_base_path(State(base): State<&'static str>, mut req: Request, next: Next) -> Response {
+    if base.is_empty() {
+        return next.run(req).await;
+    }
+
+    let path = req.uri().path();
+    if path == base {
+        let target = match req.uri().query() {
+            Some(q) => format!("{base}/?{q}"),
+            None => format!("{base}/"),
+        };
+        return Redirect::permanent(&target).into_response();
+    }
+
+    let Some(rest) = path.strip_prefix(base).filter(|r| r.starts_with('/')) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let rewritten = match req.uri().query() {
+        Some(q) => format!("{rest}?{q}"),
+        None => rest.to_string(),
+    };
+    match rewritten.parse::<Uri>() {
+        Ok(uri) => *req.uri_mut() = uri,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    }
+    next.run(req).await
+}
+
+// --recover: when db.json cannot be parsed, load_db has copied it to
+// db.json.corrupt-<timestamp>; replace it with an empty db. A valid
+// db.json is left alone. False when startup should stop.
+async fn run_recover() -> bool {
+    let _db_lock = match store::lock_db_wait(store::CLI_LOCK_WAIT).await {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("  {}", e.describe());
+            return false;
+        }
+    };
+    match store::load_db().await {
+        Ok(_) => true,
+        Err(StoreError::Corrupt { backup: Some(backup), .. }) => match store::save_db(Arc::new(Db::default())).await {
+            Ok(()) => {
+                println!("  Recovered: the unreadable db was saved to {backup}; starting empty");
+                true
+            }
+            Err(_) => false,
+        },
+        // Not backed up, or not a parse error: leave the file as it is
+        Err(_) => false,
+    }
+}
+
+// --repair: fix what store::check_integrity reports and is safe to
+// fix (store::repair), save, and list each change. False when
+// startup should stop.
+async fn run_repair() -> bool {
+    let _db_lock = match store::lock_db_wait(store::CLI_LOCK_WAIT).await {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("  {}", e.describe());
+            return false;
+        }
+    };
+    let Ok(mut db) = store::load_db().await else {
+        return false;
+    };
+    let fixes = store::repair(&mut db);
+    if fixes.is_empty() {
+        println!("  Repair: nothing to fix");
+        return true;
+    }
+    if store::save_db(Arc::new(db)).await.is_err() {
+        return false;
+    }
+    println!("  Repaired {} problem(s) in {}:", fixes.len(), store::db_label());
+    for fix in &fixes {
+        println!("    {}", fix.describe());
+    }
+    true
+}
+
+// --migrate-check prints what loading db.json would change; --migrate-only
+// also saves the result (taking the db lock, so never under a running server).
+async fn run_migrate(apply: bool) -> i32 {
+    let path = store::db_path();
+    if config::get().storage.backend != StorageBackend::Json {
+        eprintln!("  --migrate-check and --migrate-only apply to the json storage backend");
+        return 1;
+    }
+    let report = match migrate::check(path) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("  Migration would fail: {e}");
+            return 1;
+        }
+    };
+    print!("{}", migrate::render(path, &report));
+    if !apply || report.is_empty() {
+        return 0;
+    }
+
+    let _db_lock = match store::lock_db_wait(store::CLI_LOCK_WAIT).await {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("  {}", e.describe());
+            return 1;
+        }
+    };
+    match store::save_db(Arc::new(report.db)).await {
+        Ok(()) => {
+            println!("  Migrated {path}");
+            0
+        }
+        Err(_) => 1,
+    }
+}
+
+// --compact: the same as POST /api/maintenance/compact, with the server
+// stopped (it takes the db lock). Archive files first, then the db.
+async fn run_compact(days: u32) -> i32 {
+    let _db_lock = match store::lock_db_wait(store::CLI_LOCK_WAIT).await {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("  {}", e.describe());
+            return 1;
+        }
+    };
+    let Ok(mut db) = store::load_db().await else {
+        return 1;
+    };
+    let moved = compact::select(&db, days, clock::now());
+    if moved.is_empty() {
+        println!("  Nothing to compact: no Done task was due more than {days} days ago");
+        return 0;
+    }
+    if let Err(e) = store::write_archive(&moved).await {
+        eprintln!("  {e}; nothing was removed from {}", store::db_label());
+        return 1;
+    }
+    let removed = compact::remove(&mut db, &moved);
+    if store::save_db(Arc::new(db)).await.is_err() {
+        return 1;
+    }
+    println!("  Compacted {removed} task(s) into {}", store::archive_dir().display());
+    for m in compact::months(&moved) {
+        println!("    {}: {}", m.month, m.tasks);
+    }
+    0
+}
+
+// Remove "--flag VALUE" or "--flag=VALUE" from `args`; the value, if given.
+fn take_value(args: &mut Vec<String>, flag: &str) -> Result<Option<String>, String> {
+    let prefix = format!("{flag}=");
+    let Some(i) = args.iter().position(|a| a == flag || a.starts_with(&prefix)) else {
+        return Ok(None);
+    };
+    let arg = args.remove(i);
+    match arg.strip_prefix(&prefix) {
+        Some(value) => Ok(Some(value.to_string())),
+        None if i < args.len() => Ok(Some(args.remove(i))),
+        None => Err(format!("missing value for {flag}")),
+    }
+}
+
+// The db.json --import-json reads, migrated and upgraded like a load.
+fn read_import_source(source: &str) -> Result<Db, String> {
+    let bytes = std::fs::read(source).map_err(|e| e.to_string())?;
+    // describe() names the configured db, not the source file
+    let decoded = store::db_text(bytes).and_then(|text| store::decode(&text)).map_err(|e| match e {
+        StoreError::Corrupt { message, .. } => message,
+        StoreError::TooNew { version } => format!("schema version {version} is newer than this build reads"),
+        StoreError::Encrypted => "it is encrypted and no db key is set".to_string(),
+        StoreError::WrongKey => "the db key does not decrypt it".to_string(),
+        e => e.code().to_string(),
+    })?;
+    let mut db = decoded.db;
+    store::upgrade(&mut db);
+    Ok(db)
+}
+
+// --import-json PATH: load a db.json (with the usual load-time upgrade
+// and repair of duplicate ids) and save it through the configured
+// backend, e.g. into a new db.sqlite or PostgreSQL database. Refuses a target that already
+// holds tasks, so it cannot merge or overwrite by accident.
+async fn run_import_json(source: &str) -> i32 {
+    // PostgreSQL keeps nothing at db_path, so importing its db.json is fine
+    let backend = config::get().storage.backend;
+    if backend != StorageBackend::Postgres && Path::new(source) == Path::new(store::db_path()) {
+        eprintln!("  --import-json: {source} is the configured database itself");
+        return 1;
+    }
+    let mut db = match read_import_source(source) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("  Cannot import {source}: {e}");
+            return 1;
+        }
+    };
+    for fix in store::repair(&mut db) {
+        println!("  Repaired: {}", serde_json::to_string(&fix).unwrap_or_default());
+    }
+
+    let _db_lock = match store::lock_db_wait(store::CLI_LOCK_WAIT).await {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("  {}", e.describe());
+            return 1;
+        }
+    };
+    match store::load_db().await {
+        Ok(target) if target.tasks.is_empty() && target.archived.is_empty() => {}
+        Ok(target) => {
+            eprintln!(
+                "  {} already holds {} task(s); import into a new, empty database",
+                store::db_label(),
+                target.tasks.len() + target.archived.len()
+            );
+            return 1;
+        }
+        Err(_) => return 1,
+    }
+    let (tasks, archived) = (db.tasks.len(), db.archived.len());
+    match store::save_db(Arc::new(db)).await {
+        Ok(()) => {
+            println!("  Imported {tasks} task(s) and {archived} archived from {source} into {}", store::db_label());
+            0
+        }
+        Err(_) => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use tower::ServiceExt as _;
+
+    async fn status(path: &str) -> (StatusCode, Option<String>) {
+        writer::spawn_for_tests();
+        let app = middleware::from_fn_with_state("/scheduler", strip_base_path).layer(router(None, false));
+        let req = Request::builder().uri(path).body(Body::empty()).unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        let location = res.headers().get("location").and_then(|v| v.to_str().ok()).map(str::to_string);
+        (res.status(), location)
+    }
+
+    #[tokio::test]
+    async fn api_is_served_under_the_prefix_only() {
+        assert_eq!(status("/scheduler/api/tasks?date=2024-06-03").await.0, StatusCode::OK);
+        assert_eq!(status("/api/tasks?date=2024-06-03").await.0, StatusCode::NOT_FOUND);
+        assert_eq!(status("/schedulerx/api/tasks?date=2024-06-03").await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn frontend_is_served_under_the_prefix_only() {
+        assert_eq!(status("/scheduler/").await.0, StatusCode::OK);
+        assert_eq!(status("/scheduler/styles.css").await.0, StatusCode::OK);
+        assert_eq!(status("/styles.css").await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn bare_prefix_redirects_to_trailing_slash() {
+        let (code, location) = status("/scheduler?x=1").await;
+        assert_eq!(code, StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(location.as_deref(), Some("/scheduler/?x=1"));
+    }
+
+    #[tokio::test]
+    async fn empty_prefix_serves_at_the_root() {
+        writer::spawn_for_tests();
+        let app = middleware::from_fn_with_state("", strip_base_path).layer(router(None, false));
+        let req = Request::builder().uri("/api/tasks?date=2024-06-03").body(Body::empty()).unwrap();
+        assert_eq!(app.oneshot(req).await.unwrap().status(), StatusCode::OK);
+    }
+}
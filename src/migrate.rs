@@ -0,0 +1,497 @@
+/*
+Schema migrations of the stored db, and their dry run / report
+(--migrate-check) and application without serving (--migrate-only).
+
+Every load goes through these steps, in order:
+1. migrate_value: the raw JSON is brought from its schema_version
+   (1 for files written before the field existed) up to
+   models::SCHEMA_VERSION, one STEPS entry per version. A file
+   from a newer build is refused rather than loaded half-understood
+2. serde fills defaults for fields added since (rev, postpone_count,
+   newer settings, ...)
+3. keys the current version does not know are kept in the
+   `extra` maps of Db, DaySettings and Task; elsewhere they are
+   dropped (lossy)
+
+store.rs saves a file back as soon as a version step ran; the other
+steps are written by the next save.
+
+The report compares the file as written with the db as the next
+save would write it, grouped by field path ("tasks[].rev"), so no
+step has to describe itself beyond its title.
+*/
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use chrono::DateTime;
+use serde_json::Value;
+
+use crate::models::{self, Db, SCHEMA_VERSION, Task};
+use crate::store;
+use crate::validation;
+
+// One schema upgrade: turns a version `from` document into `from + 1`.
+pub struct Step {
+    pub from: u32,
+    pub title: &'static str,
+    run: fn(&mut Value),
+}
+
+// Every step since version 1, oldest first.
+pub const STEPS: &[Step] = &[
+    Step {
+        from: 1,
+        title: "done tasks without completed_at get their updated_at",
+        run: completed_at_from_updated_at,
+    },
+    Step {
+        from: 2,
+        title: "tags are normalized (lossy: the original spelling is not kept)",
+        run: normalize_tags,
+    },
+    Step {
+        from: 3,
+        title: "tasks without updated_at get their created_at",
+        run: updated_at_from_created_at,
+    },
+];
+
+// v1 -> v2. Toggling to Done set completed_at only from the version
+// that added it; such a toggle also bumped updated_at, which is the
+// best estimate left (later edits only make it later). Tasks with
+// neither keep no completed_at (archive.rs falls back to due_at).
+fn completed_at_from_updated_at(db: &mut Value) {
+    for list in ["tasks", "archived"] {
+        let Some(Value::Array(tasks)) = db.get_mut(list) else { continue };
+        for task in tasks.iter_mut().filter_map(Value::as_object_mut) {
+            let done = task.get("status").and_then(Value::as_str) == Some("done");
+            let missing = task.get("completed_at").is_none_or(Value::is_null);
+            if let (true, true, Some(at)) = (done, missing, task.get("updated_at").cloned()) {
+                task.insert("completed_at".to_string(), at);
+            }
+        }
+    }
+}
+
+// v2 -> v3. Tags saved before tag normalization existed are brought
+// into shape (validation::coerce_tags, so under the tags config of the
+// build that migrates). A tag list holding anything but strings is
+// left for deserialization to report.
+fn normalize_tags(db: &mut Value) {
+    for list in ["tasks", "archived"] {
+        let Some(Value::Array(tasks)) = db.get_mut(list) else { continue };
+        for task in tasks.iter_mut().filter_map(Value::as_object_mut) {
+            let Some(raw) = task.get("tags").and_then(|t| serde_json::from_value::<Vec<String>>(t.clone()).ok()) else {
+                continue;
+            };
+            let tags = validation::coerce_tags(&raw).map_or(Value::Null, |t| t.into());
+            task.insert("tags".to_string(), tags);
+        }
+    }
+}
+
+// v3 -> v4. updated_at became required: a task never edited (or
+// saved before the field existed) was last updated when created.
+// Trashed tasks too, as they come back with a restore.
+fn updated_at_from_created_at(db: &mut Value) {
+    for list in ["tasks", "archived", "trash"] {
+        let Some(Value::Array(tasks)) = db.get_mut(list) else { continue };
+        for task in tasks.iter_mut() {
+            let task = if list == "trash" { task.get_mut("task") } else { Some(task) };
+            let Some(task) = task.and_then(Value::as_object_mut) else { continue };
+            let missing = task.get("updated_at").is_none_or(Value::is_null);
+            if let (true, Some(at)) = (missing, task.get("created_at").cloned()) {
+                task.insert("updated_at".to_string(), at);
+            }
+        }
+    }
+}
+
+// Tags of every task in a raw db document, tasks then archived.
+fn stored_tags(db: &Value) -> Vec<Vec<String>> {
+    ["tasks", "archived"]
+        .iter()
+        .filter_map(|list| db.get(list).and_then(Value::as_array))
+        .flatten()
+        .map(|t| t.get("tags").and_then(|t| serde_json::from_value(t.clone()).ok()).unwrap_or_default())
+        .collect()
+}
+
+// A db written by a newer build than this one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TooNew {
+    pub version: u32,
+}
+
+// Version of a raw db document; 1 when it has none.
+pub fn version_of(db: &Value) -> u32 {
+    db.get("schema_version")
+        .and_then(Value::as_u64)
+        .map_or_else(models::legacy_schema_version, |v| u32::try_from(v).unwrap_or(u32::MAX))
+}
+
+// Bring a raw db document up to SCHEMA_VERSION in place. Returns
+// the titles of the steps that ran (empty when it was current).
+pub fn migrate_value(db: &mut Value) -> Result<Vec<&'static str>, TooNew> {
+    let version = version_of(db);
+    if version > SCHEMA_VERSION {
+        return Err(TooNew { version });
+    }
+    let mut ran = Vec::new();
+    for step in STEPS.iter().filter(|s| s.from >= version) {
+        (step.run)(db);
+        ran.push(step.title);
+    }
+    if let Some(db) = db.as_object_mut() {
+        db.insert("schema_version".to_string(), SCHEMA_VERSION.into());
+    }
+    Ok(ran)
+}
+
+// What loading and saving the file once would change.
+pub struct Report {
+    pub from_version: u32,                  // schema_version of the file
+    pub steps: Vec<&'static str>,           // version steps that would run
+    pub filled: BTreeMap<String, usize>,    // missing field -> default, count per path
+    pub dropped: BTreeMap<String, usize>,   // unknown field with a value -> removed
+    pub rewritten: BTreeMap<String, usize>, // value written differently (not tags)
+    pub tags: Vec<TagChange>,               // tasks whose tags were normalized
+    pub db: Db,                             // upgraded db, what --migrate-only saves
+}
+
+pub struct TagChange {
+    pub task_id: String,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+impl Report {
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty() && self.filled.is_empty() && self.dropped.is_empty() && self.rewritten.is_empty() && self.tags.is_empty()
+    }
+}
+
+// Run the upgrade on `path` in memory. Err when the file cannot be
+// read or parsed, i.e. when a server start would fail on it.
+pub fn check(path: &str) -> Result<Report, String> {
+    let bytes = fs::read(path).map_err(|e| format!("cannot read {path}: {e}"))?;
+    let text = store::db_text(bytes).map_err(|e| e.describe())?;
+    let raw: Value = serde_json::from_str(&text).map_err(|e| format!("{path} is not JSON: {e}"))?;
+    let mut migrated = raw.clone();
+    let steps = migrate_value(&mut migrated).map_err(|e| {
+        format!("{path} has schema version {}; this build reads up to {SCHEMA_VERSION}", e.version)
+    })?;
+    let mut db: Db =
+        serde_json::from_value(migrated.clone()).map_err(|e| format!("{path} is not a valid database: {e}"))?;
+    store::upgrade(&mut db);
+
+    let tags = stored_tags(&raw)
+        .into_iter()
+        .zip(all_tasks(&db))
+        .filter(|(old, t)| *old != t.tags.clone().unwrap_or_default())
+        .map(|(before, t)| TagChange {
+            task_id: t.id.to_string(),
+            before,
+            after: t.tags.clone().unwrap_or_default(),
+        })
+        .collect();
+
+    let upgraded = serde_json::to_value(&db).map_err(|e| format!("cannot serialize {path}: {e}"))?;
+    let mut report = Report {
+        from_version: version_of(&raw),
+        steps,
+        filled: BTreeMap::new(),
+        dropped: BTreeMap::new(),
+        rewritten: BTreeMap::new(),
+        tags,
+        db,
+    };
+    // Against the stepped document: the steps are listed by title
+    diff("", &migrated, &upgraded, &mut report);
+    Ok(report)
+}
+
+fn all_tasks(db: &Db) -> impl Iterator<Item = &Task> {
+    db.tasks.iter().chain(db.archived.iter())
+}
+
+fn diff(path: &str, raw: &Value, new: &Value, report: &mut Report) {
+    let join = |key: &str| if path.is_empty() { key.to_string() } else { format!("{path}.{key}") };
+    match (raw, new) {
+        (Value::Object(raw), Value::Object(new)) => {
+            for (key, value) in new {
+                match raw.get(key) {
+                    Some(old) => diff(&join(key), old, value, report),
+                    None => *report.filled.entry(join(key)).or_default() += 1,
+                }
+            }
+            for (key, value) in raw {
+                if !new.contains_key(key) && !value.is_null() {
+                    *report.dropped.entry(join(key)).or_default() += 1;
+                }
+            }
+        }
+        (Value::Array(raw), Value::Array(new)) if raw.len() == new.len() => {
+            let path = format!("{path}[]");
+            for (old, value) in raw.iter().zip(new) {
+                diff(&path, old, value, report);
+            }
+        }
+        _ if raw == new || same_instant(raw, new) => {}
+        _ => *report.rewritten.entry(path.to_string()).or_default() += 1,
+    }
+}
+
+// Timestamps are re-formatted on save ("Z" -> "+00:00"); same instant
+// and offset is not a change.
+fn same_instant(a: &Value, b: &Value) -> bool {
+    let parse = |v: &Value| v.as_str().and_then(|s| DateTime::parse_from_rfc3339(s).ok());
+    match (parse(a), parse(b)) {
+        (Some(a), Some(b)) => a == b && a.offset() == b.offset(),
+        _ => false,
+    }
+}
+
+// Human-readable report for the terminal.
+pub fn render(path: &str, report: &Report) -> String {
+    let mut out = format!(
+        "  Migration check of {path} (schema v{}, current v{SCHEMA_VERSION})\n",
+        report.from_version
+    );
+    if report.is_empty() {
+        out.push_str("    Nothing to migrate: the file is already in the current shape\n");
+        return out;
+    }
+    if report.steps.is_empty() {
+        out.push_str("    0. schema steps: none\n");
+    } else {
+        out.push_str("    0. schema steps:\n");
+        for step in &report.steps {
+            out.push_str(&format!("      {step}\n"));
+        }
+    }
+    let counts = |out: &mut String, title: &str, map: &BTreeMap<String, usize>| {
+        if map.is_empty() {
+            out.push_str(&format!("    {title}: none\n"));
+            return;
+        }
+        out.push_str(&format!("    {title}:\n"));
+        for (path, n) in map {
+            out.push_str(&format!("      {path}: {n}\n"));
+        }
+    };
+    counts(&mut out, "1. fill defaults for missing fields", &report.filled);
+    counts(&mut out, "2. drop unknown fields (lossy)", &report.dropped);
+    if report.tags.is_empty() {
+        out.push_str("    3. normalize tags (lossy): none\n");
+    } else {
+        out.push_str(&format!("    3. normalize tags (lossy): {} task(s)\n", report.tags.len()));
+        for c in &report.tags {
+            out.push_str(&format!("      {}: {:?} -> {:?}\n", c.task_id, c.before, c.after));
+        }
+    }
+    if !report.rewritten.is_empty() {
+        counts(&mut out, "values written differently", &report.rewritten);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock;
+    use crate::models::test_task;
+    use serde_json::json;
+
+    // A v2 file whose tags were saved before normalization existed.
+    fn messy_v2() -> Value {
+        let mut db = Db::default();
+        db.tasks = vec![
+            test_task("messy", clock::local("2024-06-03 12:00")),
+            test_task("clean", clock::local("2024-06-03 13:00")),
+            test_task("untagged", clock::local("2024-06-03 14:00")),
+        ];
+        db.archived = vec![test_task("old", clock::local("2024-05-03 12:00"))];
+        let mut doc = serde_json::to_value(&db).unwrap();
+        doc["schema_version"] = 2.into();
+        doc["tasks"][0]["tags"] = json!(["Work", " work ", "Deep  Work", "C++", ""]);
+        doc["tasks"][1]["tags"] = json!(["home"]);
+        doc["tasks"][2]["tags"] = json!(["!!!"]);
+        doc["archived"][0]["tags"] = json!(["Q3 Plans"]);
+        doc
+    }
+
+    #[test]
+    fn v2_tags_are_normalized_by_the_v3_step() {
+        let mut doc = messy_v2();
+        let steps = migrate_value(&mut doc).unwrap();
+        assert_eq!(steps, STEPS[1..].iter().map(|s| s.title).collect::<Vec<_>>());
+        assert_eq!(doc["schema_version"], SCHEMA_VERSION);
+        assert_eq!(doc["tasks"][0]["tags"], json!(["work", "deep-work", "c"]));
+        assert_eq!(doc["tasks"][1]["tags"], json!(["home"]));
+        assert_eq!(doc["tasks"][2]["tags"], Value::Null);
+        assert_eq!(doc["archived"][0]["tags"], json!(["q3-plans"]));
+
+        // A current file is not touched again
+        let mut again = doc.clone();
+        assert!(migrate_value(&mut again).unwrap().is_empty());
+        assert_eq!(again, doc);
+    }
+
+    #[test]
+    fn v1_file_runs_every_step_in_order() {
+        let mut doc = messy_v2();
+        doc.as_object_mut().unwrap().remove("schema_version");
+        doc["tasks"][1]["status"] = "done".into();
+        doc["tasks"][1]["updated_at"] = "2024-06-02T10:00:00-04:00".into();
+        doc["tasks"][1]["completed_at"] = Value::Null;
+        let steps = migrate_value(&mut doc).unwrap();
+        assert_eq!(steps, STEPS.iter().map(|s| s.title).collect::<Vec<_>>());
+        assert_eq!(doc["tasks"][1]["completed_at"], "2024-06-02T10:00:00-04:00");
+        assert_eq!(doc["tasks"][0]["tags"], json!(["work", "deep-work", "c"]));
+
+        let mut newer = json!({ "schema_version": SCHEMA_VERSION + 1 });
+        assert_eq!(migrate_value(&mut newer), Err(TooNew { version: SCHEMA_VERSION + 1 }));
+    }
+
+    #[test]
+    fn v3_tasks_get_updated_at_from_created_at() {
+        let mut doc = messy_v2();
+        doc["schema_version"] = 3.into();
+        doc["tasks"][0]["updated_at"] = "2024-06-02T10:00:00-04:00".into();
+        doc["tasks"][1].as_object_mut().unwrap().remove("updated_at");
+        doc["archived"][0]["updated_at"] = Value::Null;
+        let mut trashed = doc["tasks"][2].clone();
+        trashed.as_object_mut().unwrap().remove("updated_at");
+        doc["trash"] = json!([{ "task": trashed, "deleted_at": "2024-06-03T09:00:00-04:00" }]);
+
+        assert_eq!(migrate_value(&mut doc).unwrap(), [STEPS[2].title]);
+        let db: Db = serde_json::from_value(doc.clone()).unwrap();
+        assert_eq!(db.tasks[0].updated_at, clock::local("2024-06-02 10:00"));
+        assert_eq!(db.tasks[1].updated_at, db.tasks[1].created_at);
+        assert_eq!(db.archived[0].updated_at, db.archived[0].created_at);
+        assert_eq!(db.trash[0].task.updated_at, db.trash[0].task.created_at);
+    }
+
+    #[test]
+    fn migrate_check_reports_the_tag_step_on_a_messy_file() {
+        let path = std::env::temp_dir().join(format!("scheduler-migrate-{}-messy.json", std::process::id()));
+        fs::write(&path, serde_json::to_string_pretty(&messy_v2()).unwrap()).unwrap();
+        let path = path.to_string_lossy().into_owned();
+        let report = check(&path).unwrap();
+
+        assert_eq!((report.from_version, report.steps.clone()), (2, STEPS[1..].iter().map(|s| s.title).collect::<Vec<_>>()));
+        let changes: Vec<(&[String], &[String])> =
+            report.tags.iter().map(|c| (c.before.as_slice(), c.after.as_slice())).collect();
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0].1, ["work", "deep-work", "c"]);
+        assert_eq!((changes[1].0, changes[1].1), (&["!!!".to_string()][..], &[][..]));
+        assert_eq!(changes[2].1, ["q3-plans"]);
+        assert!(report.rewritten.is_empty(), "{:?}", report.rewritten);
+        assert_eq!(report.db.tasks[0].tags.as_deref(), Some(&["work".to_string(), "deep-work".to_string(), "c".to_string()][..]));
+
+        let text = render(&path, &report);
+        assert!(text.contains(&format!("(schema v2, current v{SCHEMA_VERSION})")), "{text}");
+        assert!(text.contains("3. normalize tags (lossy): 3 task(s)"), "{text}");
+    }
+
+    fn fixture(name: &str, doc: &Value) -> String {
+        let path = std::env::temp_dir().join(format!("scheduler-migrate-{}-{name}.json", std::process::id()));
+        fs::write(&path, serde_json::to_string_pretty(doc).unwrap()).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    // A file as the first release wrote it: no schema_version, rev,
+    // postpone_count or completed_at, plus a key no version knows.
+    fn v1() -> Value {
+        json!({
+            "settings": { "day_start": "09:00", "day_end": "18:00", "focus_block_min": 60 },
+            "tasks": [
+                {
+                    "id": "6f1c1c39-8a4e-4d47-9a51-2f0b5f1b7a01",
+                    "title": "done long ago",
+                    "due_at": "2024-06-03T12:00:00-04:00",
+                    "duration_min": 60,
+                    "priority": 3,
+                    "status": "done",
+                    "created_at": "2024-06-01T09:00:00-04:00",
+                    "updated_at": "2024-06-02T17:30:00-04:00",
+                    "tags": ["Deep  Work"],
+                    "notes": null,
+                    "comments": ["kept as is"]
+                },
+                {
+                    "id": "6f1c1c39-8a4e-4d47-9a51-2f0b5f1b7a02",
+                    "title": "open",
+                    "due_at": "2024-06-04T12:00:00Z",
+                    "duration_min": 30,
+                    "priority": 2,
+                    "status": "todo",
+                    "created_at": "2024-06-01T09:00:00-04:00",
+                    "tags": null,
+                    "notes": "kept"
+                }
+            ]
+        })
+    }
+
+    #[test]
+    fn migrate_check_on_a_v1_fixture() {
+        let path = fixture("v1", &v1());
+        let report = check(&path).unwrap();
+        assert_eq!(report.from_version, 1);
+        assert_eq!(report.steps, STEPS.iter().map(|s| s.title).collect::<Vec<_>>());
+        // Unknown keys are carried through (models::Extra)
+        assert!(report.dropped.is_empty(), "{:?}", report.dropped);
+        assert_eq!(report.db.tasks[0].extra["comments"], json!(["kept as is"]));
+        assert_eq!(report.filled.get("tasks[].rev"), Some(&2));
+        assert_eq!(report.filled.get("tasks[].postpone_count"), Some(&2));
+        // "Z" is written as "+00:00" but is the same instant
+        assert!(report.rewritten.is_empty(), "{:?}", report.rewritten);
+        assert_eq!(report.tags.len(), 1);
+        assert_eq!(report.tags[0].after, ["deep-work"]);
+        let done = &report.db.tasks[0];
+        assert_eq!(done.completed_at, Some(done.updated_at));
+        // Never edited: last updated when created
+        assert_eq!(report.db.tasks[1].updated_at, report.db.tasks[1].created_at);
+        assert_eq!(report.db.schema_version, SCHEMA_VERSION);
+
+        let text = render(&path, &report);
+        assert!(text.contains("tasks[].rev: 2") && text.contains(STEPS[0].title), "{text}");
+    }
+
+    #[test]
+    fn migrate_check_on_a_v2_fixture_runs_only_the_later_steps() {
+        let mut doc = v1();
+        doc["schema_version"] = 2.into();
+        let report = check(&fixture("v2", &doc)).unwrap();
+        assert_eq!(report.steps, STEPS[1..].iter().map(|s| s.title).collect::<Vec<_>>());
+        // v2 files were already past the completed_at step
+        assert_eq!(report.db.tasks[0].completed_at, None);
+    }
+
+    #[test]
+    fn a_migrated_file_has_nothing_left_to_migrate() {
+        // What --migrate-only saves is read back as current
+        let report = check(&fixture("v1-again", &v1())).unwrap();
+        let saved = fixture("v3", &serde_json::to_value(&report.db).unwrap());
+        let again = check(&saved).unwrap();
+        assert_eq!(again.from_version, SCHEMA_VERSION);
+        assert!(again.is_empty(), "{:?} {:?} {:?}", again.filled, again.dropped, again.rewritten);
+        assert!(render(&saved, &again).contains("Nothing to migrate"));
+    }
+
+    #[test]
+    fn migrate_check_fails_where_a_start_would() {
+        let mut newer = v1();
+        newer["schema_version"] = (SCHEMA_VERSION + 1).into();
+        let err = check(&fixture("newer", &newer)).err().unwrap();
+        assert!(err.contains(&format!("schema version {}", SCHEMA_VERSION + 1)), "{err}");
+
+        let mut broken = v1();
+        broken["tasks"][0]["priority"] = "high".into();
+        assert!(check(&fixture("broken", &broken)).err().unwrap().contains("not a valid database"));
+        assert!(check("/nonexistent/scheduler/db.json").is_err());
+    }
+}
@@ -1,7 +1,15 @@
-use chrono::{DateTime, FixedOffset};
+use std::collections::HashMap;
+
+use chrono::{DateTime, FixedOffset, NaiveDate, Weekday};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use uuid::Uuid;
 
+// Keys in db.json this version does not know (written by a newer version
+// or another tool). Kept through load -> save so nothing is lost when
+// moving between versions; API responses leave them out.
+pub type Extra = Map<String, Value>;
+
 // Represents the current state of a task.
 //
 // This enum is serialized as snake_case strings in JSON:
@@ -32,9 +40,122 @@ pub struct Task {
     pub created_at: DateTime<FixedOffset>,
     pub tags: Option<Vec<String>>,
     pub notes: Option<String>,
+    // when the task was last marked Done; None while open or for older data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<FixedOffset>>,
+    // times due_at was moved later (moving it earlier does not count)
+    #[serde(default)]
+    pub postpone_count: i64,
+    // bumped on every edit or toggle; the task's ETag is "<rev>"
+    #[serde(default)]
+    pub rev: u64,
+    // when rev was last bumped; created_at until the first edit
+    pub updated_at: DateTime<FixedOffset>,
+    // set once a deadline push notification was sent for the current due_at
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline_notified_at: Option<DateTime<FixedOffset>>,
+    // plan item pinned with POST /api/plan/items/:id/lock; cleared when Done
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locked: Option<PlanLock>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+// An open task for tests: due `due_at`, 60 minutes, priority 3,
+// created a day before it is due.
+#[cfg(test)]
+pub fn test_task(title: &str, due_at: DateTime<FixedOffset>) -> Task {
+    Task {
+        id: Uuid::new_v4(),
+        title: title.to_string(),
+        due_at,
+        duration_min: 60,
+        priority: 3,
+        status: TaskStatus::Todo,
+        created_at: due_at - chrono::Duration::days(1),
+        tags: None,
+        notes: None,
+        completed_at: None,
+        postpone_count: 0,
+        rev: 0,
+        updated_at: due_at - chrono::Duration::days(1),
+        deadline_notified_at: None,
+        locked: None,
+        extra: Extra::new(),
+    }
+}
+
+// xorshift64: reproducible pseudo-random input for tests without a rand dependency
+#[cfg(test)]
+pub struct TestRng(u64);
+
+#[cfg(test)]
+impl TestRng {
+    pub fn new(seed: u64) -> Self {
+        TestRng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+
+    // Fisher-Yates
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+// Time a plan item was locked to. Replans keep it there and place
+// other tasks around it (logic::build_today_plan).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PlanLock {
+    pub start: DateTime<FixedOffset>,
+    pub end: DateTime<FixedOffset>,
 }
 
 
+// How urgency counts the days until a task is due.
+//
+// - "rolling": 24-hour windows from now
+// - "calendar": whole calendar days between today and the due date
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UrgencyMode {
+    #[default]
+    Rolling,
+    Calendar,
+}
+
+// Which date a due_at exactly at midnight belongs to (logic::due_date).
+//
+// - "strict": its own calendar date, so 00:00 on the 5th is due on the 5th
+// - "inclusive_next_midnight": the date before, so 00:00 on the 6th means
+//   "by the end of the 5th"
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DueBoundary {
+    #[default]
+    Strict,
+    InclusiveNextMidnight,
+}
+
+// Which Scorer ranks tasks for the plan (logic::scorer_for).
+//
+// - "default": urgency buckets + priority + duration score
+// - "deadline_pressure": urgency from hours left until due per hour of work
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ScorerKind {
+    #[default]
+    Default,
+    DeadlinePressure,
+}
+
 // User-configurable settings that affect daily scheduling.
 //
 // These settings are shared by all tasks
@@ -44,14 +165,515 @@ pub struct DaySettings {
     pub day_start: String, // start of the day with format "HH:MM"
     pub day_end: String,   // end of the day with format "HH:MM"
     pub focus_block_min: i64,   // preferred focus block length in minutes
+    #[serde(default = "default_due_time")]
+    pub default_due_time: String, // time used when a due date has no time, "HH:MM"
+    #[serde(default)]
+    pub urgency_mode: UrgencyMode,
+    #[serde(default)]
+    pub scorer: ScorerKind, // how tasks are ranked for the plan
+    #[serde(default)]
+    pub due_boundary: DueBoundary, // date of a due_at at exactly midnight
+    #[serde(default = "default_sort_locale")]
+    pub sort_locale: String, // BCP 47 locale for ordering titles, e.g. "fr"; "und" = root
+    #[serde(default)]
+    pub dedupe_on_create: bool, // reject new tasks duplicating an open task on the same day
+    #[serde(default)]
+    pub time_format: TimeFormat, // clock style in human-readable output (digest, messages)
+    #[serde(default = "default_week_start")]
+    pub week_start: Weekday, // first day of the week for weekly views, "Mon".."Sun"
+    #[serde(default)]
+    pub strict_concurrency: bool, // task PUT/DELETE/toggle must send If-Match
+    #[serde(default)]
+    pub overdue_grace_min: i64, // minutes past due_at before a task counts as overdue
+    #[serde(default)]
+    pub auto_archive_days: Option<i64>, // archive tasks Done for longer than this; None = never
+    #[serde(default)]
+    pub wip_limit: Option<i64>, // most InProgress tasks at once; None = no limit
+    #[serde(default = "default_true")]
+    pub prefer_due_today: bool, // place unplanned tasks due on the plan date in leftover time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority_labels: Option<[PriorityMeta; 5]>, // priority 1..=5; None = DEFAULT_PRIORITY_LABELS
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+// Display name and color of one priority level.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PriorityMeta {
+    pub label: String,
+    pub color: String, // "#rrggbb"
+}
+
+// (label, color) for priority 1..=5 when priority_labels is not set.
+pub const DEFAULT_PRIORITY_LABELS: [(&str, &str); 5] = [
+    ("Lowest", "#9ca3af"),
+    ("Low", "#60a5fa"),
+    ("Medium", "#34d399"),
+    ("High", "#f59e0b"),
+    ("Highest", "#ef4444"),
+];
+
+// Label and color for `priority` (clamped to 1..=5) under `labels`.
+pub fn priority_meta(labels: Option<&[PriorityMeta; 5]>, priority: i64) -> PriorityMeta {
+    let i = (priority.clamp(1, 5) - 1) as usize;
+    match labels {
+        Some(labels) => labels[i].clone(),
+        None => {
+            let (label, color) = DEFAULT_PRIORITY_LABELS[i];
+            PriorityMeta { label: label.to_string(), color: color.to_string() }
+        }
+    }
+}
+
+impl DaySettings {
+    pub fn priority_meta(&self, priority: i64) -> PriorityMeta {
+        priority_meta(self.priority_labels.as_ref(), priority)
+    }
+
+    // Copy for API responses: unknown keys left out.
+    pub fn without_extra(&self) -> DaySettings {
+        DaySettings { extra: Extra::new(), ..self.clone() }
+    }
+
+    // Settings from a client, keeping the unknown keys already stored
+    // (a client never sets or removes them).
+    pub fn with_extra_of(self, stored: &DaySettings) -> DaySettings {
+        DaySettings { extra: stored.extra.clone(), ..self }
+    }
+}
+
+// Clock style for human-readable times. API JSON always uses RFC3339.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum TimeFormat {
+    #[default]
+    #[serde(rename = "24h")]
+    H24, // 14:30
+    #[serde(rename = "12h")]
+    H12, // 2:30 PM
+}
+
+fn default_due_time() -> String {
+    "23:59".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_week_start() -> Weekday {
+    Weekday::Mon
+}
+
+fn default_sort_locale() -> String {
+    crate::collate::DEFAULT_LOCALE.to_string()
+}
+
+// Settings of a newly created db (store::load_db). Optional fields
+// match their serde defaults, so a file that leaves them out loads
+// the same values.
+impl Default for DaySettings {
+    fn default() -> Self {
+        Self {
+            day_start: "09:00".to_string(),
+            day_end: "18:00".to_string(),
+            focus_block_min: 50,
+            default_due_time: default_due_time(),
+            urgency_mode: UrgencyMode::default(),
+            scorer: ScorerKind::default(),
+            due_boundary: DueBoundary::default(),
+            sort_locale: default_sort_locale(),
+            dedupe_on_create: false,
+            time_format: TimeFormat::default(),
+            week_start: default_week_start(),
+            strict_concurrency: false,
+            overdue_grace_min: 0,
+            auto_archive_days: None,
+            wip_limit: None,
+            prefer_due_today: default_true(),
+            priority_labels: None,
+            extra: Extra::new(),
+        }
+    }
+}
+
+// Message format used when posting the plan to a webhook.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookFormat {
+    #[default]
+    Slack,
+    Discord,
+}
+
+// Push service used for deadline notifications.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PushProvider {
+    #[default]
+    Ntfy,
+    Gotify,
+}
+
+// Deadline push notification settings.
+//
+// A push is sent once per task when its due_at is
+// less than `lead_min` minutes away.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushSettings {
+    pub enabled: bool,
+    pub provider: PushProvider,
+    pub url: String,   // ntfy topic URL, or Gotify server base URL
+    pub token: String, // Gotify application token (unused for ntfy)
+    pub lead_min: i64, // minutes before due_at to notify
+}
+
+impl Default for PushSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            provider: PushProvider::Ntfy,
+            url: String::new(),
+            token: String::new(),
+            lead_min: 30,
+        }
+    }
+}
+
+// Daily plan notification settings.
+//
+// When enabled, the plan for today is posted to
+// `webhook_url` every day at `time`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationSettings {
+    pub enabled: bool,
+    pub webhook_url: String,
+    pub time: String, // local time to post with format "HH:MM"
+    pub format: WebhookFormat,
+    #[serde(default)]
+    pub push: PushSettings,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            webhook_url: String::new(),
+            time: "08:00".to_string(),
+            format: WebhookFormat::Slack,
+            push: PushSettings::default(),
+        }
+    }
+}
+
+// One webhook POST attempt, kept in Db::deliveries for debugging.
+//
+// `payload` holds the exact bytes that were sent so a redelivery
+// repeats them instead of building a new message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub event: String, // "plan" (daily post), "test" or "redeliver"
+    pub url: String,
+    pub status: Option<u16>,   // HTTP status; None if no response arrived
+    pub error: Option<String>, // None when the receiver answered 2xx
+    pub latency_ms: u64,
+    pub at: DateTime<FixedOffset>,
+    pub response_body: String, // truncated
+    pub payload: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redelivery_of: Option<Uuid>,
+}
+
+// One day of plan vs reality, appended to history.jsonl after the
+// day window ends (history.rs). Frozen once written, so later edits
+// to tasks do not rewrite the past.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaySnapshot {
+    pub date: NaiveDate,
+    pub recorded_at: DateTime<FixedOffset>,
+    pub planned_count: usize, // plan for the full day window, as of day_start
+    pub planned_min: i64,
+    pub completed_count: usize, // tasks whose completed_at falls on the date
+    pub completed_min: i64,
+    pub carried_over_count: usize, // planned but still open when recorded
+    pub carried_over_min: i64,
+    pub settings: DaySettings, // in force when recorded
+    #[serde(default)]
+    pub adherence: Option<Adherence>, // None for days recorded before it existed
+}
+
+// How much of the morning plan got done (history::adherence).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Adherence {
+    pub score: Option<f64>,     // done_min / planned_min (0..=1, 3 decimals); None with `reason`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>, // why there is no score: "nothing_planned"
+    pub planned_min: i64,
+    pub done_count: usize, // planned tasks completed on the date, before or during their block
+    pub done_min: i64,
+    pub dropped_count: usize, // planned tasks not completed on the date (or deleted)
+    pub dropped_min: i64,
+    pub ad_hoc_count: usize, // completed on the date without being in the plan
+    pub ad_hoc_min: i64,
+}
+
+// A task deleted with DELETE /api/tasks/:id, kept as it was.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashedTask {
+    pub task: Task,
+    pub deleted_at: DateTime<FixedOffset>,
+}
+
+// One line of audit.log: a task or settings write, recorded once it
+// was saved (writer.rs, store::append_audit).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub at: DateTime<FixedOffset>,
+    pub op: AuditOp,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<Uuid>, // None for settings
+    pub before: Option<Value>, // the task or settings as they were; None for a create
+    pub after: Option<Value>,  // as saved; None for a delete
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOp {
+    Create,
+    Update,
+    Delete, // moved to the trash
+    Purge,   // deleted permanently
+    Restore, // moved back from the trash
+    Toggle,
+    Settings,
+}
+
+// One line of db.journal: a write as it will be saved, appended
+// before the save and dropped after it (writer.rs). Replaying it
+// (store::load_db) gives the state the write saves, so replaying one
+// that was saved changes nothing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum JournalOp {
+    CreateTask { task: Task },
+    UpdateTask { task: Task }, // the task after the update
+    DeleteTask { id: Uuid, trashed: Option<TrashedTask> }, // None when purged
+    Toggle { task: Task },
+    PutSettings { settings: DaySettings },
+    Snapshot { db: Box<Db> }, // a compound write (writer::apply): the whole Db after it
 }
 
 // Top-level structure representing the entire database.
 //
 // This is what gets serialized/deserialized
 // from `data/db.json`.
+//
+// `tasks` stays a plain array on disk; `index` maps id -> position
+// in `tasks` and is rebuilt after loading (store::load_db).
+// Code that edits `tasks` directly must call `reindex()` afterwards.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Db {
+    // Shape of the stored data; older files are brought up to
+    // SCHEMA_VERSION before they are deserialized (migrate.rs)
+    #[serde(default = "legacy_schema_version")]
+    pub schema_version: u32,
     pub settings: DaySettings,
     pub tasks: Vec<Task>,
+    #[serde(default)]
+    pub notifications: NotificationSettings,
+    // Recent webhook attempts, oldest first (bounded, see notify.rs)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deliveries: Vec<WebhookDelivery>,
+    // Done tasks moved out of `tasks` by the auto-archive pass (archive.rs)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub archived: Vec<Task>,
+    // Deleted tasks, oldest deletion first, until restored or purged
+    // (routes_trash.rs). Neither planned nor listed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trash: Vec<TrashedTask>,
+    #[serde(skip)]
+    index: HashMap<Uuid, usize>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+// Schema version this build reads and writes. Bump it together with
+// a new step in migrate::STEPS whenever the stored shape changes in a
+// way serde defaults cannot cover.
+pub const SCHEMA_VERSION: u32 = 4;
+
+// Files written before schema_version existed.
+pub fn legacy_schema_version() -> u32 {
+    1
+}
+
+// A new, empty db at the current schema version.
+impl Default for Db {
+    fn default() -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            settings: DaySettings::default(),
+            tasks: Vec::new(),
+            notifications: NotificationSettings::default(),
+            deliveries: Vec::new(),
+            archived: Vec::new(),
+            trash: Vec::new(),
+            index: HashMap::new(),
+            extra: Extra::new(),
+        }
+    }
+}
+
+impl Db {
+    // Rebuild the id index from `tasks`.
+    pub fn reindex(&mut self) {
+        self.index = self.tasks.iter().enumerate().map(|(i, t)| (t.id, i)).collect();
+    }
+
+    // Position of a task in `tasks`.
+    fn position(&self, id: Uuid) -> Option<usize> {
+        self.index
+            .get(&id)
+            .copied()
+            .filter(|&i| self.tasks.get(i).is_some_and(|t| t.id == id))
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<&Task> {
+        self.position(id).map(|i| &self.tasks[i])
+    }
+
+    pub fn get_mut(&mut self, id: Uuid) -> Option<&mut Task> {
+        self.position(id).map(|i| &mut self.tasks[i])
+    }
+
+    // Append a task, or replace the one with the same id in place.
+    pub fn insert(&mut self, task: Task) {
+        match self.position(task.id) {
+            Some(i) => self.tasks[i] = task,
+            None => {
+                self.index.insert(task.id, self.tasks.len());
+                self.tasks.push(task);
+            }
+        }
+    }
+
+    // Remove a task, keeping the order of the others.
+    pub fn remove(&mut self, id: Uuid) -> Option<Task> {
+        let i = self.position(id)?;
+        let task = self.tasks.remove(i);
+        self.index.remove(&id);
+        for (j, t) in self.tasks.iter().enumerate().skip(i) {
+            self.index.insert(t.id, j);
+        }
+        Some(task)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock;
+    use std::time::{Duration, Instant};
+
+    fn db_with(n: usize) -> Db {
+        let due = clock::local("2024-06-03 12:00");
+        let mut db = Db { tasks: (0..n).map(|i| test_task(&format!("task {i}"), due)).collect(), ..Db::default() };
+        db.reindex();
+        db
+    }
+
+    // The index maps exactly the ids in `tasks` to their positions.
+    fn assert_consistent(db: &Db) {
+        assert_eq!(db.index.len(), db.tasks.len());
+        for (i, t) in db.tasks.iter().enumerate() {
+            assert_eq!(db.index.get(&t.id), Some(&i));
+            assert!(std::ptr::eq(db.get(t.id).unwrap(), t));
+        }
+    }
+
+    #[test]
+    fn index_stays_consistent_after_mixed_mutations() {
+        for seed in 1..=20u64 {
+            let mut rng = TestRng::new(seed);
+            let mut db = db_with(rng.below(20));
+            // Reference model: the tasks in order
+            let mut model: Vec<Task> = db.tasks.clone();
+            let mut removed: Vec<Uuid> = Vec::new();
+
+            for step in 0..300 {
+                match rng.below(5) {
+                    0 => {
+                        let task = test_task(&format!("new {step}"), clock::local("2024-06-04 09:00"));
+                        model.push(task.clone());
+                        db.insert(task);
+                    }
+                    1 if !model.is_empty() => {
+                        // Insert with an existing id replaces in place
+                        let i = rng.below(model.len());
+                        let mut task = model[i].clone();
+                        task.title = format!("replaced {step}");
+                        model[i] = task.clone();
+                        db.insert(task);
+                    }
+                    2 if !model.is_empty() => {
+                        let id = model.remove(rng.below(model.len())).id;
+                        assert_eq!(db.remove(id).map(|t| t.id), Some(id));
+                        assert!(db.remove(id).is_none());
+                        removed.push(id);
+                    }
+                    3 if !model.is_empty() => {
+                        let i = rng.below(model.len());
+                        model[i].priority = (step % 5) as i64 + 1;
+                        db.get_mut(model[i].id).unwrap().priority = model[i].priority;
+                    }
+                    _ => {
+                        if let Some(&id) = removed.last() {
+                            assert!(db.get(id).is_none());
+                            assert!(db.get_mut(id).is_none());
+                        }
+                    }
+                }
+                assert_consistent(&db);
+                let ids: Vec<Uuid> = db.tasks.iter().map(|t| t.id).collect();
+                let expected: Vec<Uuid> = model.iter().map(|t| t.id).collect();
+                assert_eq!(ids, expected, "seed {seed} step {step}");
+                assert!(db.tasks.iter().zip(&model).all(|(a, b)| a.title == b.title && a.priority == b.priority));
+            }
+        }
+    }
+
+    #[test]
+    fn stale_index_entries_are_not_trusted() {
+        let mut db = db_with(3);
+        // Edited directly without reindex(): lookups miss rather than
+        // returning the wrong task
+        let gone = db.tasks.remove(0).id;
+        assert!(db.get(gone).is_none());
+        assert!(db.get(db.tasks[0].id).is_none());
+        db.reindex();
+        assert_consistent(&db);
+    }
+
+    // Time for `n` get_mut updates spread over the db.
+    fn time_updates(db: &mut Db, n: usize) -> Duration {
+        let ids: Vec<Uuid> = (0..n).map(|i| db.tasks[i * 7919 % db.tasks.len()].id).collect();
+        let started = Instant::now();
+        for id in ids {
+            db.get_mut(id).unwrap().rev += 1;
+        }
+        started.elapsed()
+    }
+
+    #[test]
+    fn lookups_do_not_scale_with_the_task_count() {
+        let mut small = db_with(1_000);
+        let mut large = db_with(50_000);
+        // Best of a few runs, so one slow run does not fail the test
+        let best = |db: &mut Db| (0..5).map(|_| time_updates(db, 20_000)).min().unwrap();
+        let (small, large) = (best(&mut small), best(&mut large));
+        // A linear scan would be ~50x slower on the larger db
+        assert!(large < small * 10 + Duration::from_millis(5), "1k: {small:?}, 50k: {large:?}");
+    }
 }
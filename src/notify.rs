@@ -0,0 +1,675 @@
+// --------------------------------------------------
+// Outgoing notifications.
+//
+// This module handles:
+// - Formatting today's plan as a Slack or Discord message
+// - Sending it to the configured webhook (retried once)
+// - A bounded log of webhook attempts, and redelivery
+// - The background loop that fires at the configured time
+// - Ntfy / Gotify pushes shortly before tasks are due
+// --------------------------------------------------
+
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime, Timelike};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::config;
+use crate::logic::{self, PlanItem, UnplannedItem};
+use crate::models::{
+    Db, NotificationSettings, PushProvider, PushSettings, Task, TaskStatus, TimeFormat,
+    WebhookDelivery, WebhookFormat,
+};
+use crate::clock;
+use crate::writer;
+
+// Delay before the single retry of a failed delivery (short in tests).
+const RETRY_DELAY: Duration = if cfg!(test) { Duration::from_millis(10) } else { Duration::from_secs(5) };
+
+// Discord rejects messages longer than this.
+const DISCORD_MAX_LEN: usize = 2000;
+
+// Build today's plan using the full day window as available time.
+fn todays_plan(db: &Db, now: DateTime<FixedOffset>) -> (Vec<PlanItem>, Vec<UnplannedItem>) {
+    let available_min = logic::day_window_min(&db.settings);
+    let date = logic::plan_date(&db.settings, now);
+    let (plan, unplanned, _) = logic::plan_for_date(&db.tasks, &db.settings, date, now, available_min, true);
+    (plan, unplanned)
+}
+
+// One line per plan item, e.g. "09:00–10:00 Write report (overdue)".
+fn plan_lines(plan: &[PlanItem], fmt: TimeFormat) -> Vec<String> {
+    plan.iter()
+        .map(|p| {
+            let overdue = if p.is_overdue { " (overdue)" } else { "" };
+            let start = clock::format_time(&p.start, fmt);
+            let end = clock::format_time(&p.end, fmt);
+            format!("{start}–{end} {}{overdue}", p.title)
+        })
+        .collect()
+}
+
+// Format the plan as a webhook JSON payload.
+//
+// - Slack: a header block plus one mrkdwn section, with `text` as fallback
+// - Discord: a single markdown `content` string
+pub fn format_message(
+    format: WebhookFormat,
+    date: NaiveDate,
+    plan: &[PlanItem],
+    unplanned_count: usize,
+    time_format: TimeFormat,
+) -> Value {
+    let header = format!("Plan for {date}");
+    let lines = plan_lines(plan, time_format);
+    let footer = match unplanned_count {
+        0 => String::new(),
+        n => format!("{n} task(s) did not fit today."),
+    };
+
+    match format {
+        WebhookFormat::Slack => {
+            let body = if lines.is_empty() {
+                "_Nothing planned._".to_string()
+            } else {
+                lines.iter().map(|l| format!("• {l}")).collect::<Vec<_>>().join("\n")
+            };
+            let mut blocks = vec![
+                json!({ "type": "header", "text": { "type": "plain_text", "text": header } }),
+                json!({ "type": "section", "text": { "type": "mrkdwn", "text": body } }),
+            ];
+            if !footer.is_empty() {
+                blocks.push(json!({
+                    "type": "context",
+                    "elements": [{ "type": "mrkdwn", "text": footer }]
+                }));
+            }
+            json!({ "text": header, "blocks": blocks })
+        }
+        WebhookFormat::Discord => {
+            let mut content = format!("**{header}**\n");
+            if lines.is_empty() {
+                content.push_str("_Nothing planned._\n");
+            }
+            for l in &lines {
+                content.push_str(&format!("- {l}\n"));
+            }
+            if !footer.is_empty() {
+                content.push_str(&footer);
+            }
+            if content.chars().count() > DISCORD_MAX_LEN {
+                content = content.chars().take(DISCORD_MAX_LEN - 1).collect::<String>() + "…";
+            }
+            json!({ "content": content })
+        }
+    }
+}
+
+// Response body characters kept per logged delivery.
+const DELIVERY_BODY_MAX: usize = 1024;
+
+// Delivery attempts kept in db.json; the oldest are dropped first.
+const DELIVERY_LOG_LEN: usize = 200;
+
+// POST raw JSON once and describe the attempt.
+async fn deliver(event: &str, url: &str, payload: &str, redelivery_of: Option<Uuid>) -> WebhookDelivery {
+    let started = Instant::now();
+    let result = reqwest::Client::new()
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(payload.to_string())
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await;
+
+    let (status, error, body) = match result {
+        Ok(resp) => {
+            let status = resp.status();
+            let error = (!status.is_success()).then(|| format!("webhook responded with {status}"));
+            let body = resp.text().await.unwrap_or_default();
+            (Some(status.as_u16()), error, body)
+        }
+        Err(e) => (None, Some(e.to_string()), String::new()),
+    };
+
+    WebhookDelivery {
+        id: Uuid::new_v4(),
+        event: event.to_string(),
+        url: url.to_string(),
+        status,
+        error,
+        latency_ms: started.elapsed().as_millis() as u64,
+        at: clock::now(),
+        response_body: body.chars().take(DELIVERY_BODY_MAX).collect(),
+        payload: payload.to_string(),
+        redelivery_of,
+    }
+}
+
+// Append an attempt to the log, dropping the oldest past DELIVERY_LOG_LEN.
+fn push_bounded(log: &mut Vec<WebhookDelivery>, delivery: WebhookDelivery) {
+    log.push(delivery);
+    let excess = log.len().saturating_sub(DELIVERY_LOG_LEN);
+    log.drain(..excess);
+}
+
+// Append an attempt to the delivery log in the db.
+async fn record(delivery: WebhookDelivery) {
+    let logged = writer::apply(move |db| {
+        push_bounded(&mut db.deliveries, delivery);
+        ((), true)
+    });
+    if let Err(e) = logged.await {
+        eprintln!("  Could not log webhook delivery: {}", e.message());
+    }
+}
+
+// POST a payload once and log it; non-2xx responses count as failures.
+async fn post_once(event: &str, url: &str, payload: &str) -> Result<(), String> {
+    let d = deliver(event, url, payload, None).await;
+    let result = d.error.clone().map_or(Ok(()), Err);
+    record(d).await;
+    result
+}
+
+// Generate today's plan and post it to the configured webhook.
+//
+// `event` labels the attempts in the delivery log ("plan", "test").
+// A failed delivery is logged and retried once.
+pub async fn send_plan(db: &Db, cfg: &NotificationSettings, event: &str) -> Result<(), String> {
+    if cfg.webhook_url.trim().is_empty() {
+        return Err("webhook_url is not set".to_string());
+    }
+
+    let now = clock::now();
+    let (plan, unplanned) = todays_plan(db, now);
+    let date = logic::plan_date(&db.settings, now);
+    let payload = format_message(cfg.format, date, &plan, unplanned.len(), db.settings.time_format)
+        .to_string();
+
+    if let Err(e) = post_once(event, &cfg.webhook_url, &payload).await {
+        eprintln!("  Plan webhook failed ({e}), retrying once");
+        tokio::time::sleep(RETRY_DELAY).await;
+        post_once(event, &cfg.webhook_url, &payload).await.map_err(|e| {
+            eprintln!("  Plan webhook failed again: {e}");
+            e
+        })?;
+    }
+    Ok(())
+}
+
+// Send a logged delivery again: same URL, same payload bytes.
+//
+// The new attempt is logged with `redelivery_of` set and returned.
+pub async fn redeliver(original: &WebhookDelivery) -> WebhookDelivery {
+    let d = deliver("redeliver", &original.url, &original.payload, Some(original.id)).await;
+    record(d.clone()).await;
+    d
+}
+
+// Background loop started from main.rs.
+//
+// Every tick, reload the settings and post the plan when the
+// local clock reaches the configured "HH:MM" (once per day).
+pub async fn run_daily_webhook() {
+    let mut last_sent: Option<NaiveDate> = None;
+    let tick = Duration::from_secs(config::get().notifications.check_interval_secs.max(1));
+    let mut ticker = tokio::time::interval(tick);
+
+    loop {
+        ticker.tick().await;
+
+        let db = writer::snapshot();
+        let cfg = db.notifications.clone();
+        let now = clock::now();
+        if !cfg.enabled || !daily_post_due(&cfg.time, now, last_sent) {
+            continue;
+        }
+
+        let today = now.date_naive();
+        last_sent = Some(today);
+        if send_plan(&db, &cfg, "plan").await.is_ok() {
+            println!("  Posted plan for {today} to webhook");
+        }
+    }
+}
+
+// Whether the daily post is due at `now`: the clock shows the
+// configured "HH:MM" and nothing was posted today yet.
+fn daily_post_due(time: &str, now: DateTime<FixedOffset>, last_sent: Option<NaiveDate>) -> bool {
+    let Ok(at) = NaiveTime::parse_from_str(time, "%H:%M") else {
+        return false;
+    };
+    let due_now = now.hour() == at.hour() && now.minute() == at.minute();
+    due_now && last_sent != Some(now.date_naive())
+}
+
+// How often the deadline scanner looks for tasks coming due.
+const DEADLINE_SCAN_INTERVAL: Duration = Duration::from_secs(60);
+
+// Send one push notification through ntfy or Gotify.
+pub async fn send_push(cfg: &PushSettings, title: &str, message: &str) -> Result<(), String> {
+    let url = cfg.url.trim().trim_end_matches('/');
+    if url.is_empty() {
+        return Err("push url is not set".to_string());
+    }
+
+    let client = reqwest::Client::new();
+    let req = match cfg.provider {
+        // ntfy: POST the message body to the topic URL
+        PushProvider::Ntfy => client
+            .post(url)
+            .header("Title", title)
+            .header("Priority", "high")
+            .header("Tags", "alarm_clock")
+            .body(message.to_string()),
+        // Gotify: POST JSON to /message with the app token
+        PushProvider::Gotify => client
+            .post(format!("{url}/message"))
+            .query(&[("token", cfg.token.as_str())])
+            .json(&json!({ "title": title, "message": message, "priority": 8 })),
+    };
+
+    let resp = req
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("push service responded with {}", resp.status()));
+    }
+    Ok(())
+}
+
+// Open tasks due within `lead_min` minutes that were not notified yet.
+pub fn tasks_coming_due(tasks: &[Task], now: DateTime<FixedOffset>, lead_min: i64) -> Vec<Uuid> {
+    let lead = chrono::Duration::try_minutes(lead_min.max(0)).unwrap_or(chrono::Duration::MAX);
+    tasks
+        .iter()
+        .filter(|t| t.status != TaskStatus::Done && t.deadline_notified_at.is_none())
+        // A lead reaching before the earliest representable time starts "always"
+        .filter(|t| t.due_at.checked_sub_signed(lead).is_none_or(|start| start <= now))
+        .filter(|t| now < t.due_at)
+        .map(|t| t.id)
+        .collect()
+}
+
+// Background loop started from main.rs.
+//
+// Every minute, push a notification for each task entering its
+// lead window (push_deadlines).
+pub async fn run_deadline_pusher() {
+    let mut ticker = tokio::time::interval(DEADLINE_SCAN_INTERVAL);
+
+    loop {
+        ticker.tick().await;
+
+        let cfg = writer::snapshot().notifications.push.clone();
+        if cfg.enabled {
+            push_deadlines(&cfg, clock::now()).await;
+        }
+    }
+}
+
+// One scan at `now`: push each task coming due and mark it so it
+// only fires once per due_at. Returns the tasks pushed.
+async fn push_deadlines(cfg: &PushSettings, now: DateTime<FixedOffset>) -> Vec<Uuid> {
+    let db = writer::snapshot();
+    let due = tasks_coming_due(&db.tasks, now, cfg.lead_min);
+
+    let mut sent = Vec::new();
+    for id in due {
+        let Some(t) = db.get(id) else {
+            continue;
+        };
+        let mins = (t.due_at - now).num_minutes();
+        let at = clock::format_time(&t.due_at, db.settings.time_format);
+        let message = format!("Due at {at} (in {mins} min)");
+        match send_push(cfg, &t.title, &message).await {
+            Ok(()) => sent.push(id),
+            Err(e) => eprintln!("  Deadline push for \"{}\" failed: {e}", t.title),
+        }
+    }
+    if sent.is_empty() {
+        return sent;
+    }
+
+    // Mark through the writer so edits made meanwhile are kept
+    let marked = sent.clone();
+    let saved = writer::apply(move |db| {
+        for id in marked {
+            if let Some(t) = db.get_mut(id) {
+                t.deadline_notified_at = Some(now);
+            }
+        }
+        ((), true)
+    });
+    if saved.await.is_err() {
+        eprintln!("  Failed to save deadline notification state");
+    }
+    sent
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::{extract::State, http::StatusCode, routing::post, Router};
+
+    use super::*;
+    use crate::logic::test_item as item;
+    use crate::models::test_task;
+
+    type Received = Arc<Mutex<Vec<String>>>;
+    type Script = Arc<Mutex<std::vec::IntoIter<u16>>>;
+
+    // A webhook receiver on a free local port. It answers with
+    // `statuses` in turn, then 200; returns its URL and the bodies
+    // it received.
+    async fn receiver(statuses: Vec<u16>) -> (String, Received) {
+        let received = Received::default();
+        let script: Script = Arc::new(Mutex::new(statuses.into_iter()));
+        let app = Router::new()
+            .route(
+                "/hook",
+                post(|State((received, script)): State<(Received, Script)>, body: String| async move {
+                    received.lock().unwrap().push(body);
+                    let status = script.lock().unwrap().next().unwrap_or(200);
+                    StatusCode::from_u16(status).unwrap()
+                }),
+            )
+            .with_state((received.clone(), script));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, received)
+    }
+
+    // A db with one overdue task, so the plan has something to show
+    // whatever the time of day.
+    fn db_with_task() -> Db {
+        let mut db = Db::default();
+        db.insert(test_task("Write report", clock::now() - chrono::Duration::days(1)));
+        db
+    }
+
+    fn settings(url: &str, format: WebhookFormat) -> NotificationSettings {
+        NotificationSettings { enabled: true, webhook_url: url.to_string(), format, ..Default::default() }
+    }
+
+    #[test]
+    fn slack_message_has_header_plan_and_footer_blocks() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let plan = [item("Write report", "2024-06-03 09:00", "2024-06-03 10:00")];
+        let msg = format_message(WebhookFormat::Slack, date, &plan, 2, TimeFormat::H24);
+        assert_eq!(msg["text"], "Plan for 2024-06-03");
+        assert_eq!(msg["blocks"][0]["text"]["text"], "Plan for 2024-06-03");
+        assert_eq!(msg["blocks"][1]["text"]["text"], "• 09:00–10:00 Write report");
+        assert_eq!(msg["blocks"][2]["elements"][0]["text"], "2 task(s) did not fit today.");
+
+        let empty = format_message(WebhookFormat::Slack, date, &[], 0, TimeFormat::H12);
+        assert_eq!(empty["blocks"][1]["text"]["text"], "_Nothing planned._");
+        assert_eq!(empty["blocks"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn discord_message_is_markdown_and_capped() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let plan = [item("Write report", "2024-06-03 13:00", "2024-06-03 14:30")];
+        let msg = format_message(WebhookFormat::Discord, date, &plan, 0, TimeFormat::H12);
+        assert_eq!(msg["content"], "**Plan for 2024-06-03**\n- 1:00 PM–2:30 PM Write report\n");
+
+        let long = [item(&"x".repeat(3000), "2024-06-03 09:00", "2024-06-03 10:00")];
+        let msg = format_message(WebhookFormat::Discord, date, &long, 0, TimeFormat::H24);
+        let content = msg["content"].as_str().unwrap();
+        assert_eq!(content.chars().count(), DISCORD_MAX_LEN);
+        assert!(content.ends_with('…'));
+    }
+
+    #[test]
+    fn plan_lines_follow_the_time_format() {
+        let plan = [
+            item("Standup", "2024-06-03 00:00", "2024-06-03 00:15"),
+            item("Lunch", "2024-06-03 12:00", "2024-06-03 13:00"),
+            item("Review", "2024-06-03 14:30", "2024-06-03 15:45"),
+        ];
+        assert_eq!(
+            plan_lines(&plan, TimeFormat::H24),
+            ["00:00–00:15 Standup", "12:00–13:00 Lunch", "14:30–15:45 Review"]
+        );
+        assert_eq!(
+            plan_lines(&plan, TimeFormat::H12),
+            ["12:00 AM–12:15 AM Standup", "12:00 PM–1:00 PM Lunch", "2:30 PM–3:45 PM Review"]
+        );
+    }
+
+    #[tokio::test]
+    async fn plan_is_posted_to_the_receiver() {
+        let (url, received) = receiver(vec![]).await;
+        send_plan(&db_with_task(), &settings(&url, WebhookFormat::Discord), "test").await.unwrap();
+
+        let bodies = received.lock().unwrap().clone();
+        assert_eq!(bodies.len(), 1);
+        let body: Value = serde_json::from_str(&bodies[0]).unwrap();
+        let content = body["content"].as_str().unwrap();
+        assert!(content.starts_with("**Plan for "), "{content}");
+        // Planned, or (late at night) listed as not fitting
+        assert!(content.contains("Write report") || content.contains("1 task(s) did not fit"), "{content}");
+    }
+
+    #[tokio::test]
+    async fn failed_delivery_is_retried_once_with_the_same_payload() {
+        let (url, received) = receiver(vec![500]).await;
+        send_plan(&db_with_task(), &settings(&url, WebhookFormat::Slack), "test").await.unwrap();
+
+        let bodies = received.lock().unwrap().clone();
+        assert_eq!(bodies.len(), 2);
+        assert_eq!(bodies[0], bodies[1]);
+    }
+
+    #[tokio::test]
+    async fn two_failures_give_up() {
+        let (url, received) = receiver(vec![500, 502]).await;
+        let err = send_plan(&db_with_task(), &settings(&url, WebhookFormat::Slack), "test").await.unwrap_err();
+        assert!(err.contains("502"), "{err}");
+        assert_eq!(received.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn missing_url_is_an_error() {
+        assert!(send_plan(&Db::default(), &settings(" ", WebhookFormat::Slack), "test").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn delivery_log_records_failures_and_the_redelivery() {
+        writer::spawn_for_tests();
+        let (url, received) = receiver(vec![500, 503]).await;
+        let err = send_plan(&db_with_task(), &settings(&url, WebhookFormat::Slack), "plan").await.unwrap_err();
+        assert!(err.contains("503"), "{err}");
+
+        let logged = |url: &str| -> Vec<WebhookDelivery> {
+            writer::snapshot().deliveries.iter().filter(|d| d.url == url).cloned().collect()
+        };
+        let failures = logged(&url);
+        assert_eq!(failures.iter().map(|d| d.status).collect::<Vec<_>>(), [Some(500), Some(503)]);
+        assert!(failures.iter().all(|d| d.event == "plan" && d.error.is_some() && d.redelivery_of.is_none()));
+
+        let retry = redeliver(&failures[0]).await;
+        assert_eq!((retry.status, retry.error.as_deref()), (Some(200), None));
+        assert_eq!(retry.redelivery_of, Some(failures[0].id));
+        let bodies = received.lock().unwrap().clone();
+        assert_eq!(bodies.len(), 3);
+        assert_eq!(bodies[2], failures[0].payload);
+
+        let after = logged(&url);
+        assert_eq!(after.len(), 3);
+        assert_eq!(after[2].id, retry.id);
+        assert_eq!(after[2].payload, failures[0].payload);
+    }
+
+    #[tokio::test]
+    async fn unreachable_receiver_is_logged_without_a_status() {
+        let d = deliver("test", "http://127.0.0.1:9/hook", "{}", None).await;
+        assert_eq!(d.status, None);
+        assert!(d.error.is_some());
+    }
+
+    #[test]
+    fn delivery_log_keeps_the_newest_entries() {
+        let entry = |n: usize| WebhookDelivery {
+            id: Uuid::new_v4(),
+            event: format!("e{n}"),
+            url: "http://example.invalid/hook".to_string(),
+            status: Some(200),
+            error: None,
+            latency_ms: 1,
+            at: clock::local("2024-06-03 08:00"),
+            response_body: String::new(),
+            payload: "{}".to_string(),
+            redelivery_of: None,
+        };
+        let mut log = Vec::new();
+        for n in 0..DELIVERY_LOG_LEN + 5 {
+            push_bounded(&mut log, entry(n));
+        }
+        assert_eq!(log.len(), DELIVERY_LOG_LEN);
+        assert_eq!(log[0].event, "e5");
+        assert_eq!(log[DELIVERY_LOG_LEN - 1].event, format!("e{}", DELIVERY_LOG_LEN + 4));
+    }
+
+    #[test]
+    fn daily_post_fires_once_in_its_minute() {
+        let at = clock::local("2024-06-03 08:00");
+        assert!(daily_post_due("08:00", at, None));
+        assert!(daily_post_due("08:00", at + chrono::Duration::seconds(59), None));
+        assert!(!daily_post_due("08:00", at + chrono::Duration::minutes(1), None));
+        assert!(!daily_post_due("08:00", at, Some(at.date_naive())));
+        // Posted yesterday: due again today
+        assert!(daily_post_due("08:00", at, Some(at.date_naive().pred_opt().unwrap())));
+        assert!(!daily_post_due("8 o'clock", at, None));
+    }
+
+    // A push request as the receiver saw it.
+    #[derive(Debug, Clone)]
+    struct Pushed {
+        uri: String,
+        title: Option<String>,
+        body: String,
+    }
+
+    type Pushes = Arc<Mutex<Vec<Pushed>>>;
+
+    // A push service on a free local port that accepts any POST;
+    // returns its base URL and the requests it received.
+    async fn push_receiver() -> (String, Pushes) {
+        let pushes = Pushes::default();
+        let app = Router::new()
+            .fallback(|State(pushes): State<Pushes>, req: axum::extract::Request| async move {
+                let uri = req.uri().to_string();
+                let title = req.headers().get("Title").and_then(|v| v.to_str().ok()).map(str::to_string);
+                let bytes = axum::body::to_bytes(req.into_body(), usize::MAX).await.unwrap();
+                let body = String::from_utf8(bytes.to_vec()).unwrap();
+                pushes.lock().unwrap().push(Pushed { uri, title, body });
+                StatusCode::OK
+            })
+            .with_state(pushes.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, pushes)
+    }
+
+    fn push_settings(provider: PushProvider, url: &str) -> PushSettings {
+        PushSettings { enabled: true, provider, url: url.to_string(), token: "app-token".to_string(), lead_min: 15 }
+    }
+
+    #[test]
+    fn tasks_come_due_inside_the_lead_window_only() {
+        let now = clock::local("2024-06-03 09:00");
+        let at = |min: i64| now + chrono::Duration::minutes(min);
+        let inside = test_task("inside", at(10));
+        let edge = test_task("edge", at(15));
+        let later = test_task("later", at(16));
+        let past = test_task("past", at(-1));
+        let due_now = test_task("due now", now);
+        let mut notified = test_task("notified", at(5));
+        notified.deadline_notified_at = Some(at(-5));
+        let mut done = test_task("done", at(5));
+        done.status = TaskStatus::Done;
+        let tasks = [inside.clone(), edge.clone(), later, past, due_now, notified, done];
+
+        assert_eq!(tasks_coming_due(&tasks, now, 15), [inside.id, edge.id]);
+        assert_eq!(tasks_coming_due(&tasks, now, 0), Vec::<Uuid>::new());
+        // A lead too long to represent covers everything still ahead
+        assert_eq!(tasks_coming_due(&tasks, now, i64::MAX).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn ntfy_push_posts_the_message_to_the_topic() {
+        let (url, pushes) = push_receiver().await;
+        let cfg = push_settings(PushProvider::Ntfy, &format!("{url}/scheduler-topic/"));
+        send_push(&cfg, "Write report", "Due at 09:15 (in 15 min)").await.unwrap();
+
+        let pushes = pushes.lock().unwrap().clone();
+        assert_eq!(pushes.len(), 1);
+        assert_eq!(pushes[0].uri, "/scheduler-topic");
+        assert_eq!(pushes[0].title.as_deref(), Some("Write report"));
+        assert_eq!(pushes[0].body, "Due at 09:15 (in 15 min)");
+    }
+
+    #[tokio::test]
+    async fn gotify_push_posts_json_to_message_with_the_token() {
+        let (url, pushes) = push_receiver().await;
+        send_push(&push_settings(PushProvider::Gotify, &url), "Write report", "Due soon").await.unwrap();
+
+        let pushes = pushes.lock().unwrap().clone();
+        assert_eq!(pushes[0].uri, "/message?token=app-token");
+        let body: Value = serde_json::from_str(&pushes[0].body).unwrap();
+        assert_eq!((&body["title"], &body["message"]), (&json!("Write report"), &json!("Due soon")));
+
+        let unset = push_settings(PushProvider::Gotify, " ");
+        assert!(send_push(&unset, "t", "m").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn a_task_is_pushed_once_per_due_at() {
+        writer::spawn_for_tests();
+        let due = clock::local("2031-09-17 03:17");
+        let task = test_task("Push me 423", due);
+        let id = task.id;
+        writer::apply(move |db| {
+            db.insert(task);
+            ((), true)
+        })
+        .await
+        .unwrap();
+        let (url, pushes) = push_receiver().await;
+        let cfg = push_settings(PushProvider::Ntfy, &format!("{url}/topic"));
+        let ours = |pushes: &Pushes| pushes.lock().unwrap().iter().filter(|p| p.title.as_deref() == Some("Push me 423")).count();
+
+        let now = due - chrono::Duration::minutes(10);
+        assert!(push_deadlines(&cfg, now).await.contains(&id));
+        assert_eq!(writer::snapshot().get(id).unwrap().deadline_notified_at, Some(now));
+        assert!(!push_deadlines(&cfg, now + chrono::Duration::minutes(1)).await.contains(&id));
+        assert_eq!(ours(&pushes), 1);
+
+        // A new due date arms it again
+        let t = writer::snapshot().get(id).unwrap().clone();
+        let later = due + chrono::Duration::hours(1);
+        let changes = writer::TaskChanges {
+            title: t.title,
+            due_at: later,
+            duration_min: t.duration_min,
+            priority: t.priority,
+            status: t.status,
+            tags: t.tags,
+            notes: t.notes,
+        };
+        writer::send(|reply| writer::Command::UpdateTask(id, None, writer::WipCheck::Force, changes, reply)).await.unwrap();
+        assert_eq!(writer::snapshot().get(id).unwrap().deadline_notified_at, None);
+        assert!(!push_deadlines(&cfg, now).await.contains(&id));
+        assert!(push_deadlines(&cfg, later - chrono::Duration::minutes(5)).await.contains(&id));
+        assert_eq!(ours(&pushes), 2);
+    }
+}
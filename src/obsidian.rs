@@ -0,0 +1,169 @@
+/*
+Obsidian Tasks plugin Markdown export.
+Renders tasks as "- [ ] Title ⏫ 📅 2024-06-01" lines grouped into
+one file per tag. Pure formatting, independent from HTTP / Axum.
+*/
+
+use std::collections::BTreeMap;
+
+use crate::collate::TitleOrder;
+use crate::config;
+use crate::models::{Task, TaskStatus};
+
+// File used for tasks that carry no tag.
+const UNTAGGED_FILE: &str = "Untagged.md";
+
+// Obsidian Tasks status checkbox.
+fn checkbox(status: &TaskStatus) -> &'static str {
+    match status {
+        TaskStatus::Todo => "[ ]",
+        TaskStatus::InProgress => "[/]",
+        TaskStatus::Done => "[x]",
+    }
+}
+
+// Priority 1..=5 mapped onto the plugin's signifiers
+// (lowest ⏬, low 🔽, medium 🔼, high ⏫, highest 🔺).
+fn priority_symbol(priority: i64) -> &'static str {
+    match priority {
+        i64::MIN..=1 => "⏬",
+        2 => "🔽",
+        3 => "🔼",
+        4 => "⏫",
+        _ => "🔺",
+    }
+}
+
+// One task line in Obsidian Tasks syntax.
+// With `app_url`, a link to the task follows the title
+// (before the signifiers, which the plugin reads from the end).
+pub fn task_line(t: &Task, app_url: Option<&str>) -> String {
+    // Newlines would break the list item; keep the title on one line
+    let mut title = t.title.split_whitespace().collect::<Vec<_>>().join(" ");
+    if let Some(app_url) = app_url {
+        title.push_str(&format!(" [🔗](<{}>)", config::task_link(app_url, t.id)));
+    }
+    format!(
+        "- {} {} {} 📅 {}",
+        checkbox(&t.status),
+        title,
+        priority_symbol(t.priority),
+        t.due_at.format("%Y-%m-%d")
+    )
+}
+
+// Make a tag usable as a file name.
+fn file_name(tag: &str) -> String {
+    let cleaned: String = tag
+        .trim()
+        .chars()
+        .map(|c| if "/\\:*?\"<>|".contains(c) || c.is_control() { '-' } else { c })
+        .collect();
+    format!("{cleaned}.md")
+}
+
+// Group tasks into one Markdown file per tag.
+//
+// - Tasks with several tags appear in each tag's file
+// - Untagged tasks go to "Untagged.md"
+// - Files and lines are ordered deterministically (name; due_at, title)
+//   with titles collated for `locale`
+pub fn export_by_tag(tasks: &[Task], locale: &str, app_url: Option<&str>) -> BTreeMap<String, String> {
+    let titles = TitleOrder::new(locale);
+    let mut sorted: Vec<&Task> = tasks.iter().collect();
+    sorted.sort_by(|a, b| {
+        a.due_at.cmp(&b.due_at).then_with(|| titles.compare_titles(&a.title, &b.title))
+    });
+
+    let mut groups: BTreeMap<String, (String, Vec<String>)> = BTreeMap::new();
+    for t in sorted {
+        let tags: Vec<&str> = t
+            .tags
+            .iter()
+            .flatten()
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .collect();
+        if tags.is_empty() {
+            groups
+                .entry(UNTAGGED_FILE.to_string())
+                .or_insert_with(|| ("Untagged".to_string(), Vec::new()))
+                .1
+                .push(task_line(t, app_url));
+        }
+        for tag in tags {
+            groups
+                .entry(file_name(tag))
+                .or_insert_with(|| (tag.to_string(), Vec::new()))
+                .1
+                .push(task_line(t, app_url));
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(file, (heading, lines))| (file, format!("# {heading}\n\n{}\n", lines.join("\n"))))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock;
+    use crate::models::test_task;
+
+    fn fixture() -> Vec<Task> {
+        let mut report = test_task("Write report", clock::local("2024-06-01 14:00"));
+        report.priority = 4;
+        report.tags = Some(vec!["work".to_string(), "writing".to_string()]);
+        let mut call = test_task("Call  bank\nabout card", clock::local("2024-06-01 10:00"));
+        call.priority = 1;
+        call.status = TaskStatus::InProgress;
+        call.tags = Some(vec!["work".to_string()]);
+        let mut plants = test_task("Water plants", clock::local("2024-05-30 09:00"));
+        plants.priority = 5;
+        plants.status = TaskStatus::Done;
+        let mut odd = test_task("Sort receipts", clock::local("2024-06-02 09:00"));
+        odd.priority = 2;
+        odd.tags = Some(vec!["home/admin".to_string()]);
+        vec![report, call, plants, odd]
+    }
+
+    #[test]
+    fn files_snapshot() {
+        let files = export_by_tag(&fixture(), "en", None);
+        let names: Vec<&str> = files.keys().map(String::as_str).collect();
+        assert_eq!(names, ["Untagged.md", "home-admin.md", "work.md", "writing.md"]);
+        assert_eq!(
+            files["work.md"],
+            "# work\n\n\
+             - [/] Call bank about card ⏬ 📅 2024-06-01\n\
+             - [ ] Write report ⏫ 📅 2024-06-01\n"
+        );
+        assert_eq!(files["Untagged.md"], "# Untagged\n\n- [x] Water plants 🔺 📅 2024-05-30\n");
+        assert_eq!(files["home-admin.md"], "# home/admin\n\n- [ ] Sort receipts 🔽 📅 2024-06-02\n");
+        assert_eq!(files["writing.md"], "# writing\n\n- [ ] Write report ⏫ 📅 2024-06-01\n");
+    }
+
+    #[test]
+    fn priorities_map_to_plugin_symbols() {
+        let symbols: Vec<&str> = (0..=6).map(priority_symbol).collect();
+        assert_eq!(symbols, ["⏬", "⏬", "🔽", "🔼", "⏫", "🔺", "🔺"]);
+    }
+
+    #[test]
+    fn link_goes_before_the_signifiers() {
+        let task = &fixture()[0];
+        let line = task_line(task, Some("https://example.com/s"));
+        let link = format!(" [🔗](<{}>) ⏫ 📅 2024-06-01", config::task_link("https://example.com/s", task.id));
+        assert_eq!(line, format!("- [ ] Write report{link}"));
+    }
+
+    #[test]
+    fn output_does_not_depend_on_input_order() {
+        let mut tasks = fixture();
+        let first = export_by_tag(&tasks, "en", None);
+        tasks.reverse();
+        assert_eq!(export_by_tag(&tasks, "en", None), first);
+    }
+}
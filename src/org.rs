@@ -0,0 +1,207 @@
+/*
+Org-mode export.
+Renders tasks as "* TODO" headings with DEADLINE timestamps,
+:PROPERTIES: drawers and org tags, plus an optional section per
+planned day. Pure formatting, independent from HTTP / Axum.
+*/
+
+use chrono::{DateTime, FixedOffset, NaiveDate};
+
+use crate::logic::PlanItem;
+use crate::collate::TitleOrder;
+use crate::config;
+use crate::models::{Task, TaskStatus};
+
+// Org timestamp, e.g. "<2024-06-01 Sat 18:00>".
+fn timestamp(dt: DateTime<FixedOffset>) -> String {
+    dt.format("<%Y-%m-%d %a %H:%M>").to_string()
+}
+
+// Escape heading text: "*" would start bold markup, newlines would end the heading.
+pub fn escape_title(title: &str) -> String {
+    title
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('*', "\\ast{}")
+}
+
+// Org tags allow only [A-Za-z0-9_@#%]; reduce to alphanumerics and "_".
+fn org_tag(tag: &str) -> Option<String> {
+    let cleaned: String = tag
+        .trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    let cleaned = cleaned.trim_matches('_').to_string();
+    (!cleaned.is_empty()).then_some(cleaned)
+}
+
+// Priority 1..=5 mapped onto org's [#A]..[#C].
+fn org_priority(priority: i64) -> char {
+    match priority {
+        4.. => 'A',
+        3 => 'B',
+        _ => 'C',
+    }
+}
+
+// One heading with DEADLINE, properties drawer and indented notes.
+// With `app_url`, the drawer also holds a :URL: link to the task.
+pub fn task_heading(t: &Task, level: usize, app_url: Option<&str>) -> String {
+    let keyword = if t.status == TaskStatus::Done { "DONE" } else { "TODO" };
+    let mut head = format!(
+        "{} {} [#{}] {}",
+        "*".repeat(level),
+        keyword,
+        org_priority(t.priority),
+        escape_title(&t.title)
+    );
+    let tags: Vec<String> = t.tags.iter().flatten().filter_map(|s| org_tag(s)).collect();
+    if !tags.is_empty() {
+        head.push_str(&format!(" :{}:", tags.join(":")));
+    }
+
+    let mut out = vec![head];
+    out.push(format!("DEADLINE: {}", timestamp(t.due_at)));
+    out.push(":PROPERTIES:".to_string());
+    out.push(format!(":ID: {}", t.id));
+    out.push(format!(":Effort: {}:{:02}", t.duration_min / 60, t.duration_min % 60));
+    if let Some(app_url) = app_url {
+        out.push(format!(":URL: {}", config::task_link(app_url, t.id)));
+    }
+    if t.status == TaskStatus::InProgress {
+        out.push(":STATUS: in_progress".to_string());
+    }
+    out.push(":END:".to_string());
+    // Indented so note lines starting with "*" never become headings
+    for line in t.notes.iter().flat_map(|n| n.lines()) {
+        out.push(format!("  {line}"));
+    }
+    out.join("\n")
+}
+
+// Full org document.
+//
+// - "Tasks": one heading per task, ordered by due_at then title
+//   (collated for `locale`)
+// - "Plan" (when `plan_days` is given): one subheading per day with
+//   SCHEDULED time ranges for the planned items
+// - `app_url`: see task_heading
+pub fn format_document(
+    tasks: &[Task],
+    plan_days: Option<&[(NaiveDate, Vec<PlanItem>)]>,
+    locale: &str,
+    app_url: Option<&str>,
+) -> String {
+    let titles = TitleOrder::new(locale);
+    let mut sorted: Vec<&Task> = tasks.iter().collect();
+    sorted.sort_by(|a, b| {
+        a.due_at.cmp(&b.due_at).then_with(|| titles.compare_titles(&a.title, &b.title))
+    });
+
+    let mut out = vec![
+        "#+TITLE: Project Scheduler tasks".to_string(),
+        "#+TODO: TODO | DONE".to_string(),
+        String::new(),
+        "* Tasks".to_string(),
+    ];
+    for t in sorted {
+        out.push(task_heading(t, 2, app_url));
+    }
+
+    if let Some(days) = plan_days {
+        out.push(String::new());
+        out.push("* Plan".to_string());
+        for (date, items) in days {
+            out.push(format!("** {}", date.format("%Y-%m-%d %A")));
+            for p in items {
+                out.push(format!("*** {}", escape_title(&p.title)));
+                out.push(format!(
+                    "SCHEDULED: <{}-{}>",
+                    p.start.format("%Y-%m-%d %a %H:%M"),
+                    p.end.format("%H:%M")
+                ));
+            }
+        }
+    }
+
+    out.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock;
+    use crate::logic::test_item;
+    use crate::models::test_task;
+
+    fn fixture() -> Vec<Task> {
+        let mut report = test_task("Write *final* report", clock::local("2024-06-01 18:00"));
+        report.priority = 5;
+        report.duration_min = 90;
+        report.tags = Some(vec!["deep-work".to_string(), "Q2 review!".to_string(), "--".to_string()]);
+        report.notes = Some("* not a heading\nsecond line".to_string());
+        let mut call = test_task("Call\nbank", clock::local("2024-05-31 09:30"));
+        call.status = TaskStatus::InProgress;
+        call.priority = 1;
+        vec![report, call]
+    }
+
+    #[test]
+    fn document_snapshot() {
+        let tasks = fixture();
+        let doc = format_document(&tasks, None, "en", None);
+        let expected = format!(
+            "#+TITLE: Project Scheduler tasks\n\
+             #+TODO: TODO | DONE\n\
+             \n\
+             * Tasks\n\
+             ** TODO [#C] Call bank\n\
+             DEADLINE: <2024-05-31 Fri 09:30>\n\
+             :PROPERTIES:\n\
+             :ID: {call}\n\
+             :Effort: 1:00\n\
+             :STATUS: in_progress\n\
+             :END:\n\
+             ** TODO [#A] Write \\ast{{}}final\\ast{{}} report :deep_work:Q2_review:\n\
+             DEADLINE: <2024-06-01 Sat 18:00>\n\
+             :PROPERTIES:\n\
+             :ID: {report}\n\
+             :Effort: 1:30\n\
+             :END:\n\
+             \x20 * not a heading\n\
+             \x20 second line\n",
+            call = tasks[1].id,
+            report = tasks[0].id,
+        );
+        assert_eq!(doc, expected);
+    }
+
+    #[test]
+    fn done_tasks_use_the_done_keyword() {
+        let mut task = test_task("Filed", clock::local("2024-06-01 18:00"));
+        task.status = TaskStatus::Done;
+        assert!(task_heading(&task, 2, None).starts_with("** DONE [#B] Filed\n"));
+    }
+
+    #[test]
+    fn plan_section_lists_scheduled_ranges_per_day() {
+        let day = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let plan = [(day, vec![test_item("Deep *work*", "2024-06-03 09:00", "2024-06-03 10:30")])];
+        let doc = format_document(&[], Some(&plan), "en", None);
+        assert!(doc.ends_with(
+            "* Plan\n\
+             ** 2024-06-03 Monday\n\
+             *** Deep \\ast{}work\\ast{}\n\
+             SCHEDULED: <2024-06-03 Mon 09:00-10:30>\n"
+        ));
+    }
+
+    #[test]
+    fn url_property_links_to_the_task() {
+        let task = &fixture()[0];
+        let heading = task_heading(task, 2, Some("https://example.com/s"));
+        assert!(heading.contains(&format!("\n:URL: {}\n", config::task_link("https://example.com/s", task.id))));
+    }
+}
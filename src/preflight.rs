@@ -0,0 +1,243 @@
+// --------------------------------------------------
+// Startup checks of the environment.
+//
+// Runs once before the server binds, so a broken setup
+// stops the process with every problem listed instead of
+// surfacing later as per-request 500s:
+// - config values that parsed but cannot be used
+// - the db directory is writable (a probe file is created and
+//   removed again), or, when missing, can be created under its
+//   nearest existing ancestor
+// - db.json, when present, parses (at a schema version this
+//   build knows) and holds valid settings
+//   (a missing one is created by store::load_db; a SQLite file
+//   is checked when it is opened)
+// - --static-dir is a directory with an index.html
+//
+// `--check` runs only these checks and exits 0 or 1.
+// --------------------------------------------------
+
+use std::fs::{self, OpenOptions};
+use std::path::Path;
+
+use crate::config::{self, Config, StorageBackend};
+use crate::i18n::Lang;
+use crate::store::{self, StoreError};
+use crate::validation;
+
+// Every problem found, in a stable order; empty when startup can go ahead.
+pub fn run(cfg: &Config) -> Vec<String> {
+    let mut problems = config::problems(cfg);
+    if !cfg.storage.db_path.trim().is_empty() {
+        problems.extend(check_storage(&cfg.storage.db_path, cfg.storage.backend));
+    }
+    if let Some(dir) = &cfg.server.static_dir {
+        problems.extend(check_static_dir(dir));
+    }
+    problems
+}
+
+// Numbered list for the terminal.
+pub fn report(problems: &[String]) -> String {
+    let mut out = format!("  Startup check found {} problem(s):\n", problems.len());
+    for (i, p) in problems.iter().enumerate() {
+        out.push_str(&format!("    {}. {p}\n", i + 1));
+    }
+    out
+}
+
+fn check_storage(db_path: &str, backend: StorageBackend) -> Vec<String> {
+    let mut out = Vec::new();
+    let path = Path::new(db_path);
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+
+    // save_db writes a temp file next to db.json and renames it
+    // (SQLite keeps a journal next to its file), so the directory
+    // (not the file) needs write access. A missing
+    // one is created at startup (store::lock_db).
+    if dir.is_dir() {
+        let probe = format!("{db_path}.preflight");
+        if let Some(e) = probe_writable(Path::new(&probe)) {
+            out.push(format!("db directory {} is not writable: {e}", dir.display()));
+        }
+    } else {
+        let base = dir.ancestors().skip(1).find(|a| a.as_os_str().is_empty() || a.exists());
+        let base = base.map(|a| if a.as_os_str().is_empty() { Path::new(".") } else { a });
+        match base {
+            Some(base) if !base.is_dir() => out.push(format!(
+                "db directory {} cannot be created: {} is not a directory",
+                dir.display(),
+                base.display()
+            )),
+            Some(base) => {
+                if let Some(e) = probe_writable(&base.join(".scheduler-preflight")) {
+                    out.push(format!(
+                        "db directory {} does not exist and cannot be created in {}: {e}",
+                        dir.display(),
+                        base.display()
+                    ));
+                }
+            }
+            None => out.push(format!("db directory {} does not exist", dir.display())),
+        }
+    }
+
+    if backend != StorageBackend::Json || !path.exists() {
+        return out;
+    }
+    let db = match fs::read(path) {
+        Ok(bytes) => store::db_text(bytes).and_then(|text| store::decode(&text)).map(|decoded| decoded.db),
+        Err(e) => {
+            out.push(format!("cannot read {db_path}: {e}"));
+            return out;
+        }
+    };
+    match db {
+        Ok(db) => {
+            if let Err(errors) = validation::validate_settings(&db.settings) {
+                for e in errors {
+                    out.push(format!("{db_path}: settings: {}", e.message(Lang::En)));
+                }
+            }
+        }
+        Err(e @ StoreError::Corrupt { .. }) => out.push(format!(
+            "{} (start with --recover to back it up and start empty)",
+            e.describe()
+        )),
+        Err(e) => out.push(e.describe()),
+    }
+    out
+}
+
+// Create and remove a probe file; what went wrong, if anything.
+fn probe_writable(probe: &Path) -> Option<String> {
+    match OpenOptions::new().write(true).create_new(true).open(probe) {
+        Ok(_) => fs::remove_file(probe)
+            .err()
+            .map(|e| format!("cannot remove probe file {}: {e}", probe.display())),
+        Err(e) => Some(e.to_string()),
+    }
+}
+
+fn check_static_dir(dir: &str) -> Vec<String> {
+    let path = Path::new(dir);
+    if !path.is_dir() {
+        vec![format!("server.static_dir: {dir} is not a directory")]
+    } else if !path.join("index.html").is_file() {
+        vec![format!("server.static_dir: {dir} has no index.html")]
+    } else {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    // A fresh directory under the temp dir for one test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("scheduler-preflight-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn db_in(dir: &Path) -> String {
+        dir.join("db.json").to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn usable_storage_has_no_problems() {
+        let dir = temp_dir("ok");
+        assert_eq!(check_storage(&db_in(&dir), StorageBackend::Json), Vec::<String>::new());
+        // A missing directory that can be created is fine too
+        assert_eq!(check_storage(&db_in(&dir.join("new/data")), StorageBackend::Json), Vec::<String>::new());
+        // and the probes are gone again
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn read_only_db_directory_is_reported() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = temp_dir("read-only");
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o555)).unwrap();
+        // Permissions do not bind root; nothing to check then
+        if probe_writable(&dir.join("probe")).is_some() {
+            let problems = check_storage(&db_in(&dir), StorageBackend::Json);
+            assert_eq!(problems.len(), 1, "{problems:?}");
+            assert!(problems[0].starts_with(&format!("db directory {} is not writable", dir.display())), "{problems:?}");
+            let problems = check_storage(&db_in(&dir.join("sub")), StorageBackend::Json);
+            assert!(problems[0].contains("does not exist and cannot be created in"), "{problems:?}");
+        }
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn db_directory_under_a_file_is_reported() {
+        let dir = temp_dir("under-file");
+        fs::write(dir.join("file"), "").unwrap();
+        let problems = check_storage(&db_in(&dir.join("file/data")), StorageBackend::Json);
+        assert_eq!(problems.len(), 1, "{problems:?}");
+        assert!(problems[0].ends_with("file is not a directory"), "{problems:?}");
+    }
+
+    #[test]
+    fn unreadable_db_contents_are_reported() {
+        let dir = temp_dir("db-contents");
+        let path = db_in(&dir);
+        fs::write(&path, "{ not json").unwrap();
+        let problems = check_storage(&path, StorageBackend::Json);
+        assert!(problems[0].ends_with("(start with --recover to back it up and start empty)"), "{problems:?}");
+
+        let mut db = serde_json::to_value(crate::models::Db::default()).unwrap();
+        db["settings"]["day_start"] = "9am".into();
+        db["settings"]["focus_block_min"] = 0.into();
+        fs::write(&path, db.to_string()).unwrap();
+        let problems = check_storage(&path, StorageBackend::Json);
+        assert_eq!(problems.len(), 2, "{problems:?}");
+        assert!(problems.iter().all(|p| p.starts_with(&format!("{path}: settings: "))), "{problems:?}");
+
+        db["schema_version"] = 99.into();
+        fs::write(&path, db.to_string()).unwrap();
+        assert_eq!(check_storage(&path, StorageBackend::Json).len(), 1);
+        // Only a JSON db is read here
+        assert!(check_storage(&path, StorageBackend::Sqlite).is_empty());
+    }
+
+    #[test]
+    fn static_dir_needs_an_index() {
+        let dir = temp_dir("static");
+        let name = dir.to_string_lossy().into_owned();
+        assert_eq!(check_static_dir(&name), [format!("server.static_dir: {name} has no index.html")]);
+        fs::write(dir.join("index.html"), "<html>").unwrap();
+        assert!(check_static_dir(&name).is_empty());
+        let missing = format!("{name}/missing");
+        assert_eq!(check_static_dir(&missing), [format!("server.static_dir: {missing} is not a directory")]);
+    }
+
+    #[test]
+    fn every_problem_is_listed_and_numbered() {
+        let dir = temp_dir("all");
+        fs::write(dir.join("file"), "").unwrap();
+        let mut cfg = Config::default();
+        cfg.server.timezone = Some("Mars/Olympus".to_string());
+        cfg.server.public_url = Some("ftp://x.test".to_string());
+        cfg.server.static_dir = Some(dir.join("missing").to_string_lossy().into_owned());
+        cfg.storage.db_path = db_in(&dir.join("file/data"));
+        let problems = run(&cfg);
+        assert_eq!(problems.len(), 4, "{problems:?}");
+        assert!(problems[0].starts_with("server.public_url"));
+        assert!(problems[1].starts_with("server.timezone"));
+        assert!(problems[2].starts_with("db directory"));
+        assert!(problems[3].starts_with("server.static_dir"));
+
+        let text = report(&problems);
+        assert!(text.starts_with("  Startup check found 4 problem(s):\n    1. server.public_url"), "{text}");
+        assert!(text.contains("\n    4. server.static_dir"), "{text}");
+    }
+}
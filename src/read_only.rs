@@ -0,0 +1,99 @@
+// --------------------------------------------------
+// Read-only mode for /api routes (--read-only).
+//
+// Enabled with server.read_only, passed in as the middleware
+// state. Every request that could change data (any method
+// other than GET, HEAD or OPTIONS) is refused with
+// 403 {"error": "read_only"}, so a shared
+// screen can show the plan without risking edits. POST
+// routes that only compute (READS) still pass.
+//
+// Background jobs (auto-archive, snapshots, notifications)
+// keep writing; only the API is read-only.
+// --------------------------------------------------
+
+use axum::{
+    extract::{Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+// POST routes that do not change anything.
+const READS: &[&str] = &["/plan/compare"];
+
+pub async fn reject_writes(State(read_only): State<bool>, req: Request, next: Next) -> Response {
+    let safe = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    if !read_only || safe || READS.contains(&req.uri().path()) {
+        return next.run(req).await;
+    }
+    let body = json!({ "error": "read_only", "message": "the server is in read-only mode" });
+    (StatusCode::FORBIDDEN, Json(body)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::{Request, StatusCode}};
+    use serde_json::{json, Value};
+    use tower::ServiceExt as _;
+
+    use crate::{clock, writer};
+    use crate::models::test_task;
+
+    async fn call(method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        writer::spawn_for_tests();
+        let req = Request::builder().method(method).uri(uri).header("content-type", "application/json");
+        let req = req.body(body.map_or_else(Body::empty, |b| Body::from(b.to_string()))).unwrap();
+        let res = crate::router(None, true).oneshot(req).await.unwrap();
+        let status = res.status();
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn writes_are_refused_and_reads_still_served() {
+        writer::spawn_for_tests();
+        let task = test_task("Read only 518", clock::local("2031-09-10 12:00"));
+        let id = task.id;
+        writer::apply(move |db| {
+            db.insert(task);
+            ((), true)
+        })
+        .await
+        .unwrap();
+        let draft = json!({ "title": "Refused 518", "due_at": "2031-09-10T12:00:00-04:00", "duration_min": 30, "priority": 3 });
+        let settings = serde_json::to_value(&writer::snapshot().settings).unwrap();
+
+        let writes = [
+            ("POST", "/api/tasks".to_string(), Some(draft.clone())),
+            ("PUT", format!("/api/tasks/{id}"), Some(draft)),
+            ("DELETE", format!("/api/tasks/{id}"), None),
+            ("POST", format!("/api/tasks/{id}/toggle"), None),
+            ("PUT", "/api/settings".to_string(), Some(settings)),
+            ("POST", format!("/api/plan/items/{id}/lock"), None),
+        ];
+        for (method, uri, body) in writes {
+            let (status, body) = call(method, &uri, body).await;
+            assert_eq!((status, body["error"].as_str()), (StatusCode::FORBIDDEN, Some("read_only")), "{method} {uri}");
+        }
+        let task = writer::snapshot().get(id).cloned().unwrap();
+        assert_eq!((task.title.as_str(), task.rev, task.locked), ("Read only 518", 0, None));
+
+        let reads = [
+            "/api/tasks?date=2031-09-10".to_string(),
+            format!("/api/tasks/{id}"),
+            "/api/settings".to_string(),
+            "/api/plan/today?date=2031-09-10&available_min=540".to_string(),
+            "/api/trash".to_string(),
+            "/api/health".to_string(),
+        ];
+        for uri in reads {
+            assert_eq!(call("GET", &uri, None).await.0, StatusCode::OK, "{uri}");
+        }
+        // Computes without saving, so it passes
+        let (status, _) = call("POST", "/api/plan/compare?date=2031-09-10", Some(json!({}))).await;
+        assert_ne!(status, StatusCode::FORBIDDEN);
+    }
+}
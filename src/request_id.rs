@@ -0,0 +1,176 @@
+// --------------------------------------------------
+// Per-request correlation ids for /api routes.
+//
+// - Taken from an incoming X-Request-Id header when it is
+//   short printable ASCII, otherwise a new UUID
+// - Kept in a task-local while the request is handled, so
+//   log lines (store.rs, the writer) can be tagged with it
+// - Returned in the X-Request-Id response header
+// - Added to error bodies: "request_id" in JSON objects,
+//   "(request id: ...)" after plain-text messages
+// --------------------------------------------------
+
+use axum::{
+    body::{to_bytes, Body, HttpBody},
+    extract::Request,
+    http::{header, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use uuid::Uuid;
+
+pub const HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+// Longest incoming id that is reused as is.
+const MAX_LEN: usize = 128;
+
+// Error bodies larger than this are passed through unchanged.
+const MAX_ERROR_BODY: usize = 64 * 1024;
+
+tokio::task_local! {
+    static CURRENT: String;
+}
+
+// Id of the request being handled on this task, if any.
+pub fn current() -> Option<String> {
+    CURRENT.try_with(|id| id.clone()).ok()
+}
+
+// Run `f` with `id` as the current request id (for work handed to
+// another task, such as the db writer).
+pub async fn scope<F: Future>(id: Option<String>, f: F) -> F::Output {
+    match id {
+        Some(id) => CURRENT.scope(id, f).await,
+        None => f.await,
+    }
+}
+
+// "[id] " for log lines, or "" outside a request.
+pub fn log_tag() -> String {
+    current().map(|id| format!("[{id}] ")).unwrap_or_default()
+}
+
+fn incoming(req: &Request) -> Option<String> {
+    let id = req.headers().get(&HEADER)?.to_str().ok()?.trim();
+    let ok = !id.is_empty() && id.len() <= MAX_LEN && id.bytes().all(|b| b.is_ascii_graphic());
+    ok.then(|| id.to_string())
+}
+
+pub async fn assign(req: Request, next: Next) -> Response {
+    let id = incoming(&req).unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let resp = CURRENT.scope(id.clone(), next.run(req)).await;
+    let mut resp = if resp.status().is_client_error() || resp.status().is_server_error() {
+        tag_error_body(resp, &id).await
+    } else {
+        resp
+    };
+    if let Ok(v) = HeaderValue::from_str(&id) {
+        resp.headers_mut().insert(HEADER, v);
+    }
+    resp
+}
+
+// Add the id to a JSON object or plain-text error body.
+async fn tag_error_body(resp: Response, id: &str) -> Response {
+    let content_type = resp
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let is_json = content_type.starts_with("application/json");
+    let is_text = content_type.starts_with("text/plain");
+    let small = resp.body().size_hint().upper().is_some_and(|n| n <= MAX_ERROR_BODY as u64);
+    if !(is_json || is_text) || !small {
+        return resp;
+    }
+
+    let (mut parts, body) = resp.into_parts();
+    let Ok(bytes) = to_bytes(body, MAX_ERROR_BODY).await else {
+        return Response::from_parts(parts, Body::empty());
+    };
+
+    let tagged = if is_json {
+        match serde_json::from_slice::<serde_json::Value>(&bytes) {
+            Ok(serde_json::Value::Object(mut obj)) => {
+                obj.insert("request_id".into(), id.into());
+                serde_json::Value::Object(obj).to_string().into_bytes()
+            }
+            _ => bytes.to_vec(),
+        }
+    } else {
+        let text = String::from_utf8_lossy(&bytes);
+        format!("{text} (request id: {id})").into_bytes()
+    };
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(tagged))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode, middleware, response::IntoResponse, routing::get, Json, Router};
+    use tower::ServiceExt as _;
+
+    // A tiny app behind the middleware: one success and two error shapes.
+    fn app() -> Router {
+        Router::new()
+            .route("/ok", get(|| async { current().unwrap_or_default() }))
+            .route("/text", get(|| async { (StatusCode::INTERNAL_SERVER_ERROR, "failed to save db").into_response() }))
+            .route(
+                "/json",
+                get(|| async { (StatusCode::UNPROCESSABLE_ENTITY, Json(serde_json::json!({ "errors": [] }))).into_response() }),
+            )
+            .layer(middleware::from_fn(assign))
+    }
+
+    async fn get_with(uri: &str, id: Option<&str>) -> (StatusCode, String, String) {
+        let mut req = Request::builder().uri(uri);
+        if let Some(id) = id {
+            req = req.header(&HEADER, id);
+        }
+        let res = app().oneshot(req.body(Body::empty()).unwrap()).await.unwrap();
+        let status = res.status();
+        let header = res.headers()[&HEADER].to_str().unwrap().to_string();
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        (status, header, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn incoming_id_round_trips_and_is_current_in_the_handler() {
+        let (status, header, body) = get_with("/ok", Some("client-abc-123")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((header.as_str(), body.as_str()), ("client-abc-123", "client-abc-123"));
+    }
+
+    #[tokio::test]
+    async fn unusable_incoming_ids_are_replaced() {
+        let long = "x".repeat(MAX_LEN + 1);
+        for id in [None, Some(""), Some("has space"), Some(long.as_str())] {
+            let (_, header, body) = get_with("/ok", id).await;
+            assert!(Uuid::parse_str(&header).is_ok(), "{id:?} -> {header}");
+            assert_eq!(body, header);
+        }
+    }
+
+    #[tokio::test]
+    async fn error_bodies_carry_the_id() {
+        let (status, header, body) = get_with("/text", Some("req-7")).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(header, "req-7");
+        assert_eq!(body, "failed to save db (request id: req-7)");
+
+        let (_, _, body) = get_with("/json", Some("req-8")).await;
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body, serde_json::json!({ "errors": [], "request_id": "req-8" }));
+    }
+
+    #[tokio::test]
+    async fn no_id_outside_a_request() {
+        assert_eq!(current(), None);
+        assert_eq!(log_tag(), "");
+        let inside = scope(Some("job-1".to_string()), async { log_tag() }).await;
+        assert_eq!(inside, "[job-1] ");
+    }
+}
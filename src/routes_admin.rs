@@ -0,0 +1,225 @@
+// --------------------------------------------------
+// Handles database maintenance endpoints.
+//
+// Responsibilities:
+// - Report integrity issues in db.json (store::check_integrity)
+// - Apply the safe repairs (store::repair), optionally as a dry run
+// - Compact old Done tasks into month archives (compact.rs) and
+//   read them back
+// --------------------------------------------------
+
+use axum::{
+    extract::Query,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::clock;
+use crate::compact::{self, Outcome};
+use crate::models::Db;
+use crate::request_id;
+use crate::routes_tasks::{self, TaskView};
+use crate::store;
+use crate::writer;
+
+// One compaction at a time, so two never rewrite the same month file
+static COMPACTING: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+// -----------------------------
+// GET /api/admin/integrity
+// GET /api/maintenance/validate
+// Lists inconsistencies without changing anything
+// -----------------------------
+pub async fn get_integrity() -> impl IntoResponse {
+    let db = writer::snapshot();
+    Json(json!({ "issues": store::check_integrity(&db) })).into_response()
+}
+
+// Query parameters for POST /api/admin/repair
+#[derive(Debug, Deserialize)]
+pub struct RepairQuery {
+    #[serde(default)]
+    pub dry_run: bool, // report the fixes without saving them
+}
+
+// -----------------------------
+// POST /api/admin/repair?dry_run=BOOL
+// Applies the safe fixes and reports each change
+// -----------------------------
+pub async fn repair(Query(q): Query<RepairQuery>) -> impl IntoResponse {
+    let fixes = if q.dry_run {
+        let mut db = Db::clone(&writer::snapshot());
+        Ok(store::repair(&mut db))
+    } else {
+        writer::apply(|db| {
+            let fixes = store::repair(db);
+            let changed = !fixes.is_empty();
+            (fixes, changed)
+        })
+        .await
+    };
+
+    match fixes {
+        Ok(fixes) => Json(json!({ "dry_run": q.dry_run, "fixes": fixes })).into_response(),
+        Err(e) => routes_tasks::write_error(&e),
+    }
+}
+
+// Query parameters for POST /api/maintenance/compact
+#[derive(Debug, Deserialize)]
+pub struct CompactQuery {
+    #[serde(default = "default_compact_days")]
+    pub days: u32,
+    #[serde(default)]
+    pub dry_run: bool, // report what would move without writing anything
+}
+
+fn default_compact_days() -> u32 {
+    compact::DEFAULT_DAYS
+}
+
+// -----------------------------
+// POST /api/maintenance/compact?days=90&dry_run=BOOL
+// Moves Done tasks due more than `days` days ago into
+// archive/<YYYY-MM>.json; the db keeps them if that fails
+// -----------------------------
+pub async fn compact(Query(q): Query<CompactQuery>) -> impl IntoResponse {
+    let _running = COMPACTING.lock().await;
+    let moved = compact::select(&writer::snapshot(), q.days, clock::now());
+    let mut outcome = Outcome { days: q.days, dry_run: q.dry_run, compacted: moved.len(), months: compact::months(&moved) };
+    if q.dry_run || moved.is_empty() {
+        return Json(outcome).into_response();
+    }
+
+    if let Err(e) = store::write_archive(&moved).await {
+        eprintln!("  {}{e}", request_id::log_tag());
+        let body = json!({ "error": "archive_failed", "message": format!("{e}; nothing was removed") });
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json(body)).into_response();
+    }
+    match writer::apply(move |db| {
+        let removed = compact::remove(db, &moved);
+        (removed, removed > 0)
+    })
+    .await
+    {
+        Ok(removed) => {
+            outcome.compacted = removed;
+            Json(outcome).into_response()
+        }
+        Err(e) => routes_tasks::write_error(&e),
+    }
+}
+
+// Query parameters for GET /api/archive
+#[derive(Debug, Deserialize)]
+pub struct ArchiveQuery {
+    pub month: Option<String>, // "YYYY-MM"; absent lists the months
+}
+
+// -----------------------------
+// GET /api/archive?month=YYYY-MM
+// Compacted tasks due in that month, by due_at;
+// without month, the months that have an archive
+// -----------------------------
+pub async fn get_archive(Query(q): Query<ArchiveQuery>) -> impl IntoResponse {
+    let Some(month) = q.month else {
+        return match store::list_archive_months().await {
+            Ok(months) => Json(json!({ "months": months })).into_response(),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("cannot list archives: {e}")).into_response(),
+        };
+    };
+    if !store::is_month(&month) {
+        return (StatusCode::BAD_REQUEST, "invalid month, expected YYYY-MM").into_response();
+    }
+    match store::read_archive(&month).await {
+        Ok(tasks) => {
+            let labels = writer::snapshot().settings.priority_labels.clone();
+            let tasks: Vec<TaskView> = tasks.into_iter().map(|t| TaskView::new(t, labels.as_ref())).collect();
+            Json(json!({ "month": month, "tasks": tasks })).into_response()
+        }
+        Err(e) => routes_tasks::store_error(&e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request};
+    use serde_json::Value;
+
+    use crate::models::test_task;
+
+    async fn call(method: &str, uri: &str) -> (StatusCode, Value) {
+        let req = Request::builder().method(method).uri(uri).body(Body::empty()).unwrap();
+        let (status, _, body) = crate::test_request(req).await;
+        (status, body)
+    }
+
+    #[tokio::test]
+    async fn validate_lists_the_issues_a_dry_repair_would_fix() {
+        writer::spawn_for_tests();
+        // Put in past validation, as a hand edit of db.json would
+        let mut bad = test_task("Out of range 519", clock::local("2031-11-12 12:00"));
+        bad.priority = 9;
+        bad.duration_min = -30;
+        let id = bad.id;
+        writer::apply(move |db| {
+            db.insert(bad);
+            ((), true)
+        })
+        .await
+        .unwrap();
+
+        let about = |list: &Value, key: &str| -> Vec<Value> {
+            let items = list.as_array().unwrap().iter().filter(|i| i["task_id"] == id.to_string());
+            items.map(|i| i[key].clone()).collect()
+        };
+        let (status, validate) = call("GET", "/api/maintenance/validate").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(about(&validate["issues"], "kind"), ["priority_out_of_range", "duration_out_of_range"]);
+        assert_eq!(about(&validate["issues"], "priority")[0], 9);
+        let (_, integrity) = call("GET", "/api/admin/integrity").await;
+        assert_eq!(about(&integrity["issues"], "kind"), about(&validate["issues"], "kind"));
+
+        let (status, dry) = call("POST", "/api/admin/repair?dry_run=true").await;
+        assert_eq!(status, StatusCode::OK);
+        let fixes = dry["fixes"].as_array().unwrap().iter().filter(|f| f["task_id"] == id.to_string());
+        let fixes: Vec<(Value, Value)> = fixes.map(|f| (f["from"].clone(), f["to"].clone())).collect();
+        assert_eq!(fixes, [(9.into(), 5.into()), ((-30).into(), 1.into())]);
+        assert_eq!(writer::snapshot().get(id).map(|t| (t.priority, t.duration_min)), Some((9, -30)));
+
+        writer::apply(move |db| (db.remove(id), true)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_failed_archive_write_keeps_the_tasks() {
+        writer::spawn_for_tests();
+        // A month file that is not an archive makes write_archive fail
+        let corrupt = store::archive_dir().join("2003-02.json");
+        std::fs::create_dir_all(store::archive_dir()).unwrap();
+        std::fs::write(&corrupt, "not an archive").unwrap();
+        let mut old = test_task("Compacted 514", clock::local("2003-02-10 09:00"));
+        old.status = crate::models::TaskStatus::Done;
+        let id = old.id;
+        writer::apply(move |db| {
+            db.insert(old);
+            ((), true)
+        })
+        .await
+        .unwrap();
+
+        // Old enough to pick only tasks due before 2004
+        let (status, body) = call("POST", "/api/maintenance/compact?days=8000").await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["error"], "archive_failed");
+        assert!(body["message"].as_str().unwrap().contains("2003-02.json"));
+        assert_eq!(writer::snapshot().get(id).map(|t| t.title.as_str()), Some("Compacted 514"));
+        assert_eq!(std::fs::read_to_string(&corrupt).unwrap(), "not an archive");
+
+        std::fs::remove_file(&corrupt).unwrap();
+        writer::apply(move |db| (db.remove(id), true)).await.unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+// --------------------------------------------------
+// Handles the audit log endpoint.
+//
+// Reads back the entries the writer appends to audit.log
+// for every saved task and settings write.
+// --------------------------------------------------
+
+use axum::{
+    extract::Query,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::store;
+
+// Query parameters for /audit
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+    pub task_id: Option<String>, // only entries about this task
+}
+
+fn default_limit() -> usize {
+    100
+}
+
+// -----------------------------
+// GET /api/audit?limit=100&task_id=
+// The most recent entries, newest first
+// -----------------------------
+pub async fn get_audit(Query(q): Query<AuditQuery>) -> impl IntoResponse {
+    let task_id = match q.task_id.as_deref().map(Uuid::parse_str).transpose() {
+        Ok(id) => id,
+        Err(_) => return (StatusCode::BAD_REQUEST, "invalid task_id").into_response(),
+    };
+
+    let entries = match store::load_audit().await {
+        Ok(entries) => entries,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "failed to load audit log").into_response(),
+    };
+    let entries: Vec<_> = entries
+        .into_iter()
+        .rev()
+        .filter(|e| task_id.is_none() || e.task_id == task_id)
+        .take(q.limit)
+        .collect();
+
+    Json(json!({ "entries": entries })).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::{Request, StatusCode}};
+    use serde_json::{json, Value};
+
+    use crate::store;
+
+    async fn call(method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let req = Request::builder().method(method).uri(uri).header("content-type", "application/json");
+        let req = req.body(body.map_or_else(Body::empty, |b| Body::from(b.to_string()))).unwrap();
+        let (status, _, body) = crate::test_request(req).await;
+        (status, body)
+    }
+
+    #[tokio::test]
+    async fn the_log_is_jsonl_and_filters_by_task() {
+        let draft = json!({ "title": "Audited 512", "due_at": "2031-05-06T12:00:00-04:00", "duration_min": 30, "priority": 3 });
+        let (_, created) = call("POST", "/api/tasks", Some(draft)).await;
+        let id = created["id"].as_str().unwrap();
+        call("POST", &format!("/api/tasks/{id}/toggle"), None).await;
+        call("POST", &format!("/api/tasks/{id}/toggle"), None).await;
+
+        let (status, body) = call("GET", &format!("/api/audit?task_id={id}"), None).await;
+        assert_eq!(status, StatusCode::OK);
+        let entries = body["entries"].as_array().unwrap();
+        let ops: Vec<&str> = entries.iter().map(|e| e["op"].as_str().unwrap()).collect();
+        assert_eq!(ops, ["toggle", "toggle", "create"]);
+        assert!(entries.iter().all(|e| e["task_id"] == id));
+        assert_eq!((&entries[0]["before"]["status"], &entries[0]["after"]["status"]), (&json!("in_progress"), &json!("done")));
+        assert_eq!((&entries[2]["before"], &entries[2]["after"]["title"]), (&Value::Null, &json!("Audited 512")));
+
+        let (_, latest) = call("GET", &format!("/api/audit?task_id={id}&limit=1"), None).await;
+        assert_eq!(latest["entries"].as_array().unwrap().len(), 1);
+        assert_eq!(latest["entries"][0], entries[0]);
+        assert_eq!(call("GET", "/api/audit?task_id=nope", None).await.0, StatusCode::BAD_REQUEST);
+
+        // Every line of the file is a complete JSON object (skipping
+        // one other tests may be appending as it is read)
+        let text = std::fs::read_to_string(store::audit_path()).unwrap();
+        for line in text.split_inclusive('\n').filter(|l| l.ends_with('\n')) {
+            let entry: Value = serde_json::from_str(line).unwrap_or_else(|e| panic!("{e}: {line}"));
+            assert!(entry["at"].is_string() && entry["op"].is_string(), "{line}");
+        }
+    }
+
+    // The newest audit.log line about `task_id`, parsed from the file.
+    fn last_line_about(task_id: &str) -> Value {
+        let text = std::fs::read_to_string(store::audit_path()).unwrap();
+        let line = text.lines().rev().find(|l| l.contains(task_id)).unwrap_or_else(|| panic!("no line for {task_id}"));
+        serde_json::from_str(line).unwrap_or_else(|e| panic!("{e}: {line}"))
+    }
+
+    #[tokio::test]
+    async fn trash_restores_and_purges_are_logged() {
+        let draft = json!({ "title": "Trashed 512", "due_at": "2031-05-07T12:00:00-04:00", "duration_min": 30, "priority": 3 });
+        let (_, created) = call("POST", "/api/tasks", Some(draft)).await;
+        let id = created["id"].as_str().unwrap();
+        call("DELETE", &format!("/api/tasks/{id}"), None).await;
+
+        assert_eq!(call("POST", &format!("/api/trash/{id}/restore"), None).await.0, StatusCode::OK);
+        let restored = last_line_about(id);
+        assert_eq!((&restored["op"], &restored["task_id"]), (&json!("restore"), &json!(id)));
+        assert_eq!(restored["before"]["title"], "Trashed 512");
+        assert_eq!(restored["after"]["title"], "Trashed 512");
+        assert!(restored["at"].is_string());
+
+        call("DELETE", &format!("/api/tasks/{id}"), None).await;
+        assert_eq!(call("DELETE", &format!("/api/trash/{id}"), None).await.0, StatusCode::OK);
+        let purged = last_line_about(id);
+        assert_eq!((&purged["op"], &purged["task_id"]), (&json!("purge"), &json!(id)));
+        assert_eq!(purged["before"]["title"], "Trashed 512");
+        assert_eq!(purged["after"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn a_settings_import_is_logged() {
+        let (_, bundle) = call("GET", "/api/settings/export", None).await;
+        let mut settings = bundle["settings"].clone();
+        let original = settings["time_format"].clone();
+        settings["time_format"] = json!(if original == "12h" { "24h" } else { "12h" });
+        let (status, body) = call("POST", "/api/settings/import", Some(json!({ "settings": settings }))).await;
+        assert_eq!((status, &body["changed"]), (StatusCode::OK, &json!(["settings"])));
+
+        let (_, log) = call("GET", "/api/audit?limit=1000", None).await;
+        let entry = log["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|e| e["op"] == "settings" && e["after"]["time_format"] == settings["time_format"])
+            .cloned()
+            .unwrap();
+        assert_eq!(entry["task_id"], Value::Null);
+        assert_eq!(entry["before"]["time_format"], original);
+        assert!(entry["at"].is_string());
+
+        // Put back what other tests expect
+        settings["time_format"] = original;
+        call("POST", "/api/settings/import", Some(json!({ "settings": settings }))).await;
+    }
+}
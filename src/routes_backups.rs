@@ -0,0 +1,147 @@
+// --------------------------------------------------
+// Handles the rolling backup endpoints.
+//
+// Responsibilities:
+// - List the backups in backups/ (store::list_backups)
+// - Restore one as the live db; the db it replaces is kept
+//   as a backup first, so a restore can itself be undone
+// --------------------------------------------------
+
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+
+use crate::models::SCHEMA_VERSION;
+use crate::routes_tasks;
+use crate::store::{self, Decoded, StoreError};
+use crate::writer;
+
+// -----------------------------
+// GET /api/backups
+// Lists backups, newest first
+// -----------------------------
+pub async fn get_backups() -> impl IntoResponse {
+    match store::list_backups().await {
+        Ok(backups) => Json(json!({ "backups": backups })).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("cannot list backups: {e}")).into_response(),
+    }
+}
+
+// -----------------------------
+// POST /api/backups/:name/restore
+// Replaces the db with the backup (migrated if it has an older
+// schema). 404 for an unknown name, 422 for a backup that does
+// not load.
+// -----------------------------
+pub async fn restore_backup(Path(name): Path<String>) -> impl IntoResponse {
+    let Decoded { mut db, from_version, steps } = match store::read_backup(&name).await {
+        Ok(Some(decoded)) => decoded,
+        Ok(None) => return (StatusCode::NOT_FOUND, "backup not found").into_response(),
+        Err(e @ (StoreError::Corrupt { .. } | StoreError::TooNew { .. })) => {
+            // describe() would name the live db, not the backup
+            let message = match &e {
+                StoreError::TooNew { version } => {
+                    format!("{name} has schema version {version}; this build reads up to {SCHEMA_VERSION}")
+                }
+                StoreError::Corrupt { message, .. } => message.clone(),
+                _ => e.code().to_string(),
+            };
+            let body = json!({ "error": e.code(), "message": message });
+            return (StatusCode::UNPROCESSABLE_ENTITY, Json(body)).into_response();
+        }
+        Err(e) => return routes_tasks::store_error(&e),
+    };
+    store::upgrade(&mut db);
+    let tasks = db.tasks.len();
+
+    // With backups on save, the save below copies the replaced db.json
+    // itself; otherwise it is written out here. A write landing between
+    // this snapshot and the restore is not in that copy.
+    let mut kept = None;
+    if !store::backs_up_on_save() {
+        match store::write_backup(writer::snapshot()).await {
+            Ok(backup) => kept = Some(backup),
+            Err(e) => {
+                let msg = format!("cannot back up the current db, nothing restored: {e}");
+                return (StatusCode::INTERNAL_SERVER_ERROR, msg).into_response();
+            }
+        }
+    }
+
+    if let Err(e) = writer::apply_audited(move |current| {
+        let audited = writer::changes(current, &db);
+        *current = db;
+        ((), true, audited)
+    })
+    .await
+    {
+        return routes_tasks::write_error(&e);
+    }
+    if kept.is_none() {
+        kept = store::list_backups().await.ok().and_then(|b| b.into_iter().next()).map(|b| b.name);
+    }
+
+    Json(json!({
+        "restored": name,
+        "migrated_from": (!steps.is_empty()).then_some(from_version),
+        "backup": kept,
+        "tasks": tasks,
+    }))
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request};
+    use chrono::{DateTime, Utc};
+    use serde_json::Value;
+
+    // A backup file in the test db's backups/. Dated far ahead so the
+    // pruning done by other tests' saves never reaches it.
+    fn put_backup(at: &str, doc: &Value) -> String {
+        let name = store::backup_name(DateTime::parse_from_rfc3339(at).unwrap().with_timezone(&Utc));
+        std::fs::create_dir_all(store::backups_dir()).unwrap();
+        std::fs::write(store::backups_dir().join(&name), doc.to_string()).unwrap();
+        name
+    }
+
+    async fn restore(name: &str) -> (StatusCode, Value) {
+        let req = Request::builder().method("POST").uri(format!("/api/backups/{name}/restore")).body(Body::empty()).unwrap();
+        let (status, _, body) = crate::test_request(req).await;
+        (status, body)
+    }
+
+    #[tokio::test]
+    async fn an_older_schema_backup_is_migrated_when_read() {
+        let mut doc = serde_json::to_value(crate::models::Db::default()).unwrap();
+        doc.as_object_mut().unwrap().remove("schema_version");
+        let name = put_backup("2099-01-01T00:00:00.001Z", &doc);
+
+        let decoded = store::read_backup(&name).await.unwrap().unwrap();
+        assert_eq!(decoded.from_version, 1);
+        assert_eq!(decoded.steps.len(), SCHEMA_VERSION as usize - 1);
+        assert_eq!(decoded.db.schema_version, SCHEMA_VERSION);
+        assert!(store::list_backups().await.unwrap().iter().any(|b| b.name == name));
+    }
+
+    #[tokio::test]
+    async fn backups_that_cannot_be_restored_leave_the_db_alone() {
+        let newer = put_backup("2099-01-01T00:00:00.002Z", &json!({ "schema_version": SCHEMA_VERSION + 1 }));
+        let (status, body) = restore(&newer).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["error"], "db_too_new");
+        assert!(body["message"].as_str().unwrap().starts_with(&newer), "{body}");
+
+        let broken = put_backup("2099-01-01T00:00:00.003Z", &json!({ "tasks": "none" }));
+        let (status, body) = restore(&broken).await;
+        assert_eq!((status, body["error"].as_str()), (StatusCode::UNPROCESSABLE_ENTITY, Some("db_corrupt")));
+
+        assert_eq!(restore("db-2099-01-01T00-00-00.999Z.json").await.0, StatusCode::NOT_FOUND);
+        assert_eq!(restore("db.json").await.0, StatusCode::NOT_FOUND);
+    }
+}
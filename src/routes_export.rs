@@ -0,0 +1,282 @@
+// --------------------------------------------------
+// Handles API endpoints for exporting tasks to
+// other tools' formats.
+//
+// Responsibilities:
+// - Load tasks from db.json
+// - Hand them to the matching pure formatter
+// - Package the result (JSON / zip) for download
+// --------------------------------------------------
+
+use std::io::{Cursor, Write};
+
+use axum::{
+    extract::Query,
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::anonymize;
+use crate::import;
+use crate::logic;
+use crate::models::TaskStatus;
+use crate::obsidian;
+use crate::org;
+use crate::tasks_csv;
+use crate::clock;
+use crate::config;
+use crate::writer;
+
+#[derive(Debug, Deserialize)]
+pub struct ObsidianQuery {
+    pub group_by: Option<String>, // "tag" (default)
+    pub format: Option<String>,   // "zip" (default) or "json"
+}
+
+// Pack files into a zip archive.
+// Entries keep the map's order and a fixed timestamp so output is reproducible.
+fn build_zip(files: &std::collections::BTreeMap<String, String>) -> zip::result::ZipResult<Vec<u8>> {
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .last_modified_time(zip::DateTime::default());
+    for (name, content) in files {
+        zip.start_file(name.as_str(), options)?;
+        zip.write_all(content.as_bytes())?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+// -----------------------------
+// GET /api/export/obsidian
+// Exports all tasks as Obsidian Tasks Markdown, one file per tag
+// -----------------------------
+pub async fn export_obsidian(Query(q): Query<ObsidianQuery>) -> impl IntoResponse {
+    match q.group_by.as_deref().unwrap_or("tag") {
+        "tag" => {}
+        "project" => {
+            return (StatusCode::BAD_REQUEST, "group_by=project is not supported: tasks have no project")
+                .into_response()
+        }
+        _ => return (StatusCode::BAD_REQUEST, "group_by must be tag").into_response(),
+    }
+
+    let db = writer::snapshot();
+
+    let app_url = config::app_url();
+    let files = obsidian::export_by_tag(&db.tasks, &db.settings.sort_locale, app_url.as_deref());
+
+    match q.format.as_deref().unwrap_or("zip") {
+        "json" => Json(files).into_response(),
+        "zip" => match build_zip(&files) {
+            Ok(bytes) => (
+                [
+                    (header::CONTENT_TYPE, "application/zip"),
+                    (header::CONTENT_DISPOSITION, "attachment; filename=\"tasks-obsidian.zip\""),
+                ],
+                bytes,
+            )
+                .into_response(),
+            Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "failed to build zip").into_response(),
+        },
+        _ => (StatusCode::BAD_REQUEST, "format must be zip or json").into_response(),
+    }
+}
+
+// -----------------------------
+// GET /api/export/taskwarrior
+// Exports all tasks as `task import` JSON
+// -----------------------------
+pub async fn export_taskwarrior() -> impl IntoResponse {
+    let db = writer::snapshot();
+    Json(import::export_taskwarrior(&db.tasks)).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrgQuery {
+    #[serde(default)]
+    pub include_plan: bool, // add a section per day of the next 7 days' plan
+    #[serde(default)]
+    pub include_done: bool, // also export Done tasks (as DONE headings)
+}
+
+// -----------------------------
+// GET /api/export/org
+// Exports tasks (and optionally the week plan) as an org document
+// -----------------------------
+pub async fn export_org(Query(q): Query<OrgQuery>) -> impl IntoResponse {
+    let db = writer::snapshot();
+
+    let tasks: Vec<_> = db
+        .tasks
+        .iter()
+        .filter(|t| q.include_done || t.status != TaskStatus::Done)
+        .cloned()
+        .collect();
+
+    let week = q.include_plan.then(|| {
+        let now = clock::now();
+        logic::plan_days(&db.tasks, &db.settings, logic::plan_date(&db.settings, now), 7, now)
+    });
+
+    let app_url = config::app_url();
+    (
+        [(header::CONTENT_TYPE, "text/org; charset=utf-8")],
+        org::format_document(&tasks, week.as_deref(), &db.settings.sort_locale, app_url.as_deref()),
+    )
+        .into_response()
+}
+
+// -----------------------------
+// GET /api/export/anonymized
+// Whole db with titles, tags, notes and URLs replaced by placeholders
+// (anonymize.rs); loads and plans like the original
+// -----------------------------
+pub async fn export_anonymized() -> impl IntoResponse {
+    let db = writer::snapshot();
+    (
+        [(header::CONTENT_DISPOSITION, "attachment; filename=\"db-anonymized.json\"")],
+        Json(anonymize::anonymize(&db)),
+    )
+        .into_response()
+}
+
+// -----------------------------
+// GET /api/export
+// The whole db as a JSON download, for POST /api/import on
+// another machine
+// -----------------------------
+pub async fn export_db() -> impl IntoResponse {
+    let db = writer::snapshot();
+    let name = format!("scheduler-{}.json", clock::now().format("%Y-%m-%d"));
+    (
+        [(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{name}\""))],
+        Json(&*db),
+    )
+        .into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TasksCsvQuery {
+    pub date: Option<String>, // "YYYY-MM-DD": only the tasks GET /api/tasks lists for it
+    #[serde(default)]
+    pub include_done: bool, // also Done tasks (due on `date`, when given)
+}
+
+// -----------------------------
+// GET /api/tasks/export.csv?date=&include_done=BOOL
+// Open tasks (plus Done ones with include_done) as a CSV
+// download, in db order (tasks_csv.rs)
+// -----------------------------
+pub async fn export_tasks_csv(Query(q): Query<TasksCsvQuery>) -> impl IntoResponse {
+    let date = match q.date.as_deref().map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d")).transpose() {
+        Ok(date) => date,
+        Err(_) => return (StatusCode::BAD_REQUEST, "invalid date").into_response(),
+    };
+    let db = writer::snapshot();
+    let now = clock::now();
+
+    let tasks: Vec<_> = match date {
+        Some(date) => {
+            let relevant = logic::relevant_tasks(&db.tasks, &db.settings, date, now);
+            db.tasks
+                .iter()
+                .filter(|t| {
+                    relevant.iter().any(|r| r.id == t.id)
+                        || (q.include_done
+                            && t.status == TaskStatus::Done
+                            && logic::is_due_on(t.due_at, date, &db.settings))
+                })
+                .collect()
+        }
+        None => db.tasks.iter().filter(|t| q.include_done || t.status != TaskStatus::Done).collect(),
+    };
+
+    match tasks_csv::format(&tasks) {
+        Ok(csv) => {
+            let day = date.unwrap_or_else(|| clock::local_date(&now));
+            (
+                [
+                    (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+                    (header::CONTENT_DISPOSITION, format!("attachment; filename=\"tasks-{day}.csv\"")),
+                ],
+                csv,
+            )
+                .into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("cannot write csv: {e}")).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn zip_is_deterministic_and_ordered() {
+        let files: BTreeMap<String, String> = [("work.md", "# work\n"), ("Untagged.md", "# Untagged\n"), ("home.md", "# home\n")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let first = build_zip(&files).unwrap();
+        assert_eq!(build_zip(&files).unwrap(), first);
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(first)).unwrap();
+        let names: Vec<String> = (0..archive.len()).map(|i| archive.by_index(i).unwrap().name().to_string()).collect();
+        assert_eq!(names, ["Untagged.md", "home.md", "work.md"]);
+        let mut content = String::new();
+        std::io::Read::read_to_string(&mut archive.by_name("work.md").unwrap(), &mut content).unwrap();
+        assert_eq!(content, "# work\n");
+    }
+
+    async fn get_csv(uri: &str) -> (StatusCode, axum::http::HeaderMap, String) {
+        use tower::ServiceExt as _;
+        writer::spawn_for_tests();
+        let req = axum::http::Request::builder().uri(uri).body(axum::body::Body::empty()).unwrap();
+        let res = crate::router(None, false).oneshot(req).await.unwrap();
+        let (parts, body) = res.into_parts();
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        (parts.status, parts.headers, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn csv_export_is_a_download_filtered_like_the_task_list() {
+        let due = clock::local("2031-07-08 12:00");
+        let mut open = crate::models::test_task("Open, \"quoted\" 515", due);
+        open.notes = Some("two\nlines".to_string());
+        let mut done = crate::models::test_task("Done 515", due);
+        done.status = TaskStatus::Done;
+        let mut other_day = crate::models::test_task("Other day 515", due + chrono::Duration::days(1));
+        other_day.status = TaskStatus::Done;
+        let ids = [open.id, done.id, other_day.id];
+        writer::spawn_for_tests();
+        writer::apply(move |db| {
+            for t in [open, done, other_day] {
+                db.insert(t);
+            }
+            ((), true)
+        })
+        .await
+        .unwrap();
+
+        let (status, headers, body) = get_csv("/api/tasks/export.csv?date=2031-07-08").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[header::CONTENT_TYPE], "text/csv; charset=utf-8");
+        assert_eq!(headers[header::CONTENT_DISPOSITION], "attachment; filename=\"tasks-2031-07-08.csv\"");
+        let listed = |body: &str| ids.map(|id| body.contains(&id.to_string()));
+        assert_eq!(listed(&body), [true, false, false]);
+        assert!(body.contains("\"Open, \"\"quoted\"\" 515\""), "{body}");
+
+        let (_, _, body) = get_csv("/api/tasks/export.csv?date=2031-07-08&include_done=true").await;
+        assert_eq!(listed(&body), [true, true, false]);
+        let (_, _, body) = get_csv("/api/tasks/export.csv?include_done=true").await;
+        assert_eq!(listed(&body), [true, true, true]);
+        let (_, _, body) = get_csv("/api/tasks/export.csv").await;
+        assert_eq!(listed(&body), [true, false, false]);
+        assert_eq!(get_csv("/api/tasks/export.csv?date=July").await.0, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,110 @@
+// --------------------------------------------------
+// Handles calendar subscription feeds.
+//
+// GET /api/feeds/:tag.ics serves open tasks carrying a tag
+// as an iCalendar feed. Calendar clients cannot send headers,
+// so when an api key is configured it is passed as ?token=.
+// --------------------------------------------------
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Path, Query},
+    http::{header, StatusCode},
+    response::IntoResponse,
+};
+use chrono::NaiveTime;
+use serde::Deserialize;
+
+use crate::config;
+use crate::ics;
+use crate::models::{Task, TaskStatus};
+use crate::clock;
+use crate::writer;
+use crate::validation;
+
+// Generated feeds are reused for this long to absorb aggressive polling.
+const FEED_CACHE_TTL: Duration = Duration::from_secs(60);
+
+// tag (lowercase) -> (generated at, feed body)
+static FEED_CACHE: Mutex<Option<HashMap<String, (Instant, String)>>> = Mutex::new(None);
+
+#[derive(Debug, Deserialize)]
+pub struct FeedQuery {
+    pub token: Option<String>,
+}
+
+fn has_tag(task: &Task, tag: &str) -> bool {
+    task.tags
+        .as_ref()
+        .is_some_and(|tags| tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+}
+
+// -----------------------------
+// GET /api/feeds/:tag.ics
+// Returns an iCalendar feed of open tasks with the tag
+// -----------------------------
+pub async fn get_tag_feed(Path(file): Path<String>, Query(q): Query<FeedQuery>) -> impl IntoResponse {
+    if let Some(key) = config::get().auth.api_key.as_deref()
+        && q.token.as_deref() != Some(key)
+    {
+        return (StatusCode::UNAUTHORIZED, "missing or invalid token").into_response();
+    }
+
+    let Some(tag) = file.strip_suffix(".ics").filter(|t| !t.is_empty()) else {
+        return (StatusCode::NOT_FOUND, "feed not found").into_response();
+    };
+    // "Deep Work.ics" finds tasks tagged "deep-work"
+    let tag = validation::normalize_tag(tag);
+    let key = tag.to_lowercase();
+
+    let cached = FEED_CACHE
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|c| c.get(&key))
+        .filter(|(at, _)| at.elapsed() < FEED_CACHE_TTL)
+        .map(|(_, body)| body.clone());
+
+    let body = match cached {
+        Some(body) => body,
+        None => {
+            let db = writer::snapshot();
+
+            let mut tasks: Vec<Task> = db
+                .tasks
+                .iter()
+                .filter(|t| t.status != TaskStatus::Done && has_tag(t, &tag))
+                .cloned()
+                .collect();
+            tasks.sort_by(|a, b| a.due_at.cmp(&b.due_at).then_with(|| a.id.cmp(&b.id)));
+
+            let all_day = NaiveTime::parse_from_str(&db.settings.default_due_time, "%H:%M").ok();
+            let app_url = config::app_url();
+            let body = ics::format_calendar(
+                &format!("Tasks: {tag}"),
+                &tasks,
+                all_day,
+                clock::now(),
+                app_url.as_deref(),
+            );
+
+            FEED_CACHE
+                .lock()
+                .unwrap()
+                .get_or_insert_with(HashMap::new)
+                .insert(key, (Instant::now(), body.clone()));
+            body
+        }
+    };
+
+    (
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        body,
+    )
+        .into_response()
+}
@@ -0,0 +1,32 @@
+// --------------------------------------------------
+// Handles the health endpoint.
+//
+// Reports liveness and the non-secret parts of the
+// effective server configuration.
+// --------------------------------------------------
+
+use axum::{response::IntoResponse, Json};
+use serde_json::json;
+
+use crate::archive;
+use crate::config;
+
+// -----------------------------
+// GET /api/health
+// Returns ok, the effective config (without secrets)
+// and the result of the last auto-archive pass
+// -----------------------------
+pub async fn get_health() -> impl IntoResponse {
+    let cfg = config::get();
+    Json(json!({
+        "ok": true,
+        "version": env!("CARGO_PKG_VERSION"),
+        "config": {
+            "server": cfg.server,
+            "storage": cfg.storage,
+            "auth": { "enabled": cfg.auth.api_key.is_some() },
+            "notifications": cfg.notifications,
+        },
+        "auto_archive": archive::last_run(),
+    }))
+}
@@ -0,0 +1,47 @@
+// --------------------------------------------------
+// Handles the plan history endpoint.
+//
+// Reads back the daily snapshots written by history.rs.
+// --------------------------------------------------
+
+use axum::{
+    extract::Query,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::store;
+
+// Query parameters for /history (both inclusive, "YYYY-MM-DD")
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+fn parse_date(value: Option<&str>) -> Result<Option<NaiveDate>, ()> {
+    value.map(|v| NaiveDate::parse_from_str(v, "%Y-%m-%d").map_err(|_| ())).transpose()
+}
+
+// -----------------------------
+// GET /api/history?from=&to=
+// Recorded days in date order, optionally limited to a range
+// -----------------------------
+pub async fn get_history(Query(q): Query<HistoryQuery>) -> impl IntoResponse {
+    let (Ok(from), Ok(to)) = (parse_date(q.from.as_deref()), parse_date(q.to.as_deref())) else {
+        return (StatusCode::BAD_REQUEST, "invalid date").into_response();
+    };
+
+    let mut days = match store::load_history().await {
+        Ok(days) => days,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "failed to load history").into_response(),
+    };
+    days.retain(|d| from.is_none_or(|f| d.date >= f) && to.is_none_or(|t| d.date <= t));
+    days.sort_by_key(|d| d.date);
+
+    Json(json!({ "days": days })).into_response()
+}
@@ -0,0 +1,479 @@
+// --------------------------------------------------
+// Handles API endpoints for importing tasks exported
+// from other task managers.
+//
+// Responsibilities:
+// - Accept the raw export file as the request body
+// - Hand it to the matching parser in import.rs
+// - Deduplicate against the db and save through the writer (unless dry_run)
+//
+// Also imports tasks from our own CSV (POST /api/tasks/import).
+// --------------------------------------------------
+
+use std::collections::HashSet;
+
+use axum::{
+    extract::{FromRequest, Multipart, Query, Request},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::import;
+use crate::clock;
+use crate::i18n::Lang;
+use crate::models::{AuditOp, Db, SCHEMA_VERSION};
+use crate::routes_tasks;
+use crate::tasks_csv::{self, ImportRow};
+use crate::transfer::{self, Incoming, Rejected};
+use crate::writer;
+
+// Largest body POST /api/import accepts (a whole db, larger than
+// axum's 2 MB default).
+pub const MAX_DB_IMPORT_BYTES: usize = 64 * 1024 * 1024;
+
+// Query parameters shared by all import endpoints
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    #[serde(default)]
+    pub dry_run: bool, // report what would be imported without saving
+    #[serde(default)]
+    pub dedupe: bool, // skip same-day duplicates of open tasks (also on with dedupe_on_create)
+    #[serde(default)]
+    pub skip_duplicates: bool, // same as dedupe
+}
+
+// -----------------------------
+// POST /api/import/todoist
+// Imports a Todoist CSV or JSON export
+// -----------------------------
+pub async fn import_todoist(Query(q): Query<ImportQuery>, body: String) -> impl IntoResponse {
+    let now = clock::now();
+
+    let db = writer::snapshot();
+
+    let parsed = match import::parse_todoist(&body, &db.settings, now) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    let dry_run = q.dry_run;
+    let dedupe = q.dedupe || q.skip_duplicates;
+    let merged = writer::apply_audited(move |db| {
+        let dedupe = dedupe || db.settings.dedupe_on_create;
+        let known: HashSet<_> = db.tasks.iter().map(|t| t.id).collect();
+        let report = import::merge_into(&mut db.tasks, parsed, dry_run, dedupe);
+        db.reindex();
+        let changed = !dry_run && report.imported > 0;
+        (report, changed, created(db, &known))
+    });
+    match merged.await {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => routes_tasks::write_error(&e),
+    }
+}
+
+// -----------------------------
+// POST /api/import/google-tasks
+// Imports a Google Tasks Takeout JSON export
+// -----------------------------
+pub async fn import_google_tasks(Query(q): Query<ImportQuery>, body: String) -> impl IntoResponse {
+    let now = clock::now();
+
+    let db = writer::snapshot();
+
+    let parsed = match import::parse_google_tasks(&body, &db.settings, now) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    let dry_run = q.dry_run;
+    let dedupe = q.dedupe || q.skip_duplicates;
+    let merged = writer::apply_audited(move |db| {
+        let dedupe = dedupe || db.settings.dedupe_on_create;
+        let known: HashSet<_> = db.tasks.iter().map(|t| t.id).collect();
+        let report = import::merge_into(&mut db.tasks, parsed, dry_run, dedupe);
+        db.reindex();
+        let changed = !dry_run && report.imported > 0;
+        (report, changed, created(db, &known))
+    });
+    match merged.await {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => routes_tasks::write_error(&e),
+    }
+}
+
+// -----------------------------
+// POST /api/import/taskwarrior
+// Imports `task export` JSON
+// -----------------------------
+pub async fn import_taskwarrior(Query(q): Query<ImportQuery>, body: String) -> impl IntoResponse {
+    let now = clock::now();
+
+    let db = writer::snapshot();
+
+    let parsed = match import::parse_taskwarrior(&body, &db.settings, now) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    let dry_run = q.dry_run;
+    let dedupe = q.dedupe || q.skip_duplicates;
+    let merged = writer::apply_audited(move |db| {
+        let dedupe = dedupe || db.settings.dedupe_on_create;
+        let known: HashSet<_> = db.tasks.iter().map(|t| t.id).collect();
+        let report = import::merge_into(&mut db.tasks, parsed, dry_run, dedupe);
+        db.reindex();
+        let changed = !dry_run && report.imported > 0;
+        (report, changed, created(db, &known))
+    });
+    match merged.await {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => routes_tasks::write_error(&e),
+    }
+}
+
+// A create for each task the import added: those not in `known`.
+fn created(db: &Db, known: &HashSet<Uuid>) -> Vec<writer::Audited> {
+    db.tasks.iter().filter(|t| !known.contains(&t.id)).map(|t| (AuditOp::Create, Some(t.id))).collect()
+}
+
+// How POST /api/import applies the document
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DbImportMode {
+    Replace, // the document becomes the db
+    #[default]
+    Merge, // its tasks are added or updated by id
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DbImportQuery {
+    #[serde(default)]
+    pub mode: DbImportMode,
+    #[serde(default)]
+    pub dry_run: bool, // report what would change without saving
+}
+
+// -----------------------------
+// POST /api/import?mode=replace|merge&dry_run=BOOL
+// Imports a GET /api/export document (older schema versions are
+// migrated). 400 if it is not JSON; 422 listing every invalid
+// field, by task index, if it does not check out.
+// -----------------------------
+pub async fn import_db(Query(q): Query<DbImportQuery>, headers: HeaderMap, body: String) -> impl IntoResponse {
+    let lang = Lang::from_headers(&headers);
+    let Incoming { db: incoming, migrated_from } = match transfer::parse(&body, lang) {
+        Ok(incoming) => incoming,
+        Err(Rejected::NotJson(e)) => return (StatusCode::BAD_REQUEST, format!("not a db export: {e}")).into_response(),
+        Err(Rejected::TooNew(version)) => {
+            let message = format!("schema version {version} is newer than this build reads ({SCHEMA_VERSION})");
+            let body = json!({ "errors": [{ "field": "schema_version", "code": "too_new", "message": message }] });
+            return (StatusCode::UNPROCESSABLE_ENTITY, Json(body)).into_response();
+        }
+        Err(Rejected::Invalid(errors)) => {
+            return (StatusCode::UNPROCESSABLE_ENTITY, Json(json!({ "errors": errors }))).into_response();
+        }
+    };
+
+    let (mode, dry_run) = (q.mode, q.dry_run);
+    let applied = writer::apply_audited(move |db| {
+        let before = (!dry_run).then(|| db.clone());
+        let outcome = match mode {
+            DbImportMode::Replace => transfer::replace(db, incoming),
+            DbImportMode::Merge => transfer::merge(db, incoming),
+        };
+        // A dry run still applies, to the writer's scratch copy, which
+        // is dropped unsaved
+        let audited = before.map(|before| writer::changes(&before, db)).unwrap_or_default();
+        (outcome, !dry_run, audited)
+    });
+    match applied.await {
+        Ok(outcome) => Json(json!({
+            "mode": mode,
+            "dry_run": dry_run,
+            "migrated_from": migrated_from,
+            "added": outcome.added,
+            "updated": outcome.updated,
+            "removed": outcome.removed,
+        }))
+        .into_response(),
+        Err(e) => routes_tasks::write_error(&e),
+    }
+}
+
+// What POST /api/tasks/import does with a row whose id is taken
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnConflict {
+    #[default]
+    Reject, // the row is an error, so nothing is imported
+    Skip,   // the row is left out and listed under "skipped"
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TasksCsvImportQuery {
+    #[serde(default)]
+    pub dry_run: bool, // validate and report without saving
+    #[serde(default)]
+    pub on_conflict: OnConflict,
+}
+
+// A row left out because its id is taken.
+#[derive(Debug, Serialize)]
+struct SkippedRow {
+    line: u64,
+    id: String,
+}
+
+// Result of a CSV import inside the writer.
+enum CsvImport {
+    Imported { imported: usize, skipped: Vec<SkippedRow> },
+    Conflicts(Vec<SkippedRow>), // on_conflict=reject and some ids are taken
+}
+
+// -----------------------------
+// POST /api/tasks/import?dry_run=BOOL&on_conflict=reject|skip
+// Creates tasks from a CSV with the columns of GET /api/tasks/export.csv,
+// sent as the raw body (text/csv) or as a multipart/form-data file.
+// All rows are saved at once, or none: any invalid row -> 422 with
+// {"errors": [{line, field, code, message}]}. An id already in
+// tasks, archived or trash (or on an earlier row) is an "id_taken"
+// error, or skipped with on_conflict=skip.
+// -----------------------------
+pub async fn import_tasks_csv(Query(q): Query<TasksCsvImportQuery>, request: Request) -> impl IntoResponse {
+    let lang = Lang::from_headers(request.headers());
+    let body = match csv_body(request).await {
+        Ok(body) => body,
+        Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+    };
+
+    let (rows, errors) = match tasks_csv::parse(&body, clock::now()) {
+        Ok(parsed) => parsed,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("not a tasks CSV: {e}")).into_response(),
+    };
+    if !errors.is_empty() {
+        let errors: Vec<_> = errors
+            .iter()
+            .map(|e| {
+                let mut entry = e.error.to_json(lang);
+                entry["line"] = e.line.into();
+                entry
+            })
+            .collect();
+        return (StatusCode::UNPROCESSABLE_ENTITY, Json(json!({ "errors": errors }))).into_response();
+    }
+
+    let (dry_run, on_conflict) = (q.dry_run, q.on_conflict);
+    let applied = writer::apply_audited(move |db| {
+        let mut taken: HashSet<_> = db
+            .tasks
+            .iter()
+            .chain(&db.archived)
+            .chain(db.trash.iter().map(|t| &t.task))
+            .map(|t| t.id)
+            .collect();
+        let (mut fresh, mut clashes) = (Vec::new(), Vec::new());
+        for ImportRow { line, task } in rows {
+            if taken.insert(task.id) {
+                fresh.push(task);
+            } else {
+                clashes.push(SkippedRow { line, id: task.id.to_string() });
+            }
+        }
+        if !clashes.is_empty() && matches!(on_conflict, OnConflict::Reject) {
+            return (CsvImport::Conflicts(clashes), false, Vec::new());
+        }
+        let imported = fresh.len();
+        let audited = fresh.iter().map(|t| (AuditOp::Create, Some(t.id))).collect();
+        for task in fresh {
+            db.insert(task);
+        }
+        // One save for the whole file
+        (CsvImport::Imported { imported, skipped: clashes }, !dry_run && imported > 0, audited)
+    });
+    match applied.await {
+        Ok(CsvImport::Imported { imported, skipped }) => {
+            Json(json!({ "dry_run": dry_run, "imported": imported, "skipped": skipped })).into_response()
+        }
+        Ok(CsvImport::Conflicts(clashes)) => {
+            let errors: Vec<_> = clashes
+                .iter()
+                .map(|c| {
+                    let message = format!("a task with id {} already exists", c.id);
+                    json!({ "line": c.line, "field": "id", "code": "id_taken", "message": message })
+                })
+                .collect();
+            (StatusCode::UNPROCESSABLE_ENTITY, Json(json!({ "errors": errors }))).into_response()
+        }
+        Err(e) => routes_tasks::write_error(&e),
+    }
+}
+
+// The CSV text of an import request: the body itself, or the first
+// file of a multipart/form-data form.
+async fn csv_body(request: Request) -> Result<String, String> {
+    let content_type = request.headers().get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok());
+    if !content_type.is_some_and(|t| t.starts_with("multipart/form-data")) {
+        return String::from_request(request, &()).await.map_err(|e| e.body_text());
+    }
+    let mut form = Multipart::from_request(request, &()).await.map_err(|e| e.body_text())?;
+    while let Some(field) = form.next_field().await.map_err(|e| e.body_text())? {
+        if field.file_name().is_some() || field.name() == Some("file") {
+            return field.text().await.map_err(|e| e.body_text());
+        }
+    }
+    Err("no file in the form".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::{Request, StatusCode}};
+    use serde_json::{json, Value};
+
+    use crate::models::{test_task, Db};
+    use crate::{clock, writer};
+
+    const HEADER: &str = "id,title,due_at,duration_min,priority";
+
+    async fn call(method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let req = Request::builder().method(method).uri(uri).header("content-type", "application/json");
+        let req = req.body(body.map_or_else(Body::empty, |b| Body::from(b.to_string()))).unwrap();
+        let (status, _, body) = crate::test_request(req).await;
+        (status, body)
+    }
+
+    // POST /api/tasks/import with a CSV body, under its own request id.
+    async fn import_csv(query: &str, csv: &str, request_id: &str) -> (StatusCode, Value) {
+        let req = Request::builder().method("POST").uri(format!("/api/tasks/import{query}"));
+        let req = req.header("content-type", "text/csv").header("x-request-id", request_id);
+        let (status, _, body) = crate::test_request(req.body(Body::from(csv.to_string())).unwrap()).await;
+        (status, body)
+    }
+
+    async fn create(title: &str) -> String {
+        let draft = json!({ "title": title, "due_at": "2031-06-09T12:00:00-04:00", "duration_min": 30, "priority": 3 });
+        call("POST", "/api/tasks", Some(draft)).await.1["id"].as_str().unwrap().to_string()
+    }
+
+    fn has(id: &str) -> bool {
+        let db = writer::snapshot();
+        db.tasks.iter().chain(&db.archived).any(|t| t.id.to_string() == id)
+    }
+
+    #[tokio::test]
+    async fn bad_rows_are_reported_by_line() {
+        let good = uuid::Uuid::new_v4();
+        let csv = format!(
+            "{HEADER}\n\
+             {good},Fine 516,2031-06-10T09:00:00-04:00,30,3\n\
+             ,Bad date 516,2031-13-40T09:00:00-04:00,30,3\n\
+             ,Too low 516,2031-06-10T09:00:00-04:00,30,0\n\
+             ,Too high 516,2031-06-10T09:00:00-04:00,30,6\n\
+             ,,2031-06-10T09:00:00-04:00,30,3\n"
+        );
+        let (status, body) = import_csv("", &csv, "csv-errors-516").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let errors: Vec<(u64, &str, &str)> = body["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| (e["line"].as_u64().unwrap(), e["field"].as_str().unwrap(), e["code"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            errors,
+            [(3, "due_at", "invalid_format"), (4, "priority", "out_of_range"), (5, "priority", "out_of_range"), (6, "title", "required")]
+        );
+        assert!(!has(&good.to_string()));
+        assert_eq!(writer::saves_for("csv-errors-516"), 0);
+    }
+
+    #[tokio::test]
+    async fn a_dry_run_csv_import_writes_nothing() {
+        let id = uuid::Uuid::new_v4().to_string();
+        let csv = format!("{HEADER}\n{id},Dry 516,2031-06-10T09:00:00-04:00,30,3\n");
+        let (status, body) = import_csv("?dry_run=true", &csv, "csv-dry-516").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((&body["dry_run"], &body["imported"]), (&json!(true), &json!(1)));
+        assert!(!has(&id));
+        assert_eq!(writer::saves_for("csv-dry-516"), 0);
+    }
+
+    #[tokio::test]
+    async fn taken_ids_are_rejected_or_skipped() {
+        let open = create("Open 516").await;
+        let trashed = create("Trashed 516").await;
+        assert_eq!(call("DELETE", &format!("/api/tasks/{trashed}"), None).await.0, StatusCode::OK);
+        let new = uuid::Uuid::new_v4().to_string();
+        let csv = format!(
+            "{HEADER}\n\
+             {open},Again 516,2031-06-10T09:00:00-04:00,30,3\n\
+             {trashed},Again 516,2031-06-10T09:00:00-04:00,30,3\n\
+             {new},New 516,2031-06-10T09:00:00-04:00,30,3\n"
+        );
+
+        let (status, body) = import_csv("?on_conflict=reject", &csv, "csv-reject-516").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let errors = body["errors"].as_array().unwrap();
+        let lines: Vec<(&Value, &Value)> = errors.iter().map(|e| (&e["line"], &e["code"])).collect();
+        assert_eq!(lines, [(&json!(2), &json!("id_taken")), (&json!(3), &json!("id_taken"))]);
+        assert!(!has(&new));
+
+        let (status, body) = import_csv("?on_conflict=skip", &csv, "csv-skip-516").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["imported"], 1);
+        assert_eq!(body["skipped"], json!([{ "line": 2, "id": open }, { "line": 3, "id": trashed }]));
+        assert!(has(&new));
+        let db = writer::snapshot();
+        assert_eq!(db.tasks.iter().find(|t| t.id.to_string() == open).map(|t| t.title.as_str()), Some("Open 516"));
+        assert!(db.trash.iter().any(|t| t.task.id.to_string() == trashed && t.task.title == "Trashed 516"));
+    }
+
+    #[tokio::test]
+    async fn a_large_file_is_saved_once() {
+        let mut csv = format!("{HEADER}\n");
+        for i in 0..500 {
+            csv += &format!(",Bulk {i} 516,2031-06-11T09:00:00-04:00,15,2\n");
+        }
+        let (status, body) = import_csv("", &csv, "csv-bulk-516").await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(body["imported"], 500);
+        assert_eq!(writer::saves_for("csv-bulk-516"), 1);
+
+        // Keep the shared test db small
+        writer::apply(|db| {
+            db.tasks.retain(|t| !t.title.starts_with("Bulk ") || !t.title.ends_with(" 516"));
+            db.reindex();
+            ((), true)
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_dry_run_db_import_leaves_the_db_alone() {
+        let draft = json!({ "title": "Kept 509", "due_at": "2031-06-07T12:00:00-04:00", "duration_min": 30, "priority": 3 });
+        let (_, created) = call("POST", "/api/tasks", Some(draft)).await;
+        let id = created["id"].as_str().unwrap().to_string();
+        let before = writer::snapshot();
+        let mut kept = before.tasks.iter().find(|t| t.id.to_string() == id).unwrap().clone();
+        kept.title = "Overwritten 509".to_string();
+        let new = test_task("New 509", clock::local("2031-06-08 09:00"));
+        let mut doc = Db::default();
+        doc.tasks = vec![kept, new.clone()];
+
+        for mode in ["replace", "merge"] {
+            let uri = format!("/api/import?mode={mode}&dry_run=true");
+            let (status, body) = call("POST", &uri, Some(serde_json::to_value(&doc).unwrap())).await;
+            assert_eq!(status, StatusCode::OK, "{body}");
+            assert_eq!((&body["dry_run"], &body["added"], &body["updated"]), (&json!(true), &json!(1), &json!(1)), "{mode}");
+        }
+
+        let after = writer::snapshot();
+        assert_eq!(after.tasks.iter().find(|t| t.id.to_string() == id).map(|t| t.title.as_str()), Some("Kept 509"));
+        assert!(after.get(new.id).is_none());
+    }
+}
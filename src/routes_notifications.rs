@@ -0,0 +1,135 @@
+// --------------------------------------------------
+// Handles API endpoints for daily plan notifications.
+//
+// Responsibilities:
+// - Get / update webhook notification settings
+// - Fire a test delivery (webhook or push) immediately
+// - List logged webhook deliveries and resend one
+// --------------------------------------------------
+
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::NaiveTime;
+use uuid::Uuid;
+
+use crate::api_json::ApiJson;
+use crate::models::NotificationSettings;
+use crate::notify;
+use crate::routes_tasks;
+use crate::writer;
+
+// -----------------------------
+// GET /api/notifications
+// Returns webhook notification settings
+// -----------------------------
+pub async fn get_notifications() -> impl IntoResponse {
+    let db = writer::snapshot();
+    Json(&db.notifications).into_response()
+}
+
+// First problem with notification settings (also used by settings import).
+pub fn check_notifications(n: &NotificationSettings) -> Result<(), &'static str> {
+    if NaiveTime::parse_from_str(&n.time, "%H:%M").is_err() {
+        return Err("time must be HH:MM");
+    }
+    let is_http = |u: &str| u.trim().starts_with("http://") || u.trim().starts_with("https://");
+    if n.enabled && !is_http(&n.webhook_url) {
+        return Err("webhook_url must be an http(s) URL");
+    }
+    if n.push.enabled && !is_http(&n.push.url) {
+        return Err("push.url must be an http(s) URL");
+    }
+    if n.push.lead_min < 0 {
+        return Err("push.lead_min must be >= 0");
+    }
+    Ok(())
+}
+
+// -----------------------------
+// PUT /api/notifications
+// Updates webhook notification settings
+// -----------------------------
+pub async fn put_notifications(ApiJson(n): ApiJson<NotificationSettings>) -> impl IntoResponse {
+    if let Err(msg) = check_notifications(&n) {
+        return (StatusCode::BAD_REQUEST, msg).into_response();
+    }
+
+    let saved = writer::apply(move |db| {
+        db.notifications = n;
+        (db.notifications.clone(), true)
+    });
+    match saved.await {
+        Ok(n) => Json(n).into_response(),
+        Err(e) => routes_tasks::write_error(&e),
+    }
+}
+
+// -----------------------------
+// POST /api/notifications/test
+// Posts today's plan to the webhook right now
+// -----------------------------
+pub async fn test_notification() -> impl IntoResponse {
+    let db = writer::snapshot();
+
+    if db.notifications.webhook_url.trim().is_empty() {
+        return (StatusCode::BAD_REQUEST, "webhook_url is not set").into_response();
+    }
+
+    match notify::send_plan(&db, &db.notifications, "test").await {
+        Ok(()) => Json(serde_json::json!({ "ok": true })).into_response(),
+        Err(e) => (StatusCode::BAD_GATEWAY, e).into_response(),
+    }
+}
+
+// -----------------------------
+// POST /api/notifications/test-push
+// Sends a test push through ntfy / Gotify right now
+// -----------------------------
+pub async fn test_push() -> impl IntoResponse {
+    let db = writer::snapshot();
+
+    if db.notifications.push.url.trim().is_empty() {
+        return (StatusCode::BAD_REQUEST, "push.url is not set").into_response();
+    }
+
+    let msg = "Deadline notifications are working.";
+    match notify::send_push(&db.notifications.push, "Project Scheduler", msg).await {
+        Ok(()) => Json(serde_json::json!({ "ok": true })).into_response(),
+        Err(e) => (StatusCode::BAD_GATEWAY, e).into_response(),
+    }
+}
+
+// -----------------------------
+// GET /api/notifications/deliveries
+// Logged webhook attempts, newest first
+// -----------------------------
+pub async fn get_deliveries() -> impl IntoResponse {
+    let db = writer::snapshot();
+    let deliveries: Vec<_> = db.deliveries.iter().rev().collect();
+    Json(deliveries).into_response()
+}
+
+// -----------------------------
+// POST /api/notifications/deliveries/:id/redeliver
+// Resends a logged payload unchanged to its original URL
+// 200 with the new attempt, 502 with it when delivery failed
+// -----------------------------
+pub async fn redeliver(Path(id): Path<String>) -> impl IntoResponse {
+    let id = match Uuid::parse_str(&id) {
+        Ok(u) => u,
+        Err(_) => return (StatusCode::BAD_REQUEST, "invalid id").into_response(),
+    };
+
+    let db = writer::snapshot();
+    let Some(original) = db.deliveries.iter().find(|d| d.id == id) else {
+        return (StatusCode::NOT_FOUND, "delivery not found").into_response();
+    };
+
+    let attempt = notify::redeliver(original).await;
+    let status = if attempt.error.is_none() { StatusCode::OK } else { StatusCode::BAD_GATEWAY };
+    (status, Json(attempt)).into_response()
+}
@@ -4,18 +4,28 @@
 // to the core scheduling logic implemented in logic.rs.
 // --------------------------------------------------
 
+use std::collections::HashSet;
+
 use axum::{
-    extract::Query,         // parse query parameter
-    http::StatusCode,       // return HTTP status codes
+    extract::{Path, Query}, // parse path and query parameters
+    http::{header, HeaderMap, StatusCode}, // return HTTP status codes
     response::IntoResponse, // allow returning different responses
     Json,                   // JSON response wrapper
 };
 use chrono::{DateTime, FixedOffset, NaiveDate};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
-use crate::logic; // scheduling logic
-use crate::models::{Db, DaySettings};
-use crate::store; // JSON database load/save utilities
+use crate::api_json::{self, ApiJson}; // JSON bodies, strict about unknown fields
+use crate::clock; // configured local time zone
+use crate::digest; // daily digest formatting
+use crate::i18n::{self, Lang}; // localized message text
+use crate::logic::{self, BudgetLedger, PlanItem, PlanWarning, PlanWindow, ScoreBreakdown, UnplannedItem, UrgencyBasis}; // scheduling logic
+use crate::models::{AuditOp, Db, DaySettings, DueBoundary, PlanLock, TaskStatus};
+use crate::routes_tasks::{self, TaskView}; // 422 validation responses
+use crate::sticky; // plans kept across requests (sticky=true)
+use crate::validation::{self, FieldError, Problem};
+use crate::writer; // single db writer
 
 
 // Query parameters for /plan/today
@@ -23,6 +33,30 @@ use crate::store; // JSON database load/save utilities
 pub struct PlanQuery {
     pub date: String,         // Target date in "YYYY-MM-DD" format
     pub available_min: i64,   // Total minutes user can work today
+    pub limit_unplanned: Option<usize>, // most unplanned entries returned (default 100)
+    #[serde(default)]
+    pub full: bool,           // return every unplanned entry
+    #[serde(default)]
+    pub sticky: bool,         // keep the earlier plan for today unless its inputs changed
+    #[serde(default = "default_true")]
+    pub clamp_to_date: bool,  // no item may end after midnight (logic::resolve_window)
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// Unplanned entries returned when limit_unplanned is not given.
+const DEFAULT_UNPLANNED_LIMIT: usize = 100;
+
+// The unplanned entries a /plan/today response carries, and how many
+// there were before the cut (all of them with full=true).
+fn cap_unplanned(mut unplanned: Vec<UnplannedItem>, q: &PlanQuery) -> (Vec<UnplannedItem>, usize) {
+    let total = unplanned.len();
+    if !q.full {
+        unplanned.truncate(q.limit_unplanned.unwrap_or(DEFAULT_UNPLANNED_LIMIT));
+    }
+    (unplanned, total)
 }
 
 
@@ -32,9 +66,88 @@ pub struct PlanResponse {
     pub date: String,                       // requested date
     pub now: String,                        // server time(now)
     pub available_min: i64, 
+    pub finish_at: Option<String>,          // end of the last scheduled item, null if none
+    pub slack_min: i64,                     // minutes between finish and day_end
+    pub slack_after_unplanned_min: i64,     // slack minus unplanned work (< 0: overcommitted)
+    pub completed_today_min: i64,           // minutes of tasks completed on the date
+    pub planned_min: i64,                   // minutes in the plan
+    pub utilization_pct: Option<i64>,       // (completed + planned) over the whole day window
     pub settings: DaySettings,              // day start/end setting
+    pub scorer: &'static str,               // Scorer that ranked the tasks, e.g. "default"
+    pub due_boundary: DueBoundary,          // date of a due_at at midnight: "strict" | "inclusive_next_midnight"
+    pub window: PlanWindowResponse,         // window the plan was actually built in
+    pub ledger: BudgetLedger,               // minutes of the window by use; they sum to gross_min
+    pub generated_at: String,               // when the plan was built (earlier for a sticky re-serve)
+    pub replanned: bool,                    // false when sticky=true re-served the earlier plan
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replan_trigger: Option<sticky::Trigger>, // sticky=true: why it was planned again
     pub plan: Vec<PlanItemResponse>,        // scheduled task
-    pub unplanned: Vec<UnplannedResponse>,  // tasks that do not fit
+    pub unplanned: Vec<UnplannedResponse>,  // tasks that do not fit, first limit_unplanned of them
+    pub unplanned_total: usize,             // all tasks that do not fit
+    pub excluded: Vec<ExcludedResponse>,    // left out on request (exclude_ids)
+    pub warnings: Vec<PlanWarningResponse>, // problems worth a banner; empty when clean
+}
+
+// Resolved placement window, with where each value came from
+// ("settings", "now", "fallback"; "request" or "window" for available_min)
+#[derive(Debug, Serialize)]
+pub struct PlanWindowResponse {
+    pub start: String,
+    pub end: String,
+    pub available_min: i64, // after clamping to end - start
+    pub now: String,
+    pub start_source: &'static str,
+    pub end_source: &'static str,
+    pub available_source: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_end: Option<String>, // latest item end under clamp_to_date
+}
+
+impl From<PlanWindow> for PlanWindowResponse {
+    fn from(w: PlanWindow) -> Self {
+        PlanWindowResponse {
+            start: w.start.to_rfc3339(),
+            end: w.end.to_rfc3339(),
+            available_min: w.available_min,
+            now: w.now.to_rfc3339(),
+            start_source: w.start_source.code(),
+            end_source: w.end_source.code(),
+            available_source: w.available_source.code(),
+            date_end: w.date_end.map(|t| t.to_rfc3339()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlanWarningResponse {
+    pub code: String,    // stable code, e.g. "deadline_missed"
+    pub message: String, // text in the Accept-Language language
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,
+}
+
+// Relevant task left out because it was listed in exclude_ids
+#[derive(Debug, Serialize)]
+pub struct ExcludedResponse {
+    pub task_id: String,
+    pub title: String,
+}
+
+// Task ids from every `exclude_ids` parameter, each a comma-separated list.
+fn exclude_ids(params: &[(String, String)]) -> Result<Vec<Uuid>, Vec<FieldError>> {
+    let mut ids = Vec::new();
+    let mut errors = Vec::new();
+    let values = params.iter().filter(|(k, _)| k == "exclude_ids").flat_map(|(_, v)| v.split(','));
+    for raw in values.map(str::trim).filter(|v| !v.is_empty()) {
+        match Uuid::parse_str(raw) {
+            Ok(id) => ids.push(id),
+            Err(_) => errors.push(FieldError::new(
+                "exclude_ids",
+                Problem::InvalidFormat { expected: "UUID" },
+            )),
+        }
+    }
+    if errors.is_empty() { Ok(ids) } else { Err(errors) }
 }
 
 // A single scheduled task in the final plan
@@ -46,33 +159,84 @@ pub struct PlanItemResponse {
     pub end: String,    // end time
     pub score_breakdown: ScoreBreakdownResponse,
     pub is_overdue: bool,
+    pub is_due_now: bool, // past due_at but inside overdue_grace_min
+    pub placed_by: &'static str, // "score_pass", "deadline_pass" (prefer_due_today) or "locked"
+    pub priority_label: String,  // settings.priority_labels
+    pub priority_color: String,
+}
+
+impl PlanItemResponse {
+    pub fn new(p: PlanItem, settings: &DaySettings) -> Self {
+        let meta = settings.priority_meta(p.score_breakdown.priority);
+        PlanItemResponse {
+            task_id: p.task_id,
+            title: p.title,
+            start: p.start.to_rfc3339(),
+            end: p.end.to_rfc3339(),
+            score_breakdown: p.score_breakdown.into(),
+            is_overdue: p.is_overdue,
+            is_due_now: p.is_due_now,
+            placed_by: p.placed_by.code(),
+            priority_label: meta.label,
+            priority_color: meta.color,
+        }
+    }
 }
 
 // Score breakdown used for ranking tasks
 #[derive(Debug, Serialize)]
 pub struct ScoreBreakdownResponse {
     pub urgency: i64,
+    pub urgency_mode: UrgencyBasis, // "rolling", "calendar" or "deadline_pressure"
     pub priority: i64,
     pub duration_score: i64,
     pub total: i64,
 }
 
-// Tasks that could not be scheduled
+impl From<ScoreBreakdown> for ScoreBreakdownResponse {
+    fn from(b: ScoreBreakdown) -> Self {
+        ScoreBreakdownResponse {
+            urgency: b.urgency,
+            urgency_mode: b.urgency_mode,
+            priority: b.priority,
+            duration_score: b.duration_score,
+            total: b.total,
+        }
+    }
+}
+
+// Tasks that could not be scheduled, with how close they came
 #[derive(Debug, Serialize)]
 pub struct UnplannedResponse {
     pub task_id: String,
-    pub reason: String,
+    pub title: String,
+    pub duration_min: i64,
+    pub score_breakdown: ScoreBreakdownResponse,
+    pub is_overdue: bool,
+    pub is_due_now: bool,
+    pub reason: String,  // stable code, e.g. "insufficient_time"
+    pub message: String, // reason text in the Accept-Language language
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minutes_short: Option<i64>, // insufficient_time: minutes beyond what was left
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub would_end_at: Option<String>, // insufficient_time: end if placed anyway
 }
 
-// --------------------------------------------------
-// Helper: returns "current time" with a fixed offset.
-// For now, CST (-06:00) is hardcoded for simplicity.
-// --------------------------------------------------
-fn now_fixed_offset() -> DateTime<FixedOffset> {
-    let local = chrono::Local::now();
-    let offset_seconds = local.offset().local_minus_utc();
-    let fixed = FixedOffset::east_opt(offset_seconds).unwrap();
-    local.with_timezone(&fixed)
+impl UnplannedResponse {
+    pub fn new(u: UnplannedItem, lang: Lang) -> Self {
+        UnplannedResponse {
+            task_id: u.task_id,
+            title: u.title,
+            duration_min: u.duration_min,
+            score_breakdown: u.score_breakdown.into(),
+            is_overdue: u.is_overdue,
+            is_due_now: u.is_due_now,
+            reason: u.reason.code().to_string(),
+            message: i18n::unplanned_reason(lang, u.reason).to_string(),
+            minutes_short: u.minutes_short,
+            would_end_at: u.would_end_at.map(|t| t.to_rfc3339()),
+        }
+    }
 }
 
 
@@ -82,69 +246,1274 @@ fn now_fixed_offset() -> DateTime<FixedOffset> {
 // High-level flow:
 // 1. Parse and validate query parameters
 // 2. Load DB from JSON
-// 3. Filter tasks relevant to the given date
+// 3. Filter tasks relevant to the given date, minus exclude_ids
+//    (repeatable and/or comma-separated; an invalid UUID is 422)
 // 4. Score and sort tasks by urgency/priority/duration
-// 5. Build today's plan within available time
-// 6. Return structured JSON for frontend rendering
+// 5. Build today's plan within available time; with clamp_to_date
+//    (default true) nothing ends after midnight unless the window is overnight
+// 6. Return structured JSON for frontend rendering; unplanned is cut to
+//    limit_unplanned (default 100) unless full=true, with the count in
+//    unplanned_total (warnings and slack follow the entries returned)
 // --------------------------------------------------
-pub async fn get_today_plan(Query(q): Query<PlanQuery>) -> impl IntoResponse {
+pub async fn get_today_plan(
+    headers: HeaderMap,
+    Query(q): Query<PlanQuery>,
+    Query(params): Query<Vec<(String, String)>>,
+) -> impl IntoResponse {
+    let lang = Lang::from_headers(&headers);
+
+    let exclude = match exclude_ids(&params) {
+        Ok(ids) => ids,
+        Err(errors) => return routes_tasks::validation_error(errors, lang),
+    };
+
     // Parse date string into NaiveDate
     let date = match NaiveDate::parse_from_str(&q.date, "%Y-%m-%d") {
         Ok(d) => d,
         Err(_) => return (StatusCode::BAD_REQUEST, "invalid date").into_response(),
     };
 
-    let now = now_fixed_offset();
+    let now = clock::now();
 
-    // Load database from data/db.json
-    let db: Db = match store::load_db() {
-        Ok(db) => db,
-        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "failed to load db").into_response(),
-    };
+    // Committed db, kept in memory by the writer
+    let db = writer::snapshot();
 
-    // Step 1: extract tasks relevant to this date
-    let relevant = logic::relevant_tasks(&db.tasks, date, now);
+    // Step 1: extract tasks relevant to this date, minus the excluded ones
+    let relevant = logic::relevant_tasks(&db.tasks, &db.settings, date, now);
+    let (excluded, relevant): (Vec<_>, Vec<_>) =
+        relevant.into_iter().partition(|t| exclude.contains(&t.id));
 
-    // Step 2: score tasks and sort by total score (descending)
-    let scored_sorted = logic::score_and_sort(relevant, now);
+    // sticky=true: reuse the earlier plan while its inputs are unchanged
+    let sticky_key = q.sticky.then(|| {
+        let mut exclude = exclude.clone();
+        exclude.sort_unstable();
+        sticky::Key { date, available_min: q.available_min, exclude, clamp_to_date: q.clamp_to_date }
+    });
+    let snapshot = sticky::Snapshot::of(&relevant, &db.settings);
+    let cached = sticky_key.as_ref().and_then(sticky::get);
+    let trigger = match &cached {
+        Some(entry) => sticky::diff(&entry.snapshot, &snapshot),
+        None => Some(sticky::Trigger::FirstPlan),
+    };
 
-    // Step 3: build today's schedule within available minutes
-    let (plan, unplanned) =
-        logic::build_today_plan(scored_sorted, date, now, &db.settings, q.available_min);
+    let (plan, unplanned, window, ledger, generated_at) = match cached {
+        Some(entry) if trigger.is_none() => {
+            // Same inputs: drop items already over, refresh overdue flags
+            let mut plan = entry.plan;
+            plan.retain(|p| p.end > now);
+            for p in &mut plan {
+                if let Some(t) = relevant.iter().find(|t| t.id.to_string() == p.task_id) {
+                    p.is_overdue = logic::is_overdue(t.due_at, now, &db.settings);
+                    p.is_due_now = logic::is_due_now(t.due_at, now, &db.settings);
+                }
+            }
+            (plan, entry.unplanned, entry.window, entry.ledger, entry.generated_at)
+        }
+        _ => {
+            // Step 2: score tasks and sort by total score (descending)
+            let scorer = logic::scorer_for(db.settings.scorer);
+            let scored_sorted = logic::score_and_sort(relevant, now, &db.settings, scorer);
+
+            // Step 3: build today's schedule within available minutes
+            let (plan, unplanned, window) =
+                logic::build_today_plan(scored_sorted, date, now, &db.settings, q.available_min, q.clamp_to_date);
+            let ledger = logic::budget_ledger(&plan, &window);
+            if let Some(key) = sticky_key.clone() {
+                let entry = sticky::Entry {
+                    snapshot,
+                    generated_at: now,
+                    plan: plan.clone(),
+                    unplanned: unplanned.clone(),
+                    window,
+                    ledger,
+                };
+                sticky::put(key, entry, logic::plan_date(&db.settings, now));
+            }
+            (plan, unplanned, window, ledger, now)
+        }
+    };
+    let (unplanned, unplanned_total) = cap_unplanned(unplanned, &q);
+    let summary = logic::summarize_plan(&plan, &unplanned, &db.tasks, date, now, &db.settings);
 
     // Convert internal structs into API response format
-    let plan_resp: Vec<PlanItemResponse> = plan
+    let plan_resp: Vec<PlanItemResponse> =
+        plan.into_iter().map(|p| PlanItemResponse::new(p, &db.settings)).collect();
+    let unplanned_resp: Vec<UnplannedResponse> =
+        unplanned.into_iter().map(|u| UnplannedResponse::new(u, lang)).collect();
+
+    Json(PlanResponse {
+        date: q.date,
+        now: now.to_rfc3339(),
+        available_min: q.available_min,
+        finish_at: summary.finish_at.map(|t| t.to_rfc3339()),
+        slack_min: summary.slack_min,
+        slack_after_unplanned_min: summary.slack_after_unplanned_min,
+        completed_today_min: summary.completed_today_min,
+        planned_min: summary.planned_min,
+        utilization_pct: summary.utilization_pct,
+        window: window.into(),
+        ledger,
+        generated_at: generated_at.to_rfc3339(),
+        replanned: sticky_key.is_none() || trigger.is_some(),
+        replan_trigger: if q.sticky { trigger } else { None },
+        warnings: summary
+            .warnings
+            .iter()
+            .map(|w| PlanWarningResponse {
+                code: w.code().to_string(),
+                message: i18n::plan_warning(lang, w),
+                task_id: match w {
+                    PlanWarning::DeadlineMissed { task_id } | PlanWarning::LockConflict { task_id } => {
+                        Some(task_id.clone())
+                    }
+                    _ => None,
+                },
+            })
+            .collect(),
+        scorer: logic::scorer_for(db.settings.scorer).name(),
+        due_boundary: db.settings.due_boundary,
+        settings: db.settings.without_extra(),
+        plan: plan_resp,
+        unplanned: unplanned_resp,
+        unplanned_total,
+        excluded: excluded
+            .into_iter()
+            .map(|t| ExcludedResponse { task_id: t.id.to_string(), title: t.title.clone() })
+            .collect(),
+    })
+    .into_response()
+}
+
+// Query parameters for /plan/week
+#[derive(Debug, Deserialize)]
+pub struct WeekQuery {
+    pub start: Option<String>, // "YYYY-MM-DD"; defaults to the week containing today
+}
+
+#[derive(Debug, Serialize)]
+pub struct WeekResponse {
+    pub start: String, // first day, "YYYY-MM-DD"
+    pub end: String,   // last day, inclusive
+    pub now: String,
+    pub days: Vec<WeekDayResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WeekDayResponse {
+    pub date: String,
+    pub plan: Vec<PlanItemResponse>,
+}
+
+// --------------------------------------------------
+// GET /api/plan/week
+//
+// Seven consecutive day plans (logic::plan_days), each using
+// the full day window. Without `start` the week begins on
+// settings.week_start in the week containing today; an
+// explicit start is used as given.
+// --------------------------------------------------
+pub async fn get_week_plan(Query(q): Query<WeekQuery>) -> impl IntoResponse {
+    let db = writer::snapshot();
+    let now = clock::now();
+
+    let start = match q.start.as_deref() {
+        Some(s) => match NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            Ok(d) => d,
+            Err(_) => return (StatusCode::BAD_REQUEST, "invalid start").into_response(),
+        },
+        None => logic::week_bounds(logic::plan_date(&db.settings, now), db.settings.week_start).0,
+    };
+
+    let days: Vec<WeekDayResponse> = logic::plan_days(&db.tasks, &db.settings, start, 7, now)
         .into_iter()
-        .map(|p| PlanItemResponse {
-            task_id: p.task_id,
-            title: p.title,
-            start: p.start.to_rfc3339(),
-            end: p.end.to_rfc3339(),
-            score_breakdown: ScoreBreakdownResponse {
-                urgency: p.score_breakdown.urgency,
-                priority: p.score_breakdown.priority,
-                duration_score: p.score_breakdown.duration_score,
-                total: p.score_breakdown.total,
-            },
-            is_overdue: p.is_overdue,
+        .map(|(date, plan)| WeekDayResponse {
+            date: date.to_string(),
+            plan: plan.into_iter().map(|p| PlanItemResponse::new(p, &db.settings)).collect(),
         })
         .collect();
 
-    let unplanned_resp: Vec<UnplannedResponse> = unplanned
+    Json(WeekResponse {
+        start: start.to_string(),
+        end: (start + chrono::Duration::days(6)).to_string(),
+        now: now.to_rfc3339(),
+        days,
+    })
+    .into_response()
+}
+
+// Query parameters for /plan/simulate
+#[derive(Debug, Deserialize)]
+pub struct SimulateQuery {
+    pub date: String, // "YYYY-MM-DD"
+    #[serde(default = "default_sim_min")]
+    pub min: i64,
+    #[serde(default = "default_sim_max")]
+    pub max: i64,
+    #[serde(default = "default_sim_step")]
+    pub step: i64,
+}
+
+fn default_sim_min() -> i64 {
+    60
+}
+
+fn default_sim_max() -> i64 {
+    600
+}
+
+fn default_sim_step() -> i64 {
+    60
+}
+
+// Most budgets one simulation may evaluate.
+const MAX_SIM_POINTS: i64 = 1000;
+
+#[derive(Debug, Serialize)]
+pub struct SimulatePointResponse {
+    pub available_min: i64,
+    pub scheduled_count: usize,
+    pub scheduled_min: i64,
+    pub unplanned_count: usize,
+}
+
+// --------------------------------------------------
+// GET /api/plan/simulate?date=&min=60&max=600&step=60
+//
+// Plan size for each available_min in min, min+step, ..., max.
+// Tasks are filtered and scored once; only placement repeats, and
+// each plan keeps what the smaller budget scheduled
+// (logic::simulate_budgets).
+// --------------------------------------------------
+pub async fn simulate_plan(Query(q): Query<SimulateQuery>) -> impl IntoResponse {
+    let date = match NaiveDate::parse_from_str(&q.date, "%Y-%m-%d") {
+        Ok(d) => d,
+        Err(_) => return (StatusCode::BAD_REQUEST, "invalid date").into_response(),
+    };
+    if q.min < 1 || q.max < q.min || q.step < 1 {
+        return (StatusCode::BAD_REQUEST, "need 1 <= min <= max and step >= 1").into_response();
+    }
+    if (q.max - q.min) / q.step >= MAX_SIM_POINTS {
+        let msg = format!("at most {MAX_SIM_POINTS} budgets per simulation");
+        return (StatusCode::BAD_REQUEST, msg).into_response();
+    }
+
+    let db = writer::snapshot();
+    let now = clock::now();
+
+    let relevant = logic::relevant_tasks(&db.tasks, &db.settings, date, now);
+    let scorer = logic::scorer_for(db.settings.scorer);
+    let scored_sorted = logic::score_and_sort(relevant, now, &db.settings, scorer);
+    let budgets = (q.min..=q.max).step_by(q.step as usize);
+
+    let points: Vec<SimulatePointResponse> =
+        logic::simulate_budgets(&scored_sorted, date, now, &db.settings, budgets)
+            .into_iter()
+            .map(|r| SimulatePointResponse {
+                available_min: r.available_min,
+                scheduled_count: r.scheduled_count,
+                scheduled_min: r.scheduled_min,
+                unplanned_count: r.unplanned_count,
+            })
+            .collect();
+
+    Json(points).into_response()
+}
+
+// One configuration to compare: settings fields to override, e.g.
+// {"name": "calendar", "settings": {"urgency_mode": "calendar"}}
+#[derive(Debug, Deserialize)]
+pub struct CompareProfile {
+    pub name: String,
+    #[serde(default)]
+    pub settings: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompareInput {
+    pub date: String,             // "YYYY-MM-DD"
+    pub available_min: i64,
+    pub profiles: Vec<CompareProfile>,
+}
+
+// Most profiles one comparison may run.
+const MAX_COMPARE_PROFILES: usize = 10;
+
+#[derive(Debug, Serialize)]
+pub struct CompareProfileResponse {
+    pub name: String,
+    pub scheduled_count: usize,
+    pub scheduled_min: i64,
+    pub unplanned_count: usize,
+    pub finish_at: Option<String>,
+    pub window: PlanWindowResponse,
+    pub plan: Vec<PlanItemResponse>,
+}
+
+// A task scheduled under some profiles but not others
+#[derive(Debug, Serialize)]
+pub struct CompareDiffResponse {
+    pub task_id: String,
+    pub title: String,
+    pub scheduled_in: Vec<String>,
+    pub unscheduled_in: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompareResponse {
+    pub date: String,
+    pub now: String,
+    pub profiles: Vec<CompareProfileResponse>,
+    pub diff: Vec<CompareDiffResponse>,
+}
+
+// Stored settings with a profile's fields laid over them.
+fn profile_settings(
+    base: &DaySettings,
+    overrides: &serde_json::Map<String, serde_json::Value>,
+) -> Result<DaySettings, String> {
+    let mut merged = serde_json::to_value(base).map_err(|e| e.to_string())?;
+    if let Some(obj) = merged.as_object_mut() {
+        obj.extend(overrides.clone());
+    }
+    serde_json::from_value(merged).map_err(|e| e.to_string())
+}
+
+// Every task scheduled by at least one profile but not all, in
+// first-seen order.
+fn profile_diff(plans: &[(&str, &[PlanItem])]) -> Vec<CompareDiffResponse> {
+    let mut diff: Vec<CompareDiffResponse> = Vec::new();
+    let mut seen: HashSet<&str> = HashSet::new();
+    for (_, plan) in plans {
+        for item in plan.iter() {
+            if !seen.insert(&item.task_id) {
+                continue;
+            }
+            let (scheduled_in, unscheduled_in): (Vec<_>, Vec<_>) = plans
+                .iter()
+                .map(|(name, plan)| (name.to_string(), plan.iter().any(|p| p.task_id == item.task_id)))
+                .partition(|(_, scheduled)| *scheduled);
+            if unscheduled_in.is_empty() {
+                continue;
+            }
+            diff.push(CompareDiffResponse {
+                task_id: item.task_id.clone(),
+                title: item.title.clone(),
+                scheduled_in: scheduled_in.into_iter().map(|(name, _)| name).collect(),
+                unscheduled_in: unscheduled_in.into_iter().map(|(name, _)| name).collect(),
+            });
+        }
+    }
+    diff
+}
+
+// --------------------------------------------------
+// POST /api/plan/compare
+//
+// Runs the planning pipeline once per profile, each with the
+// stored settings plus that profile's overrides (nothing is
+// saved), and lists tasks the profiles disagree on.
+// --------------------------------------------------
+pub async fn compare_plans(headers: HeaderMap, ApiJson(input): ApiJson<CompareInput>) -> impl IntoResponse {
+    let lang = Lang::from_headers(&headers);
+
+    let date = match NaiveDate::parse_from_str(&input.date, "%Y-%m-%d") {
+        Ok(d) => d,
+        Err(_) => return (StatusCode::BAD_REQUEST, "invalid date").into_response(),
+    };
+    let n = input.profiles.len();
+    if !(2..=MAX_COMPARE_PROFILES).contains(&n) {
+        let msg = format!("need 2 to {MAX_COMPARE_PROFILES} profiles");
+        return (StatusCode::BAD_REQUEST, msg).into_response();
+    }
+    let mut names: Vec<&str> = input.profiles.iter().map(|p| p.name.as_str()).collect();
+    names.sort_unstable();
+    names.dedup();
+    if names.len() != n {
+        return (StatusCode::BAD_REQUEST, "profile names must be unique").into_response();
+    }
+
+    let db = writer::snapshot();
+    let now = clock::now();
+
+    let mut runs = Vec::with_capacity(n);
+    for (i, p) in input.profiles.iter().enumerate() {
+        let settings = match profile_settings(&db.settings, &p.settings) {
+            Ok(s) => s,
+            Err(e) => {
+                let msg = format!("profile \"{}\": {e}", p.name);
+                return (StatusCode::BAD_REQUEST, msg).into_response();
+            }
+        };
+        // Overrides the settings model does not know end up in `extra`
+        let unknown = p.settings.keys().filter(|k| settings.extra.contains_key(*k));
+        if let Some(resp) = api_json::unknown_extra(&format!("profiles.{i}.settings"), unknown, lang) {
+            return resp;
+        }
+        if let Err(errors) = validation::validate_settings(&settings) {
+            return routes_tasks::validation_error(errors, lang);
+        }
+        let (plan, unplanned, window) =
+            logic::plan_for_date(&db.tasks, &settings, date, now, input.available_min, true);
+        runs.push((p.name.clone(), plan, unplanned, window));
+    }
+
+    let plans: Vec<(&str, &[PlanItem])> = runs.iter().map(|(name, plan, _, _)| (name.as_str(), plan.as_slice())).collect();
+    let diff = profile_diff(&plans);
+
+    let profiles = runs
         .into_iter()
-        .map(|u| UnplannedResponse {
-            task_id: u.task_id,
-            reason: u.reason,
+        .map(|(name, plan, unplanned, window)| CompareProfileResponse {
+            name,
+            scheduled_count: plan.len(),
+            scheduled_min: plan.iter().map(|p| (p.end - p.start).num_minutes()).sum(),
+            unplanned_count: unplanned.len(),
+            finish_at: plan.last().map(|p| p.end.to_rfc3339()),
+            window: window.into(),
+            plan: plan.into_iter().map(|p| PlanItemResponse::new(p, &db.settings)).collect(),
         })
         .collect();
 
-    Json(PlanResponse {
+    Json(CompareResponse {
+        date: input.date,
+        now: now.to_rfc3339(),
+        profiles,
+        diff,
+    })
+    .into_response()
+}
+
+// Query parameters for /plan/scores
+#[derive(Debug, Deserialize)]
+pub struct ScoresQuery {
+    pub date: String,           // "YYYY-MM-DD"
+    pub format: Option<String>, // "json" or "csv"; default from Accept, else json
+}
+
+// One relevant task's score components. Field order is the CSV
+// column order. The scorers have no modifier components (aging,
+// hard deadline), so total = urgency + priority + duration_score.
+#[derive(Debug, Serialize)]
+pub struct ScoreRow {
+    pub id: String,
+    pub title: String,
+    pub due_at: String,
+    pub duration_min: i64,
+    pub priority: i64,
+    pub urgency: i64,
+    pub urgency_mode: UrgencyBasis,
+    pub duration_score: i64,
+    pub total: i64,
+    pub planned: bool, // in the plan for the default budget (the whole day window)
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScoresResponse {
+    pub date: String,
+    pub now: String,
+    pub scorer: &'static str,
+    pub available_min: i64, // budget of the trial plan
+    pub tasks: Vec<ScoreRow>,
+}
+
+// Rows as CSV with a header line.
+fn scores_csv(rows: &[ScoreRow]) -> Result<String, String> {
+    let mut w = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        w.serialize(row).map_err(|e| e.to_string())?;
+    }
+    let bytes = w.into_inner().map_err(|e| e.to_string())?;
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+// --------------------------------------------------
+// GET /api/plan/scores?date=&format=
+//
+// Score components of every relevant task for the date, in
+// score order (logic::score_and_sort), for tuning the scoring.
+// `planned` comes from a trial plan with the whole day window
+// as budget, built from the same scores, so totals match the
+// breakdowns of /plan/today. CSV with ?format=csv or an
+// Accept header naming text/csv.
+// --------------------------------------------------
+pub async fn get_scores(headers: HeaderMap, Query(q): Query<ScoresQuery>) -> impl IntoResponse {
+    let date = match NaiveDate::parse_from_str(&q.date, "%Y-%m-%d") {
+        Ok(d) => d,
+        Err(_) => return (StatusCode::BAD_REQUEST, "invalid date").into_response(),
+    };
+    let wants_csv = match q.format.as_deref() {
+        Some("csv") => true,
+        Some("json") => false,
+        Some(_) => return (StatusCode::BAD_REQUEST, "format must be json or csv").into_response(),
+        None => headers
+            .get(header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.contains("text/csv")),
+    };
+
+    let now = clock::now();
+
+    let db = writer::snapshot();
+
+    let relevant = logic::relevant_tasks(&db.tasks, &db.settings, date, now);
+    let scorer = logic::scorer_for(db.settings.scorer);
+    let scored_sorted = logic::score_and_sort(relevant, now, &db.settings, scorer);
+    let available_min = logic::day_window_min(&db.settings);
+    let (plan, _, _) =
+        logic::build_today_plan(scored_sorted.clone(), date, now, &db.settings, available_min, true);
+
+    let rows: Vec<ScoreRow> = scored_sorted
+        .into_iter()
+        .map(|st| {
+            let id = st.task.id.to_string();
+            ScoreRow {
+                planned: plan.iter().any(|p| p.task_id == id),
+                id,
+                title: st.task.title.clone(),
+                due_at: st.task.due_at.to_rfc3339(),
+                duration_min: st.task.duration_min,
+                priority: st.score.priority,
+                urgency: st.score.urgency,
+                urgency_mode: st.score.urgency_mode,
+                duration_score: st.score.duration_score,
+                total: st.score.total,
+            }
+        })
+        .collect();
+
+    if !wants_csv {
+        return Json(ScoresResponse {
+            date: q.date,
+            now: now.to_rfc3339(),
+            scorer: scorer.name(),
+            available_min,
+            tasks: rows,
+        })
+        .into_response();
+    }
+    match scores_csv(&rows) {
+        Ok(text) => ([(header::CONTENT_TYPE, "text/csv; charset=utf-8")], text).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+// Query parameters for /plan/diff
+#[derive(Debug, Deserialize)]
+pub struct DiffQuery {
+    pub left: String,               // plan side, see PlanSide::parse
+    pub right: String,
+    pub available_min: Option<i64>, // defaults to the whole day window
+}
+
+// Which plan one side of a diff is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlanSide {
+    Current(NaiveDate),   // "YYYY-MM-DD": built now
+    Accepted(NaiveDate),  // "accepted:YYYY-MM-DD": kept by /plan/today?sticky=true
+    Simulated(NaiveDate), // "simulated:YYYY-MM-DD": built as of the date's day_start
+}
+
+impl PlanSide {
+    fn parse(raw: &str) -> Option<PlanSide> {
+        let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok();
+        match raw.split_once(':') {
+            Some(("accepted", d)) => date(d).map(PlanSide::Accepted),
+            Some(("simulated", d)) => date(d).map(PlanSide::Simulated),
+            Some(_) => None,
+            None => date(raw).map(PlanSide::Current),
+        }
+    }
+
+    // The plan's items, or None for an accepted plan that is not kept.
+    fn plan(self, db: &Db, now: DateTime<FixedOffset>, available_min: i64) -> Option<Vec<PlanItem>> {
+        match self {
+            PlanSide::Current(date) => {
+                Some(logic::plan_for_date(&db.tasks, &db.settings, date, now, available_min, true).0)
+            }
+            PlanSide::Accepted(date) => {
+                let key = sticky::Key { date, available_min, exclude: Vec::new(), clamp_to_date: true };
+                sticky::get(&key).map(|entry| entry.plan)
+            }
+            PlanSide::Simulated(date) => {
+                let at = logic::day_window(date, &db.settings).map_or(now, |(start, _)| start);
+                Some(logic::plan_for_date(&db.tasks, &db.settings, date, at, available_min, true).0)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpanResponse {
+    pub start: String,
+    pub end: String,
+    pub minutes: i64,
+}
+
+impl From<logic::Span> for SpanResponse {
+    fn from(s: logic::Span) -> Self {
+        SpanResponse { start: s.start.to_rfc3339(), end: s.end.to_rfc3339(), minutes: s.minutes() }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ItemDiffResponse {
+    pub task_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub left_task_id: Option<String>, // the other task it was matched to by title
+    pub title: String,
+    pub left: Option<SpanResponse>,
+    pub right: Option<SpanResponse>,
+    pub uncertain: bool, // matched by title only
+}
+
+impl From<logic::ItemDiff> for ItemDiffResponse {
+    fn from(d: logic::ItemDiff) -> Self {
+        ItemDiffResponse {
+            task_id: d.task_id,
+            left_task_id: d.left_task_id,
+            title: d.title,
+            left: d.left.map(Into::into),
+            right: d.right.map(Into::into),
+            uncertain: d.uncertain,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiffSummaryResponse {
+    pub left_count: usize,
+    pub right_count: usize,
+    pub count_delta: i64,
+    pub left_min: i64,
+    pub right_min: i64,
+    pub minutes_delta: i64,
+    pub unchanged: usize,
+    pub finish_delta_min: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiffResponse {
+    pub left: String,
+    pub right: String,
+    pub now: String,
+    pub available_min: i64,
+    pub added: Vec<ItemDiffResponse>,
+    pub removed: Vec<ItemDiffResponse>,
+    pub moved: Vec<ItemDiffResponse>,
+    pub resized: Vec<ItemDiffResponse>,
+    pub summary: DiffSummaryResponse,
+}
+
+// --------------------------------------------------
+// GET /api/plan/diff?left=&right=&available_min=
+//
+// How one plan differs from another (logic::diff_plans). Each
+// side is "YYYY-MM-DD" (planned now), "accepted:YYYY-MM-DD"
+// (the plan /plan/today?sticky=true kept for that date and
+// available_min, without exclusions) or "simulated:YYYY-MM-DD"
+// (planned as of that date's day_start). 404 when no accepted
+// plan is kept.
+// --------------------------------------------------
+pub async fn get_plan_diff(Query(q): Query<DiffQuery>) -> impl IntoResponse {
+    let (Some(left), Some(right)) = (PlanSide::parse(&q.left), PlanSide::parse(&q.right)) else {
+        let msg = "left and right must be YYYY-MM-DD, accepted:YYYY-MM-DD or simulated:YYYY-MM-DD";
+        return (StatusCode::BAD_REQUEST, msg).into_response();
+    };
+
+    let now = clock::now();
+
+    let db = writer::snapshot();
+
+    let available_min = q
+        .available_min
+        .unwrap_or_else(|| logic::day_window_min(&db.settings));
+    let (Some(left_plan), Some(right_plan)) =
+        (left.plan(&db, now, available_min), right.plan(&db, now, available_min))
+    else {
+        return (StatusCode::NOT_FOUND, "no accepted plan kept for that date and available_min").into_response();
+    };
+
+    let d = logic::diff_plans(&left_plan, &right_plan);
+    let list = |items: Vec<logic::ItemDiff>| items.into_iter().map(Into::into).collect();
+    let sm = d.summary;
+    Json(DiffResponse {
+        left: q.left,
+        right: q.right,
+        now: now.to_rfc3339(),
+        available_min,
+        added: list(d.added),
+        removed: list(d.removed),
+        moved: list(d.moved),
+        resized: list(d.resized),
+        summary: DiffSummaryResponse {
+            left_count: sm.left_count,
+            right_count: sm.right_count,
+            count_delta: sm.right_count as i64 - sm.left_count as i64,
+            left_min: sm.left_min,
+            right_min: sm.right_min,
+            minutes_delta: sm.right_min - sm.left_min,
+            unchanged: sm.unchanged,
+            finish_delta_min: sm.finish_delta_min,
+        },
+    })
+    .into_response()
+}
+
+// Query parameters for /digest
+#[derive(Debug, Deserialize)]
+pub struct DigestQuery {
+    pub date: String,               // Target date in "YYYY-MM-DD" format
+    pub available_min: Option<i64>, // defaults to the whole day window
+}
+
+// --------------------------------------------------
+// GET /api/digest
+//
+// Daily summary for piping into mail(1):
+// overdue tasks, the plan, unplanned items, tomorrow's due tasks.
+// Returns text/plain unless the client accepts application/json.
+// --------------------------------------------------
+pub async fn get_digest(headers: HeaderMap, Query(q): Query<DigestQuery>) -> impl IntoResponse {
+    let date = match NaiveDate::parse_from_str(&q.date, "%Y-%m-%d") {
+        Ok(d) => d,
+        Err(_) => return (StatusCode::BAD_REQUEST, "invalid date").into_response(),
+    };
+
+    let now = clock::now();
+
+    let db = writer::snapshot();
+
+    let available_min = q
+        .available_min
+        .unwrap_or_else(|| logic::day_window_min(&db.settings));
+    let (plan, unplanned, _) =
+        logic::plan_for_date(&db.tasks, &db.settings, date, now, available_min, true);
+    let d = digest::build_digest(&db.tasks, &db.settings, date, now, &plan, &unplanned);
+
+    let wants_json = headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("application/json"));
+
+    if wants_json {
+        Json(d).into_response()
+    } else {
+        (
+            [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+            digest::format_text(&d, Lang::from_headers(&headers), db.settings.time_format),
+        )
+            .into_response()
+    }
+}
+
+// Query parameters for GET /api/plan/now
+#[derive(Debug, Deserialize)]
+pub struct NowQuery {
+    pub date: String,               // Target date in "YYYY-MM-DD" format
+    pub available_min: Option<i64>, // defaults to the whole day window
+}
+
+// Item running now, with its progress
+#[derive(Debug, Serialize)]
+pub struct ActiveResponse {
+    pub item: PlanItemResponse,
+    pub elapsed_min: i64,
+    pub remaining_min: i64,
+    pub done: bool, // its task is already completed
+}
+
+// Item coming up next
+#[derive(Debug, Serialize)]
+pub struct NextResponse {
+    pub item: PlanItemResponse,
+    pub starts_in_min: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NowResponse {
+    pub date: String,
+    pub now: String,
+    pub source: &'static str, // "sticky" (kept plan) or "fresh" (built now)
+    pub generated_at: String, // when the plan was built
+    pub active: Option<ActiveResponse>, // null between items
+    pub next: Option<NextResponse>,
+    pub drift_min: i64,       // > 0 ahead of the plan, < 0 behind
+    pub pace: &'static str,   // "ahead" | "on_track" | "behind"
+}
+
+// --------------------------------------------------
+// GET /api/plan/now
+//
+// Position of this instant in the day's plan, for a live
+// "now line". Uses the plan kept by /plan/today?sticky=true
+// for the same date and available_min (no exclusions) when
+// there is one, otherwise builds a fresh plan. Done tasks are
+// looked up in the db, so completing a block moves the pace
+// without replanning (logic::plan_position).
+// --------------------------------------------------
+pub async fn get_plan_now(Query(q): Query<NowQuery>) -> impl IntoResponse {
+    let date = match NaiveDate::parse_from_str(&q.date, "%Y-%m-%d") {
+        Ok(d) => d,
+        Err(_) => return (StatusCode::BAD_REQUEST, "invalid date").into_response(),
+    };
+
+    let now = clock::now();
+
+    let db = writer::snapshot();
+
+    let available_min = q
+        .available_min
+        .unwrap_or_else(|| logic::day_window_min(&db.settings));
+    let key = sticky::Key { date, available_min, exclude: Vec::new(), clamp_to_date: true };
+    let (plan, source, generated_at) = match sticky::get(&key) {
+        Some(entry) => (entry.plan, "sticky", entry.generated_at),
+        None => {
+            let (plan, _, _) = logic::plan_for_date(&db.tasks, &db.settings, date, now, available_min, true);
+            (plan, "fresh", now)
+        }
+    };
+
+    let is_done = |id: &str| {
+        db.tasks
+            .iter()
+            .chain(&db.archived)
+            .any(|t| t.id.to_string() == id && t.status == TaskStatus::Done)
+    };
+    let pos = logic::plan_position(&plan, now, is_done);
+
+    let item = |i: usize| PlanItemResponse::new(plan[i].clone(), &db.settings);
+    let active = pos.active.map(|a| ActiveResponse {
+        item: item(a.index),
+        elapsed_min: a.elapsed_min,
+        remaining_min: a.remaining_min,
+        done: is_done(&plan[a.index].task_id),
+    });
+    let next = pos.next.map(|n| NextResponse { item: item(n.index), starts_in_min: n.starts_in_min });
+
+    Json(NowResponse {
         date: q.date,
         now: now.to_rfc3339(),
-        available_min: q.available_min,
-        settings: db.settings,
-        plan: plan_resp,
-        unplanned: unplanned_resp,
+        source,
+        generated_at: generated_at.to_rfc3339(),
+        active,
+        next,
+        drift_min: pos.drift_min,
+        pace: pos.pace.code(),
     })
     .into_response()
 }
+
+// Body of POST /api/plan/items/:task_id/lock: the accepted item's times
+#[derive(Debug, Deserialize)]
+pub struct LockInput {
+    pub start: String, // RFC3339
+    pub end: String,   // RFC3339, after start
+}
+
+// Parse and check a LockInput.
+fn plan_lock(input: &LockInput) -> Result<PlanLock, Vec<FieldError>> {
+    let parse = |field, raw: &str| {
+        DateTime::parse_from_rfc3339(raw)
+            .map_err(|_| FieldError::new(field, Problem::InvalidFormat { expected: "RFC3339" }))
+    };
+    match (parse("start", &input.start), parse("end", &input.end)) {
+        (Ok(start), Ok(end)) if end > start => Ok(PlanLock { start, end }),
+        (Ok(_), Ok(_)) => Err(vec![FieldError::new("end", Problem::InvalidFormat { expected: "a time after start" })]),
+        (start, end) => Err(start.err().into_iter().chain(end.err()).collect()),
+    }
+}
+
+// Set or clear a task's lock in the writer; the task as returned by the API.
+async fn set_lock(id: Uuid, lock: Option<PlanLock>) -> axum::response::Response {
+    let result = writer::apply_audited(move |db| {
+        let labels = db.settings.priority_labels.clone();
+        let Some(t) = db.get_mut(id) else {
+            return (Err((StatusCode::NOT_FOUND, "task not found")), false, Vec::new());
+        };
+        if lock.is_some() && t.status == TaskStatus::Done {
+            return (Err((StatusCode::CONFLICT, "task is done")), false, Vec::new());
+        }
+        if t.locked == lock {
+            return (Ok(TaskView::new(t.clone(), labels.as_ref())), false, Vec::new());
+        }
+        t.locked = lock;
+        t.rev += 1;
+        t.updated_at = clock::now();
+        (Ok(TaskView::new(t.clone(), labels.as_ref())), true, vec![(AuditOp::Update, Some(id))])
+    })
+    .await;
+    match result {
+        Ok(Ok(view)) => Json(view).into_response(),
+        Ok(Err(refused)) => refused.into_response(),
+        Err(e) => routes_tasks::write_error(&e),
+    }
+}
+
+// --------------------------------------------------
+// POST /api/plan/items/:task_id/lock
+//
+// Pins a plan item to start..end, normally the times the plan
+// gave it. Plans for that date keep it there and schedule the
+// other tasks around it (logic::build_today_plan); completing
+// or deleting the task releases it. Locking again moves it.
+// 404 unknown task, 409 task already done, 422 bad times.
+// --------------------------------------------------
+pub async fn lock_item(
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    ApiJson(input): ApiJson<LockInput>,
+) -> impl IntoResponse {
+    let id = match Uuid::parse_str(&id) {
+        Ok(u) => u,
+        Err(_) => return (StatusCode::BAD_REQUEST, "invalid id").into_response(),
+    };
+    let lock = match plan_lock(&input) {
+        Ok(lock) => lock,
+        Err(errors) => return routes_tasks::validation_error(errors, Lang::from_headers(&headers)),
+    };
+    set_lock(id, Some(lock)).await
+}
+
+// --------------------------------------------------
+// POST /api/plan/items/:task_id/unlock
+//
+// Releases a lock, so replans may move the item again.
+// Unlocking a task that is not locked changes nothing.
+// --------------------------------------------------
+pub async fn unlock_item(Path(id): Path<String>) -> impl IntoResponse {
+    let id = match Uuid::parse_str(&id) {
+        Ok(u) => u,
+        Err(_) => return (StatusCode::BAD_REQUEST, "invalid id").into_response(),
+    };
+    set_lock(id, None).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::UnplannedReason;
+    use crate::models::UrgencyMode;
+    use chrono::{Datelike, Weekday};
+    use axum::{body::Body, http::Request};
+    use serde_json::{json, Value};
+
+    async fn send(method: &str, uri: &str, lang: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .header("accept-language", lang)
+            .body(body.map_or_else(Body::empty, |b| Body::from(b.to_string())))
+            .unwrap();
+        let (status, _, body) = crate::test_request(req).await;
+        (status, body)
+    }
+
+    fn today() -> String {
+        logic::plan_date(&writer::snapshot().settings, clock::now()).to_string()
+    }
+
+    #[tokio::test]
+    async fn unplanned_reasons_are_localized_but_codes_stay() {
+        let due = (clock::now() - chrono::Duration::hours(1)).to_rfc3339();
+        let task = json!({ "title": "Whole day 437", "due_at": due, "duration_min": 1440, "priority": 3 });
+        let (status, created) = send("POST", "/api/tasks", "en", Some(task)).await;
+        assert_eq!(status, StatusCode::OK);
+        let uri = format!("/api/plan/today?date={}&available_min=60&full=true", today());
+
+        let mut seen = Vec::new();
+        for lang in ["ko-KR,ko;q=0.9,en;q=0.8", "en-US", "fr"] {
+            let (status, plan) = send("GET", &uri, lang, None).await;
+            assert_eq!(status, StatusCode::OK);
+            let entry = plan["unplanned"].as_array().unwrap().iter().find(|u| u["task_id"] == created["id"]).unwrap().clone();
+            seen.push((entry["reason"].as_str().unwrap().to_string(), entry["message"].as_str().unwrap().to_string()));
+        }
+        let code = "exceeds_day_window".to_string();
+        assert_eq!(seen[0], (code.clone(), i18n::unplanned_reason(Lang::Ko, UnplannedReason::ExceedsDayWindow).to_string()));
+        assert_eq!(seen[1], (code.clone(), i18n::unplanned_reason(Lang::En, UnplannedReason::ExceedsDayWindow).to_string()));
+        // Unsupported languages fall back to English
+        assert_eq!(seen[2], seen[1]);
+        assert_ne!(seen[0].1, seen[1].1);
+    }
+
+    #[tokio::test]
+    async fn validation_messages_are_localized_but_codes_stay() {
+        let task = json!({ "title": "", "due_at": "2024-06-03T12:00:00-04:00", "duration_min": 30, "priority": 3 });
+        let (status, ko) = send("POST", "/api/tasks", "ko", Some(task.clone())).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let (_, en) = send("POST", "/api/tasks", "en", Some(task)).await;
+        assert_eq!(ko["errors"][0]["code"], "required");
+        assert_eq!(ko["errors"][0]["code"], en["errors"][0]["code"]);
+        assert_eq!(en["errors"][0]["message"], "title required");
+        assert_ne!(ko["errors"][0]["message"], en["errors"][0]["message"]);
+    }
+
+    #[tokio::test]
+    async fn week_plan_starts_on_the_configured_week_start() {
+        let (_, settings) = send("GET", "/api/settings", "en", None).await;
+        let today = logic::plan_date(&writer::snapshot().settings, clock::now());
+        for (week_start, code) in [(Weekday::Sun, "Sun"), (Weekday::Mon, "Mon")] {
+            let mut body = settings.clone();
+            body["week_start"] = json!(code);
+            let (status, _) = send("PUT", "/api/settings", "en", Some(body)).await;
+            assert_eq!(status, StatusCode::OK);
+
+            let (_, week) = send("GET", "/api/plan/week", "en", None).await;
+            let (first, last) = logic::week_bounds(today, week_start);
+            assert_eq!(week["start"], first.to_string(), "{code}");
+            assert_eq!(week["end"], last.to_string(), "{code}");
+            assert_eq!(NaiveDate::parse_from_str(week["start"].as_str().unwrap(), "%Y-%m-%d").unwrap().weekday(), week_start);
+        }
+
+        // An explicit start is used as given
+        let (_, week) = send("GET", "/api/plan/week?start=2024-05-29", "en", None).await;
+        assert_eq!((week["start"].as_str(), week["end"].as_str()), (Some("2024-05-29"), Some("2024-06-04")));
+        assert_eq!(week["days"].as_array().unwrap().len(), 7);
+    }
+
+    fn params(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn exclude_ids_are_repeatable_and_comma_separated() {
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let list = format!("{a}, {b},");
+        let got = exclude_ids(&params(&[("exclude_ids", &list), ("date", "2024-06-03"), ("exclude_ids", &c.to_string())]));
+        assert_eq!(got.unwrap(), [a, b, c]);
+        assert!(exclude_ids(&params(&[])).unwrap().is_empty());
+
+        let errors = exclude_ids(&params(&[("exclude_ids", &format!("{a},nope,42"))])).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|e| e.field == "exclude_ids" && e.problem == Problem::InvalidFormat { expected: "UUID" }));
+    }
+
+    #[tokio::test]
+    async fn excluded_tasks_are_listed_apart_from_the_plan() {
+        let due = (clock::now() - chrono::Duration::days(1)).to_rfc3339();
+        let task = json!({ "title": "On the train 447", "due_at": due, "duration_min": 30, "priority": 5 });
+        let (_, created) = send("POST", "/api/tasks", "en", Some(task)).await;
+        let id = created["id"].as_str().unwrap();
+        let base = format!("/api/plan/today?date={}&available_min=600&full=true", today());
+
+        let (_, plan) = send("GET", &format!("{base}&exclude_ids={id}"), "en", None).await;
+        assert_eq!(plan["excluded"], json!([{ "task_id": id, "title": "On the train 447" }]));
+        let listed = |list: &Value| list.as_array().unwrap().iter().any(|p| p["task_id"] == id);
+        assert!(!listed(&plan["plan"]) && !listed(&plan["unplanned"]));
+
+        let (_, plan) = send("GET", &base, "en", None).await;
+        assert_eq!(plan["excluded"], json!([]));
+        assert!(listed(&plan["plan"]) || listed(&plan["unplanned"]));
+
+        let (status, body) = send("GET", &format!("{base}&exclude_ids={id},not-a-uuid"), "en", None).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["errors"][0]["field"], "exclude_ids");
+    }
+
+    #[test]
+    fn profile_settings_overlay_the_stored_ones() {
+        let base = DaySettings { day_start: "08:00".to_string(), ..DaySettings::default() };
+        let overrides = json!({ "urgency_mode": "calendar", "day_end": "15:00" });
+        let merged = profile_settings(&base, overrides.as_object().unwrap()).unwrap();
+        assert_eq!((merged.day_start.as_str(), merged.day_end.as_str()), ("08:00", "15:00"));
+        assert_eq!(merged.urgency_mode, UrgencyMode::Calendar);
+        assert_eq!(base.urgency_mode, UrgencyMode::Rolling);
+        assert!(profile_settings(&base, json!({ "focus_block_min": "long" }).as_object().unwrap()).is_err());
+    }
+
+    #[test]
+    fn profiles_with_a_known_divergence_are_diffed() {
+        use crate::models::test_task;
+        let (date, now) = (NaiveDate::from_ymd_opt(2024, 6, 3).unwrap(), clock::local("2024-06-03 07:00"));
+        // Overdue, so only the score decides
+        let due = clock::local("2024-06-02 17:00");
+        let tasks: Vec<_> = (1..=4)
+            .map(|p| crate::models::Task { priority: p, ..test_task(&format!("priority {p}"), due) })
+            .collect();
+        let full_day = DaySettings::default();
+        let afternoon = DaySettings { day_start: "16:00".to_string(), ..DaySettings::default() };
+        let (a, _, _) = logic::plan_for_date(&tasks, &full_day, date, now, 600, true);
+        let (b, _, _) = logic::plan_for_date(&tasks, &afternoon, date, now, 600, true);
+        assert_eq!((a.len(), b.len()), (4, 2));
+
+        let diff = profile_diff(&[("full day", &a), ("afternoon", &b)]);
+        let rows: Vec<(&str, Vec<String>, Vec<String>)> =
+            diff.iter().map(|d| (d.title.as_str(), d.scheduled_in.clone(), d.unscheduled_in.clone())).collect();
+        let row = |title, yes: &str, no: &str| (title, vec![yes.to_string()], vec![no.to_string()]);
+        assert_eq!(rows, [row("priority 2", "full day", "afternoon"), row("priority 1", "full day", "afternoon")]);
+        assert!(profile_diff(&[("same", &a), ("again", &a)]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn compare_rejects_bad_profile_lists() {
+        let body = |profiles: Value| json!({ "date": "2024-06-03", "available_min": 120, "profiles": profiles });
+        let one = body(json!([{ "name": "a" }]));
+        let (status, _) = send("POST", "/api/plan/compare", "en", Some(one)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let twins = body(json!([{ "name": "a" }, { "name": "a" }]));
+        let (status, _) = send("POST", "/api/plan/compare", "en", Some(twins)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let invalid = body(json!([{ "name": "a" }, { "name": "b", "settings": { "day_start": "9am" } }]));
+        let (status, errors) = send("POST", "/api/plan/compare", "en", Some(invalid)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(errors["errors"][0]["field"], "day_start");
+
+        let ok = body(json!([{ "name": "rolling" }, { "name": "calendar", "settings": { "urgency_mode": "calendar" } }]));
+        let (status, compared) = send("POST", "/api/plan/compare", "en", Some(ok)).await;
+        assert_eq!(status, StatusCode::OK);
+        let names: Vec<&str> = compared["profiles"].as_array().unwrap().iter().map(|p| p["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["rolling", "calendar"]);
+    }
+
+    #[test]
+    fn unplanned_list_is_capped_and_the_plan_unchanged() {
+        let mut rng = crate::models::TestRng::new(458);
+        let now = clock::local("2024-06-03 08:00");
+        let day = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let tasks: Vec<crate::models::Task> = (0..10_000)
+            .map(|i| {
+                let due = clock::local("2024-06-02 09:00") - chrono::Duration::minutes(rng.below(5000) as i64);
+                let mut t = crate::models::test_task(&format!("overdue {i}"), due);
+                t.duration_min = [15, 30, 45, 60, 90][rng.below(5)];
+                t.priority = 1 + rng.below(5) as i64;
+                t
+            })
+            .collect();
+        let settings = DaySettings::default();
+        let relevant = logic::relevant_tasks(&tasks, &settings, day, now);
+        let scored = logic::score_and_sort(relevant, now, &settings, logic::scorer_for(settings.scorer));
+        let (plan, unplanned, _) = logic::build_today_plan(scored, day, now, &settings, 480, true);
+        assert!(!plan.is_empty());
+
+        let query = |extra: Value| {
+            let mut q = json!({ "date": "2024-06-03", "available_min": 480 });
+            q.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+            serde_json::from_value::<PlanQuery>(q).unwrap()
+        };
+        let (capped, total) = cap_unplanned(unplanned.clone(), &query(json!({})));
+        let (full, full_total) = cap_unplanned(unplanned, &query(json!({ "full": true })));
+        let (five, _) = cap_unplanned(full.clone(), &query(json!({ "limit_unplanned": 5 })));
+        assert_eq!((capped.len(), five.len()), (DEFAULT_UNPLANNED_LIMIT, 5));
+        assert_eq!((total, full_total, full.len()), (10_000 - plan.len(), full.len(), full.len()));
+
+        // The cut keeps the first entries, in order
+        let ids = |items: &[UnplannedItem]| items.iter().map(|u| u.task_id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&capped), ids(&full[..DEFAULT_UNPLANNED_LIMIT]));
+
+        // The plan and its slack do not depend on the cut
+        let bounded = logic::summarize_plan(&plan, &capped, &tasks, day, now, &settings);
+        let unbounded = logic::summarize_plan(&plan, &full, &tasks, day, now, &settings);
+        assert_eq!((bounded.finish_at, bounded.slack_min, bounded.planned_min), (unbounded.finish_at, unbounded.slack_min, unbounded.planned_min));
+
+        let size = |items: Vec<UnplannedItem>| {
+            let resp: Vec<UnplannedResponse> = items.into_iter().map(|u| UnplannedResponse::new(u, Lang::En)).collect();
+            serde_json::to_vec(&resp).unwrap().len()
+        };
+        let (capped_size, full_size) = (size(capped), size(full));
+        assert!(capped_size < 64 * 1024, "{capped_size}");
+        assert!(full_size > 1024 * 1024, "{full_size}");
+    }
+
+    #[test]
+    fn unplanned_entries_match_the_plan_item_shape() {
+        let now = clock::local("2024-06-03 08:00");
+        let settings = DaySettings::default();
+        let tasks = [
+            crate::models::test_task("fits", clock::local("2024-06-02 17:00")),
+            crate::models::test_task("left over", clock::local("2024-06-02 17:00")),
+        ];
+        let day = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let (plan, unplanned, _) = logic::plan_for_date(&tasks, &settings, day, now, 60, true);
+        let item = serde_json::to_value(PlanItemResponse::new(plan[0].clone(), &settings)).unwrap();
+        let entry = serde_json::to_value(UnplannedResponse::new(unplanned[0].clone(), Lang::En)).unwrap();
+
+        for key in ["task_id", "title", "score_breakdown", "is_overdue", "is_due_now"] {
+            assert!(item.get(key).is_some() && entry.get(key).is_some(), "{key}");
+        }
+        assert_eq!(entry["score_breakdown"], item["score_breakdown"]);
+        assert_eq!(entry["duration_min"], 60);
+        assert_eq!((entry["reason"].as_str(), entry["minutes_short"].as_i64()), (Some("insufficient_time"), Some(60)));
+        assert_eq!(entry["would_end_at"], "2024-06-03T11:00:00-04:00");
+
+        // Time-related fields only for insufficient_time
+        let mut other = unplanned[0].clone();
+        other.reason = UnplannedReason::CrossesMidnight;
+        other.minutes_short = None;
+        other.would_end_at = None;
+        let entry = serde_json::to_value(UnplannedResponse::new(other, Lang::En)).unwrap();
+        assert!(entry.get("minutes_short").is_none() && entry.get("would_end_at").is_none());
+    }
+
+    #[tokio::test]
+    async fn score_totals_match_the_plan_breakdowns() {
+        let mut ids = Vec::new();
+        for (i, (priority, duration)) in [(5, 30), (3, 120), (1, 240)].into_iter().enumerate() {
+            let due = format!("2031-05-20T1{i}:00:00-04:00");
+            let task = json!({ "title": format!("scored {i} 486"), "due_at": due, "duration_min": duration, "priority": priority });
+            let (status, created) = send("POST", "/api/tasks", "en", Some(task)).await;
+            assert_eq!(status, StatusCode::OK);
+            ids.push(created["id"].as_str().unwrap().to_string());
+        }
+
+        let (status, scores) = send("GET", "/api/plan/scores?date=2031-05-20", "en", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, plan) = send("GET", "/api/plan/today?date=2031-05-20&available_min=540", "en", None).await;
+        assert_eq!(scores["available_min"], plan["window"]["available_min"]);
+        for id in &ids {
+            let row = scores["tasks"].as_array().unwrap().iter().find(|r| r["id"] == *id).unwrap();
+            // Other tests add overdue tasks concurrently, so which of these
+            // get planned may differ between the two requests
+            if let Some(item) = plan["plan"].as_array().unwrap().iter().find(|p| p["task_id"] == *id) {
+                for key in ["urgency", "urgency_mode", "priority", "duration_score", "total"] {
+                    assert_eq!(row[key], item["score_breakdown"][key], "{id} {key}");
+                }
+            }
+            let total = ["urgency", "priority", "duration_score"].iter().map(|k| row[k].as_i64().unwrap()).sum::<i64>();
+            assert_eq!(row["total"], total);
+        }
+
+        let (status, _) = send("GET", "/api/plan/scores?date=2031-05-20&format=xml", "en", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let req = Request::builder().uri("/api/plan/scores?date=2031-05-20").header("accept", "text/csv").body(Body::empty()).unwrap();
+        let (status, headers, _) = crate::test_request(req).await;
+        assert_eq!((status, headers["content-type"].to_str().unwrap()), (StatusCode::OK, "text/csv; charset=utf-8"));
+    }
+
+    #[test]
+    fn score_csv_columns_follow_the_json_fields() {
+        let row = ScoreRow {
+            id: "id-1".into(),
+            title: "Write, then review".into(),
+            due_at: "2024-06-03T12:00:00-04:00".into(),
+            duration_min: 30,
+            priority: 4,
+            urgency: 5,
+            urgency_mode: UrgencyBasis::Rolling,
+            duration_score: 5,
+            total: 14,
+            planned: true,
+        };
+        let json_keys: Vec<String> = match serde_json::to_value(&row).unwrap() {
+            Value::Object(map) => map.keys().cloned().collect(),
+            _ => unreachable!(),
+        };
+        let csv = scores_csv(&[row]).unwrap();
+        let mut lines = csv.lines();
+        let header = lines.next().unwrap();
+        assert_eq!(header, "id,title,due_at,duration_min,priority,urgency,urgency_mode,duration_score,total,planned");
+        let mut sorted = header.split(',').map(str::to_string).collect::<Vec<_>>();
+        sorted.sort();
+        let mut json_sorted = json_keys;
+        json_sorted.sort();
+        assert_eq!(sorted, json_sorted);
+        assert_eq!(lines.next(), Some("id-1,\"Write, then review\",2024-06-03T12:00:00-04:00,30,4,5,rolling,5,14,true"));
+    }
+
+    #[tokio::test]
+    async fn plan_states_the_due_boundary_it_applied() {
+        let (status, plan) = send("GET", "/api/plan/today?date=2031-05-21&available_min=60", "en", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let settings = writer::snapshot().settings.clone();
+        assert_eq!(plan["due_boundary"], serde_json::to_value(settings.due_boundary).unwrap());
+        assert!(matches!(plan["due_boundary"].as_str(), Some("strict" | "inclusive_next_midnight")));
+    }
+}
@@ -0,0 +1,283 @@
+// --------------------------------------------------
+// Handles settings export / import between instances.
+//
+// Responsibilities:
+// - Export the settings side of the db (day settings and
+//   notification settings) as one bundle, secrets redacted
+// - Import a bundle, all sections or none, optionally as a dry run
+//
+// Tasks, archived tasks and the delivery log are never part
+// of a bundle.
+// --------------------------------------------------
+
+use axum::{
+    extract::Query,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::api_json::{self, ApiJson};
+use crate::i18n::Lang;
+use crate::models::{AuditOp, DaySettings, Db, NotificationSettings};
+use crate::routes_notifications::check_notifications;
+use crate::routes_tasks::{validation_error, write_error};
+use crate::validation;
+use crate::writer;
+
+// Stand-in for a secret in an exported bundle. On import it means
+// "keep the value already configured".
+const REDACTED: &str = "***";
+
+// Settings side of the db. A section left out of an import is not touched.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SettingsBundle {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<DaySettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notifications: Option<NotificationSettings>,
+}
+
+// Webhook URL, push URL and push token replaced by REDACTED (empty stays empty).
+fn redact(n: &NotificationSettings) -> NotificationSettings {
+    let hide = |s: &str| if s.is_empty() { String::new() } else { REDACTED.to_string() };
+    let mut out = n.clone();
+    out.webhook_url = hide(&n.webhook_url);
+    out.push.url = hide(&n.push.url);
+    out.push.token = hide(&n.push.token);
+    out
+}
+
+// Put back the configured value wherever an import still holds REDACTED.
+fn unredact(n: &mut NotificationSettings, current: &NotificationSettings) {
+    for (field, kept) in [
+        (&mut n.webhook_url, &current.webhook_url),
+        (&mut n.push.url, &current.push.url),
+        (&mut n.push.token, &current.push.token),
+    ] {
+        if field == REDACTED {
+            field.clone_from(kept);
+        }
+    }
+}
+
+// Whether two values serialize identically (the settings
+// types do not implement PartialEq).
+fn same<T: Serialize>(a: &T, b: &T) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+// -----------------------------
+// GET /api/settings/export
+// Returns settings and notification settings as one bundle
+// -----------------------------
+pub async fn export_settings() -> impl IntoResponse {
+    let db = writer::snapshot();
+    Json(SettingsBundle {
+        settings: Some(db.settings.without_extra()),
+        notifications: Some(redact(&db.notifications)),
+    })
+    .into_response()
+}
+
+// Query parameters for POST /api/settings/import
+#[derive(Debug, Deserialize)]
+pub struct ImportSettingsQuery {
+    #[serde(default)]
+    pub dry_run: bool, // validate and report without saving
+}
+
+// Sections an import changed (or would change) and left as they were
+#[derive(Debug, Serialize)]
+pub struct ImportSettingsResponse {
+    pub dry_run: bool,
+    pub changed: Vec<&'static str>,
+    pub unchanged: Vec<&'static str>,
+}
+
+// Apply an import to `db` (only report it with `dry_run`). Settings
+// are validated by the caller; notifications are checked here, once
+// their secrets are resolved. Nothing changes on an error.
+fn apply_bundle(db: &mut Db, bundle: SettingsBundle, dry_run: bool) -> Result<ImportSettingsResponse, String> {
+    let mut notifications = bundle.notifications;
+    if let Some(n) = notifications.as_mut() {
+        unredact(n, &db.notifications);
+        check_notifications(n)?;
+    }
+
+    let mut changed = Vec::new();
+    let mut unchanged = Vec::new();
+    if let Some(s) = bundle.settings {
+        let s = s.with_extra_of(&db.settings);
+        if same(&s, &db.settings) {
+            unchanged.push("settings")
+        } else {
+            changed.push("settings")
+        }
+        if !dry_run {
+            db.settings = s;
+        }
+    }
+    if let Some(n) = notifications {
+        if same(&n, &db.notifications) {
+            unchanged.push("notifications")
+        } else {
+            changed.push("notifications")
+        }
+        if !dry_run {
+            db.notifications = n;
+        }
+    }
+    Ok(ImportSettingsResponse { dry_run, changed, unchanged })
+}
+
+// -----------------------------
+// POST /api/settings/import?dry_run=BOOL
+// Applies a bundle from GET /api/settings/export
+// - Invalid settings -> 422 listing all of them, invalid
+//   notifications -> 400; either way nothing is saved
+// - REDACTED secrets keep the configured value
+// -----------------------------
+pub async fn import_settings(
+    headers: HeaderMap,
+    Query(q): Query<ImportSettingsQuery>,
+    ApiJson(bundle): ApiJson<SettingsBundle>,
+) -> impl IntoResponse {
+    if bundle.settings.is_none() && bundle.notifications.is_none() {
+        return (StatusCode::BAD_REQUEST, "bundle has no settings or notifications").into_response();
+    }
+    if let Some(s) = &bundle.settings
+        && let Some(resp) = api_json::unknown_extra("settings", s.extra.keys(), Lang::from_headers(&headers))
+    {
+        return resp;
+    }
+    if let Some(s) = &bundle.settings
+        && let Err(errors) = validation::validate_settings(s)
+    {
+        return validation_error(errors, Lang::from_headers(&headers));
+    }
+
+    // Secrets are resolved against the db inside the writer,
+    // so the check and the save see the same state
+    let dry_run = q.dry_run;
+    let result = writer::apply_audited(move |db| {
+        let result = apply_bundle(db, bundle, dry_run);
+        let save = result.as_ref().is_ok_and(|r| !dry_run && !r.changed.is_empty());
+        let audited = match &result {
+            Ok(r) if save && r.changed.contains(&"settings") => vec![(AuditOp::Settings, None)],
+            _ => Vec::new(),
+        };
+        (result, save, audited)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(resp)) => Json(resp).into_response(),
+        Ok(Err(msg)) => (StatusCode::BAD_REQUEST, msg).into_response(),
+        Err(e) => write_error(&e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request};
+    use serde_json::{json, Value};
+
+    fn instance(webhook: &str, token: &str) -> Db {
+        let mut db = Db::default();
+        db.notifications.enabled = true;
+        db.notifications.webhook_url = webhook.to_string();
+        db.notifications.push.url = format!("{webhook}/push");
+        db.notifications.push.token = token.to_string();
+        db
+    }
+
+    // What GET /api/settings/export sends, parsed back as an import.
+    fn exported(db: &Db) -> SettingsBundle {
+        let bundle = SettingsBundle { settings: Some(db.settings.without_extra()), notifications: Some(redact(&db.notifications)) };
+        serde_json::from_value(serde_json::to_value(bundle).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn export_round_trips_without_leaking_secrets() {
+        let mut source = instance("https://hooks.one.test/a", "one-token");
+        source.settings.day_start = "08:30".to_string();
+        source.settings.wip_limit = Some(2);
+        source.notifications.time = "07:45".to_string();
+
+        let bundle = exported(&source);
+        let text = serde_json::to_string(&bundle).unwrap();
+        assert!(!text.contains("one.test") && !text.contains("one-token"), "{text}");
+
+        // Into a second instance: its own secrets stay, the rest is copied
+        let mut target = instance("https://hooks.two.test/b", "two-token");
+        target.tasks.push(crate::models::test_task("kept", crate::clock::local("2024-06-03 12:00")));
+        let resp = apply_bundle(&mut target, bundle, false).unwrap();
+        assert_eq!((resp.changed, resp.unchanged), (vec!["settings", "notifications"], vec![]));
+        assert!(same(&target.settings, &source.settings));
+        assert_eq!(target.notifications.time, "07:45");
+        assert_eq!(target.notifications.webhook_url, "https://hooks.two.test/b");
+        assert_eq!(target.notifications.push.token, "two-token");
+        assert_eq!(target.tasks.len(), 1);
+
+        // Importing an instance's own export changes nothing
+        let own = exported(&source);
+        let resp = apply_bundle(&mut source, own, false).unwrap();
+        assert_eq!((resp.changed, resp.unchanged), (vec![], vec!["settings", "notifications"]));
+    }
+
+    #[test]
+    fn dry_run_reports_without_applying() {
+        let mut db = instance("https://hooks.test/a", "token");
+        let mut bundle = exported(&db);
+        bundle.settings.as_mut().unwrap().day_end = "17:00".to_string();
+        let resp = apply_bundle(&mut db, bundle, true).unwrap();
+        assert_eq!((resp.dry_run, resp.changed, resp.unchanged), (true, vec!["settings"], vec!["notifications"]));
+        assert_eq!(db.settings.day_end, DaySettings::default().day_end);
+    }
+
+    #[test]
+    fn invalid_section_rejects_the_whole_import() {
+        let mut db = instance("https://hooks.test/a", "token");
+        let mut bundle = exported(&db);
+        bundle.settings.as_mut().unwrap().day_end = "17:00".to_string();
+        bundle.notifications.as_mut().unwrap().time = "7pm".to_string();
+        assert_eq!(apply_bundle(&mut db, bundle, false).unwrap_err(), "time must be HH:MM");
+        assert_eq!(db.settings.day_end, DaySettings::default().day_end);
+        assert_eq!(db.notifications.time, instance("", "").notifications.time);
+
+        // A blanked secret is a real value, and an enabled webhook needs one
+        let mut bundle = exported(&db);
+        bundle.notifications.as_mut().unwrap().webhook_url = String::new();
+        assert_eq!(apply_bundle(&mut db, bundle, false).unwrap_err(), "webhook_url must be an http(s) URL");
+    }
+
+    async fn import(body: Value) -> (StatusCode, Value) {
+        let req = Request::builder()
+            .method("POST")
+            .uri("/api/settings/import")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let (status, _, body) = crate::test_request(req).await;
+        (status, body)
+    }
+
+    #[tokio::test]
+    async fn invalid_settings_are_refused_before_anything_is_saved() {
+        let mut settings = serde_json::to_value(DaySettings::default()).unwrap();
+        settings["day_start"] = json!("9am");
+        settings["focus_block_min"] = json!(0);
+        let notifications = serde_json::to_value(NotificationSettings::default()).unwrap();
+        let (status, body) = import(json!({ "settings": settings, "notifications": notifications })).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let fields: Vec<&str> = body["errors"].as_array().unwrap().iter().filter_map(|e| e["field"].as_str()).collect();
+        assert_eq!(fields, ["day_start", "focus_block_min"]);
+        assert_eq!(writer::snapshot().settings.day_start, DaySettings::default().day_start);
+
+        let (status, _) = import(json!({})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
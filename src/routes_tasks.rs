@@ -10,71 +10,284 @@
 
 use axum::{
     extract::{Path, Query},
-    http::StatusCode,
-    response::IntoResponse,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
-use chrono::{DateTime, FixedOffset, NaiveDate};
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::models::{Db, DaySettings, Task, TaskStatus};
-use crate::store;
-
-fn now_fixed_offset() -> DateTime<FixedOffset> {
-    let local = chrono::Local::now();
-    let offset_seconds = local.offset().local_minus_utc();
-    let fixed = FixedOffset::east_opt(offset_seconds).unwrap();
-    local.with_timezone(&fixed)
-}
+use crate::api_json::{self, ApiJson};
+use crate::clock;
+use crate::config;
+use crate::i18n::Lang;
+use crate::logic;
+use crate::models::{priority_meta, AuditOp, Db, DaySettings, Extra, PriorityMeta, Task, TaskStatus};
+use crate::stale::{self, AgeBucket, SuggestedAction};
+use crate::store::StoreError;
+use crate::validation::{self, FieldError, Problem, TaskDraft};
+use crate::writer::{self, Command, Created, DedupeCheck, Guarded, IfMatch, Removal, TaskChanges, WipCheck, WriteError};
 
 #[derive(Debug, Deserialize)]
 pub struct TasksQuery {
     pub date: String, // "YYYY-MM-DD"
+    pub fields: Option<String>, // comma-separated TASK_FIELDS to return; all when absent
 }
 
 #[derive(Debug, Serialize)]
 pub struct TasksResponse {
     pub date: String,
     pub now: String,
-    pub tasks: Vec<Task>,
+    pub in_progress_count: usize, // across all tasks, not only those listed
+    pub tasks: Vec<TaskProjection>,
+}
+
+// Query parameters for GET /api/tasks/:id
+#[derive(Debug, Deserialize)]
+pub struct TaskQuery {
+    pub fields: Option<String>, // as for GET /api/tasks
+}
+
+// Keys of a TaskView that ?fields= may select.
+pub const TASK_FIELDS: &[&str] = &[
+    "id",
+    "title",
+    "due_at",
+    "duration_min",
+    "priority",
+    "status",
+    "created_at",
+    "tags",
+    "notes",
+    "completed_at",
+    "postpone_count",
+    "rev",
+    "updated_at",
+    "deadline_notified_at",
+    "locked",
+    "url",
+    "priority_label",
+    "priority_color",
+];
+
+// A TaskView as selected by ?fields=: whole, or only the listed keys
+// (a listed optional field that is unset stays absent)
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum TaskProjection {
+    Full(Box<TaskView>),
+    Fields(serde_json::Map<String, serde_json::Value>),
+}
+
+impl TaskProjection {
+    pub fn new(view: TaskView, fields: Option<&[&str]>) -> Self {
+        let Some(fields) = fields else {
+            return TaskProjection::Full(Box::new(view));
+        };
+        let mut map = match serde_json::to_value(view) {
+            Ok(serde_json::Value::Object(map)) => map,
+            _ => serde_json::Map::new(),
+        };
+        map.retain(|k, _| fields.contains(&k.as_str()));
+        TaskProjection::Fields(map)
+    }
+}
+
+// Parse ?fields= against TASK_FIELDS.
+fn task_fields(raw: Option<&str>) -> Result<Option<Vec<&'static str>>, Vec<FieldError>> {
+    match raw {
+        Some(raw) => validation::parse_names("fields", raw, TASK_FIELDS),
+        None => Ok(None),
+    }
+}
+
+// Task as returned by the API: stored fields plus its deep link
+// and the display label/color of its priority
+#[derive(Debug, Serialize)]
+pub struct TaskView {
+    #[serde(flatten)]
+    pub task: Task,
+    pub url: String, // config::task_url
+    pub priority_label: String,
+    pub priority_color: String,
+    // create/update only: non-fatal remarks about the saved task
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+impl TaskView {
+    pub fn new(mut task: Task, labels: Option<&[PriorityMeta; 5]>) -> Self {
+        // Unknown stored keys stay in db.json only
+        task.extra.clear();
+        let url = config::task_url(task.id);
+        let PriorityMeta { label, color } = priority_meta(labels, task.priority);
+        TaskView { task, url, priority_label: label, priority_color: color, warnings: Vec::new() }
+    }
+}
+
+// Remarks about a task that was saved anyway.
+// - Longer than the day window: the planner can never place it
+fn task_warnings(task: &Task, settings: &DaySettings) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if logic::exceeds_day_window(task.duration_min, settings) {
+        warnings.push(format!(
+            "duration_min {} is longer than the {} minute day window ({}-{}); it will never be planned, consider splitting it",
+            task.duration_min,
+            logic::day_window_min(settings),
+            settings.day_start,
+            settings.day_end
+        ));
+    }
+    warnings
+}
+
+// Configured priority labels, for responses built after a write.
+fn current_labels() -> Option<[PriorityMeta; 5]> {
+    writer::snapshot().settings.priority_labels.clone()
 }
 
 // -----------------------------
 // GET /api/tasks
-// Returns all tasks stored in db.json
+// Returns open tasks that are overdue or due on the date
+// (same filter the planner uses, logic::relevant_tasks)
+// ?fields=id,title,... returns only those keys of each task;
+// an unknown name -> 422 listing the valid ones
 // -----------------------------
-pub async fn get_tasks(Query(q): Query<TasksQuery>) -> impl IntoResponse {
+pub async fn get_tasks(headers: HeaderMap, Query(q): Query<TasksQuery>) -> impl IntoResponse {
     let date = match NaiveDate::parse_from_str(&q.date, "%Y-%m-%d") {
         Ok(d) => d,
         Err(_) => return (StatusCode::BAD_REQUEST, "invalid date").into_response(),
     };
-    let now = now_fixed_offset();
-
-    let db: Db = match store::load_db() {
-        Ok(db) => db,
-        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "failed to load db").into_response(),
+    let fields = match task_fields(q.fields.as_deref()) {
+        Ok(f) => f,
+        Err(errors) => return validation_error(errors, Lang::from_headers(&headers)),
     };
+    let now = clock::now();
+
+    let db = writer::snapshot();
 
-    let tasks: Vec<Task> = db
-        .tasks
+    let tasks: Vec<TaskProjection> = logic::relevant_tasks(&db.tasks, &db.settings, date, now)
         .into_iter()
-        .filter(|t| t.status != TaskStatus::Done)
-        .filter(|t| {
-            let overdue = now > t.due_at;
-            let due_today = t.due_at.date_naive() == date;
-            overdue || due_today
-        })
+        .cloned()
+        .map(|t| TaskProjection::new(TaskView::new(t, db.settings.priority_labels.as_ref()), fields.as_deref()))
         .collect();
 
     Json(TasksResponse {
         date: q.date,
         now: now.to_rfc3339(),
+        in_progress_count: db.tasks.iter().filter(|t| t.status == TaskStatus::InProgress).count(),
         tasks,
     })
     .into_response()
 }
 
+// Strong ETag of a task: its revision in quotes.
+fn etag(task: &Task) -> String {
+    format!("\"{}\"", task.rev)
+}
+
+// Parse If-Match. Weak or malformed entries never match (strong comparison).
+fn if_match(headers: &HeaderMap) -> Option<IfMatch> {
+    let values: Vec<&str> = headers
+        .get_all(header::IF_MATCH)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .collect();
+    if values.is_empty() {
+        return None;
+    }
+    if values.contains(&"*") {
+        return Some(IfMatch::Any);
+    }
+    let revs = values
+        .iter()
+        .filter_map(|v| v.strip_prefix('"')?.strip_suffix('"')?.parse().ok())
+        .collect();
+    Some(IfMatch::Revs(revs))
+}
+
+// Task as JSON with its ETag header.
+fn task_response(status: StatusCode, task: Task, labels: Option<&[PriorityMeta; 5]>) -> Response {
+    (status, [(header::ETAG, etag(&task))], Json(TaskView::new(task, labels))).into_response()
+}
+
+// 200 response for a task just created or updated, with task_warnings.
+pub fn saved_response(task: Task) -> Response {
+    let db = writer::snapshot();
+    let warnings = task_warnings(&task, &db.settings);
+    let tag = etag(&task);
+    let view = TaskView { warnings, ..TaskView::new(task, db.settings.priority_labels.as_ref()) };
+    (StatusCode::OK, [(header::ETAG, tag)], Json(view)).into_response()
+}
+
+// HTTP response for a refused conditional write.
+//   NotFound          -> 404
+//   Stale             -> 412 with the current task and its ETag
+//   ConditionRequired -> 428
+//   OverWipLimit      -> 409 with the tasks in progress
+fn refused<T>(g: Guarded<T>) -> Response {
+    match g {
+        Guarded::Applied(_) | Guarded::NotFound => {
+            (StatusCode::NOT_FOUND, "task not found").into_response()
+        }
+        Guarded::Stale(current) => {
+            task_response(StatusCode::PRECONDITION_FAILED, *current, current_labels().as_ref())
+        }
+        Guarded::ConditionRequired => {
+            (StatusCode::PRECONDITION_REQUIRED, "If-Match required").into_response()
+        }
+        Guarded::OverWipLimit(in_progress) => {
+            let labels = current_labels();
+            let in_progress: Vec<TaskView> =
+                in_progress.into_iter().map(|t| TaskView::new(t, labels.as_ref())).collect();
+            (
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({
+                    "error": "wip_limit reached; park a task first or send force",
+                    "in_progress": in_progress,
+                })),
+            )
+                .into_response()
+        }
+    }
+}
+
+// -----------------------------
+// GET /api/tasks/:id
+// Returns one task with an ETag for If-Match
+// ?fields= as for GET /api/tasks
+// -----------------------------
+pub async fn get_task(
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(q): Query<TaskQuery>,
+) -> impl IntoResponse {
+    let id = match Uuid::parse_str(&id) {
+        Ok(u) => u,
+        Err(_) => return (StatusCode::BAD_REQUEST, "invalid id").into_response(),
+    };
+    let fields = match task_fields(q.fields.as_deref()) {
+        Ok(f) => f,
+        Err(errors) => return validation_error(errors, Lang::from_headers(&headers)),
+    };
+
+    let db = writer::snapshot();
+
+    match db.get(id) {
+        Some(task) => {
+            let tag = etag(task);
+            let view = TaskView::new(task.clone(), db.settings.priority_labels.as_ref());
+            let body = TaskProjection::new(view, fields.as_deref());
+            (StatusCode::OK, [(header::ETAG, tag)], Json(body)).into_response()
+        }
+        None => (StatusCode::NOT_FOUND, "task not found").into_response(),
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CreateTaskInput {
     pub title: String,
@@ -83,51 +296,137 @@ pub struct CreateTaskInput {
     pub priority: i64, // 1..=5
     pub tags: Option<Vec<String>>,
     pub notes: Option<String>,
+    #[serde(default)]
+    pub force: bool, // create even if it duplicates an open task
+}
+
+// Query parameters for POST /api/tasks
+#[derive(Debug, Deserialize)]
+pub struct CreateTaskQuery {
+    #[serde(default)]
+    pub dedupe: bool, // check for duplicates even if dedupe_on_create is off
+}
+
+// 422 listing every violation: {"errors": [{field, code, message}, ...]}
+// Messages follow the request's Accept-Language.
+pub fn validation_error(errors: Vec<FieldError>, lang: Lang) -> Response {
+    let errors: Vec<_> = errors.iter().map(|e| e.to_json(lang)).collect();
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Json(serde_json::json!({ "errors": errors })),
+    )
+        .into_response()
+}
+
+// Response for a db.json that could not be loaded or saved:
+// {"error": code, "message": ...}, plus line/column and backup when corrupt.
+//   NotFound    -> 404
+//   StorageFull -> 507
+//   otherwise   -> 500
+pub fn store_error(e: &StoreError) -> Response {
+    let status = match e {
+        StoreError::NotFound => StatusCode::NOT_FOUND,
+        StoreError::Io(std::io::ErrorKind::StorageFull) => StatusCode::INSUFFICIENT_STORAGE,
+        StoreError::Corrupt { .. }
+        | StoreError::TooNew { .. }
+        | StoreError::Encrypted
+        | StoreError::WrongKey
+        | StoreError::PermissionDenied
+        | StoreError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    let mut body = serde_json::json!({ "error": e.code(), "message": e.describe() });
+    if let StoreError::Corrupt { line, column, backup, .. } = e {
+        body["line"] = (*line).into();
+        body["column"] = (*column).into();
+        if let Some(backup) = backup {
+            body["backup"] = backup.as_str().into();
+        }
+    }
+    if let StoreError::TooNew { version } = e {
+        body["schema_version"] = (*version).into();
+    }
+    (status, Json(body)).into_response()
+}
+
+// Response for a writer command that failed (store_error for db.json).
+pub fn write_error(e: &WriteError) -> Response {
+    match e {
+        WriteError::Save(e) | WriteError::Reload(e) => store_error(e),
+        WriteError::Removed(_) => {
+            let body = serde_json::json!({ "error": "task_removed", "message": e.message() });
+            (StatusCode::CONFLICT, Json(body)).into_response()
+        }
+        WriteError::Stopped => (StatusCode::INTERNAL_SERVER_ERROR, e.message()).into_response(),
+    }
 }
 
 // -----------------------------
 // POST /api/tasks
 // Creates a new task and saves it to db.json
+// With ?dedupe=true or the dedupe_on_create setting, an open task with
+// the same title (case/whitespace-insensitive) due the same day
+// -> 409 with the existing task, unless "force": true
+// Longer than the day window -> saved, with `warnings`
 // -----------------------------
-pub async fn create_task(Json(input): Json<CreateTaskInput>) -> impl IntoResponse {
-    if input.title.trim().is_empty() {
-        return (StatusCode::BAD_REQUEST, "title required").into_response();
-    }
-    if !(1..=5).contains(&input.priority) {
-        return (StatusCode::BAD_REQUEST, "priority must be 1..=5").into_response();
-    }
-
-    let due_at = match DateTime::parse_from_rfc3339(&input.due_at) {
-        Ok(dt) => dt,
-        Err(_) => return (StatusCode::BAD_REQUEST, "invalid due_at").into_response(),
+pub async fn create_task(
+    headers: HeaderMap,
+    Query(q): Query<CreateTaskQuery>,
+    ApiJson(input): ApiJson<CreateTaskInput>,
+) -> impl IntoResponse {
+    let draft = TaskDraft {
+        title: &input.title,
+        due_at: &input.due_at,
+        duration_min: input.duration_min,
+        priority: input.priority,
+        notes: input.notes.as_deref(),
     };
-
-    let now = now_fixed_offset();
-
-    let mut db: Db = match store::load_db() {
-        Ok(db) => db,
-        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "failed to load db").into_response(),
+    let tags = validation::clean_tags("tags", input.tags.as_deref().unwrap_or_default());
+    let (v, tags) = match (validation::validate_task(draft), tags) {
+        (Ok(v), Ok(tags)) => (v, tags),
+        (v, tags) => {
+            let mut errors = v.err().unwrap_or_default();
+            errors.extend(tags.err().unwrap_or_default());
+            return validation_error(errors, Lang::from_headers(&headers));
+        }
     };
 
+    let now = clock::now();
+
     let task = Task {
         id: Uuid::new_v4(),
-        title: input.title,
-        due_at,
-        duration_min: input.duration_min,
-        priority: input.priority,
+        title: v.title,
+        due_at: v.due_at,
+        duration_min: v.duration_min,
+        priority: v.priority,
         status: TaskStatus::Todo,
         created_at: now,
-        tags: input.tags,
-        notes: input.notes,
+        tags,
+        notes: v.notes,
+        completed_at: None,
+        postpone_count: 0,
+        rev: 0,
+        updated_at: now,
+        deadline_notified_at: None,
+        locked: None,
+        extra: Extra::new(),
     };
 
-    db.tasks.push(task.clone());
+    let check = if input.force {
+        DedupeCheck::Never
+    } else if q.dedupe {
+        DedupeCheck::Always
+    } else {
+        DedupeCheck::Setting
+    };
 
-    if store::save_db(&db).is_err() {
-        return (StatusCode::INTERNAL_SERVER_ERROR, "failed to save db").into_response();
+    match writer::send(|reply| Command::CreateTask(task, check, reply)).await {
+        Ok(Created::New(task)) => saved_response(task),
+        Ok(Created::Duplicate(existing)) => {
+            let view = TaskView::new(existing, current_labels().as_ref());
+            (StatusCode::CONFLICT, Json(view)).into_response()
+        }
+        Err(e) => write_error(&e),
     }
-
-    Json(task).into_response()
 }
 
 #[derive(Debug, Deserialize)]
@@ -139,120 +438,359 @@ pub struct UpdateTaskInput {
     pub status: TaskStatus,
     pub tags: Option<Vec<String>>,
     pub notes: Option<String>,
+    #[serde(default)]
+    pub force: bool, // move to InProgress even past wip_limit
+}
+
+// Query parameters for POST /api/tasks/:id/toggle
+#[derive(Debug, Deserialize)]
+pub struct ToggleQuery {
+    #[serde(default)]
+    pub force: bool, // move to InProgress even past wip_limit
 }
 
 // -----------------------------
 // PUT /api/tasks/:id
 // Updates an existing task by ID
+// If-Match: "<rev>" guards against overwriting a newer edit (412)
+// Moving into InProgress past wip_limit -> 409 unless "force": true
+// Longer than the day window -> saved, with `warnings`
 // ----------------------------
 pub async fn update_task(
+    headers: HeaderMap,
     Path(id): Path<String>,
-    Json(input): Json<UpdateTaskInput>,
+    ApiJson(input): ApiJson<UpdateTaskInput>,
 ) -> impl IntoResponse {
     let id = match Uuid::parse_str(&id) {
         Ok(u) => u,
         Err(_) => return (StatusCode::BAD_REQUEST, "invalid id").into_response(),
     };
 
-    if input.title.trim().is_empty() {
-        return (StatusCode::BAD_REQUEST, "title required").into_response();
-    }
-    if !(1..=5).contains(&input.priority) {
-        return (StatusCode::BAD_REQUEST, "priority must be 1..=5").into_response();
-    }
-
-    let due_at = match DateTime::parse_from_rfc3339(&input.due_at) {
-        Ok(dt) => dt,
-        Err(_) => return (StatusCode::BAD_REQUEST, "invalid due_at").into_response(),
+    let draft = TaskDraft {
+        title: &input.title,
+        due_at: &input.due_at,
+        duration_min: input.duration_min,
+        priority: input.priority,
+        notes: input.notes.as_deref(),
     };
-
-    let mut db: Db = match store::load_db() {
-        Ok(db) => db,
-        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "failed to load db").into_response(),
+    let tags = validation::clean_tags("tags", input.tags.as_deref().unwrap_or_default());
+    let (v, tags) = match (validation::validate_task(draft), tags) {
+        (Ok(v), Ok(tags)) => (v, tags),
+        (v, tags) => {
+            let mut errors = v.err().unwrap_or_default();
+            errors.extend(tags.err().unwrap_or_default());
+            return validation_error(errors, Lang::from_headers(&headers));
+        }
     };
 
-    let Some(t) = db.tasks.iter_mut().find(|t| t.id == id) else {
-        return (StatusCode::NOT_FOUND, "task not found").into_response();
+    let changes = TaskChanges {
+        title: v.title,
+        due_at: v.due_at,
+        duration_min: v.duration_min,
+        priority: v.priority,
+        status: input.status,
+        tags,
+        notes: v.notes,
     };
 
-    t.title = input.title;
-    t.due_at = due_at;
-    t.duration_min = input.duration_min;
-    t.priority = input.priority;
-    t.status = input.status.clone();
-    t.tags = input.tags;
-    t.notes = input.notes;
-
-    let updated = t.clone();
-
-    if store::save_db(&db).is_err() {
-        return (StatusCode::INTERNAL_SERVER_ERROR, "failed to save db").into_response();
+    let wip = if input.force { WipCheck::Force } else { WipCheck::Setting };
+    let cond = if_match(&headers);
+    match writer::send(|reply| Command::UpdateTask(id, cond, wip, changes, reply)).await {
+        Ok(Guarded::Applied(updated)) => saved_response(updated),
+        Ok(g) => refused(g),
+        Err(e) => write_error(&e),
     }
+}
 
-    Json(updated).into_response()
+// Query parameters for DELETE /api/tasks/:id
+#[derive(Debug, Deserialize)]
+pub struct DeleteQuery {
+    #[serde(default)]
+    pub purge: bool, // erase instead of moving to the trash
 }
 
 // -----------------------------
 // DELETE /api/tasks/:id
-// Removes a task permanently
+// Moves a task to the trash (honors If-Match)
+// ?purge=true removes it permanently
 // -----------------------------
-pub async fn delete_task(Path(id): Path<String>) -> impl IntoResponse {
+pub async fn delete_task(
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(q): Query<DeleteQuery>,
+) -> impl IntoResponse {
     let id = match Uuid::parse_str(&id) {
         Ok(u) => u,
         Err(_) => return (StatusCode::BAD_REQUEST, "invalid id").into_response(),
     };
 
-    let mut db: Db = match store::load_db() {
-        Ok(db) => db,
-        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "failed to load db").into_response(),
-    };
-
-    let before = db.tasks.len();
-    db.tasks.retain(|t| t.id != id);
-
-    if db.tasks.len() == before {
-        return (StatusCode::NOT_FOUND, "task not found").into_response();
+    let cond = if_match(&headers);
+    let removal = if q.purge { Removal::Purge } else { Removal::Trash };
+    match writer::send(|reply| Command::DeleteTask(id, cond, removal, reply)).await {
+        Ok(Guarded::Applied(())) => Json(serde_json::json!({ "ok": true })).into_response(),
+        Ok(g) => refused(g),
+        Err(e) => write_error(&e),
     }
-
-    if store::save_db(&db).is_err() {
-        return (StatusCode::INTERNAL_SERVER_ERROR, "failed to save db").into_response();
-    }
-
-    Json(serde_json::json!({ "ok": true })).into_response()
 }
 
 // -----------------------------
 // POST /api/tasks/:id/toggle
-// Toggles task status between Todo and Done
+// Toggles task status between Todo and Done (honors If-Match)
+// Moving into InProgress past wip_limit -> 409 unless ?force=true
 // -----------------------------
-pub async fn toggle_task(Path(id): Path<String>) -> impl IntoResponse {
+pub async fn toggle_task(
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(q): Query<ToggleQuery>,
+) -> impl IntoResponse {
     let id = match Uuid::parse_str(&id) {
         Ok(u) => u,
         Err(_) => return (StatusCode::BAD_REQUEST, "invalid id").into_response(),
     };
 
-    let mut db: Db = match store::load_db() {
-        Ok(db) => db,
-        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "failed to load db").into_response(),
+    let wip = if q.force { WipCheck::Force } else { WipCheck::Setting };
+    let cond = if_match(&headers);
+    match writer::send(|reply| Command::ToggleTask(id, cond, wip, reply)).await {
+        Ok(Guarded::Applied(updated)) => {
+            task_response(StatusCode::OK, updated, current_labels().as_ref())
+        }
+        Ok(g) => refused(g),
+        Err(e) => write_error(&e),
+    }
+}
+
+// Tasks matching every given field
+#[derive(Debug, Deserialize)]
+pub struct TagFilter {
+    pub tag: Option<String>, // has this tag (case-insensitive)
+    pub status: Option<TaskStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkTagsInput {
+    pub ids: Option<Vec<String>>,
+    pub filter: Option<TagFilter>,
+    #[serde(default)]
+    pub add: Vec<String>,
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+// Query parameters for POST /api/tasks/bulk-tags
+#[derive(Debug, Deserialize)]
+pub struct BulkTagsQuery {
+    #[serde(default)]
+    pub dry_run: bool, // report the changes without saving them
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagChange {
+    pub task_id: Uuid,
+    pub title: String,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkTagsResponse {
+    pub dry_run: bool,
+    pub tasks: Vec<TagChange>, // every selected task, changed or not
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub not_found: Vec<Uuid>, // listed ids with no active task
+}
+
+// Apply the tag edit to the selected active tasks in place.
+// A selected task must be listed in `ids` (when given) and match `filter` (when given).
+fn retag(
+    db: &mut Db,
+    ids: Option<&[Uuid]>,
+    filter: Option<&TagFilter>,
+    add: &[String],
+    remove: &[String],
+) -> BulkTagsResponse {
+    let selected = |t: &Task| {
+        ids.is_none_or(|ids| ids.contains(&t.id))
+            && filter.is_none_or(|f| {
+                f.status.as_ref().is_none_or(|s| *s == t.status)
+                    && f.tag.as_deref().is_none_or(|tag| {
+                        let tag = validation::tag_key(tag);
+                        t.tags.iter().flatten().any(|x| validation::tag_key(x) == tag)
+                    })
+            })
     };
 
-    let Some(t) = db.tasks.iter_mut().find(|t| t.id == id) else {
-        return (StatusCode::NOT_FOUND, "task not found").into_response();
+    let mut tasks = Vec::new();
+    for t in db.tasks.iter_mut().filter(|t| selected(t)) {
+        let before = t.tags.clone().unwrap_or_default();
+        let after = logic::edit_tags(t.tags.as_deref(), add, remove);
+        if after != t.tags {
+            t.tags = after;
+            t.rev += 1;
+            t.updated_at = clock::now();
+        }
+        tasks.push(TagChange {
+            task_id: t.id,
+            title: t.title.clone(),
+            before,
+            after: t.tags.clone().unwrap_or_default(),
+        });
+    }
+
+    let not_found = ids
+        .unwrap_or_default()
+        .iter()
+        .filter(|id| db.get(**id).is_none())
+        .copied()
+        .collect();
+    BulkTagsResponse { dry_run: false, tasks, not_found }
+}
+
+// -----------------------------
+// POST /api/tasks/bulk-tags?dry_run=BOOL
+// Adds and removes tags on many tasks in one save
+// - Select with "ids", "filter" ({tag, status}) or both (tasks must match both)
+// - Needs a selector and a non-empty "add" or "remove" (422 otherwise)
+// - A tag in both lists is removed; see logic::edit_tags
+// -----------------------------
+pub async fn bulk_tags(
+    headers: HeaderMap,
+    Query(q): Query<BulkTagsQuery>,
+    ApiJson(input): ApiJson<BulkTagsInput>,
+) -> impl IntoResponse {
+    let mut errors = Vec::new();
+    let mut tag_list = |field, raw: &[String]| match validation::clean_tags(field, raw) {
+        Ok(tags) => tags.unwrap_or_default(),
+        Err(e) => {
+            errors.extend(e);
+            Vec::new()
+        }
     };
+    let add = tag_list("add", &input.add);
+    let remove = tag_list("remove", &input.remove);
+
+    if input.ids.is_none() && input.filter.is_none() {
+        errors.push(FieldError::new("ids", Problem::Required));
+    }
+    if add.is_empty() && remove.is_empty() && errors.is_empty() {
+        errors.push(FieldError::new("add", Problem::Required));
+    }
+    let ids: Option<Vec<Uuid>> = input.ids.map(|ids| {
+        ids.iter()
+            .filter_map(|raw| match Uuid::parse_str(raw.trim()) {
+                Ok(id) => Some(id),
+                Err(_) => {
+                    errors.push(FieldError::new("ids", Problem::InvalidFormat { expected: "UUID" }));
+                    None
+                }
+            })
+            .collect()
+    });
+    if !errors.is_empty() {
+        return validation_error(errors, Lang::from_headers(&headers));
+    }
 
-    t.status = match t.status {
-        TaskStatus::Todo => TaskStatus::InProgress,
-        TaskStatus::InProgress => TaskStatus::Done,
-        TaskStatus::Done => TaskStatus::Todo,
+    let result = if q.dry_run {
+        let mut db = Db::clone(&writer::snapshot());
+        Ok(retag(&mut db, ids.as_deref(), input.filter.as_ref(), &add, &remove))
+    } else {
+        writer::apply_audited(move |db| {
+            let resp = retag(db, ids.as_deref(), input.filter.as_ref(), &add, &remove);
+            let audited: Vec<_> = resp
+                .tasks
+                .iter()
+                .filter(|c| c.before != c.after)
+                .map(|c| (AuditOp::Update, Some(c.task_id)))
+                .collect();
+            (resp, !audited.is_empty(), audited)
+        })
+        .await
     };
 
-    let updated = t.clone();
+    match result {
+        Ok(resp) => Json(BulkTagsResponse { dry_run: q.dry_run, ..resp }).into_response(),
+        Err(e) => write_error(&e),
+    }
+}
+
+// Query parameters for GET /api/tasks/stale
+#[derive(Debug, Deserialize)]
+pub struct StaleQuery {
+    #[serde(default = "default_stale_days")]
+    pub days: i64, // created more than this many days ago
+}
+
+fn default_stale_days() -> i64 {
+    14
+}
+
+#[derive(Debug, Serialize)]
+pub struct StaleTaskResponse {
+    pub task_id: Uuid,
+    pub title: String,
+    pub url: String,
+    pub status: TaskStatus,
+    pub due_at: String,
+    pub created_at: String,
+    pub age_days: i64,
+    pub postpone_count: i64,
+    pub days_since_update: i64,
+    pub suggested_action: SuggestedAction,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StaleBucketResponse {
+    pub bucket: AgeBucket,
+    pub tasks: Vec<StaleTaskResponse>,
+}
 
-    if store::save_db(&db).is_err() {
-        return (StatusCode::INTERNAL_SERVER_ERROR, "failed to save db").into_response();
+#[derive(Debug, Serialize)]
+pub struct StaleResponse {
+    pub now: String,
+    pub days: i64,
+    pub total: usize,
+    pub buckets: Vec<StaleBucketResponse>, // every bucket the threshold allows, possibly empty
+}
+
+// -----------------------------
+// GET /api/tasks/stale?days=N
+// Open tasks created more than N days ago (default 14), oldest
+// first, grouped into age buckets with a suggested action each
+// (stale.rs)
+// -----------------------------
+pub async fn get_stale_tasks(headers: HeaderMap, Query(q): Query<StaleQuery>) -> impl IntoResponse {
+    if q.days < 0 {
+        let error = FieldError::new("days", Problem::OutOfRange { min: 0, max: None });
+        return validation_error(vec![error], Lang::from_headers(&headers));
     }
+    let db = writer::snapshot();
+    let now = clock::now();
+
+    let stale = stale::stale_tasks(&db.tasks, q.days, now, &db.settings);
+    let buckets = AgeBucket::ALL
+        .into_iter()
+        .filter(|b| *b >= AgeBucket::of(q.days))
+        .map(|bucket| StaleBucketResponse {
+            bucket,
+            tasks: stale
+                .iter()
+                .filter(|s| s.bucket == bucket)
+                .map(|s| StaleTaskResponse {
+                    task_id: s.task.id,
+                    title: s.task.title.clone(),
+                    url: config::task_url(s.task.id),
+                    status: s.task.status.clone(),
+                    due_at: s.task.due_at.to_rfc3339(),
+                    created_at: s.task.created_at.to_rfc3339(),
+                    age_days: s.age_days,
+                    postpone_count: s.task.postpone_count,
+                    days_since_update: s.days_since_update,
+                    suggested_action: s.action,
+                })
+                .collect(),
+        })
+        .collect();
 
-    Json(updated).into_response()
+    Json(StaleResponse { now: now.to_rfc3339(), days: q.days, total: stale.len(), buckets }).into_response()
 }
 
 // -----------------------------
@@ -260,28 +798,493 @@ pub async fn toggle_task(Path(id): Path<String>) -> impl IntoResponse {
 // Returns day-level settings (start/end/focus block)
 // -----------------------------
 pub async fn get_settings() -> impl IntoResponse {
-    let db: Db = match store::load_db() {
-        Ok(db) => db,
-        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "failed to load db").into_response(),
-    };
-    Json(db.settings).into_response()
+    let db = writer::snapshot();
+    Json(db.settings.without_extra()).into_response()
+}
+
+// Saved settings plus non-fatal remarks about them
+#[derive(Debug, Serialize)]
+pub struct SettingsResponse {
+    #[serde(flatten)]
+    pub settings: DaySettings,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
 }
 
 // -----------------------------
 // PUT /api/settings
 // Updates day-level settings
+// - Invalid fields -> 422 listing all of them (validation::validate_settings)
+// - day_end <= day_start is accepted as an overnight window
+//   and reported in `warnings`
 // -----------------------------
-pub async fn put_settings(Json(s): Json<DaySettings>) -> impl IntoResponse {
-    let mut db: Db = match store::load_db() {
-        Ok(db) => db,
-        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "failed to load db").into_response(),
-    };
+pub async fn put_settings(headers: HeaderMap, ApiJson(s): ApiJson<DaySettings>) -> impl IntoResponse {
+    if let Some(resp) = api_json::unknown_extra("", s.extra.keys(), Lang::from_headers(&headers)) {
+        return resp;
+    }
+    if let Err(errors) = validation::validate_settings(&s) {
+        return validation_error(errors, Lang::from_headers(&headers));
+    }
 
-    db.settings = s;
+    let mut warnings = Vec::new();
+    if logic::is_overnight(&s) {
+        warnings.push(format!(
+            "day_end {} is not after day_start {}; the day window ends on the next date",
+            s.day_end, s.day_start
+        ));
+    }
 
-    if store::save_db(&db).is_err() {
-        return (StatusCode::INTERNAL_SERVER_ERROR, "failed to save db").into_response();
+    match writer::send(|reply| Command::PutSettings(s, reply)).await {
+        Ok(settings) => Json(SettingsResponse { settings: settings.without_extra(), warnings }).into_response(),
+        Err(e) => write_error(&e),
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request};
+    use serde_json::{json, Value};
+
+    async fn call(method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let (status, _, body) = call_with(method, uri, &[], body).await;
+        (status, body)
+    }
+
+    async fn call_with(method: &str, uri: &str, headers: &[(&str, &str)], body: Option<Value>) -> (StatusCode, HeaderMap, Value) {
+        let mut req = Request::builder().method(method).uri(uri).header("content-type", "application/json");
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        crate::test_request(req.body(body.map_or_else(Body::empty, |b| Body::from(b.to_string()))).unwrap()).await
+    }
+
+    fn draft(title: &str) -> Value {
+        json!({ "title": title, "due_at": "2024-06-03T12:00:00-04:00", "duration_min": 30, "priority": 3 })
+    }
+
+    fn error_fields(body: &Value) -> Vec<(String, String)> {
+        body["errors"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|e| (e["field"].as_str().unwrap().to_string(), e["code"].as_str().unwrap().to_string()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn out_of_range_durations_are_rejected_on_create_and_update() {
+        let (status, created) = call("POST", "/api/tasks", Some(draft("duration-431"))).await;
+        assert_eq!(status, StatusCode::OK);
+        let uri = format!("/api/tasks/{}", created["id"].as_str().unwrap());
+
+        for duration in [0, -1, 1441, i64::MAX, i64::MIN] {
+            let mut body = draft("duration-431");
+            body["duration_min"] = json!(duration);
+            let (status, errors) = call("POST", "/api/tasks", Some(body.clone())).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "create {duration}");
+            assert_eq!(error_fields(&errors), [("duration_min".to_string(), "out_of_range".to_string())]);
+
+            body["status"] = json!("todo");
+            let (status, errors) = call("PUT", &uri, Some(body)).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "update {duration}");
+            assert_eq!(error_fields(&errors), [("duration_min".to_string(), "out_of_range".to_string())]);
+        }
+
+        for duration in [1, 1440] {
+            let mut body = draft("duration-431");
+            body["duration_min"] = json!(duration);
+            body["status"] = json!("todo");
+            let (status, saved) = call("PUT", &uri, Some(body)).await;
+            assert_eq!(status, StatusCode::OK, "update {duration}");
+            assert_eq!(saved["duration_min"], duration);
+        }
+    }
+
+    #[tokio::test]
+    async fn duplicate_create_conflicts_until_forced() {
+        let (status, first) = call("POST", "/api/tasks", Some(draft("Pay rent 434"))).await;
+        assert_eq!(status, StatusCode::OK);
 
-    Json(db.settings).into_response()
+        // Same day, different case and spacing
+        let mut again = draft("  pay   RENT 434 ");
+        again["due_at"] = json!("2024-06-03T20:00:00-04:00");
+        let (status, existing) = call("POST", "/api/tasks?dedupe=true", Some(again.clone())).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(existing["id"], first["id"]);
+
+        again["force"] = json!(true);
+        let (status, forced) = call("POST", "/api/tasks?dedupe=true", Some(again)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_ne!(forced["id"], first["id"]);
+    }
+
+    #[tokio::test]
+    async fn near_misses_are_not_duplicates() {
+        let (status, _) = call("POST", "/api/tasks", Some(draft("Water plants 434"))).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let mut next_day = draft("Water plants 434");
+        next_day["due_at"] = json!("2024-06-04T00:30:00-04:00");
+        let (status, _) = call("POST", "/api/tasks?dedupe=true", Some(next_day)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = call("POST", "/api/tasks?dedupe=true", Some(draft("Water plants 434 too"))).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Without ?dedupe (and the setting off) nothing is checked
+        let (status, _) = call("POST", "/api/tasks", Some(draft("Water plants 434"))).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn create_reports_every_field_error_together() {
+        let body = json!({ "title": "", "due_at": "next week", "duration_min": 30, "priority": 0 });
+        let (status, errors) = call("POST", "/api/tasks", Some(body)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let fields: Vec<String> = error_fields(&errors).into_iter().map(|(field, _)| field).collect();
+        assert_eq!(fields, ["title", "due_at", "priority"]);
+        assert!(errors["errors"].as_array().unwrap().iter().all(|e| e["message"].as_str().is_some_and(|m| !m.is_empty())));
+    }
+
+    fn etag_of(headers: &HeaderMap) -> String {
+        headers[header::ETAG].to_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn if_match_guards_update_delete_and_toggle() {
+        let (_, created) = call("POST", "/api/tasks", Some(draft("if-match 440"))).await;
+        let uri = format!("/api/tasks/{}", created["id"].as_str().unwrap());
+        let (status, headers, _) = call_with("GET", &uri, &[], None).await;
+        assert_eq!(status, StatusCode::OK);
+        let first = etag_of(&headers);
+
+        // Happy path: the current ETag lets the write through and a new one comes back
+        let mut body = draft("if-match 440 edited");
+        body["status"] = json!("todo");
+        let (status, headers, saved) = call_with("PUT", &uri, &[("if-match", &first)], Some(body.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        let second = etag_of(&headers);
+        assert_ne!(first, second);
+        assert_eq!(second, format!("\"{}\"", saved["rev"]));
+
+        // The old ETag is now stale: 412 with the current task, nothing changed
+        body["title"] = json!("clobbered 440");
+        let (status, headers, current) = call_with("PUT", &uri, &[("if-match", &first)], Some(body)).await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+        assert_eq!(etag_of(&headers), second);
+        assert_eq!(current["title"], "if-match 440 edited");
+        let toggle = format!("{uri}/toggle");
+        let (status, _, _) = call_with("POST", &toggle, &[("if-match", &first)], None).await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+        let (status, _, _) = call_with("DELETE", &uri, &[("if-match", "W/\"0\", \"999\"")], None).await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+
+        // Any listed ETag or "*" matches; no If-Match behaves as before
+        let (status, headers, _) = call_with("POST", &toggle, &[("if-match", &format!("\"999\", {second}"))], None).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _, _) = call_with("POST", &toggle, &[("if-match", "*")], None).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _, _) = call_with("POST", &toggle, &[], None).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _, _) = call_with("DELETE", &uri, &[("if-match", &etag_of(&headers))], None).await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+        let (_, headers, _) = call_with("GET", &uri, &[], None).await;
+        let (status, _, _) = call_with("DELETE", &uri, &[("if-match", &etag_of(&headers))], None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn task_responses_report_postpone_count() {
+        let (_, created) = call("POST", "/api/tasks", Some(draft("postponed 449"))).await;
+        assert_eq!(created["postpone_count"], 0);
+        let uri = format!("/api/tasks/{}", created["id"].as_str().unwrap());
+
+        for (due, expected) in [("2024-06-04T12:00:00-04:00", 1), ("2024-06-03T08:00:00-04:00", 1), ("2024-06-05T08:00:00-04:00", 2)] {
+            let mut body = draft("postponed 449");
+            body["due_at"] = json!(due);
+            body["status"] = json!("todo");
+            let (_, saved) = call("PUT", &uri, Some(body)).await;
+            assert_eq!(saved["postpone_count"], expected, "{due}");
+        }
+        let (_, fetched) = call("GET", &uri, None).await;
+        assert_eq!(fetched["postpone_count"], 2);
+    }
+
+    #[tokio::test]
+    async fn api_errors_carry_the_request_id() {
+        let body = json!({ "title": "", "due_at": "2024-06-03T12:00:00-04:00", "duration_min": 30, "priority": 3 });
+        let (status, headers, errors) = call_with("POST", "/api/tasks", &[("x-request-id", "form-453")], Some(body)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(headers["x-request-id"], "form-453");
+        assert_eq!(errors["request_id"], "form-453");
+    }
+
+    #[tokio::test]
+    async fn task_list_counts_every_task_in_progress() {
+        let mut body = draft("started 456");
+        body["due_at"] = json!("2031-01-10T12:00:00-05:00");
+        let (_, created) = call("POST", "/api/tasks", Some(body)).await;
+        let id = created["id"].as_str().unwrap();
+        let (status, toggled) = call("POST", &format!("/api/tasks/{id}/toggle"), None).await;
+        assert_eq!((status, toggled["status"].as_str()), (StatusCode::OK, Some("in_progress")));
+
+        // Counted even on a day the task is not listed for
+        let (_, listed) = call("GET", "/api/tasks?date=2031-01-06", None).await;
+        assert!(listed["tasks"].as_array().unwrap().iter().all(|t| t["id"] != created["id"]));
+        assert!(listed["in_progress_count"].as_u64().unwrap() >= 1);
+    }
+
+    #[tokio::test]
+    async fn bulk_tags_remove_wins_and_ignores_case() {
+        let mut ids = Vec::new();
+        for (title, tags) in [("retag a 460", json!(["Über", "home"])), ("retag b 460", json!(["über", "urgent"]))] {
+            let mut body = draft(title);
+            body["tags"] = tags;
+            let (_, created) = call("POST", "/api/tasks", Some(body)).await;
+            ids.push(created["id"].clone());
+        }
+        let edit = json!({ "ids": ids, "add": ["urgent", "later"], "remove": ["ÜBER", "Urgent"] });
+
+        let (status, preview) = call("POST", "/api/tasks/bulk-tags?dry_run=true", Some(edit.clone())).await;
+        assert_eq!((status, preview["dry_run"].as_bool()), (StatusCode::OK, Some(true)));
+        let (_, unchanged) = call("GET", &format!("/api/tasks/{}", ids[0].as_str().unwrap()), None).await;
+        assert_eq!(unchanged["tags"], json!(["über", "home"]));
+
+        let (status, applied) = call("POST", "/api/tasks/bulk-tags", Some(edit)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(applied["tasks"], preview["tasks"]);
+        let after: Vec<&Value> = applied["tasks"].as_array().unwrap().iter().map(|t| &t["after"]).collect();
+        assert_eq!(after, [&json!(["home", "later"]), &json!(["later"])]);
+
+        // By filter: the tag matches in any case
+        let edit = json!({ "filter": { "tag": "LATER", "status": "todo" }, "remove": ["Later"] });
+        let (_, applied) = call("POST", "/api/tasks/bulk-tags", Some(edit)).await;
+        let ours: Vec<&Value> = applied["tasks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|t| ids.contains(&t["task_id"]))
+            .map(|t| &t["after"])
+            .collect();
+        assert_eq!(ours, [&json!(["home"]), &json!([])]);
+    }
+
+    #[tokio::test]
+    async fn task_responses_link_to_the_task() {
+        let (_, created) = call("POST", "/api/tasks", Some(draft("linked 464"))).await;
+        let id = created["id"].as_str().unwrap();
+        let url = created["url"].as_str().unwrap();
+        assert_eq!(url, crate::config::task_url(Uuid::parse_str(id).unwrap()));
+        assert_eq!(url.rsplit_once("/#/task/").map(|(_, tail)| tail), Some(id));
+
+        let (_, fetched) = call("GET", &format!("/api/tasks/{id}"), None).await;
+        assert_eq!(fetched["url"], created["url"]);
+        let (_, toggled) = call("POST", &format!("/api/tasks/{id}/toggle"), None).await;
+        assert_eq!(toggled["url"], created["url"]);
+    }
+
+    #[test]
+    fn task_views_resolve_the_priority_label() {
+        let task = |priority| Task { priority, ..crate::models::test_task("labeled", crate::clock::local("2024-06-03 12:00")) };
+        let view = TaskView::new(task(5), None);
+        assert_eq!((view.priority_label.as_str(), view.priority_color.as_str()), ("Highest", "#ef4444"));
+
+        let labels = ["P1", "P2", "P3", "P4", "Critical"].map(|label| PriorityMeta { label: label.to_string(), color: "#aa0000".to_string() });
+        let json = serde_json::to_value(TaskView::new(task(5), Some(&labels))).unwrap();
+        assert_eq!((&json["priority"], &json["priority_label"], &json["priority_color"]), (&json!(5), &json!("Critical"), &json!("#aa0000")));
+        // Out-of-range priorities from old data take the nearest level
+        assert_eq!(TaskView::new(task(9), Some(&labels)).priority_label, "Critical");
+        assert_eq!(TaskView::new(task(0), Some(&labels)).priority_label, "P1");
+    }
+
+    #[tokio::test]
+    async fn tasks_longer_than_the_day_window_are_saved_with_a_warning() {
+        let mut body = draft("too long-476");
+        body["duration_min"] = json!(700);
+        let (status, created) = call("POST", "/api/tasks", Some(body.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(created["duration_min"], 700);
+        let warnings = created["warnings"].as_array().unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].as_str().unwrap().contains("splitting"));
+
+        // Back within the window: no warnings key at all
+        let uri = format!("/api/tasks/{}", created["id"].as_str().unwrap());
+        body["duration_min"] = json!(60);
+        body["status"] = json!("todo");
+        let (status, saved) = call("PUT", &uri, Some(body)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(saved.get("warnings").is_none());
+    }
+
+    #[test]
+    fn only_durations_past_a_usable_window_are_warned_about() {
+        let settings = DaySettings::default(); // 09:00-18:00
+        let task = |duration_min| Task { duration_min, ..crate::models::test_task("t", crate::clock::local("2024-06-03 12:00")) };
+        assert!(task_warnings(&task(540), &settings).is_empty());
+        assert_eq!(task_warnings(&task(541), &settings).len(), 1);
+
+        let overnight = DaySettings { day_start: "22:00".into(), day_end: "06:00".into(), ..DaySettings::default() };
+        assert!(task_warnings(&task(480), &overnight).is_empty());
+        let broken = DaySettings { day_start: "late".into(), ..DaySettings::default() };
+        assert!(task_warnings(&task(1440), &broken).is_empty());
+    }
+
+    #[tokio::test]
+    async fn fields_project_tasks_to_the_listed_keys() {
+        let mut body = draft("projected-477");
+        body["due_at"] = json!("2031-03-17T12:00:00-04:00");
+        body["notes"] = json!("n".repeat(2000));
+        body["tags"] = json!(["watch", "bulk"]);
+        let (status, created) = call("POST", "/api/tasks", Some(body)).await;
+        assert_eq!(status, StatusCode::OK);
+        let id = created["id"].as_str().unwrap();
+        let wanted = ["id", "title", "due_at", "priority", "status"];
+        let keys = |v: &Value| {
+            let mut keys: Vec<String> = v.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+        let mut expected: Vec<String> = wanted.iter().map(|k| k.to_string()).collect();
+        expected.sort();
+
+        let (_, full) = call("GET", "/api/tasks?date=2031-03-17", None).await;
+        let (status, compact) = call("GET", "/api/tasks?date=2031-03-17&fields=id,title,due_at,priority,status", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let listed = compact["tasks"].as_array().unwrap();
+        assert!(listed.iter().all(|t| keys(t) == expected));
+        // The heavy fields are not serialized at all. Compared on this
+        // task only: the lists also hold other tests' overdue tasks.
+        let entry = |list: &Value| list["tasks"].as_array().unwrap().iter().find(|t| t["id"] == id).unwrap().to_string();
+        let (full, compact) = (entry(&full), entry(&compact));
+        assert!(full.contains(&"n".repeat(2000)) && !compact.contains("nnnn") && !compact.contains("watch"));
+        assert!(compact.len() * 4 < full.len(), "{} vs {}", compact.len(), full.len());
+
+        let (status, one) = call("GET", &format!("/api/tasks/{id}?fields=title,%20title%20,notes"), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(keys(&one), ["notes", "title"]);
+
+        let (status, errors) = call("GET", &format!("/api/tasks/{id}?fields=title,comments"), None).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error_fields(&errors), [("fields".to_string(), "not_one_of".to_string())]);
+        let message = errors["errors"][0]["message"].as_str().unwrap();
+        assert!(message.contains("comments") && TASK_FIELDS.iter().all(|f| message.contains(f)));
+        let (status, _) = call("GET", "/api/tasks?date=2031-03-17&fields=bogus", None).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[test]
+    fn every_selectable_field_is_a_task_view_key() {
+        let mut task = crate::models::test_task("all", crate::clock::local("2024-06-03 12:00"));
+        task.notes = Some("n".into());
+        task.tags = Some(vec!["t".into()]);
+        task.completed_at = Some(task.due_at);
+        task.deadline_notified_at = Some(task.due_at);
+        task.updated_at = task.due_at;
+        task.locked = Some(crate::models::PlanLock { start: task.due_at, end: task.due_at });
+        let view = TaskView::new(task, None);
+        let TaskProjection::Fields(map) = TaskProjection::new(view, Some(TASK_FIELDS)) else { panic!("not projected") };
+        let missing: Vec<_> = TASK_FIELDS.iter().filter(|f| !map.contains_key(**f)).collect();
+        assert!(missing.is_empty(), "{missing:?}");
+    }
+
+    #[tokio::test]
+    async fn stale_report_lists_the_buckets_its_threshold_allows() {
+        let buckets = |body: &Value| -> Vec<String> {
+            body["buckets"].as_array().unwrap().iter().map(|b| b["bucket"].as_str().unwrap().to_string()).collect()
+        };
+        let (status, all) = call("GET", "/api/tasks/stale?days=0", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(buckets(&all), ["under_2_weeks", "2_4_weeks", "1_3_months", "older"]);
+        let (_, default) = call("GET", "/api/tasks/stale", None).await;
+        assert_eq!((default["days"].as_i64(), buckets(&default).len()), (Some(14), 3));
+        let (_, old) = call("GET", "/api/tasks/stale?days=100", None).await;
+        assert_eq!(buckets(&old), ["older"]);
+
+        let (status, errors) = call("GET", "/api/tasks/stale?days=-1", None).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error_fields(&errors), [("days".to_string(), "out_of_range".to_string())]);
+    }
+
+    #[test]
+    fn unknown_stored_keys_stay_out_of_responses() {
+        let mut task = crate::models::test_task("extra", crate::clock::local("2024-06-03 12:00"));
+        task.extra.insert("subtasks".to_string(), json!([]));
+        let mut settings = DaySettings::default();
+        settings.extra.insert("theme".to_string(), json!("dark"));
+
+        let view = serde_json::to_value(TaskView::new(task, None)).unwrap();
+        assert!(view.get("subtasks").is_none() && view.get("title").is_some());
+        let settings = serde_json::to_value(settings.without_extra()).unwrap();
+        assert!(settings.get("theme").is_none() && settings.get("day_start").is_some());
+    }
+
+    #[tokio::test]
+    async fn store_errors_map_to_status_and_code() {
+        let corrupt = StoreError::Corrupt { line: 3, column: 14, message: "expected value".into(), backup: None };
+        let cases = [
+            (StoreError::NotFound, StatusCode::NOT_FOUND, "db_not_found"),
+            (corrupt, StatusCode::INTERNAL_SERVER_ERROR, "db_corrupt"),
+            (StoreError::PermissionDenied, StatusCode::INTERNAL_SERVER_ERROR, "db_permission_denied"),
+            (StoreError::Io(std::io::ErrorKind::StorageFull), StatusCode::INSUFFICIENT_STORAGE, "db_storage_full"),
+            (StoreError::Io(std::io::ErrorKind::Other), StatusCode::INTERNAL_SERVER_ERROR, "db_io"),
+        ];
+        for (e, status, code) in cases {
+            let res = store_error(&e);
+            assert_eq!(res.status(), status, "{code}");
+            let bytes = axum::body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+            let body: Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(body["error"], code);
+            assert_eq!(body["message"], e.describe());
+            if code == "db_corrupt" {
+                assert_eq!((body["line"].as_u64(), body["column"].as_u64()), (Some(3), Some(14)));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn a_hand_edit_of_db_json_is_reloaded_not_overwritten() {
+        let (_, kept) = call("POST", "/api/tasks", Some(draft("Edited by hand 510"))).await;
+        let (_, removed) = call("POST", "/api/tasks", Some(draft("Removed by hand 510"))).await;
+        let kept_id = kept["id"].as_str().unwrap().to_string();
+        let removed_id = removed["id"].as_str().unwrap().to_string();
+        let added = crate::models::test_task("Added by hand 510", crate::clock::local("2024-06-03 12:00"));
+        let added_id = added.id;
+
+        // Edited from inside the writer: no save can land between the
+        // last one and the edit, and the next write sees the change
+        let edit = {
+            let kept_id = kept_id.clone();
+            let removed_id = removed_id.clone();
+            move |_: &mut Db| {
+                let path = crate::store::db_path();
+                let mut doc: Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+                let tasks = doc["tasks"].as_array_mut().unwrap();
+                tasks.retain(|t| t["id"] != removed_id.as_str());
+                let edited = tasks.iter_mut().find(|t| t["id"] == kept_id.as_str()).unwrap();
+                edited["title"] = "Edited by hand 510 (again)".into();
+                tasks.push(serde_json::to_value(&added).unwrap());
+                std::fs::write(path, serde_json::to_string_pretty(&doc).unwrap()).unwrap();
+                ((), false)
+            }
+        };
+        writer::apply(edit).await.unwrap();
+
+        // A command on a task the edit removed does not run
+        let (status, body) = call("POST", &format!("/api/tasks/{removed_id}/toggle"), None).await;
+        assert_eq!((status, body["error"].as_str()), (StatusCode::CONFLICT, Some("task_removed")));
+
+        let (status, _) = call("POST", "/api/tasks", Some(draft("Created after the edit 510"))).await;
+        assert_eq!(status, StatusCode::OK);
+        let db = writer::snapshot();
+        let title = |id: &str| db.get(id.parse().unwrap()).map(|t| t.title.clone());
+        assert_eq!(title(&kept_id).as_deref(), Some("Edited by hand 510 (again)"));
+        assert_eq!(title(&removed_id), None);
+        assert!(db.get(added_id).is_some());
+
+        let saved = std::fs::read_to_string(crate::store::db_path()).unwrap();
+        assert!(saved.contains("Edited by hand 510 (again)") && saved.contains("Added by hand 510"));
+        assert!(saved.contains("Created after the edit 510") && !saved.contains("Removed by hand 510"));
+    }
 }
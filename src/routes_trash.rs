@@ -0,0 +1,209 @@
+// --------------------------------------------------
+// Handles the trash: tasks deleted with DELETE /api/tasks/:id.
+//
+// Responsibilities:
+// - List trashed tasks, most recently deleted first
+// - Restore one into tasks (at the end of the list)
+// - Purge one for good
+//
+// Trashed tasks live in db.trash, outside db.tasks, so listing,
+// planning and stats never see them.
+// --------------------------------------------------
+
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Serialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::models::{AuditOp, Task};
+use crate::routes_tasks::{self, TaskView};
+use crate::writer;
+
+// One entry of GET /api/trash.
+#[derive(Debug, Serialize)]
+pub struct TrashEntry {
+    pub task: TaskView,
+    pub deleted_at: String, // RFC3339
+}
+
+// Result of a restore inside the writer.
+enum Restore {
+    Restored(Box<Task>),
+    NotFound,
+    Conflict, // a task with the id is already in tasks or archived
+}
+
+// -----------------------------
+// GET /api/trash
+// Lists trashed tasks, most recently deleted first
+// -----------------------------
+pub async fn get_trash() -> impl IntoResponse {
+    let db = writer::snapshot();
+    let labels = db.settings.priority_labels.as_ref();
+    let trash: Vec<TrashEntry> = db
+        .trash
+        .iter()
+        .rev()
+        .map(|t| TrashEntry {
+            task: TaskView::new(t.task.clone(), labels),
+            deleted_at: t.deleted_at.to_rfc3339(),
+        })
+        .collect();
+    Json(json!({ "trash": trash }))
+}
+
+// -----------------------------
+// POST /api/trash/:id/restore
+// Moves a trashed task back into tasks
+// 404 when it is not in the trash, 409 when the id is taken
+// -----------------------------
+pub async fn restore_task(Path(id): Path<String>) -> impl IntoResponse {
+    let id = match Uuid::parse_str(&id) {
+        Ok(u) => u,
+        Err(_) => return (StatusCode::BAD_REQUEST, "invalid id").into_response(),
+    };
+
+    let restored = writer::apply_audited(move |db| {
+        let Some(i) = db.trash.iter().position(|t| t.task.id == id) else {
+            return (Restore::NotFound, false, Vec::new());
+        };
+        if db.get(id).is_some() || db.archived.iter().any(|t| t.id == id) {
+            return (Restore::Conflict, false, Vec::new());
+        }
+        let task = db.trash.remove(i).task;
+        db.insert(task.clone());
+        (Restore::Restored(Box::new(task)), true, vec![(AuditOp::Restore, Some(id))])
+    })
+    .await;
+
+    match restored {
+        Ok(Restore::Restored(task)) => routes_tasks::saved_response(*task),
+        Ok(Restore::NotFound) => (StatusCode::NOT_FOUND, "task not in trash").into_response(),
+        Ok(Restore::Conflict) => {
+            let body = json!({
+                "error": "id_taken",
+                "message": format!("a task with id {id} already exists; purge the trashed copy or delete the other"),
+            });
+            (StatusCode::CONFLICT, Json(body)).into_response()
+        }
+        Err(e) => routes_tasks::write_error(&e),
+    }
+}
+
+// -----------------------------
+// DELETE /api/trash/:id
+// Removes a trashed task permanently
+// -----------------------------
+pub async fn purge_task(Path(id): Path<String>) -> impl IntoResponse {
+    let id = match Uuid::parse_str(&id) {
+        Ok(u) => u,
+        Err(_) => return (StatusCode::BAD_REQUEST, "invalid id").into_response(),
+    };
+
+    let purged = writer::apply_audited(move |db| {
+        let before = db.trash.len();
+        db.trash.retain(|t| t.task.id != id);
+        let purged = db.trash.len() < before;
+        let audited = if purged { vec![(AuditOp::Purge, Some(id))] } else { Vec::new() };
+        (purged, purged, audited)
+    })
+    .await;
+
+    match purged {
+        Ok(true) => Json(json!({ "ok": true })).into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "task not in trash").into_response(),
+        Err(e) => routes_tasks::write_error(&e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use chrono::NaiveDate;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::{clock, logic};
+
+    async fn call(method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let req = Request::builder().method(method).uri(uri).header("content-type", "application/json");
+        let req = req.body(body.map_or_else(Body::empty, |b| Body::from(b.to_string()))).unwrap();
+        let (status, _, body) = crate::test_request(req).await;
+        (status, body)
+    }
+
+    // A new task due 2031-07-01, deleted into the trash.
+    async fn trashed(title: &str) -> Uuid {
+        let draft = json!({ "title": title, "due_at": "2031-07-01T12:00:00-04:00", "duration_min": 30, "priority": 3 });
+        let (_, created) = call("POST", "/api/tasks", Some(draft)).await;
+        let id = created["id"].as_str().unwrap().to_string();
+        assert_eq!(call("DELETE", &format!("/api/tasks/{id}"), None).await.0, StatusCode::OK);
+        Uuid::parse_str(&id).unwrap()
+    }
+
+    fn in_trash(id: Uuid) -> bool {
+        writer::snapshot().trash.iter().any(|t| t.task.id == id)
+    }
+
+    #[tokio::test]
+    async fn trashed_tasks_are_not_listed_or_planned() {
+        let id = trashed("Trashed 511").await;
+        let draft = json!({ "title": "Listed 511", "due_at": "2031-07-01T13:00:00-04:00", "duration_min": 30, "priority": 3 });
+        call("POST", "/api/tasks", Some(draft)).await;
+        let (_, listed) = call("GET", "/api/tasks?date=2031-07-01", None).await;
+        let titles: Vec<&Value> = listed["tasks"].as_array().unwrap().iter().map(|t| &t["title"]).collect();
+        assert!(titles.contains(&&json!("Listed 511")) && !titles.contains(&&json!("Trashed 511")), "{titles:?}");
+
+        let db = writer::snapshot();
+        let date = NaiveDate::from_ymd_opt(2031, 7, 1).unwrap();
+        let relevant = logic::relevant_tasks(&db.tasks, &db.settings, date, clock::now());
+        assert!(relevant.iter().all(|t| t.id != id));
+        assert!(in_trash(id));
+    }
+
+    #[tokio::test]
+    async fn restore_refuses_a_taken_or_unknown_id() {
+        let live = trashed("Back in tasks 511").await;
+        let archived = trashed("Back in archived 511").await;
+        // Copies brought back another way, as an import would
+        writer::apply(move |db| {
+            let copies: Vec<Task> = db.trash.iter().map(|t| t.task.clone()).filter(|t| t.id == live || t.id == archived).collect();
+            for t in copies {
+                if t.id == live {
+                    db.insert(t);
+                } else {
+                    db.archived.push(t);
+                }
+            }
+            ((), true)
+        })
+        .await
+        .unwrap();
+
+        for id in [live, archived] {
+            let (status, body) = call("POST", &format!("/api/trash/{id}/restore"), None).await;
+            assert_eq!(status, StatusCode::CONFLICT);
+            assert_eq!(body["error"], "id_taken");
+            assert!(in_trash(id));
+        }
+        let unknown = Uuid::new_v4();
+        assert_eq!(call("POST", &format!("/api/trash/{unknown}/restore"), None).await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn a_purged_task_is_gone_for_good() {
+        let id = trashed("Purged 511").await;
+        let (status, body) = call("DELETE", &format!("/api/trash/{id}"), None).await;
+        assert_eq!((status, body), (StatusCode::OK, json!({ "ok": true })));
+
+        let db = writer::snapshot();
+        assert!(!in_trash(id) && db.get(id).is_none() && db.archived.iter().all(|t| t.id != id));
+        assert_eq!(call("DELETE", &format!("/api/trash/{id}"), None).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(call("POST", &format!("/api/trash/{id}/restore"), None).await.0, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,180 @@
+// --------------------------------------------------
+// Aging report of open tasks (GET /api/tasks/stale).
+//
+// A task is stale when it is not Done and was created
+// more than `days` days ago. Stale tasks are grouped by
+// age into buckets and each gets a suggested action:
+// - split: longer than the day window, it can never be planned
+// - archive: older than 3 months and untouched for a month
+// - reschedule: anything else
+//
+// "Last update" is updated_at, which is created_at for tasks
+// never edited.
+// Pure, independent from HTTP / Axum.
+// --------------------------------------------------
+
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+
+use crate::logic;
+use crate::models::{DaySettings, Task, TaskStatus};
+
+// Untouched for this long, a task in the oldest bucket is suggested for archiving.
+const ARCHIVE_IDLE_DAYS: i64 = 30;
+
+// Age group of a stale task, by days since created_at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum AgeBucket {
+    #[serde(rename = "under_2_weeks")]
+    Under2Weeks, // only with days < 14
+    #[serde(rename = "2_4_weeks")]
+    Weeks2To4, // 14..28 days
+    #[serde(rename = "1_3_months")]
+    Months1To3, // 28..90 days
+    #[serde(rename = "older")]
+    Older, // 90 days and more
+}
+
+impl AgeBucket {
+    pub const ALL: [AgeBucket; 4] =
+        [AgeBucket::Under2Weeks, AgeBucket::Weeks2To4, AgeBucket::Months1To3, AgeBucket::Older];
+
+    pub fn of(age_days: i64) -> AgeBucket {
+        match age_days {
+            ..14 => AgeBucket::Under2Weeks,
+            14..28 => AgeBucket::Weeks2To4,
+            28..90 => AgeBucket::Months1To3,
+            _ => AgeBucket::Older,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestedAction {
+    Archive,
+    Reschedule,
+    Split,
+}
+
+// One stale task with the numbers its classification used.
+#[derive(Debug, Clone)]
+pub struct StaleTask<'a> {
+    pub task: &'a Task,
+    pub age_days: i64,
+    pub days_since_update: i64,
+    pub bucket: AgeBucket,
+    pub action: SuggestedAction,
+}
+
+// What to suggest for a stale task.
+pub fn suggest(task: &Task, bucket: AgeBucket, days_since_update: i64, settings: &DaySettings) -> SuggestedAction {
+    if logic::exceeds_day_window(task.duration_min, settings) {
+        SuggestedAction::Split
+    } else if bucket == AgeBucket::Older && days_since_update >= ARCHIVE_IDLE_DAYS {
+        SuggestedAction::Archive
+    } else {
+        SuggestedAction::Reschedule
+    }
+}
+
+// Classify one task; None when it is Done or not older than `days`.
+pub fn classify<'a>(
+    task: &'a Task,
+    days: i64,
+    now: DateTime<FixedOffset>,
+    settings: &DaySettings,
+) -> Option<StaleTask<'a>> {
+    if task.status == TaskStatus::Done {
+        return None;
+    }
+    let age_days = (now - task.created_at).num_days();
+    if age_days < days {
+        return None;
+    }
+    let last_update = task.updated_at;
+    let days_since_update = (now - last_update).num_days().max(0);
+    let bucket = AgeBucket::of(age_days);
+    Some(StaleTask {
+        task,
+        age_days,
+        days_since_update,
+        bucket,
+        action: suggest(task, bucket, days_since_update, settings),
+    })
+}
+
+// All stale tasks, oldest first (ties by id).
+pub fn stale_tasks<'a>(
+    tasks: &'a [Task],
+    days: i64,
+    now: DateTime<FixedOffset>,
+    settings: &DaySettings,
+) -> Vec<StaleTask<'a>> {
+    let mut out: Vec<StaleTask> = tasks.iter().filter_map(|t| classify(t, days, now, settings)).collect();
+    out.sort_by(|a, b| b.age_days.cmp(&a.age_days).then_with(|| a.task.id.cmp(&b.task.id)));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock;
+    use crate::models::test_task;
+
+    fn now() -> DateTime<FixedOffset> {
+        clock::local("2024-06-30 12:00")
+    }
+
+    // Open task created `age` days before now(), last edited `idle` days ago.
+    fn aged(title: &str, age: i64, idle: Option<i64>) -> Task {
+        let mut t = test_task(title, clock::local("2024-07-01 12:00"));
+        t.created_at = now() - chrono::Duration::days(age);
+        t.updated_at = now() - chrono::Duration::days(idle.unwrap_or(age));
+        t
+    }
+
+    #[test]
+    fn buckets_split_at_two_four_and_thirteen_weeks() {
+        let table = [(0, AgeBucket::Under2Weeks), (13, AgeBucket::Under2Weeks), (14, AgeBucket::Weeks2To4),
+            (27, AgeBucket::Weeks2To4), (28, AgeBucket::Months1To3), (89, AgeBucket::Months1To3),
+            (90, AgeBucket::Older), (400, AgeBucket::Older)];
+        for (days, bucket) in table {
+            assert_eq!(AgeBucket::of(days), bucket, "{days}");
+        }
+    }
+
+    #[test]
+    fn only_open_tasks_past_the_threshold_are_stale_oldest_first() {
+        let mut done = aged("done", 200, None);
+        done.status = TaskStatus::Done;
+        let tasks = vec![aged("young", 13, None), aged("two weeks", 14, None), aged("old", 120, None), done];
+        let settings = DaySettings::default();
+        let stale = stale_tasks(&tasks, 14, now(), &settings);
+        let found: Vec<(&str, i64, AgeBucket)> = stale.iter().map(|s| (s.task.title.as_str(), s.age_days, s.bucket)).collect();
+        assert_eq!(found, [("old", 120, AgeBucket::Older), ("two weeks", 14, AgeBucket::Weeks2To4)]);
+        assert_eq!(stale_tasks(&tasks, 0, now(), &settings).len(), 3);
+    }
+
+    #[test]
+    fn suggested_actions() {
+        let settings = DaySettings::default(); // 540 minute window
+        let mut long = aged("long", 20, None);
+        long.duration_min = 600;
+        let mut long_and_old = aged("long and old", 200, Some(100));
+        long_and_old.duration_min = 600;
+        // (task, action, days_since_update)
+        let cases = [
+            (long, SuggestedAction::Split, 20),
+            (long_and_old, SuggestedAction::Split, 100),
+            (aged("forgotten", 100, None), SuggestedAction::Archive, 100),
+            (aged("idle a month", 100, Some(30)), SuggestedAction::Archive, 30),
+            (aged("touched lately", 100, Some(5)), SuggestedAction::Reschedule, 5),
+            (aged("not that old", 60, Some(59)), SuggestedAction::Reschedule, 59),
+        ];
+        for (task, action, idle) in cases {
+            let s = classify(&task, 14, now(), &settings).unwrap();
+            assert_eq!((s.action, s.days_since_update), (action, idle), "{}", task.title);
+        }
+    }
+}
@@ -0,0 +1,189 @@
+// --------------------------------------------------
+// Sticky plans for GET /api/plan/today?sticky=true.
+//
+// The last plan for each (date, available_min, exclude_ids)
+// is kept in memory together with a snapshot of what it was
+// built from: the planning fields of every relevant task and
+// the settings. A later request with an identical snapshot
+// gets the same plan back (items already over are trimmed by
+// the caller); otherwise the plan is rebuilt from now and the
+// trigger says what changed.
+//
+// The cache lives in process memory only; a restart replans.
+// --------------------------------------------------
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::logic::{BudgetLedger, PlanItem, PlanWindow, UnplannedItem};
+use crate::models::{DaySettings, Task};
+
+// What a plan was built from: task id -> hash of its planning fields,
+// and a hash of the settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    tasks: BTreeMap<Uuid, u64>,
+    settings: u64,
+}
+
+fn hash_of(value: &impl Hash) -> u64 {
+    let mut h = DefaultHasher::new();
+    value.hash(&mut h);
+    h.finish()
+}
+
+impl Snapshot {
+    // Fields that can change a plan. rev is left out on purpose:
+    // notification bookkeeping and no-op edits should not replan.
+    pub fn of(relevant: &[&Task], settings: &DaySettings) -> Self {
+        let tasks = relevant
+            .iter()
+            .map(|t| {
+                let fields = (&t.title, t.due_at, t.duration_min, t.priority, format!("{:?}", t.status), t.locked);
+                (t.id, hash_of(&fields))
+            })
+            .collect();
+        let settings = hash_of(&serde_json::to_string(settings).unwrap_or_default());
+        Snapshot { tasks, settings }
+    }
+}
+
+// Why a sticky request was planned again.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(tag = "cause", rename_all = "snake_case")]
+pub enum Trigger {
+    FirstPlan, // nothing cached for this date/budget/exclusions
+    SettingsChanged,
+    TasksChanged {
+        added: Vec<Uuid>,   // newly relevant (created, reopened, moved to this date)
+        removed: Vec<Uuid>, // no longer relevant (completed, deleted, moved away)
+        changed: Vec<Uuid>, // title, due_at, duration, priority, status or lock edited
+    },
+}
+
+// What changed between two snapshots, or None if nothing did.
+pub fn diff(old: &Snapshot, new: &Snapshot) -> Option<Trigger> {
+    if old.settings != new.settings {
+        return Some(Trigger::SettingsChanged);
+    }
+    let added: Vec<Uuid> = new.tasks.keys().filter(|id| !old.tasks.contains_key(id)).copied().collect();
+    let removed: Vec<Uuid> = old.tasks.keys().filter(|id| !new.tasks.contains_key(id)).copied().collect();
+    let changed: Vec<Uuid> = new
+        .tasks
+        .iter()
+        .filter(|(id, h)| old.tasks.get(id).is_some_and(|o| o != *h))
+        .map(|(id, _)| *id)
+        .collect();
+    if added.is_empty() && removed.is_empty() && changed.is_empty() {
+        None
+    } else {
+        Some(Trigger::TasksChanged { added, removed, changed })
+    }
+}
+
+// Plan request a cached plan answers.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Key {
+    pub date: NaiveDate,
+    pub available_min: i64,
+    pub exclude: Vec<Uuid>, // sorted
+    pub clamp_to_date: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub snapshot: Snapshot,
+    pub generated_at: DateTime<FixedOffset>,
+    pub plan: Vec<PlanItem>,
+    pub unplanned: Vec<UnplannedItem>,
+    pub window: PlanWindow,
+    pub ledger: BudgetLedger, // of the plan as built, before items ran out
+}
+
+static CACHE: Mutex<Option<HashMap<Key, Entry>>> = Mutex::new(None);
+
+pub fn get(key: &Key) -> Option<Entry> {
+    CACHE.lock().ok()?.as_ref()?.get(key).cloned()
+}
+
+// Store a plan, dropping plans for dates before `today`.
+pub fn put(key: Key, entry: Entry, today: NaiveDate) {
+    if let Ok(mut cache) = CACHE.lock() {
+        let cache = cache.get_or_insert_with(HashMap::new);
+        cache.retain(|k, _| k.date >= today);
+        cache.insert(key, entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock;
+    use crate::models::{TaskStatus, test_task};
+
+    fn tasks() -> Vec<Task> {
+        ["write", "review", "ship"]
+            .iter()
+            .map(|title| test_task(title, clock::local("2024-06-03 12:00")))
+            .collect()
+    }
+
+    fn snapshot(tasks: &[Task], settings: &DaySettings) -> Snapshot {
+        Snapshot::of(&tasks.iter().collect::<Vec<_>>(), settings)
+    }
+
+    #[test]
+    fn unchanged_inputs_keep_the_plan() {
+        let settings = DaySettings::default();
+        let mut tasks = tasks();
+        let before = snapshot(&tasks, &settings);
+        // Bookkeeping that cannot move the plan
+        tasks[0].rev += 3;
+        tasks[1].notes = Some("more detail".to_string());
+        tasks[2].deadline_notified_at = Some(clock::local("2024-06-03 11:00"));
+        assert_eq!(diff(&before, &snapshot(&tasks, &settings)), None);
+    }
+
+    #[test]
+    fn completed_task_is_reported_removed() {
+        let settings = DaySettings::default();
+        let mut tasks = tasks();
+        let before = snapshot(&tasks, &settings);
+        // A done task drops out of the relevant list
+        let done = tasks.remove(1);
+        assert_eq!(
+            diff(&before, &snapshot(&tasks, &settings)),
+            Some(Trigger::TasksChanged { added: vec![], removed: vec![done.id], changed: vec![] })
+        );
+
+        // Still listed but done (a locked item, say) counts as changed
+        let mut tasks = self::tasks();
+        let before = snapshot(&tasks, &settings);
+        tasks[0].status = TaskStatus::Done;
+        assert_eq!(
+            diff(&before, &snapshot(&tasks, &settings)),
+            Some(Trigger::TasksChanged { added: vec![], removed: vec![], changed: vec![tasks[0].id] })
+        );
+    }
+
+    #[test]
+    fn added_task_and_settings_replan() {
+        let settings = DaySettings::default();
+        let mut tasks = tasks();
+        let before = snapshot(&tasks, &settings);
+        let new = test_task("new", clock::local("2024-06-03 15:00"));
+        tasks.push(new.clone());
+        tasks[0].duration_min += 15;
+        let trigger = diff(&before, &snapshot(&tasks, &settings));
+        assert_eq!(trigger, Some(Trigger::TasksChanged { added: vec![new.id], removed: vec![], changed: vec![tasks[0].id] }));
+
+        let later = DaySettings { day_start: "10:00".to_string(), ..settings };
+        assert_eq!(diff(&before, &snapshot(&self::tasks(), &later)), Some(Trigger::SettingsChanged));
+    }
+}
@@ -2,44 +2,439 @@
 // Responsible for persistent storage of application data.
 //
 // This module handles:
-// - Loading the database from a local JSON file
-// - Saving updates back to disk safely
+// - The Store trait the rest of the app loads and saves through
+// - JsonStore: the database as one local JSON file (the default),
+//   encrypted when a db key is configured (crypt.rs)
+// - SqliteStore (store_sqlite.rs, cargo feature "sqlite"), picked
+//   with storage.backend = "sqlite"
+// - PostgresStore (store_postgres.rs, cargo feature "postgres"),
+//   picked with storage.backend = "postgres"
+// - Checking hand-edited files for inconsistencies and repairing them
+// - Appending and reading daily snapshots (history.jsonl)
+// - The write-ahead journal (db.journal) and its replay on load
 //
 // Design choice:
-// - Local-first JSON storage (no external DB)
-// - Simple, hackathon-friendly, and portable
+// - Local-first storage (a file next to the binary, no server)
+// - The whole Db lives in memory (writer.rs); a backend only
+//   loads it at startup and persists each committed change
 // --------------------------------------------------
 
-use std::{fs, io, path::Path};
-use crate::models::Db;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::io::Write;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+use std::path::{Path, PathBuf};
+use std::{fs, io};
 
-// Path to the JSON database file.
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use crate::clock;
+use crate::compact;
+use crate::config::{self, StorageBackend};
+use crate::crypt::{self, CryptError};
+use crate::i18n::Lang;
+use crate::migrate;
+use crate::models::{self, AuditEntry, DaySettings, DaySnapshot, Db, JournalOp, SCHEMA_VERSION, Task, TaskStatus};
+use crate::request_id;
+use crate::validation;
+
+// Path to the database file (storage.db_path, default "data/db.json").
 // All application state (tasks + settings) is stored here.
-pub const DB_PATH: &str = "data/db.json";
+pub fn db_path() -> &'static str {
+    &config::get().storage.db_path
+}
+
+// The database in messages: db_path, or for PostgreSQL the host and
+// database name of database_url (no credentials).
+pub fn db_label() -> String {
+    let storage = &config::get().storage;
+    match (storage.backend, storage.database_url.as_deref()) {
+        (StorageBackend::Postgres, Some(url)) => {
+            let at = url.rsplit('@').next().unwrap_or(url);
+            let at = at.split_once("://").map_or(at, |(_, rest)| rest);
+            format!("postgres://{}", at.split('?').next().unwrap_or(at))
+        }
+        _ => db_path().to_string(),
+    }
+}
+
+// Boxed future returned by Store methods (the trait is used as
+// `dyn Store`, so its methods cannot be `async fn`).
+pub type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, StoreError>> + Send + 'a>>;
+
+// A place the Db is persisted. Implementations log their own
+// errors, tagged with the current request id.
+pub trait Store: Send + Sync {
+    // The whole Db, with the id index built. A missing database
+    // is created with default settings.
+    fn load(&self) -> StoreFuture<'_, Db>;
+
+    // Persist `db` as the new state; on error the stored state is
+    // the previous one.
+    fn save(&self, db: Arc<Db>) -> StoreFuture<'_, ()>;
+
+    // Whether the stored db was changed by someone else since this
+    // process last loaded or saved it (the writer then reloads).
+    fn changed_externally(&self) -> StoreFuture<'_, bool> {
+        Box::pin(async { Ok(false) })
+    }
+}
+
+static BACKEND: OnceLock<Box<dyn Store>> = OnceLock::new();
+
+// The configured backend (storage.backend), created on first use.
+pub fn backend() -> &'static dyn Store {
+    BACKEND
+        .get_or_init(|| match config::get().storage.backend {
+            StorageBackend::Json => Box::new(JsonStore),
+            #[cfg(feature = "sqlite")]
+            StorageBackend::Sqlite => Box::new(crate::store_sqlite::SqliteStore::new(db_path())),
+            #[cfg(feature = "postgres")]
+            StorageBackend::Postgres => Box::new(crate::store_postgres::PostgresStore::new(
+                config::get().storage.database_url.as_deref().unwrap_or_default(),
+            )),
+            // Rejected by config::resolve
+            #[cfg(not(feature = "sqlite"))]
+            StorageBackend::Sqlite => unreachable!("sqlite backend without the sqlite feature"),
+            #[cfg(not(feature = "postgres"))]
+            StorageBackend::Postgres => unreachable!("postgres backend without the postgres feature"),
+        })
+        .as_ref()
+}
+
+// Load the database through the configured backend, with the
+// writes left in db.journal by a crash replayed and saved.
+pub async fn load_db() -> Result<Db, StoreError> {
+    let mut db = backend().load().await?;
+    let ops = match load_journal().await {
+        Ok(ops) => ops,
+        Err(e) => {
+            eprintln!("  {}cannot read {}: {e}", request_id::log_tag(), journal_path());
+            return Err(StoreError::from_io(&e));
+        }
+    };
+    if ops.is_empty() {
+        return Ok(db);
+    }
+    let replayed = replay_journal(&mut db, ops);
+    let db = Arc::new(db);
+    save_db(db.clone()).await?;
+    clear_journal().await;
+    println!("  Replayed {replayed} unsaved write(s) from {}", journal_path());
+    Ok(Arc::unwrap_or_clone(db))
+}
+
+// Save the database through the configured backend.
+pub async fn save_db(db: Arc<Db>) -> Result<(), StoreError> {
+    backend().save(db).await
+}
+
+
+// Why db.json could not be loaded or saved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoreError {
+    NotFound,                                            // no file at db_path
+    // not JSON, or not a valid Db; backup: where a copy of the file was saved
+    Corrupt { line: usize, column: usize, message: String, backup: Option<String> },
+    TooNew { version: u32 }, // schema_version above what this build reads
+    Encrypted,               // encrypted, and no db key is configured
+    WrongKey,                // encrypted, and the db key does not open it
+    PermissionDenied,
+    Io(io::ErrorKind), // anything else, e.g. StorageFull
+}
+
+impl StoreError {
+    pub(crate) fn from_io(e: &io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => StoreError::NotFound,
+            io::ErrorKind::PermissionDenied => StoreError::PermissionDenied,
+            kind => StoreError::Io(kind),
+        }
+    }
+
+    fn from_crypt(e: CryptError) -> Self {
+        match e {
+            CryptError::NoKey => StoreError::Encrypted,
+            CryptError::WrongKey => StoreError::WrongKey,
+            CryptError::Damaged(message) => StoreError::Corrupt { line: 0, column: 0, message, backup: None },
+        }
+    }
+
+    fn from_json(e: &serde_json::Error) -> Self {
+        StoreError::Corrupt { line: e.line(), column: e.column(), message: e.to_string(), backup: None }
+    }
+
+    // Stable, machine-readable code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            StoreError::NotFound => "db_not_found",
+            StoreError::Corrupt { .. } => "db_corrupt",
+            StoreError::TooNew { .. } => "db_too_new",
+            StoreError::Encrypted => "db_encrypted",
+            StoreError::WrongKey => "db_wrong_key",
+            StoreError::PermissionDenied => "db_permission_denied",
+            StoreError::Io(io::ErrorKind::StorageFull) => "db_storage_full",
+            StoreError::Io(_) => "db_io",
+        }
+    }
+
+    // One-line description for logs and error bodies.
+    pub fn describe(&self) -> String {
+        let path = db_label();
+        match self {
+            StoreError::NotFound => format!("{path} does not exist"),
+            StoreError::Corrupt { message, backup: None, .. } => {
+                format!("{path} is not a valid database: {message}")
+            }
+            StoreError::Corrupt { message, backup: Some(backup), .. } => {
+                format!("{path} is not a valid database: {message}; a copy was saved to {backup}")
+            }
+            StoreError::TooNew { version } => format!(
+                "{path} has schema version {version}, written by a newer version; this build reads up to {SCHEMA_VERSION}"
+            ),
+            StoreError::Encrypted => {
+                format!("{path} is encrypted; set SCHEDULER_DB_KEY or --db-key-file to the passphrase")
+            }
+            StoreError::WrongKey => format!("cannot decrypt {path}: wrong db key, or the file was altered"),
+            StoreError::PermissionDenied => format!("no permission to access {path}"),
+            StoreError::Io(kind) => format!("cannot access {path}: {kind}"),
+        }
+    }
+}
+
+// --------------------------------------------------
+// JsonStore: db.json
+// --------------------------------------------------
+
+pub struct JsonStore;
+
+impl Store for JsonStore {
+    fn load(&self) -> StoreFuture<'_, Db> {
+        Box::pin(load_json())
+    }
+
+    fn save(&self, db: Arc<Db>) -> StoreFuture<'_, ()> {
+        Box::pin(save_json(db))
+    }
+
+    // db.json edited by hand (or by another tool) while the server
+    // runs. A file that disappeared does not count: the next save
+    // writes it again from memory.
+    fn changed_externally(&self) -> StoreFuture<'_, bool> {
+        Box::pin(async {
+            let seen = *SEEN.lock().unwrap_or_else(|e| e.into_inner());
+            Ok(matches!((seen, fingerprint().await), (Some(seen), Some(now)) if seen != now))
+        })
+    }
+}
+
+// Modification time and size of db.json, as last read or written
+// by this process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Fingerprint {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+static SEEN: Mutex<Option<Fingerprint>> = Mutex::new(None);
+
+async fn fingerprint() -> Option<Fingerprint> {
+    let meta = tokio::fs::metadata(db_path()).await.ok()?;
+    Some(Fingerprint { modified: meta.modified().ok(), len: meta.len() })
+}
 
+// Remember db.json as it is now.
+async fn remember_file() {
+    let now = fingerprint().await;
+    *SEEN.lock().unwrap_or_else(|e| e.into_inner()) = now;
+}
 
 // --------------------------------------------------
-// Load the database from disk.
+// Load db.json from disk.
+//
+// File access goes through tokio::fs and parsing runs on the
+// blocking pool, so a large db never stalls the async workers.
 //
 // Steps:
-// 1. Read the JSON file as a string
-// 2. Deserialize it into the Db struct
-// 3. Return the in-memory Db representation
+// 1. Read the file, decrypting it when it is encrypted (crypt.rs)
+// 2. Migrate it from an older schema_version (migrate.rs) and
+//    deserialize it into the Db struct
+// 3. Build the task id index
+// 4. Return the in-memory Db representation
 //
-// Errors:
-// - IO error if file is missing or unreadable
-// - Deserialization error if JSON is invalid
+// A missing file is not an error: an empty Db with default
+// settings is saved in its place and returned. A migrated file
+// is saved back at once, after copying the old one to
+// db.json.v<old version>. So is a plain file while a db key is
+// configured, which is how an existing db.json gets encrypted.
+//
+// Errors (logged with the request id, if any):
+// - PermissionDenied / Io if the file cannot be read (or created)
+// - Corrupt, with the parse position, if it is not a valid Db.
+//   The file is copied to db.json.corrupt-<timestamp> first, so
+//   it survives whatever is saved in its place (--recover)
+// - TooNew if a newer build wrote it; the file is left alone
+// - Encrypted / WrongKey if it is encrypted and the configured
+//   key (if any) does not open it; the file is left alone
 // --------------------------------------------------
-pub fn load_db() -> io::Result<Db> {
-    let text = fs::read_to_string(DB_PATH)?;
-    let db: Db =
-        serde_json::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-    Ok(db)
+async fn load_json() -> Result<Db, StoreError> {
+    // An error here is left for the read below to report
+    if let Ok(false) = tokio::fs::try_exists(db_path()).await {
+        return create_db().await;
+    }
+    // Taken before the read: an edit racing it is seen as a change
+    // by the next write, which then loads the file again. Put back
+    // when the file does not load, so every write keeps refusing
+    // to overwrite it until it is fixed.
+    let previous = *SEEN.lock().unwrap_or_else(|e| e.into_inner());
+    remember_file().await;
+    let mut plain = false;
+    let loaded = match tokio::fs::read(db_path()).await {
+        Ok(bytes) => {
+            plain = !crypt::is_encrypted(&bytes);
+            tokio::task::spawn_blocking(move || db_text(bytes).and_then(|text| decode(&text)))
+                .await
+                .map_err(|e| (StoreError::Io(io::ErrorKind::Other), Some(e.to_string())))
+                .and_then(|parsed| parsed.map_err(|e| (e, None)))
+        }
+        Err(e) => Err((StoreError::from_io(&e), Some(e.to_string()))),
+    };
+    match loaded {
+        Ok(Decoded { mut db, from_version, steps }) => {
+            upgrade(&mut db);
+            let encrypt = plain && crypt::enabled();
+            if steps.is_empty() && !encrypt {
+                return Ok(db);
+            }
+            let old = format!("{}.v{from_version}", db_path());
+            if !steps.is_empty()
+                && let Err(e) = tokio::fs::copy(db_path(), &old).await
+            {
+                eprintln!("  {}cannot copy {} to {old}: {e}", request_id::log_tag(), db_path());
+                return Err(StoreError::from_io(&e));
+            }
+            let db = Arc::new(db);
+            save_json(db.clone()).await?;
+            if !steps.is_empty() {
+                println!("  Migrated {} from schema v{from_version} to v{SCHEMA_VERSION} (old file: {old})", db_path());
+                for step in steps {
+                    println!("    {step}");
+                }
+            }
+            if encrypt {
+                println!("  Encrypted {} with the db key", db_path());
+            }
+            Ok(Arc::unwrap_or_clone(db))
+        }
+        Err((mut e, cause)) => {
+            *SEEN.lock().unwrap_or_else(|e| e.into_inner()) = previous;
+            if let StoreError::Corrupt { backup, .. } = &mut e {
+                *backup = back_up_corrupt(db_path()).await;
+            }
+            let cause = cause.map(|c| format!(" ({c})")).unwrap_or_default();
+            eprintln!("  {}failed to load: {}{cause}", request_id::log_tag(), e.describe());
+            Err(e)
+        }
+    }
+}
+
+// The JSON text of a db file's contents, decrypted first when it is
+// encrypted. Slow for an encrypted file the first time (key
+// derivation); call it off the async workers.
+pub fn db_text(bytes: Vec<u8>) -> Result<String, StoreError> {
+    let bytes = if crypt::is_encrypted(&bytes) {
+        crypt::open(&bytes).map_err(StoreError::from_crypt)?
+    } else {
+        bytes
+    };
+    String::from_utf8(bytes).map_err(|e| StoreError::Corrupt {
+        line: 0,
+        column: 0,
+        message: format!("not UTF-8 text: {e}"),
+        backup: None,
+    })
 }
 
+// The bytes to store for `value` (the db, or an archive file): pretty
+// JSON, encrypted when a db key is configured.
+fn db_bytes<T: Serialize>(value: &T) -> io::Result<Vec<u8>> {
+    let text = serde_json::to_string_pretty(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if !crypt::enabled() {
+        return Ok(text.into_bytes());
+    }
+    crypt::seal(text.as_bytes()).map_err(|e| io::Error::other(format!("cannot encrypt: {}", e.describe())))
+}
+
+// A stored db, brought up to the current schema.
+pub struct Decoded {
+    pub db: Db,
+    pub from_version: u32,        // schema_version it was stored with
+    pub steps: Vec<&'static str>, // migrate::STEPS that ran, by title
+}
+
+// Parse a db document (db.json's text). A file at the current
+// version is deserialized directly, so errors keep their line and
+// column; an older one goes through decode_value.
+pub fn decode(text: &str) -> Result<Decoded, StoreError> {
+    #[derive(Deserialize)]
+    struct Version {
+        #[serde(default = "models::legacy_schema_version")]
+        schema_version: u32,
+    }
+    let version: Version = serde_json::from_str(text).map_err(|e| StoreError::from_json(&e))?;
+    if version.schema_version != SCHEMA_VERSION {
+        let value: Value = serde_json::from_str(text).map_err(|e| StoreError::from_json(&e))?;
+        return decode_value(value);
+    }
+    let db = serde_json::from_str(text).map_err(|e| StoreError::from_json(&e))?;
+    Ok(Decoded { db, from_version: SCHEMA_VERSION, steps: Vec::new() })
+}
+
+// Migrate a db document to the current schema and deserialize it.
+pub fn decode_value(mut value: Value) -> Result<Decoded, StoreError> {
+    let from_version = migrate::version_of(&value);
+    let steps = migrate::migrate_value(&mut value).map_err(|e| StoreError::TooNew { version: e.version })?;
+    let db = serde_json::from_value(value).map_err(|e| StoreError::from_json(&e))?;
+    Ok(Decoded { db, from_version, steps })
+}
+
+// Copy an unparsable db file to <path>.corrupt-<timestamp>.
+// None (logged) if the copy could not be made.
+async fn back_up_corrupt(path: &str) -> Option<String> {
+    let backup = format!("{path}.corrupt-{}", clock::now().format("%Y%m%dT%H%M%S"));
+    match tokio::fs::copy(path, &backup).await {
+        Ok(_) => Some(backup),
+        Err(e) => {
+            eprintln!("  {}cannot back up {path} to {backup}: {e}", request_id::log_tag());
+            None
+        }
+    }
+}
+
+
+// Save and return an empty Db with default settings, for a fresh install.
+async fn create_db() -> Result<Db, StoreError> {
+    let db = Arc::new(Db::default());
+    save_json(db.clone()).await?;
+    println!("  Created {} with default settings", db_path());
+    Ok(Arc::unwrap_or_clone(db))
+}
+
+// Load-time upkeep after serde has filled defaults for missing
+// fields: rebuild the id index. Changes to the stored shape are
+// schema steps (migrate::STEPS), which run before deserializing.
+pub fn upgrade(db: &mut Db) {
+    db.reindex();
+}
 
 // --------------------------------------------------
-// Save the database back to disk.
+// Save db.json back to disk.
 //
 // Safety strategy:
 // - Write to a temporary file first
@@ -47,21 +442,1136 @@ pub fn load_db() -> io::Result<Db> {
 // This prevents corruption if the program crashes mid-write.
 //
 // Steps:
-// 1. Serialize Db into pretty JSON
+// 1. Serialize Db into pretty JSON (on the blocking pool)
 // 2. Ensure parent directory exists
 // 3. Write to temp file
 // 4. Rename temp file -> actual DB file
 // --------------------------------------------------
-pub fn save_db(db: &Db) -> io::Result<()> {
-    let tmp_path = format!("{DB_PATH}.tmp");
-    let text = serde_json::to_string_pretty(db)
-        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+async fn save_json(db: Arc<Db>) -> Result<(), StoreError> {
+    write_db(db).await.map_err(|e| {
+        let err = StoreError::from_io(&e);
+        eprintln!("  {}failed to save: {} ({e})", request_id::log_tag(), err.describe());
+        err
+    })
+}
 
-    if let Some(parent) = Path::new(DB_PATH).parent() {
-        fs::create_dir_all(parent)?;
+async fn write_db(db: Arc<Db>) -> io::Result<()> {
+    write_db_to(db_path(), db, backs_up_on_save()).await?;
+    remember_file().await;
+    Ok(())
+}
+
+async fn write_db_to(db_path: &str, db: Arc<Db>, back_up: bool) -> io::Result<()> {
+    let tmp_path = format!("{db_path}.tmp");
+    let bytes = tokio::task::spawn_blocking(move || db_bytes(&*db)).await.map_err(io::Error::other)??;
+
+    if let Some(parent) = Path::new(db_path).parent() {
+        tokio::fs::create_dir_all(parent).await?;
     }
 
-    fs::write(&tmp_path, text)?;
-    fs::rename(&tmp_path, DB_PATH)?;
+    tokio::fs::write(&tmp_path, bytes).await?;
+    if back_up {
+        back_up_current().await;
+    }
+    tokio::fs::rename(&tmp_path, db_path).await
+}
+
+
+// --------------------------------------------------
+// Rolling backups (storage.backups).
+//
+// With the json backend, every save first copies the db.json
+// it is about to replace to backups/db-<UTC time>.json next to
+// it, then deletes all but the newest `storage.backups` copies.
+// The time is RFC 3339 with '-' for ':' (not allowed in Windows
+// file names): db-2026-10-16T08-09-27.123Z.json. Other files in
+// backups/ are never touched.
+//
+// A backup that cannot be made is logged; the save goes ahead.
+// --------------------------------------------------
+
+const BACKUP_TIME: &str = "%Y-%m-%dT%H-%M-%S%.3fZ";
+
+// A backup file in backups/.
+#[derive(Debug, Clone, Serialize)]
+pub struct Backup {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub size_bytes: u64,
+}
+
+// backups/ next to the database file.
+pub fn backups_dir() -> PathBuf {
+    Path::new(db_path()).with_file_name("backups")
+}
+
+// Whether save_db copies the file it replaces (json backend, backups on).
+pub fn backs_up_on_save() -> bool {
+    let storage = &config::get().storage;
+    storage.backend == StorageBackend::Json && storage.backups > 0
+}
+
+pub fn backup_name(at: DateTime<Utc>) -> String {
+    format!("db-{}.json", at.format(BACKUP_TIME))
+}
+
+// When a backup was taken, from its file name; None for any name
+// backup_name would not have produced.
+pub fn parse_backup_name(name: &str) -> Option<DateTime<Utc>> {
+    let stamp = name.strip_prefix("db-")?.strip_suffix(".json")?;
+    let at = NaiveDateTime::parse_from_str(stamp, BACKUP_TIME).ok()?.and_utc();
+    (backup_name(at) == name).then_some(at)
+}
+
+// Every backup, newest first. A missing backups/ has none.
+pub async fn list_backups() -> io::Result<Vec<Backup>> {
+    list_backups_in(&backups_dir()).await
+}
+
+async fn list_backups_in(dir: &Path) -> io::Result<Vec<Backup>> {
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut backups = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if let Some(created_at) = parse_backup_name(&name) {
+            let size_bytes = entry.metadata().await.map(|m| m.len()).unwrap_or(0);
+            backups.push(Backup { name, created_at, size_bytes });
+        }
+    }
+    backups.sort_by_key(|b| std::cmp::Reverse(b.created_at));
+    Ok(backups)
+}
+
+// Copy db.json into backups/ and prune. Two saves within the same
+// millisecond keep the first copy.
+async fn back_up_current() {
+    let dir = backups_dir();
+    let target = dir.join(backup_name(Utc::now()));
+    let copied = async {
+        if !tokio::fs::try_exists(db_path()).await? || tokio::fs::try_exists(&target).await? {
+            return Ok(());
+        }
+        tokio::fs::create_dir_all(&dir).await?;
+        tokio::fs::copy(db_path(), &target).await.map(|_| ())
+    };
+    if let Err(e) = copied.await {
+        eprintln!("  {}cannot back up {} to {}: {e}", request_id::log_tag(), db_path(), target.display());
+        return;
+    }
+    if let Err(e) = prune_backups(&dir, config::get().storage.backups).await {
+        eprintln!("  {}cannot prune {}: {e}", request_id::log_tag(), dir.display());
+    }
+}
+
+// Delete all but the newest `keep` backups in `dir`.
+async fn prune_backups(dir: &Path, keep: usize) -> io::Result<()> {
+    for old in list_backups_in(dir).await?.into_iter().skip(keep) {
+        tokio::fs::remove_file(dir.join(&old.name)).await?;
+    }
     Ok(())
 }
+
+// Save `db` as a new backup (the db a restore replaces, when saves
+// do not back up by themselves). Never pruned by this call.
+pub async fn write_backup(db: Arc<Db>) -> io::Result<String> {
+    let name = backup_name(Utc::now());
+    let bytes = tokio::task::spawn_blocking(move || db_bytes(&*db)).await.map_err(io::Error::other)??;
+    tokio::fs::create_dir_all(backups_dir()).await?;
+    tokio::fs::write(backups_dir().join(&name), bytes).await?;
+    Ok(name)
+}
+
+// Read and decode (migrating an older schema) one backup.
+// Ok(None) when there is no backup by that name.
+pub async fn read_backup(name: &str) -> Result<Option<Decoded>, StoreError> {
+    if parse_backup_name(name).is_none() {
+        return Ok(None);
+    }
+    let bytes = match tokio::fs::read(backups_dir().join(name)).await {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(StoreError::from_io(&e)),
+    };
+    tokio::task::spawn_blocking(move || db_text(bytes).and_then(|text| decode(&text)))
+        .await
+        .map_err(|_| StoreError::Io(io::ErrorKind::Other))?
+        .map(Some)
+}
+
+// --------------------------------------------------
+// Month archives (compact.rs).
+//
+// archive/<YYYY-MM>.json next to the database file holds the
+// compacted tasks due in that month, sorted by due_at, as
+// {"schema_version", "month", "tasks"}; encrypted like db.json
+// when a db key is set. Tasks of an older schema are migrated
+// when read, like an old db.json.
+// --------------------------------------------------
+
+#[derive(Debug, Serialize)]
+struct ArchiveFile<'a> {
+    schema_version: u32,
+    month: &'a str,
+    tasks: &'a [Task],
+}
+
+// archive/ next to the database file.
+pub fn archive_dir() -> PathBuf {
+    Path::new(db_path()).with_file_name("archive")
+}
+
+// Whether `month` is a "YYYY-MM" an archive file can be named after.
+pub fn is_month(month: &str) -> bool {
+    NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d")
+        .is_ok_and(|d| d.format("%Y-%m").to_string() == month)
+}
+
+// Months with an archive file, oldest first.
+pub async fn list_archive_months() -> io::Result<Vec<String>> {
+    let mut entries = match tokio::fs::read_dir(archive_dir()).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut months = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if let Some(month) = name.strip_suffix(".json").filter(|m| is_month(m)) {
+            months.push(month.to_string());
+        }
+    }
+    months.sort();
+    Ok(months)
+}
+
+// The tasks archived for `month`; none when it has no file.
+pub async fn read_archive(month: &str) -> Result<Vec<Task>, StoreError> {
+    let bytes = match tokio::fs::read(archive_dir().join(format!("{month}.json"))).await {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(StoreError::from_io(&e)),
+    };
+    tokio::task::spawn_blocking(move || {
+        let mut doc: Value = serde_json::from_str(&db_text(bytes)?).map_err(|e| StoreError::from_json(&e))?;
+        migrate::migrate_value(&mut doc).map_err(|e| StoreError::TooNew { version: e.version })?;
+        let tasks = doc.get_mut("tasks").map(Value::take).unwrap_or_default();
+        serde_json::from_value(tasks).map_err(|e| StoreError::from_json(&e))
+    })
+    .await
+    .map_err(|_| StoreError::Io(io::ErrorKind::Other))?
+}
+
+// Add `tasks` to their month files, replacing earlier copies with the
+// same id. Each file is written to a temp file and renamed over the old
+// one; on error (described, naming the file), files already written
+// keep their new contents.
+pub async fn write_archive(tasks: &[Task]) -> Result<(), String> {
+    let mut by_month: BTreeMap<String, Vec<Task>> = BTreeMap::new();
+    for t in tasks {
+        by_month.entry(compact::month_of(t)).or_default().push(t.clone());
+    }
+    let dir = archive_dir();
+    tokio::fs::create_dir_all(&dir).await.map_err(|e| format!("cannot create {}: {e}", dir.display()))?;
+    for (month, incoming) in by_month {
+        let path = dir.join(format!("{month}.json"));
+        let mut merged = read_archive(&month).await.map_err(|e| match e {
+            StoreError::Corrupt { message, .. } => format!("{} is not a valid archive: {message}", path.display()),
+            e => format!("cannot read {}: {}", path.display(), e.code()),
+        })?;
+        merged.retain(|old| !incoming.iter().any(|t| t.id == old.id));
+        merged.extend(incoming);
+        merged.sort_by_key(|t| t.due_at);
+
+        let tmp = dir.join(format!("{month}.json.tmp"));
+        let written = async {
+            let file = ArchiveFile { schema_version: SCHEMA_VERSION, month: &month, tasks: &merged };
+            let bytes = db_bytes(&file)?;
+            tokio::fs::write(&tmp, bytes).await?;
+            tokio::fs::rename(&tmp, &path).await
+        };
+        if let Err(e) = written.await {
+            return Err(format!("cannot write {}: {e}", path.display()));
+        }
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+// Single instance per database.
+//
+// Two servers saving the same db.json interleave their
+// temp-file renames and silently lose writes. The server
+// holds an exclusive advisory lock on db.lock (next to
+// db.json) for as long as it runs; the file also records
+// the holder's PID for the error message.
+//
+// The OS drops the lock when its holder exits, crashed or
+// not, so a lock file left behind never blocks a new start:
+// only a live process can hold the lock.
+// --------------------------------------------------
+
+// Lock file: the db path with a .lock extension (data/db.lock).
+pub fn lock_path() -> String {
+    Path::new(db_path()).with_extension("lock").to_string_lossy().into_owned()
+}
+
+#[derive(Debug)]
+pub enum LockError {
+    Held { pid: Option<u32> }, // another live process holds the lock
+    Io(io::Error),
+}
+
+impl LockError {
+    pub fn describe(&self) -> String {
+        let lock = lock_path();
+        match self {
+            LockError::Held { pid: Some(pid) } => format!(
+                "{} is in use by another instance (pid {pid}, lock {lock}); stop it or pass another --db-path",
+                db_path()
+            ),
+            LockError::Held { pid: None } => {
+                format!("{} is in use by another instance (lock {lock})", db_path())
+            }
+            LockError::Io(e) => format!("cannot lock {lock}: {e}"),
+        }
+    }
+}
+
+// Take the lock without waiting (the server). Keep the returned
+// file open for as long as the lock should be held.
+// Creates the db directory when it does not exist yet.
+pub fn lock_db() -> Result<fs::File, LockError> {
+    lock_file(&lock_path())
+}
+
+// How long the command line modes wait for a server to let go of
+// the lock, and how often they try again meanwhile.
+pub const CLI_LOCK_WAIT: Duration = Duration::from_secs(5);
+const LOCK_RETRY: Duration = Duration::from_millis(100);
+
+// Take the lock, trying again until `timeout` has passed while another
+// process holds it. For the command line modes, which may be started
+// while a server is still shutting down; the server uses lock_db.
+pub async fn lock_db_wait(timeout: Duration) -> Result<fs::File, LockError> {
+    lock_file_wait(&lock_path(), timeout).await
+}
+
+async fn lock_file_wait(path: &str, timeout: Duration) -> Result<fs::File, LockError> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match lock_file(path) {
+            Err(LockError::Held { .. }) if Instant::now() < deadline => tokio::time::sleep(LOCK_RETRY).await,
+            taken => return taken,
+        }
+    }
+}
+
+fn lock_file(path: &str) -> Result<fs::File, LockError> {
+    if let Some(parent) = Path::new(path).parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).map_err(LockError::Io)?;
+    }
+    let mut file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .map_err(LockError::Io)?;
+
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(fs::TryLockError::WouldBlock) => {
+            let pid = fs::read_to_string(path).ok().and_then(|s| s.trim().parse().ok());
+            return Err(LockError::Held { pid });
+        }
+        Err(fs::TryLockError::Error(e)) => return Err(LockError::Io(e)),
+    }
+
+    // Replace whatever PID a previous holder left behind
+    file.set_len(0).map_err(LockError::Io)?;
+    writeln!(file, "{}", std::process::id()).map_err(LockError::Io)?;
+    file.flush().map_err(LockError::Io)?;
+    Ok(file)
+}
+
+// History file: history.jsonl next to the database file.
+pub fn history_path() -> String {
+    Path::new(db_path()).with_file_name("history.jsonl").to_string_lossy().into_owned()
+}
+
+// --------------------------------------------------
+// Daily snapshots (history.jsonl).
+//
+// One JSON object per line, appended in date order by the
+// snapshot job. A missing file is an empty history; lines that
+// do not parse are skipped and logged.
+// --------------------------------------------------
+pub async fn load_history() -> io::Result<Vec<DaySnapshot>> {
+    let text = match tokio::fs::read_to_string(history_path()).await {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut days = Vec::new();
+    for (i, line) in text.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        match serde_json::from_str(line) {
+            Ok(day) => days.push(day),
+            Err(e) => eprintln!("  {}: skipped line {}: {e}", history_path(), i + 1),
+        }
+    }
+    Ok(days)
+}
+
+pub async fn append_history(day: &DaySnapshot) -> io::Result<()> {
+    append_line(&history_path(), day).await
+}
+
+// Audit log: audit.log next to the database file.
+pub fn audit_path() -> String {
+    Path::new(db_path()).with_file_name("audit.log").to_string_lossy().into_owned()
+}
+
+// --------------------------------------------------
+// Audit log (audit.log).
+//
+// One JSON object per line, appended by the writer after each
+// task or settings write has been saved, so a write that failed
+// leaves no entry. Never rewritten; a missing file is an empty
+// log and lines that do not parse are skipped and logged.
+// --------------------------------------------------
+pub async fn load_audit() -> io::Result<Vec<AuditEntry>> {
+    let text = match tokio::fs::read_to_string(audit_path()).await {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut entries = Vec::new();
+    for (i, line) in text.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => eprintln!("  {}: skipped line {}: {e}", audit_path(), i + 1),
+        }
+    }
+    Ok(entries)
+}
+
+pub async fn append_audit(entry: &AuditEntry) -> io::Result<()> {
+    append_line(&audit_path(), entry).await
+}
+
+// Append `value` as one JSON line, creating the file if needed.
+async fn append_line<T: Serialize>(path: &str, value: &T) -> io::Result<()> {
+    let line = serde_json::to_string(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if let Some(parent) = Path::new(path).parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let mut file = tokio::fs::OpenOptions::new().create(true).append(true).open(path).await?;
+    file.write_all(format!("{line}\n").as_bytes()).await?;
+    // tokio writes in the background; wait for it, so lines land in call order
+    file.flush().await
+}
+
+// Write-ahead journal: db.journal next to the database file.
+pub fn journal_path() -> String {
+    Path::new(db_path()).with_file_name("db.journal").to_string_lossy().into_owned()
+}
+
+// --------------------------------------------------
+// Write-ahead journal (db.journal).
+//
+// The writer appends each task or settings write here, flushed to
+// disk, before saving it, and removes the file once the save is
+// done (or failed). A file left behind means the process died in
+// between: load_db replays it over the loaded Db and saves. Lines
+// that do not parse (one cut short by the crash) are skipped and
+// logged. Compound writes (writer::apply) are journaled as the
+// whole Db they save.
+// --------------------------------------------------
+pub async fn load_journal() -> io::Result<Vec<JournalOp>> {
+    let text = match tokio::fs::read_to_string(journal_path()).await {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut ops = Vec::new();
+    for (i, line) in text.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        match serde_json::from_str(line) {
+            Ok(op) => ops.push(op),
+            Err(e) => eprintln!("  {}: skipped line {}: {e}", journal_path(), i + 1),
+        }
+    }
+    Ok(ops)
+}
+
+// Append `op` and wait until it is on disk.
+pub async fn append_journal(op: &JournalOp) -> Result<(), StoreError> {
+    let path = journal_path();
+    let appended = async {
+        let line = serde_json::to_string(op).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if let Some(parent) = Path::new(&path).parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut file = tokio::fs::OpenOptions::new().create(true).append(true).open(&path).await?;
+        file.write_all(format!("{line}\n").as_bytes()).await?;
+        file.sync_data().await
+    };
+    appended.await.map_err(|e| {
+        eprintln!("  {}cannot append to {path}: {e}", request_id::log_tag());
+        StoreError::from_io(&e)
+    })
+}
+
+// Drop the journal once its writes are saved (or failed). A file
+// that cannot be removed is only logged: replaying a saved write
+// changes nothing.
+pub async fn clear_journal() {
+    match tokio::fs::remove_file(journal_path()).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => eprintln!("  {}cannot remove {}: {e}", request_id::log_tag(), journal_path()),
+    }
+}
+
+// Apply journaled writes to `db`, in order. A task write is skipped
+// when the db already has that revision of the task or a later
+// one. Returns how many were applied.
+pub fn replay_journal(db: &mut Db, ops: Vec<JournalOp>) -> usize {
+    let mut applied = 0;
+    for op in ops {
+        let done = match op {
+            JournalOp::CreateTask { task } | JournalOp::UpdateTask { task } | JournalOp::Toggle { task } => {
+                match db.get_mut(task.id) {
+                    Some(current) if current.rev >= task.rev => false,
+                    Some(current) => {
+                        *current = task;
+                        true
+                    }
+                    None => {
+                        db.insert(task);
+                        true
+                    }
+                }
+            }
+            JournalOp::DeleteTask { id, trashed } => match db.remove(id) {
+                Some(_) => {
+                    db.trash.extend(trashed);
+                    true
+                }
+                None => false,
+            },
+            JournalOp::PutSettings { settings } => {
+                db.settings = settings;
+                true
+            }
+            JournalOp::Snapshot { db: saved } => {
+                *db = *saved;
+                db.reindex();
+                true
+            }
+        };
+        applied += usize::from(done);
+    }
+    applied
+}
+
+// --------------------------------------------------
+// Integrity checks.
+//
+// Rules (tasks and archived tasks together):
+// - Every id is unique
+// - completed_at is only set on Done tasks
+// - priority is 1..=5
+// - duration_min is at least 1
+// - settings pass validation::validate_settings (HH:MM times,
+//   focus block length, ...)
+//
+// check_integrity only reports; repair fixes what is safe:
+// - Later copies of a duplicated id get a fresh Uuid
+//   (the first one, in file order, keeps it)
+// - completed_at is cleared on tasks that are not Done
+// - priority and duration_min are clamped into range
+// - an invalid setting is reset to its default
+// --------------------------------------------------
+
+// An inconsistency found in the database.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Issue {
+    DuplicateId { id: Uuid, count: usize },
+    CompletedWhileOpen { task_id: Uuid, status: TaskStatus },
+    PriorityOutOfRange { task_id: Uuid, priority: i64 },
+    DurationOutOfRange { task_id: Uuid, duration_min: i64 },
+    InvalidSetting { field: &'static str, message: String },
+}
+
+impl Issue {
+    // One-line description for the startup log.
+    pub fn describe(&self) -> String {
+        match self {
+            Issue::DuplicateId { id, count } => format!("id {id} is used by {count} tasks"),
+            Issue::CompletedWhileOpen { task_id, status } => {
+                format!("task {task_id} has completed_at but status {status:?}")
+            }
+            Issue::PriorityOutOfRange { task_id, priority } => {
+                format!("task {task_id} has priority {priority}, outside 1..=5")
+            }
+            Issue::DurationOutOfRange { task_id, duration_min } => {
+                format!("task {task_id} has duration_min {duration_min}, below 1")
+            }
+            Issue::InvalidSetting { message, .. } => format!("settings: {message}"),
+        }
+    }
+}
+
+// A change made by repair.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Fix {
+    NewId { old_id: Uuid, new_id: Uuid, title: String },
+    ClearedCompletedAt { task_id: Uuid, completed_at: DateTime<FixedOffset> },
+    ClampedPriority { task_id: Uuid, from: i64, to: i64 },
+    ClampedDuration { task_id: Uuid, from: i64, to: i64 },
+    ResetSetting { field: &'static str },
+}
+
+impl Fix {
+    // One-line description for the --repair log.
+    pub fn describe(&self) -> String {
+        match self {
+            Fix::NewId { old_id, new_id, title } => format!("task \"{title}\": duplicate id {old_id} -> {new_id}"),
+            Fix::ClearedCompletedAt { task_id, .. } => format!("task {task_id}: cleared completed_at"),
+            Fix::ClampedPriority { task_id, from, to } => format!("task {task_id}: priority {from} -> {to}"),
+            Fix::ClampedDuration { task_id, from, to } => format!("task {task_id}: duration_min {from} -> {to}"),
+            Fix::ResetSetting { field } => format!("settings: {field} reset to its default"),
+        }
+    }
+}
+
+// Every task, active first, then archived.
+fn all_tasks(db: &Db) -> impl Iterator<Item = &Task> {
+    db.tasks.iter().chain(db.archived.iter())
+}
+
+pub fn check_integrity(db: &Db) -> Vec<Issue> {
+    let mut issues = Vec::new();
+
+    let mut counts: HashMap<Uuid, usize> = HashMap::new();
+    let mut order = Vec::new();
+    for t in all_tasks(db) {
+        let n = counts.entry(t.id).or_insert(0);
+        if *n == 1 {
+            order.push(t.id);
+        }
+        *n += 1;
+    }
+    for id in order {
+        issues.push(Issue::DuplicateId { id, count: counts[&id] });
+    }
+
+    for t in all_tasks(db) {
+        if t.completed_at.is_some() && t.status != TaskStatus::Done {
+            issues.push(Issue::CompletedWhileOpen { task_id: t.id, status: t.status.clone() });
+        }
+        if !(1..=5).contains(&t.priority) {
+            issues.push(Issue::PriorityOutOfRange { task_id: t.id, priority: t.priority });
+        }
+        if t.duration_min < 1 {
+            issues.push(Issue::DurationOutOfRange { task_id: t.id, duration_min: t.duration_min });
+        }
+    }
+
+    if let Err(errors) = validation::validate_settings(&db.settings) {
+        for e in errors {
+            issues.push(Issue::InvalidSetting { field: e.field, message: e.message(Lang::En) });
+        }
+    }
+    issues
+}
+
+// Apply the safe fixes in place and report each change.
+pub fn repair(db: &mut Db) -> Vec<Fix> {
+    let mut fixes = Vec::new();
+    let mut seen = HashSet::new();
+
+    for t in db.tasks.iter_mut().chain(db.archived.iter_mut()) {
+        if !seen.insert(t.id) {
+            let new_id = Uuid::new_v4();
+            fixes.push(Fix::NewId { old_id: t.id, new_id, title: t.title.clone() });
+            t.id = new_id;
+            seen.insert(new_id);
+        }
+        if t.status != TaskStatus::Done
+            && let Some(completed_at) = t.completed_at.take()
+        {
+            fixes.push(Fix::ClearedCompletedAt { task_id: t.id, completed_at });
+        }
+        let priority = t.priority.clamp(1, 5);
+        if priority != t.priority {
+            fixes.push(Fix::ClampedPriority { task_id: t.id, from: t.priority, to: priority });
+            t.priority = priority;
+        }
+        if t.duration_min < 1 {
+            fixes.push(Fix::ClampedDuration { task_id: t.id, from: t.duration_min, to: 1 });
+            t.duration_min = 1;
+        }
+    }
+
+    if let Err(errors) = validation::validate_settings(&db.settings) {
+        let mut reset: Vec<&'static str> = Vec::new();
+        for e in errors {
+            let field = reset_setting(&mut db.settings, e.field);
+            if !reset.contains(&field) {
+                reset.push(field);
+                fixes.push(Fix::ResetSetting { field });
+            }
+        }
+    }
+
+    db.reindex();
+    fixes
+}
+
+// Put the setting behind a validation error back to its default;
+// returns the name of the setting reset.
+fn reset_setting(s: &mut DaySettings, field: &'static str) -> &'static str {
+    let d = DaySettings::default();
+    match field {
+        "day_start" => s.day_start = d.day_start,
+        "day_end" => s.day_end = d.day_end,
+        "default_due_time" => s.default_due_time = d.default_due_time,
+        "focus_block_min" => s.focus_block_min = d.focus_block_min,
+        "overdue_grace_min" => s.overdue_grace_min = d.overdue_grace_min,
+        "auto_archive_days" => s.auto_archive_days = d.auto_archive_days,
+        "wip_limit" => s.wip_limit = d.wip_limit,
+        "sort_locale" => s.sort_locale = d.sort_locale,
+        // priority_labels.label, priority_labels.color
+        _ => {
+            s.priority_labels = d.priority_labels;
+            return "priority_labels";
+        }
+    }
+    field
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock;
+    use crate::models::{test_task, PriorityMeta};
+
+    fn db_of(tasks: Vec<Task>) -> Db {
+        let mut db = Db::default();
+        db.tasks.extend(tasks);
+        db.reindex();
+        db
+    }
+
+    fn task(title: &str) -> Task {
+        test_task(title, clock::local("2024-06-03 12:00"))
+    }
+
+    #[test]
+    fn a_clean_db_has_no_issues_and_needs_no_fixes() {
+        let mut db = db_of(vec![task("a"), task("b")]);
+        assert_eq!(check_integrity(&db), []);
+        assert!(repair(&mut db).is_empty());
+    }
+
+    #[test]
+    fn duplicate_ids_get_fresh_ids_after_the_first() {
+        let first = task("first");
+        let mut second = task("second");
+        second.id = first.id;
+        let mut archived = task("archived copy");
+        archived.id = first.id;
+        let mut db = db_of(vec![first.clone(), second]);
+        db.archived.push(archived);
+
+        assert_eq!(check_integrity(&db), [Issue::DuplicateId { id: first.id, count: 3 }]);
+        let fixes = repair(&mut db);
+        assert_eq!(fixes.len(), 2);
+        assert!(matches!(&fixes[0], Fix::NewId { old_id, title, .. } if *old_id == first.id && title == "second"));
+        assert!(matches!(&fixes[1], Fix::NewId { title, .. } if title == "archived copy"));
+        assert_eq!(db.tasks[0].id, first.id);
+        assert_ne!(db.tasks[1].id, first.id);
+        assert_eq!(db.get(db.tasks[1].id).map(|t| t.title.as_str()), Some("second"));
+        assert_eq!(check_integrity(&db), []);
+    }
+
+    #[test]
+    fn completed_at_is_cleared_on_open_tasks() {
+        let mut open = task("open");
+        open.completed_at = Some(clock::local("2024-06-02 10:00"));
+        let mut done = task("done");
+        done.status = TaskStatus::Done;
+        done.completed_at = open.completed_at;
+        let mut db = db_of(vec![open.clone(), done]);
+
+        assert_eq!(check_integrity(&db), [Issue::CompletedWhileOpen { task_id: open.id, status: TaskStatus::Todo }]);
+        let fixes = repair(&mut db);
+        assert!(matches!(fixes.as_slice(), [Fix::ClearedCompletedAt { task_id, .. }] if *task_id == open.id));
+        assert_eq!(db.tasks[0].completed_at, None);
+        assert!(db.tasks[1].completed_at.is_some());
+    }
+
+    #[test]
+    fn priority_is_clamped_into_range() {
+        let mut low = task("low");
+        low.priority = 0;
+        let mut high = task("high");
+        high.priority = 9;
+        let mut db = db_of(vec![low.clone(), high.clone()]);
+
+        assert_eq!(
+            check_integrity(&db),
+            [
+                Issue::PriorityOutOfRange { task_id: low.id, priority: 0 },
+                Issue::PriorityOutOfRange { task_id: high.id, priority: 9 },
+            ]
+        );
+        repair(&mut db);
+        assert_eq!((db.tasks[0].priority, db.tasks[1].priority), (1, 5));
+    }
+
+    #[test]
+    fn duration_is_raised_to_one_minute() {
+        let mut zero = task("zero");
+        zero.duration_min = -30;
+        let mut db = db_of(vec![zero.clone()]);
+
+        assert_eq!(check_integrity(&db), [Issue::DurationOutOfRange { task_id: zero.id, duration_min: -30 }]);
+        let fixes = repair(&mut db);
+        assert!(matches!(fixes.as_slice(), [Fix::ClampedDuration { from: -30, to: 1, .. }]));
+        assert_eq!(db.tasks[0].duration_min, 1);
+    }
+
+    #[test]
+    fn invalid_settings_are_reset_once_each() {
+        let mut db = db_of(vec![]);
+        db.settings.day_start = "9am".to_string();
+        db.settings.focus_block_min = 0;
+        let mut labels: [PriorityMeta; 5] =
+            std::array::from_fn(|_| PriorityMeta { label: "x".to_string(), color: "#000000".to_string() });
+        labels[0].label = String::new();
+        labels[1].color = "red".to_string();
+        db.settings.priority_labels = Some(labels);
+
+        let fields: Vec<&str> = check_integrity(&db)
+            .into_iter()
+            .map(|i| match i {
+                Issue::InvalidSetting { field, .. } => field,
+                other => panic!("unexpected {other:?}"),
+            })
+            .collect();
+        assert_eq!(fields, ["day_start", "focus_block_min", "priority_labels.label", "priority_labels.color"]);
+
+        let reset: Vec<&str> = repair(&mut db)
+            .into_iter()
+            .map(|f| match f {
+                Fix::ResetSetting { field } => field,
+                other => panic!("unexpected {other:?}"),
+            })
+            .collect();
+        assert_eq!(reset, ["day_start", "focus_block_min", "priority_labels"]);
+        assert_eq!(db.settings.day_start, DaySettings::default().day_start);
+        assert_eq!(check_integrity(&db), []);
+    }
+
+    fn temp_lock(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("scheduler-lock-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("db.lock").to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn a_second_holder_is_refused_with_the_first_pid() {
+        let path = temp_lock("two");
+        let first = lock_file(&path).unwrap();
+        match lock_file(&path) {
+            Err(LockError::Held { pid }) => assert_eq!(pid, Some(std::process::id())),
+            other => panic!("expected Held, got {other:?}"),
+        }
+        // Released with the first holder
+        drop(first);
+        assert!(lock_file(&path).is_ok());
+    }
+
+    #[tokio::test]
+    async fn the_cli_waits_for_a_holder_to_let_go() {
+        let path = temp_lock("wait");
+        let held = lock_file(&path).unwrap();
+        let releaser = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(300));
+            drop(held);
+        });
+        let started = Instant::now();
+        assert!(lock_file_wait(&path, Duration::from_secs(5)).await.is_ok());
+        assert!(started.elapsed() >= Duration::from_millis(250), "{:?}", started.elapsed());
+        releaser.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn the_cli_gives_up_after_the_timeout() {
+        let path = temp_lock("timeout");
+        let _held = lock_file(&path).unwrap();
+        let started = Instant::now();
+        assert!(matches!(lock_file_wait(&path, Duration::from_millis(250)).await, Err(LockError::Held { .. })));
+        assert!(started.elapsed() >= Duration::from_millis(250));
+    }
+
+    #[test]
+    fn a_lock_file_left_by_a_dead_process_does_not_block() {
+        // What a crashed holder leaves behind: the file and its PID, no lock
+        let path = temp_lock("stale");
+        fs::create_dir_all(Path::new(&path).parent().unwrap()).unwrap();
+        fs::write(&path, "4194999\n").unwrap();
+
+        let _held = lock_file(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), format!("{}\n", std::process::id()));
+    }
+
+    // What load_json does with a file, minus the side effects
+    // (migration save, corrupt-file backup, change tracking).
+    fn read_file(path: &Path) -> Result<Decoded, StoreError> {
+        let bytes = fs::read(path).map_err(|e| StoreError::from_io(&e))?;
+        decode(&db_text(bytes)?)
+    }
+
+    fn temp_file(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("scheduler-store-{}-{name}.json", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn a_missing_file_is_not_found() {
+        let path = std::env::temp_dir().join(format!("scheduler-store-{}-missing/db.json", std::process::id()));
+        assert_eq!(read_file(&path).err(), Some(StoreError::NotFound));
+        assert_eq!(StoreError::NotFound.code(), "db_not_found");
+    }
+
+    #[test]
+    fn bad_json_is_corrupt_with_its_location() {
+        let path = temp_file("corrupt", "{\n  \"settings\": {},\n  \"tasks\": [,]\n}\n");
+        match read_file(&path) {
+            Err(e @ StoreError::Corrupt { line: 3, column: 13, .. }) => assert_eq!(e.code(), "db_corrupt"),
+            other => panic!("expected Corrupt at 3:13, got {:?}", other.err()),
+        }
+        let path = temp_file("not-utf8", "");
+        fs::write(&path, [0xff, 0xfe, 0x00]).unwrap();
+        assert!(matches!(read_file(&path), Err(StoreError::Corrupt { line: 0, column: 0, .. })));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn an_unreadable_file_is_permission_denied() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = temp_file("unreadable", "{}");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o000)).unwrap();
+        if fs::read(&path).is_ok() {
+            return; // running as root: permissions are not enforced
+        }
+        assert_eq!(read_file(&path).err(), Some(StoreError::PermissionDenied));
+        assert_eq!(StoreError::PermissionDenied.code(), "db_permission_denied");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn a_full_disk_is_its_own_io_kind() {
+        // Every write to /dev/full fails with ENOSPC
+        let e = fs::write("/dev/full", b"{}").unwrap_err();
+        let e = StoreError::from_io(&e);
+        assert_eq!(e, StoreError::Io(io::ErrorKind::StorageFull));
+        assert_eq!(e.code(), "db_storage_full");
+        assert_eq!(StoreError::Io(io::ErrorKind::Other).code(), "db_io");
+    }
+
+    #[test]
+    fn truncated_mistyped_and_empty_files_are_corrupt() {
+        let mut valid = serde_json::to_string_pretty(&db_of(vec![task("a")])).unwrap();
+        valid.truncate(valid.len() / 2);
+        let mistyped = serde_json::to_string_pretty(&db_of(vec![task("a")])).unwrap().replace("\"priority\": 3", "\"priority\": \"high\"");
+        for (name, text) in [("truncated", valid.as_str()), ("mistyped", mistyped.as_str()), ("empty", "")] {
+            match read_file(&temp_file(name, text)) {
+                Err(StoreError::Corrupt { line, column, message, backup: None }) => {
+                    assert!(line >= 1 && !message.is_empty(), "{name}: {line}:{column} {message}");
+                    if name == "mistyped" {
+                        let expected = mistyped.lines().position(|l| l.contains("\"high\"")).unwrap() + 1;
+                        assert_eq!(line, expected, "{message}");
+                        assert!(message.contains("invalid type"), "{message}");
+                    }
+                }
+                other => panic!("{name}: expected Corrupt, got {:?}", other.err()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn a_corrupt_file_is_copied_aside_unchanged() {
+        let path = temp_file("backup", "{\"tasks\": [");
+        let path = path.to_str().unwrap();
+        let backup = back_up_corrupt(path).await.unwrap();
+        assert!(backup.starts_with(&format!("{path}.corrupt-")), "{backup}");
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "{\"tasks\": [");
+        assert_eq!(fs::read_to_string(path).unwrap(), "{\"tasks\": [");
+
+        let missing = std::env::temp_dir().join(format!("scheduler-store-{}-gone/db.json", std::process::id()));
+        assert_eq!(back_up_corrupt(missing.to_str().unwrap()).await, None);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn a_save_leaves_the_runtime_free_and_replaces_the_file_whole() {
+        let path = temp_file("save", "{\"tasks\": []}");
+        let db = Arc::new(db_of((0..5000).map(|i| task(&format!("task {i}"))).collect()));
+
+        // On a single-threaded runtime another task only runs while the
+        // save is awaiting; a blocking save would leave it at zero.
+        let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let ticker = tokio::spawn({
+            let done = done.clone();
+            async move {
+                let mut ticks = 0u64;
+                while !done.load(std::sync::atomic::Ordering::SeqCst) {
+                    ticks += 1;
+                    tokio::task::yield_now().await;
+                }
+                ticks
+            }
+        });
+        write_db_to(path.to_str().unwrap(), db, false).await.unwrap();
+        done.store(true, std::sync::atomic::Ordering::SeqCst);
+        assert!(ticker.await.unwrap() > 0);
+
+        let saved = read_file(&path).unwrap();
+        assert_eq!(saved.db.tasks.len(), 5000);
+        assert!(!Path::new(&format!("{}.tmp", path.display())).exists());
+    }
+
+    // The same db as each schema version stored it: a done task without
+    // completed_at (filled from v2 on), untidy tags (tidied from v3 on)
+    // and an open task never edited, without updated_at (set from v4 on).
+    fn stored_at(version: Option<u32>) -> String {
+        let mut done = task("done");
+        done.status = TaskStatus::Done;
+        done.updated_at = clock::local("2024-06-02 17:30");
+        done.tags = Some(vec!["Deep  Work".to_string()]);
+        let mut doc = serde_json::to_value(db_of(vec![done, task("open")])).unwrap();
+        match version {
+            Some(v) => doc["schema_version"] = v.into(),
+            None => drop(doc.as_object_mut().unwrap().remove("schema_version")),
+        }
+        if version.unwrap_or(1) < 4 {
+            doc["tasks"][1].as_object_mut().unwrap().remove("updated_at");
+        }
+        serde_json::to_string_pretty(&doc).unwrap()
+    }
+
+    #[test]
+    fn every_historical_version_decodes_to_the_current_schema() {
+        for (version, from, steps) in [(None, 1, 3), (Some(1), 1, 3), (Some(2), 2, 2), (Some(3), 3, 1), (Some(SCHEMA_VERSION), SCHEMA_VERSION, 0)] {
+            let path = temp_file(&format!("schema-{from}-{steps}"), &stored_at(version));
+            let decoded = read_file(&path).unwrap_or_else(|e| panic!("{version:?}: {}", e.describe()));
+            assert_eq!((decoded.from_version, decoded.steps.len()), (from, steps), "{version:?}");
+            assert_eq!(decoded.db.schema_version, SCHEMA_VERSION);
+            let done = &decoded.db.tasks[0];
+            let expected = (from == 1).then_some(done.updated_at);
+            assert_eq!(done.completed_at, expected, "{version:?}");
+            let open = &decoded.db.tasks[1];
+            assert_eq!(open.updated_at, open.created_at, "{version:?}");
+            if steps > 1 {
+                assert_eq!(done.tags.as_deref(), Some(&["deep-work".to_string()][..]), "{version:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn a_file_from_a_newer_build_is_too_new() {
+        let path = temp_file("schema-newer", &stored_at(Some(SCHEMA_VERSION + 1)));
+        match read_file(&path) {
+            Err(e @ StoreError::TooNew { .. }) => {
+                assert_eq!(e, StoreError::TooNew { version: SCHEMA_VERSION + 1 });
+                assert_eq!(e.code(), "db_too_new");
+            }
+            other => panic!("expected TooNew, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn backup_names_round_trip_and_nothing_else_parses() {
+        let at = DateTime::parse_from_rfc3339("2026-10-16T08:09:27.123Z").unwrap().with_timezone(&Utc);
+        assert_eq!(backup_name(at), "db-2026-10-16T08-09-27.123Z.json");
+        assert_eq!(parse_backup_name(&backup_name(at)), Some(at));
+        for name in [
+            "db-2026-10-16T08:09:27.123Z.json",
+            "db-2026-10-16T08-09-27Z.json",
+            "db-2026-10-16T08-09-27.123Z.json.tmp",
+            "db-2026-13-16T08-09-27.123Z.json",
+            "notes.txt",
+            "../db.json",
+        ] {
+            assert_eq!(parse_backup_name(name), None, "{name}");
+        }
+    }
+
+    #[tokio::test]
+    async fn pruning_keeps_the_newest_backups_and_other_files() {
+        let dir = std::env::temp_dir().join(format!("scheduler-store-{}-backups", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let start = DateTime::parse_from_rfc3339("2026-10-16T08:00:00Z").unwrap().with_timezone(&Utc);
+        // Written out of order: the name decides, not the file time
+        for minutes in [3, 0, 4, 1, 2] {
+            fs::write(dir.join(backup_name(start + chrono::Duration::minutes(minutes))), "{}").unwrap();
+        }
+        fs::write(dir.join("keep-me.json"), "{}").unwrap();
+
+        prune_backups(&dir, 3).await.unwrap();
+        let left: Vec<DateTime<Utc>> = list_backups_in(&dir).await.unwrap().iter().map(|b| b.created_at).collect();
+        let expected: Vec<DateTime<Utc>> = [4, 3, 2].iter().map(|m| start + chrono::Duration::minutes(*m)).collect();
+        assert_eq!(left, expected);
+        assert!(dir.join("keep-me.json").exists());
+
+        prune_backups(&dir, 0).await.unwrap();
+        assert!(list_backups_in(&dir).await.unwrap().is_empty());
+        assert!(list_backups_in(&dir.join("missing")).await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn every_journal_op_replays_once() {
+        let (kept, mut edited, trashed, purged) = (task("kept"), task("edited"), task("trashed"), task("purged"));
+        let mut db = db_of(vec![kept.clone(), edited.clone(), trashed.clone(), purged.clone()]);
+        let created = task("created");
+        edited.title = "edited again".to_string();
+        edited.rev += 1;
+        let mut toggled = kept.clone();
+        toggled.status = TaskStatus::InProgress;
+        toggled.rev += 1;
+        let settings = DaySettings { day_start: "07:30".to_string(), ..DaySettings::default() };
+        let bin = models::TrashedTask { task: trashed.clone(), deleted_at: clock::local("2024-06-03 09:00") };
+        let ops = [
+            JournalOp::CreateTask { task: created.clone() },
+            JournalOp::UpdateTask { task: edited.clone() },
+            JournalOp::Toggle { task: toggled },
+            JournalOp::DeleteTask { id: trashed.id, trashed: Some(bin) },
+            JournalOp::DeleteTask { id: purged.id, trashed: None },
+            JournalOp::PutSettings { settings },
+        ];
+        let lines: Vec<String> = ops.iter().map(|op| serde_json::to_string(op).unwrap()).collect();
+        let ops = || lines.iter().map(|l| serde_json::from_str(l).unwrap()).collect::<Vec<JournalOp>>();
+
+        assert_eq!(replay_journal(&mut db, ops()), 6);
+        let titles: Vec<&str> = db.tasks.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, ["kept", "edited again", "created"]);
+        assert_eq!(db.get(kept.id).unwrap().status, TaskStatus::InProgress);
+        assert_eq!(db.trash.iter().map(|t| t.task.id).collect::<Vec<_>>(), [trashed.id]);
+        assert_eq!(db.settings.day_start, "07:30");
+
+        // Replaying writes that were saved after all changes nothing
+        let before = serde_json::to_value(&db).unwrap();
+        assert_eq!(replay_journal(&mut db, ops()), 1);
+        assert_eq!(serde_json::to_value(&db).unwrap(), before);
+    }
+}
@@ -0,0 +1,447 @@
+// --------------------------------------------------
+// PostgreSQL backend (cargo feature "postgres",
+// storage.backend = "postgres"), for one database shared by
+// servers on several machines.
+//
+// Connects to storage.database_url (DATABASE_URL). The tables
+// are created on first use:
+// - tasks(id, archived, pos, tags, body): one row per task; tags
+//   is a text[] (NULL when the task has none), body the rest of
+//   the task as JSON, so fields this version does not know
+//   round-trip as db.json would keep them
+// - meta(key, body): schema_version, settings, notifications,
+//   deliveries, trash and the db's unknown top-level keys, plus a
+//   generation counter bumped by every save
+//
+// Like the SQLite backend, a save writes only the rows that
+// differ from what this server last read or wrote, in one
+// transaction. Another server's save shows as a new generation:
+// the writer then loads the db again before its next command
+// (changed_externally), and a save racing another one is
+// refused rather than overwriting it.
+// --------------------------------------------------
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::{Map, Value};
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use sqlx::types::Json;
+use sqlx::Row as _;
+use uuid::Uuid;
+
+use crate::models::{DaySettings, Db, SCHEMA_VERSION};
+use crate::request_id;
+use crate::store::{self, Decoded, Store, StoreError, StoreFuture};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS tasks (
+    id       UUID PRIMARY KEY,
+    archived BOOLEAN NOT NULL,
+    pos      BIGINT NOT NULL,
+    tags     TEXT[],
+    body     JSONB NOT NULL
+);
+CREATE TABLE IF NOT EXISTS meta (
+    key  TEXT PRIMARY KEY,
+    body JSONB NOT NULL
+);
+";
+
+// Connections kept open to the database.
+const MAX_CONNECTIONS: u32 = 4;
+
+// How long a load or save waits for a connection before failing.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct PostgresStore {
+    url: String,
+    // Opened by the first load or save
+    open: tokio::sync::Mutex<Option<Open>>,
+}
+
+// A pool and what each row holds, as last read or written.
+struct Open {
+    pool: PgPool,
+    tasks: HashMap<Uuid, Row>,
+    meta: HashMap<String, Value>,
+    generation: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Row {
+    archived: bool,
+    pos: i64,
+    tags: Option<Vec<String>>,
+    body: Value, // the task without tags
+}
+
+// What went wrong, plus the underlying error for the log line.
+type Failure = (StoreError, String);
+
+impl PostgresStore {
+    pub fn new(url: &str) -> Self {
+        Self { url: url.to_string(), open: tokio::sync::Mutex::new(None) }
+    }
+}
+
+impl Store for PostgresStore {
+    fn load(&self) -> StoreFuture<'_, Db> {
+        Box::pin(async move {
+            let tag = request_id::log_tag();
+            let mut slot = self.open.lock().await;
+            let pool = match slot.take() {
+                Some(open) => open.pool,
+                None => connect(&self.url).await.map_err(|f| log_failure(&tag, "load", f))?,
+            };
+            let (db, open) = load_rows(pool).await.map_err(|f| log_failure(&tag, "load", f))?;
+            *slot = Some(open);
+            Ok(db)
+        })
+    }
+
+    fn save(&self, db: Arc<Db>) -> StoreFuture<'_, ()> {
+        Box::pin(async move {
+            let tag = request_id::log_tag();
+            let mut slot = self.open.lock().await;
+            if slot.is_none() {
+                let pool = connect(&self.url).await.map_err(|f| log_failure(&tag, "save", f))?;
+                let open = read(pool).await.map_err(|f| log_failure(&tag, "save", f))?;
+                *slot = Some(open);
+            }
+            match slot.as_mut() {
+                Some(open) => write(open, &db).await.map_err(|f| log_failure(&tag, "save", f)),
+                None => Ok(()),
+            }
+        })
+    }
+
+    // Another server saved since this one last loaded or saved.
+    fn changed_externally(&self) -> StoreFuture<'_, bool> {
+        Box::pin(async move {
+            let slot = self.open.lock().await;
+            let Some(open) = slot.as_ref() else {
+                return Ok(false);
+            };
+            Ok(current_generation(&open.pool).await.is_ok_and(|g| g != open.generation))
+        })
+    }
+}
+
+fn log_failure(tag: &str, what: &str, (e, cause): Failure) -> StoreError {
+    eprintln!("  {tag}failed to {what}: {} ({cause})", e.describe());
+    e
+}
+
+// Connect and create the tables if needed.
+async fn connect(url: &str) -> Result<PgPool, Failure> {
+    let pool = PgPoolOptions::new()
+        .max_connections(MAX_CONNECTIONS)
+        .acquire_timeout(CONNECT_TIMEOUT)
+        .connect(url)
+        .await
+        .map_err(|e| sql_failure(&e))?;
+    sqlx::raw_sql(SCHEMA).execute(&pool).await.map_err(|e| sql_failure(&e))?;
+    Ok(pool)
+}
+
+// Read every row.
+async fn read(pool: PgPool) -> Result<Open, Failure> {
+    let mut tasks = HashMap::new();
+    let rows = sqlx::query("SELECT id, archived, pos, tags, body FROM tasks")
+        .fetch_all(&pool)
+        .await
+        .map_err(|e| sql_failure(&e))?;
+    for r in rows {
+        let row = Row {
+            archived: r.try_get("archived").map_err(|e| sql_failure(&e))?,
+            pos: r.try_get("pos").map_err(|e| sql_failure(&e))?,
+            tags: r.try_get("tags").map_err(|e| sql_failure(&e))?,
+            body: r.try_get::<Json<Value>, _>("body").map_err(|e| sql_failure(&e))?.0,
+        };
+        tasks.insert(r.try_get("id").map_err(|e| sql_failure(&e))?, row);
+    }
+
+    let mut meta = HashMap::new();
+    let rows = sqlx::query("SELECT key, body FROM meta").fetch_all(&pool).await.map_err(|e| sql_failure(&e))?;
+    for r in rows {
+        let key: String = r.try_get("key").map_err(|e| sql_failure(&e))?;
+        let body: Json<Value> = r.try_get("body").map_err(|e| sql_failure(&e))?;
+        meta.insert(key, body.0);
+    }
+    let generation = meta.remove("generation").and_then(|g| g.as_i64()).unwrap_or(0);
+
+    Ok(Open { pool, tasks, meta, generation })
+}
+
+// Build the Db from the rows: they are put together as the db.json
+// document would hold them and decoded the same way, so schema
+// migrations apply to every backend. A database without settings
+// (new) gets the defaults; a migrated one is written back at once.
+async fn load_rows(pool: PgPool) -> Result<(Db, Open), Failure> {
+    let mut open = read(pool).await?;
+
+    let fresh = !open.meta.contains_key("settings");
+    let mut doc = Map::new();
+    for (key, value) in &open.meta {
+        match (key.as_str(), value) {
+            // Unknown top-level keys, flattened into the db like db.json's
+            ("extra", Value::Object(extra)) => doc.extend(extra.clone()),
+            (_, value) => {
+                doc.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    if fresh {
+        doc.insert("settings".to_string(), to_value(&DaySettings::default())?);
+        doc.insert("schema_version".to_string(), SCHEMA_VERSION.into());
+    }
+
+    let mut rows: Vec<(&Uuid, &Row)> = open.tasks.iter().collect();
+    rows.sort_by_key(|(_, row)| (row.archived, row.pos));
+    let (mut tasks, mut archived) = (Vec::new(), Vec::new());
+    for (id, row) in rows {
+        let Value::Object(mut task) = row.body.clone() else {
+            return Err(corrupt(format!("task {id}: body is not a JSON object")));
+        };
+        task.insert("tags".to_string(), to_value(&row.tags)?);
+        if row.archived {
+            archived.push(Value::Object(task));
+        } else {
+            tasks.push(Value::Object(task));
+        }
+    }
+    doc.insert("tasks".to_string(), Value::Array(tasks));
+    doc.insert("archived".to_string(), Value::Array(archived));
+
+    let Decoded { mut db, from_version, steps } =
+        store::decode_value(Value::Object(doc)).map_err(|e| (e.clone(), e.code().to_string()))?;
+    store::upgrade(&mut db);
+
+    if fresh || !steps.is_empty() {
+        write(&mut open, &db).await?;
+    }
+    if fresh {
+        println!("  Created the tables in {} with default settings", store::db_label());
+    }
+    if !steps.is_empty() {
+        println!("  Migrated {} from schema v{from_version} to v{SCHEMA_VERSION}", store::db_label());
+        for step in steps {
+            println!("    {step}");
+        }
+    }
+    Ok((db, open))
+}
+
+async fn current_generation<'e>(ex: impl sqlx::PgExecutor<'e>) -> Result<i64, Failure> {
+    let row = sqlx::query("SELECT body FROM meta WHERE key = 'generation'")
+        .fetch_optional(ex)
+        .await
+        .map_err(|e| sql_failure(&e))?;
+    match row {
+        Some(r) => {
+            let body: Json<Value> = r.try_get("body").map_err(|e| sql_failure(&e))?;
+            Ok(body.0.as_i64().unwrap_or(0))
+        }
+        None => Ok(0),
+    }
+}
+
+// Write the rows that differ from `open`'s record of the database,
+// and delete the ones `db` no longer has, in one transaction that
+// bumps the generation. Refused when another server saved since.
+async fn write(open: &mut Open, db: &Db) -> Result<(), Failure> {
+    let mut tasks = HashMap::new();
+    for (archived, list) in [(false, &db.tasks), (true, &db.archived)] {
+        for (pos, t) in list.iter().enumerate() {
+            let Value::Object(mut body) = to_value(t)? else {
+                return Err((StoreError::Io(io::ErrorKind::InvalidData), format!("task {} is not an object", t.id)));
+            };
+            body.remove("tags");
+            let row = Row { archived, pos: pos as i64, tags: t.tags.clone(), body: Value::Object(body) };
+            // Would overwrite each other's row; repair gives one a new id
+            if tasks.insert(t.id, row).is_some() {
+                let cause = format!("task id {} is used twice (POST /api/admin/repair)", t.id);
+                return Err((StoreError::Io(io::ErrorKind::InvalidData), cause));
+            }
+        }
+    }
+    let meta: HashMap<String, Value> = [
+        ("schema_version", to_value(&db.schema_version)?),
+        ("settings", to_value(&db.settings)?),
+        ("notifications", to_value(&db.notifications)?),
+        ("deliveries", to_value(&db.deliveries)?),
+        ("trash", to_value(&db.trash)?),
+        ("extra", to_value(&db.extra)?),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v))
+    .collect();
+
+    let mut tx = open.pool.begin().await.map_err(|e| sql_failure(&e))?;
+    // Saves from all servers take this lock, so the check below
+    // and the writes cannot interleave with another server's
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtext('scheduler-db'))")
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| sql_failure(&e))?;
+    let generation = current_generation(&mut *tx).await?;
+    if generation != open.generation {
+        let cause = "another server saved since this one loaded the db; the next write reloads it".to_string();
+        return Err((StoreError::Io(io::ErrorKind::Other), cause));
+    }
+
+    for (id, row) in &tasks {
+        if open.tasks.get(id) != Some(row) {
+            sqlx::query(
+                "INSERT INTO tasks (id, archived, pos, tags, body) VALUES ($1, $2, $3, $4, $5)
+                 ON CONFLICT (id) DO UPDATE SET archived = $2, pos = $3, tags = $4, body = $5",
+            )
+            .bind(id)
+            .bind(row.archived)
+            .bind(row.pos)
+            .bind(&row.tags)
+            .bind(Json(&row.body))
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| sql_failure(&e))?;
+        }
+    }
+    for id in open.tasks.keys().filter(|id| !tasks.contains_key(id)) {
+        sqlx::query("DELETE FROM tasks WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| sql_failure(&e))?;
+    }
+    let next = generation + 1;
+    let changed_meta = meta.iter().filter(|(key, body)| open.meta.get(*key) != Some(body));
+    for (key, body) in changed_meta.chain([(&"generation".to_string(), &Value::from(next))]) {
+        sqlx::query("INSERT INTO meta (key, body) VALUES ($1, $2) ON CONFLICT (key) DO UPDATE SET body = $2")
+            .bind(key)
+            .bind(Json(body))
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| sql_failure(&e))?;
+    }
+    tx.commit().await.map_err(|e| sql_failure(&e))?;
+
+    open.tasks = tasks;
+    open.meta = meta;
+    open.generation = next;
+    Ok(())
+}
+
+fn to_value<T: serde::Serialize>(value: &T) -> Result<Value, Failure> {
+    serde_json::to_value(value).map_err(|e| (StoreError::Io(io::ErrorKind::InvalidData), e.to_string()))
+}
+
+fn corrupt(message: String) -> Failure {
+    (StoreError::Corrupt { line: 0, column: 0, message: message.clone(), backup: None }, message)
+}
+
+fn sql_failure(e: &sqlx::Error) -> Failure {
+    let err = match e {
+        sqlx::Error::Io(e) => StoreError::from_io(e),
+        sqlx::Error::Database(db) => match db.code().as_deref() {
+            Some("42501") => StoreError::PermissionDenied, // insufficient_privilege
+            Some("53100") => StoreError::Io(io::ErrorKind::StorageFull), // disk_full
+            _ => StoreError::Io(io::ErrorKind::Other),
+        },
+        sqlx::Error::ColumnDecode { .. } | sqlx::Error::Decode(_) => return corrupt(e.to_string()),
+        _ => StoreError::Io(io::ErrorKind::Other),
+    };
+    (err, e.to_string())
+}
+
+// Against a real database, named by SCHEDULER_TEST_DATABASE_URL;
+// skipped without it. Each test drops the tables first.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock;
+    use crate::models::{test_task, TaskStatus};
+
+    // One test at a time: they share the tables
+    static DATABASE: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    async fn empty_database() -> Option<String> {
+        let Ok(url) = std::env::var("SCHEDULER_TEST_DATABASE_URL") else {
+            eprintln!("SCHEDULER_TEST_DATABASE_URL is not set; skipping");
+            return None;
+        };
+        let pool = PgPoolOptions::new().max_connections(1).connect(&url).await.unwrap();
+        sqlx::raw_sql("DROP TABLE IF EXISTS tasks, meta").execute(&pool).await.unwrap();
+        Some(url)
+    }
+
+    fn json<T: serde::Serialize>(value: &T) -> Value {
+        serde_json::to_value(value).unwrap()
+    }
+
+    #[tokio::test]
+    async fn a_db_round_trips_with_tags_in_a_text_array() {
+        let _one = DATABASE.lock().await;
+        let Some(url) = empty_database().await else { return };
+
+        let store = PostgresStore::new(&url);
+        let mut db = store.load().await.unwrap();
+        assert_eq!(json(&db.settings), json(&DaySettings::default()));
+        assert!(db.tasks.is_empty());
+
+        let due = clock::local("2024-06-03 12:00");
+        let mut tagged = test_task("tagged", due);
+        tagged.tags = Some(vec!["work".to_string(), "deep-work".to_string()]);
+        tagged.extra.insert("comments".to_string(), serde_json::json!(["kept"]));
+        let untagged = test_task("untagged", due);
+        let mut archived = test_task("archived", due);
+        archived.status = TaskStatus::Done;
+        db.insert(tagged.clone());
+        db.insert(untagged.clone());
+        db.archived.push(archived);
+        db.settings.day_start = "08:00".to_string();
+        db.extra.insert("from_a_newer_build".to_string(), true.into());
+        store.save(Arc::new(db.clone())).await.unwrap();
+
+        let loaded = PostgresStore::new(&url).load().await.unwrap();
+        assert_eq!(json(&loaded), json(&db));
+
+        let pool = PgPoolOptions::new().max_connections(1).connect(&url).await.unwrap();
+        let tags = |id: Uuid| {
+            sqlx::query_scalar::<_, Option<Vec<String>>>("SELECT tags FROM tasks WHERE id = $1").bind(id).fetch_one(&pool)
+        };
+        assert_eq!(tags(tagged.id).await.unwrap(), tagged.tags);
+        assert_eq!(tags(untagged.id).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn a_save_from_another_server_is_noticed_not_overwritten() {
+        let _one = DATABASE.lock().await;
+        let Some(url) = empty_database().await else { return };
+
+        let (a, b) = (PostgresStore::new(&url), PostgresStore::new(&url));
+        let mut on_a = a.load().await.unwrap();
+        let mut on_b = b.load().await.unwrap();
+        assert!(!a.changed_externally().await.unwrap() && !b.changed_externally().await.unwrap());
+
+        let from_a = test_task("from a", clock::local("2024-06-03 12:00"));
+        on_a.insert(from_a.clone());
+        a.save(Arc::new(on_a)).await.unwrap();
+        assert!(!a.changed_externally().await.unwrap());
+        assert!(b.changed_externally().await.unwrap());
+
+        // b has not seen a's task: its save is refused, and a reload has it
+        on_b.insert(test_task("from b", clock::local("2024-06-03 13:00")));
+        assert!(b.save(Arc::new(on_b)).await.is_err());
+        let mut on_b = b.load().await.unwrap();
+        assert!(on_b.get(from_a.id).is_some());
+        on_b.insert(test_task("from b", clock::local("2024-06-03 13:00")));
+        b.save(Arc::new(on_b)).await.unwrap();
+
+        assert!(a.changed_externally().await.unwrap());
+        let titles: Vec<String> = a.load().await.unwrap().tasks.iter().map(|t| t.title.clone()).collect();
+        assert_eq!(titles, ["from a", "from b"]);
+    }
+}
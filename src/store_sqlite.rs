@@ -0,0 +1,411 @@
+// --------------------------------------------------
+// SQLite backend (cargo feature "sqlite", storage.backend = "sqlite").
+//
+// Tables:
+// - tasks(id, archived, pos, body): one row per task, body is the
+//   task as JSON, so every field (tags, notes, keys this version
+//   does not know) round-trips exactly as db.json would keep it
+// - meta(key, body): schema_version, settings, notifications,
+//   deliveries, trash and the db's unknown top-level keys, as JSON
+//
+// The writer still hands over the whole Db on every save. The
+// store remembers what each row holds and writes only the rows
+// that differ, in one transaction: editing one task of thousands
+// writes one row, not the whole database.
+//
+// rusqlite blocks, so every call runs on the blocking pool.
+// --------------------------------------------------
+
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use rusqlite::{params, Connection, ErrorCode};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::models::{DaySettings, Db, SCHEMA_VERSION};
+use crate::request_id;
+use crate::store::{self, Decoded, Store, StoreError, StoreFuture};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS tasks (
+    id       TEXT PRIMARY KEY,
+    archived INTEGER NOT NULL,
+    pos      INTEGER NOT NULL,
+    body     TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS meta (
+    key  TEXT PRIMARY KEY,
+    body TEXT NOT NULL
+);
+";
+
+pub struct SqliteStore {
+    path: String,
+    // Opened by the first load or save
+    open: Arc<Mutex<Option<Open>>>,
+}
+
+// A connection and what each row holds, as last read or written.
+struct Open {
+    conn: Connection,
+    tasks: HashMap<Uuid, Row>,
+    meta: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Row {
+    archived: bool,
+    pos: i64,
+    body: String,
+}
+
+// What went wrong, plus the underlying error for the log line.
+type Failure = (StoreError, String);
+
+impl SqliteStore {
+    pub fn new(path: &str) -> Self {
+        Self { path: path.to_string(), open: Arc::new(Mutex::new(None)) }
+    }
+}
+
+impl Store for SqliteStore {
+    fn load(&self) -> StoreFuture<'_, Db> {
+        let (path, open) = (self.path.clone(), self.open.clone());
+        Box::pin(async move {
+            let tag = request_id::log_tag();
+            blocking(move || load_blocking(&path, &open)).await.map_err(|(e, cause)| {
+                eprintln!("  {tag}failed to load: {} ({cause})", e.describe());
+                e
+            })
+        })
+    }
+
+    fn save(&self, db: Arc<Db>) -> StoreFuture<'_, ()> {
+        let (path, open) = (self.path.clone(), self.open.clone());
+        Box::pin(async move {
+            let tag = request_id::log_tag();
+            blocking(move || save_blocking(&path, &open, &db)).await.map_err(|(e, cause)| {
+                eprintln!("  {tag}failed to save: {} ({cause})", e.describe());
+                e
+            })
+        })
+    }
+}
+
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, Failure> + Send + 'static,
+) -> Result<T, Failure> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .unwrap_or_else(|e| Err((StoreError::Io(io::ErrorKind::Other), e.to_string())))
+}
+
+// Open (creating the file and tables if needed) and read every row.
+fn open(path: &str) -> Result<Open, Failure> {
+    let conn = Connection::open(path).map_err(|e| sql_failure(&e))?;
+    conn.execute_batch(SCHEMA).map_err(|e| sql_failure(&e))?;
+
+    let mut tasks = HashMap::new();
+    let mut stmt = conn.prepare("SELECT id, archived, pos, body FROM tasks").map_err(|e| sql_failure(&e))?;
+    let rows = stmt
+        .query_map([], |r| {
+            let row = Row { archived: r.get(1)?, pos: r.get(2)?, body: r.get(3)? };
+            Ok((r.get::<_, String>(0)?, row))
+        })
+        .map_err(|e| sql_failure(&e))?;
+    for row in rows {
+        let (id, row) = row.map_err(|e| sql_failure(&e))?;
+        let id = Uuid::parse_str(&id).map_err(|e| corrupt(format!("task id \"{id}\": {e}")))?;
+        tasks.insert(id, row);
+    }
+    drop(stmt);
+
+    let mut meta = HashMap::new();
+    let mut stmt = conn.prepare("SELECT key, body FROM meta").map_err(|e| sql_failure(&e))?;
+    let rows = stmt
+        .query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?)))
+        .map_err(|e| sql_failure(&e))?;
+    for row in rows {
+        let (key, body) = row.map_err(|e| sql_failure(&e))?;
+        meta.insert(key, body);
+    }
+    drop(stmt);
+
+    Ok(Open { conn, tasks, meta })
+}
+
+// Build the Db from the rows: they are put together as the db.json
+// document would hold them and decoded the same way, so schema
+// migrations apply to both backends. A file without settings (new)
+// gets the defaults; a migrated one is written back at once.
+fn load_blocking(path: &str, slot: &Mutex<Option<Open>>) -> Result<Db, Failure> {
+    let created = !Path::new(path).exists();
+    let mut open = open(path)?;
+
+    let fresh = !open.meta.contains_key("settings");
+    let mut doc = Map::new();
+    for (key, body) in &open.meta {
+        let value: Value = parse(key, body)?;
+        match (key.as_str(), value) {
+            // Unknown top-level keys, flattened into the db like db.json's
+            ("extra", Value::Object(extra)) => doc.extend(extra),
+            (_, value) => {
+                doc.insert(key.clone(), value);
+            }
+        }
+    }
+    if fresh {
+        doc.insert("settings".to_string(), to_value(&DaySettings::default())?);
+        doc.insert("schema_version".to_string(), SCHEMA_VERSION.into());
+    }
+
+    let mut rows: Vec<(&Uuid, &Row)> = open.tasks.iter().collect();
+    rows.sort_by_key(|(_, row)| (row.archived, row.pos));
+    let (mut tasks, mut archived) = (Vec::new(), Vec::new());
+    for (id, row) in rows {
+        let task: Value = parse(&format!("task {id}"), &row.body)?;
+        if row.archived {
+            archived.push(task);
+        } else {
+            tasks.push(task);
+        }
+    }
+    doc.insert("tasks".to_string(), Value::Array(tasks));
+    doc.insert("archived".to_string(), Value::Array(archived));
+
+    let Decoded { mut db, from_version, steps } =
+        store::decode_value(Value::Object(doc)).map_err(|e| (e.clone(), e.code().to_string()))?;
+    store::upgrade(&mut db);
+
+    if fresh || !steps.is_empty() {
+        write(&mut open, &db)?;
+    }
+    if fresh && created {
+        println!("  Created {path} with default settings");
+    }
+    if !steps.is_empty() {
+        println!("  Migrated {path} from schema v{from_version} to v{SCHEMA_VERSION}");
+        for step in steps {
+            println!("    {step}");
+        }
+    }
+    *slot.lock().unwrap_or_else(|e| e.into_inner()) = Some(open);
+    Ok(db)
+}
+
+fn save_blocking(path: &str, slot: &Mutex<Option<Open>>, db: &Db) -> Result<(), Failure> {
+    let mut slot = slot.lock().unwrap_or_else(|e| e.into_inner());
+    if slot.is_none() {
+        *slot = Some(open(path)?);
+    }
+    match slot.as_mut() {
+        Some(open) => write(open, db),
+        None => Ok(()),
+    }
+}
+
+// Write the rows that differ from `open`'s record of the file, and
+// delete the ones `db` no longer has, in one transaction.
+fn write(open: &mut Open, db: &Db) -> Result<(), Failure> {
+    let mut tasks = HashMap::new();
+    for (archived, list) in [(false, &db.tasks), (true, &db.archived)] {
+        for (pos, t) in list.iter().enumerate() {
+            let row = Row { archived, pos: pos as i64, body: to_json(t)? };
+            // Would overwrite each other's row; repair gives one a new id
+            if tasks.insert(t.id, row).is_some() {
+                let cause = format!("task id {} is used twice (POST /api/admin/repair)", t.id);
+                return Err((StoreError::Io(io::ErrorKind::InvalidData), cause));
+            }
+        }
+    }
+    let meta: HashMap<String, String> = [
+        ("schema_version", to_json(&db.schema_version)?),
+        ("settings", to_json(&db.settings)?),
+        ("notifications", to_json(&db.notifications)?),
+        ("deliveries", to_json(&db.deliveries)?),
+        ("trash", to_json(&db.trash)?),
+        ("extra", to_json(&db.extra)?),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v))
+    .collect();
+
+    let tx = open.conn.transaction().map_err(|e| sql_failure(&e))?;
+    for (id, row) in &tasks {
+        if open.tasks.get(id) != Some(row) {
+            tx.execute(
+                "INSERT INTO tasks (id, archived, pos, body) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(id) DO UPDATE SET archived = ?2, pos = ?3, body = ?4",
+                params![id.to_string(), row.archived, row.pos, row.body],
+            )
+            .map_err(|e| sql_failure(&e))?;
+        }
+    }
+    for id in open.tasks.keys().filter(|id| !tasks.contains_key(id)) {
+        tx.execute("DELETE FROM tasks WHERE id = ?1", params![id.to_string()])
+            .map_err(|e| sql_failure(&e))?;
+    }
+    for (key, body) in &meta {
+        if open.meta.get(key) != Some(body) {
+            tx.execute(
+                "INSERT INTO meta (key, body) VALUES (?1, ?2) ON CONFLICT(key) DO UPDATE SET body = ?2",
+                params![key, body],
+            )
+            .map_err(|e| sql_failure(&e))?;
+        }
+    }
+    tx.commit().map_err(|e| sql_failure(&e))?;
+
+    open.tasks = tasks;
+    open.meta = meta;
+    Ok(())
+}
+
+fn parse<T: serde::de::DeserializeOwned>(what: &str, body: &str) -> Result<T, Failure> {
+    serde_json::from_str(body).map_err(|e| {
+        let err = StoreError::Corrupt {
+            line: e.line(),
+            column: e.column(),
+            message: format!("{what}: {e}"),
+            backup: None,
+        };
+        (err, e.to_string())
+    })
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, Failure> {
+    serde_json::to_string(value).map_err(|e| (StoreError::Io(io::ErrorKind::InvalidData), e.to_string()))
+}
+
+fn to_value<T: serde::Serialize>(value: &T) -> Result<Value, Failure> {
+    serde_json::to_value(value).map_err(|e| (StoreError::Io(io::ErrorKind::InvalidData), e.to_string()))
+}
+
+fn corrupt(message: String) -> Failure {
+    (StoreError::Corrupt { line: 0, column: 0, message: message.clone(), backup: None }, message)
+}
+
+fn sql_failure(e: &rusqlite::Error) -> Failure {
+    let err = match e.sqlite_error_code() {
+        Some(ErrorCode::NotADatabase | ErrorCode::DatabaseCorrupt) => {
+            return corrupt(e.to_string());
+        }
+        Some(ErrorCode::PermissionDenied | ErrorCode::ReadOnly) => StoreError::PermissionDenied,
+        Some(ErrorCode::DiskFull) => StoreError::Io(io::ErrorKind::StorageFull),
+        _ => StoreError::Io(io::ErrorKind::Other),
+    };
+    (err, e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock;
+    use crate::models::{test_task, PlanLock, TaskStatus, TrashedTask};
+    use serde_json::json;
+
+    // A fresh database file in the temp dir.
+    fn temp_db(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("scheduler-sqlite-{}-{name}.sqlite", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path.to_string_lossy().into_owned()
+    }
+
+    fn json<T: serde::Serialize>(value: &T) -> Value {
+        serde_json::to_value(value).unwrap()
+    }
+
+    #[tokio::test]
+    async fn every_task_field_round_trips() {
+        let path = temp_db("round-trip");
+        let store = SqliteStore::new(&path);
+        let mut db = store.load().await.unwrap();
+        assert_eq!(json(&db.settings), json(&DaySettings::default()));
+
+        let due = clock::local("2024-06-03 12:00");
+        let mut full = test_task("Write report", due);
+        full.tags = Some(vec!["work".to_string(), "deep-work".to_string()]);
+        full.notes = Some("two\nlines, \"quoted\"".to_string());
+        full.postpone_count = 2;
+        full.rev = 7;
+        full.updated_at = clock::local("2024-06-02 18:00");
+        full.deadline_notified_at = Some(clock::local("2024-06-03 11:45"));
+        full.locked = Some(PlanLock { start: clock::local("2024-06-03 09:00"), end: clock::local("2024-06-03 10:00") });
+        full.extra.insert("comments".to_string(), json!(["kept as is"]));
+        let mut done = test_task("Done", due);
+        done.status = TaskStatus::Done;
+        done.completed_at = Some(clock::local("2024-06-03 10:30"));
+        let bare = test_task("Bare", due);
+        db.insert(full.clone());
+        db.insert(done.clone());
+        db.insert(bare);
+        db.archived.push(test_task("Archived", clock::local("2024-05-03 12:00")));
+        db.trash.push(TrashedTask { task: test_task("Trashed", due), deleted_at: clock::local("2024-06-03 08:00") });
+        db.settings.day_start = "08:00".to_string();
+        db.extra.insert("from_a_newer_build".to_string(), true.into());
+        store.save(Arc::new(db.clone())).await.unwrap();
+
+        let loaded = SqliteStore::new(&path).load().await.unwrap();
+        assert_eq!(json(&loaded), json(&db));
+        assert_eq!(json(loaded.get(full.id).unwrap()), json(&full));
+        assert_eq!(loaded.get(done.id).unwrap().completed_at, done.completed_at);
+        assert_eq!(loaded.get(full.id).unwrap().extra["comments"], json!(["kept as is"]));
+    }
+
+    #[tokio::test]
+    async fn a_db_json_fixture_imports_into_an_empty_database() {
+        // A file as the first release wrote it
+        let fixture = json!({
+            "settings": { "day_start": "09:00", "day_end": "18:00", "focus_block_min": 60 },
+            "tasks": [
+                {
+                    "id": "6f1c1c39-8a4e-4d47-9a51-2f0b5f1b7a01",
+                    "title": "done long ago",
+                    "due_at": "2024-06-03T12:00:00-04:00",
+                    "duration_min": 60,
+                    "priority": 3,
+                    "status": "done",
+                    "created_at": "2024-06-01T09:00:00-04:00",
+                    "updated_at": "2024-06-02T17:30:00-04:00",
+                    "tags": ["Deep  Work"],
+                    "notes": "kept",
+                    "comments": ["kept as is"]
+                },
+                {
+                    "id": "6f1c1c39-8a4e-4d47-9a51-2f0b5f1b7a02",
+                    "title": "open",
+                    "due_at": "2024-06-04T12:00:00-04:00",
+                    "duration_min": 30,
+                    "priority": 2,
+                    "status": "todo",
+                    "created_at": "2024-06-01T09:00:00-04:00",
+                    "tags": null,
+                    "notes": null
+                }
+            ]
+        });
+        let source = std::env::temp_dir().join(format!("scheduler-sqlite-{}-fixture.json", std::process::id()));
+        std::fs::write(&source, serde_json::to_string_pretty(&fixture).unwrap()).unwrap();
+        let db = crate::read_import_source(&source.to_string_lossy()).unwrap();
+
+        let path = temp_db("import");
+        let store = SqliteStore::new(&path);
+        assert!(store.load().await.unwrap().tasks.is_empty());
+        store.save(Arc::new(db.clone())).await.unwrap();
+
+        let loaded = SqliteStore::new(&path).load().await.unwrap();
+        assert_eq!(json(&loaded), json(&db));
+        assert_eq!(loaded.schema_version, SCHEMA_VERSION);
+        let titles: Vec<&str> = loaded.tasks.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, ["done long ago", "open"]);
+        let done = &loaded.tasks[0];
+        assert_eq!(done.tags.as_deref(), Some(&["deep-work".to_string()][..]));
+        assert_eq!(done.notes.as_deref(), Some("kept"));
+        assert_eq!(done.completed_at, Some(done.updated_at));
+        assert_eq!(done.extra["comments"], json!(["kept as is"]));
+        assert_eq!(loaded.tasks[1].updated_at, loaded.tasks[1].created_at);
+    }
+}
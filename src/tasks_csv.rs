@@ -0,0 +1,251 @@
+/*
+Tasks as CSV, for spreadsheets (GET /api/tasks/export.csv and
+POST /api/tasks/import).
+
+Columns, in this order, with a header line:
+id, title, due_at, duration_min, priority, status, tags, notes, created_at
+
+- due_at and created_at are RFC 3339
+- status is todo, in_progress or done
+- tags are joined with ';' (tags never contain one)
+- a missing notes field is an empty cell
+
+Quoting follows RFC 4180: a cell holding a comma, a quote or a
+line break is quoted, with quotes doubled. Pure, independent
+from HTTP / Axum.
+
+Imports read the same columns by header name, in any order:
+title, due_at, duration_min and priority are required; an
+empty (or missing) id gets a new one, status defaults to todo
+and created_at to now. Other columns are ignored.
+*/
+
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::models::{Extra, Task, TaskStatus};
+use crate::validation::{self, FieldError, Problem, TaskDraft};
+
+pub const COLUMNS: [&str; 9] =
+    ["id", "title", "due_at", "duration_min", "priority", "status", "tags", "notes", "created_at"];
+
+#[derive(Debug, Serialize)]
+struct Row<'a> {
+    id: String,
+    title: &'a str,
+    due_at: String,
+    duration_min: i64,
+    priority: i64,
+    status: &'a TaskStatus,
+    tags: String,
+    notes: &'a str,
+    created_at: String,
+}
+
+impl<'a> Row<'a> {
+    fn new(t: &'a Task) -> Self {
+        Row {
+            id: t.id.to_string(),
+            title: &t.title,
+            due_at: t.due_at.to_rfc3339(),
+            duration_min: t.duration_min,
+            priority: t.priority,
+            status: &t.status,
+            tags: t.tags.as_deref().unwrap_or_default().join(";"),
+            notes: t.notes.as_deref().unwrap_or_default(),
+            created_at: t.created_at.to_rfc3339(),
+        }
+    }
+}
+
+// The tasks as a CSV document, header line first (also with no
+// tasks), CRLF line ends.
+pub fn format(tasks: &[&Task]) -> Result<String, String> {
+    let mut w = csv::WriterBuilder::new()
+        .has_headers(false)
+        .terminator(csv::Terminator::CRLF)
+        .from_writer(Vec::new());
+    w.write_record(COLUMNS).map_err(|e| e.to_string())?;
+    for t in tasks {
+        w.serialize(Row::new(t)).map_err(|e| e.to_string())?;
+    }
+    let bytes = w.into_inner().map_err(|e| e.to_string())?;
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+// Columns an import cannot do without.
+const REQUIRED: [&str; 4] = ["title", "due_at", "duration_min", "priority"];
+
+const STATUSES: &[&str] = &["todo", "in_progress", "done"];
+
+// A row that checked out, with the line it starts on (the header is line 1).
+#[derive(Debug, Clone)]
+pub struct ImportRow {
+    pub line: u64,
+    pub task: Task,
+}
+
+// One problem with a row.
+#[derive(Debug, Clone)]
+pub struct RowError {
+    pub line: u64,
+    pub error: FieldError,
+}
+
+// Rows of an imported CSV document: every valid row, and every
+// problem of the others. Err when the document itself is unusable
+// (not CSV, or a required column is missing).
+pub fn parse(text: &str, now: DateTime<FixedOffset>) -> Result<(Vec<ImportRow>, Vec<RowError>), String> {
+    // Spreadsheets like to start UTF-8 files with a byte order mark
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    // The reader does not count CRLF line ends as lines, which row
+    // errors report
+    let text = text.replace("\r\n", "\n");
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(text.as_bytes());
+
+    let header: Vec<String> = reader
+        .headers()
+        .map_err(|e| format!("cannot read the header line: {e}"))?
+        .iter()
+        .map(|h| h.trim().to_lowercase())
+        .collect();
+    let missing: Vec<&str> = REQUIRED.into_iter().filter(|c| !header.iter().any(|h| h == c)).collect();
+    if !missing.is_empty() {
+        return Err(format!("missing column(s): {}", missing.join(", ")));
+    }
+    let column = |name: &str| header.iter().position(|h| h == name);
+    let columns: Vec<Option<usize>> = COLUMNS.iter().map(|c| column(c)).collect();
+
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| e.to_string())?;
+        let line = record.position().map_or(0, |p| p.line());
+        if record.iter().all(|cell| cell.trim().is_empty()) {
+            continue;
+        }
+        let cell = |name: &str| {
+            let i = COLUMNS.iter().position(|c| *c == name).and_then(|i| columns[i]);
+            i.and_then(|i| record.get(i)).map(str::trim).unwrap_or_default()
+        };
+        match parse_row(cell, now) {
+            Ok(task) => rows.push(ImportRow { line, task }),
+            Err(problems) => errors.extend(problems.into_iter().map(|error| RowError { line, error })),
+        }
+    }
+    Ok((rows, errors))
+}
+
+// One row, from its cells by column name (empty when absent).
+fn parse_row<'a>(cell: impl Fn(&str) -> &'a str, now: DateTime<FixedOffset>) -> Result<Task, Vec<FieldError>> {
+    let mut errors = Vec::new();
+    let mut integer = |field: &'static str| match cell(field).parse::<i64>() {
+        Ok(n) => n,
+        Err(_) => {
+            errors.push(FieldError::new(field, Problem::InvalidFormat { expected: "an integer" }));
+            1 // in range, so validate_task does not report the field twice
+        }
+    };
+    let duration_min = integer("duration_min");
+    let priority = integer("priority");
+
+    let id = match cell("id") {
+        "" => Some(Uuid::new_v4()),
+        raw => Uuid::parse_str(raw).ok(),
+    };
+    if id.is_none() {
+        errors.push(FieldError::new("id", Problem::InvalidFormat { expected: "UUID" }));
+    }
+    let status = match cell("status") {
+        "" | "todo" => Some(TaskStatus::Todo),
+        "in_progress" => Some(TaskStatus::InProgress),
+        "done" => Some(TaskStatus::Done),
+        other => {
+            let problem = Problem::NotOneOf { value: other.to_string(), allowed: STATUSES };
+            errors.push(FieldError::new("status", problem));
+            None
+        }
+    };
+    let created_at = match cell("created_at") {
+        "" => Some(now),
+        raw => DateTime::parse_from_rfc3339(raw).ok(),
+    };
+    if created_at.is_none() {
+        errors.push(FieldError::new("created_at", Problem::InvalidFormat { expected: "RFC3339" }));
+    }
+
+    let notes = Some(cell("notes")).filter(|n| !n.is_empty());
+    let draft = TaskDraft { title: cell("title"), due_at: cell("due_at"), duration_min, priority, notes };
+    let raw_tags: Vec<String> = cell("tags").split(';').map(str::to_string).collect();
+    let valid = validation::validate_task(draft).map_err(|e| errors.extend(e)).ok();
+    let tags = validation::clean_tags("tags", &raw_tags).map_err(|e| errors.extend(e)).ok();
+
+    match (id, status, created_at, valid, tags) {
+        (Some(id), Some(status), Some(created_at), Some(v), Some(tags)) if errors.is_empty() => Ok(Task {
+            id,
+            title: v.title,
+            due_at: v.due_at,
+            duration_min: v.duration_min,
+            priority: v.priority,
+            status,
+            created_at,
+            tags,
+            notes: v.notes,
+            completed_at: None,
+            postpone_count: 0,
+            rev: 0,
+            updated_at: created_at,
+            deadline_notified_at: None,
+            locked: None,
+            extra: Extra::new(),
+        }),
+        _ => Err(errors),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock;
+    use crate::models::test_task;
+
+    fn nasty() -> Vec<Task> {
+        let due = clock::local("2024-06-03 12:00");
+        let mut quoted = test_task("Call \"Acme, Inc.\"", due);
+        quoted.notes = Some("line one\nline two, with a comma\r\nand \"quotes\"".to_string());
+        quoted.tags = Some(vec!["work".to_string(), "clients".to_string()]);
+        let mut plain = test_task("Plain", due + chrono::Duration::hours(1));
+        plain.status = TaskStatus::InProgress;
+        let mut edges = test_task("=SUM(A1), \"'\"", due + chrono::Duration::hours(2));
+        edges.status = TaskStatus::Done;
+        vec![quoted, plain, edges]
+    }
+
+    #[test]
+    fn nasty_text_round_trips_through_the_csv() {
+        let tasks = nasty();
+        let text = format(&tasks.iter().collect::<Vec<_>>()).unwrap();
+        assert!(text.starts_with("id,title,due_at,duration_min,priority,status,tags,notes,created_at\r\n"));
+        assert!(text.contains("\"Call \"\"Acme, Inc.\"\"\""), "{text}");
+
+        let (rows, errors) = parse(&text, clock::local("2024-06-03 08:00")).unwrap();
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(rows.len(), tasks.len());
+        for (row, task) in rows.iter().zip(&tasks) {
+            let back = &row.task;
+            assert_eq!((back.id, &back.title, back.due_at, &back.status), (task.id, &task.title, task.due_at, &task.status));
+            assert_eq!((back.duration_min, back.priority), (task.duration_min, task.priority));
+            assert_eq!((&back.tags, back.created_at), (&task.tags, task.created_at));
+        }
+        // Inside a quoted cell a CRLF reads back as a line feed
+        assert_eq!(rows[0].task.notes.as_deref(), Some("line one\nline two, with a comma\nand \"quotes\""));
+        // The second row starts after the first one's quoted line breaks
+        assert_eq!(rows[1].line, 5);
+    }
+
+    #[test]
+    fn no_tasks_is_still_a_header_line() {
+        assert_eq!(format(&[]).unwrap(), format!("{}\r\n", COLUMNS.join(",")));
+    }
+}
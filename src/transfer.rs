@@ -0,0 +1,305 @@
+/*
+Whole-database transfer between machines (GET /api/export,
+POST /api/import).
+
+The export document is the Db as db.json holds it, schema_version
+included, so an export from an older build is migrated on import
+like an old db.json. Checks and merging live here, independent
+from HTTP; routes_import.rs wires them into the API.
+
+An import is all or nothing: every problem in the document is
+reported (422, one entry per field, tasks by index as
+"tasks[3].priority") and nothing is written.
+*/
+
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::i18n::{self, Lang};
+use crate::migrate::{self, TooNew};
+use crate::models::{DaySettings, Db, Task, SCHEMA_VERSION};
+use crate::store;
+use crate::validation::{self, FieldError, TaskDraft};
+
+// Why an import document was refused.
+#[derive(Debug)]
+pub enum Rejected {
+    NotJson(String),
+    TooNew(u32),
+    Invalid(Vec<Value>), // {"field", "code", "message"} entries
+}
+
+// A checked document, ready to replace or merge into the db.
+pub struct Incoming {
+    pub db: Db,
+    pub migrated_from: Option<u32>, // schema_version, when older than current
+}
+
+// Parse, migrate and check an export document. Task titles and
+// notes are sanitized and tags normalized like POST /api/tasks.
+pub fn parse(body: &str, lang: Lang) -> Result<Incoming, Rejected> {
+    let mut value: Value = serde_json::from_str(body).map_err(|e| Rejected::NotJson(e.to_string()))?;
+    if !value.is_object() {
+        return Err(Rejected::NotJson("expected a JSON object".to_string()));
+    }
+    let from_version = migrate::version_of(&value);
+    let steps = migrate::migrate_value(&mut value).map_err(|TooNew { version }| Rejected::TooNew(version))?;
+
+    let mut errors = Vec::new();
+    match value.get("settings").cloned().map(serde_json::from_value::<DaySettings>) {
+        None => errors.push(invalid("settings", "required", "settings is required".to_string())),
+        Some(Err(e)) => errors.push(invalid("settings", "invalid", e.to_string())),
+        Some(Ok(settings)) => {
+            if let Err(field_errors) = validation::validate_settings(&settings) {
+                errors.extend(field_errors.iter().map(|e| field_error("settings", e, lang)));
+            }
+        }
+    }
+    let tasks = check_tasks(&value, "tasks", lang, &mut errors);
+    let archived = check_tasks(&value, "archived", lang, &mut errors);
+
+    // Ids are unique across both lists
+    let mut first: HashMap<Uuid, String> = HashMap::new();
+    for (list, tasks) in [("tasks", &tasks), ("archived", &archived)] {
+        for (i, t) in tasks.iter().enumerate() {
+            let Some(t) = t else { continue };
+            let path = format!("{list}[{i}]");
+            match first.get(&t.id) {
+                Some(earlier) => {
+                    let message = i18n::duplicate_id(lang, &t.id.to_string(), earlier);
+                    errors.push(invalid(&format!("{path}.id"), "duplicate", message));
+                }
+                None => {
+                    first.insert(t.id, path);
+                }
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return Err(Rejected::Invalid(errors));
+    }
+
+    // Everything else (notifications, deliveries, unknown keys)
+    let mut db: Db = serde_json::from_value(value)
+        .map_err(|e| Rejected::Invalid(vec![invalid("", "invalid", e.to_string())]))?;
+    db.tasks = tasks.into_iter().flatten().collect();
+    db.archived = archived.into_iter().flatten().collect();
+    store::upgrade(&mut db);
+    let migrated_from = (!steps.is_empty()).then_some(from_version);
+    Ok(Incoming { db, migrated_from })
+}
+
+// Each task of one list, None where it failed (errors recorded).
+fn check_tasks(doc: &Value, list: &str, lang: Lang, errors: &mut Vec<Value>) -> Vec<Option<Task>> {
+    let raw = match doc.get(list) {
+        Some(Value::Array(raw)) => raw.as_slice(),
+        None | Some(Value::Null) => &[],
+        Some(_) => {
+            errors.push(invalid(list, "invalid", format!("{list} must be an array")));
+            return Vec::new();
+        }
+    };
+    raw.iter()
+        .enumerate()
+        .map(|(i, raw)| {
+            let path = format!("{list}[{i}]");
+            let mut t: Task = match serde_json::from_value(raw.clone()) {
+                Ok(t) => t,
+                Err(e) => {
+                    errors.push(invalid(&path, "invalid", e.to_string()));
+                    return None;
+                }
+            };
+            let due_at = t.due_at.to_rfc3339();
+            let checked = validation::validate_task(TaskDraft {
+                title: &t.title,
+                due_at: &due_at,
+                duration_min: t.duration_min,
+                priority: t.priority,
+                notes: t.notes.as_deref(),
+            });
+            let tags = validation::clean_tags("tags", t.tags.as_deref().unwrap_or_default());
+            match (checked, tags) {
+                (Ok(v), Ok(tags)) => {
+                    t.title = v.title;
+                    t.notes = v.notes;
+                    t.tags = tags;
+                    Some(t)
+                }
+                (checked, tags) => {
+                    let field_errors = checked.err().into_iter().chain(tags.err()).flatten();
+                    errors.extend(field_errors.map(|e| field_error(&path, &e, lang)));
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn invalid(field: &str, code: &str, message: String) -> Value {
+    json!({ "field": field, "code": code, "message": message })
+}
+
+// A FieldError under `prefix` ("tasks[3]" -> "tasks[3].priority").
+fn field_error(prefix: &str, e: &FieldError, lang: Lang) -> Value {
+    let mut entry = e.to_json(lang);
+    entry["field"] = format!("{prefix}.{}", e.field).into();
+    entry
+}
+
+// How an import changed (or would change) the tasks.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Outcome {
+    pub added: usize,   // ids the db did not have
+    pub updated: usize, // ids it had, now holding the incoming copy
+    pub removed: usize, // ids only the db had (replace mode)
+}
+
+// Replace the whole db (settings, notifications, deliveries included).
+pub fn replace(db: &mut Db, incoming: Db) -> Outcome {
+    let old: HashSet<Uuid> = db.tasks.iter().chain(db.archived.iter()).map(|t| t.id).collect();
+    let new: HashSet<Uuid> = incoming.tasks.iter().chain(incoming.archived.iter()).map(|t| t.id).collect();
+    let updated = new.intersection(&old).count();
+    let outcome = Outcome {
+        added: new.len() - updated,
+        updated,
+        removed: old.len() - updated,
+    };
+    *db = incoming;
+    db.schema_version = SCHEMA_VERSION;
+    outcome
+}
+
+// Merge the incoming tasks by id, the incoming copy winning. A task
+// keeps its place when it stays in the same list; one that moves
+// between tasks and archived follows the incoming document. Local
+// settings, notifications and trash are kept, less the trashed
+// copies of tasks the document brings back.
+pub fn merge(db: &mut Db, incoming: Db) -> Outcome {
+    let mut outcome = Outcome::default();
+    let ids: HashSet<Uuid> = incoming.tasks.iter().chain(incoming.archived.iter()).map(|t| t.id).collect();
+    db.trash.retain(|t| !ids.contains(&t.task.id));
+    for t in incoming.tasks {
+        let was_archived = db.archived.iter().position(|a| a.id == t.id).map(|i| db.archived.remove(i));
+        if was_archived.is_some() || db.get(t.id).is_some() {
+            outcome.updated += 1;
+        } else {
+            outcome.added += 1;
+        }
+        db.insert(t);
+    }
+    for t in incoming.archived {
+        if let Some(slot) = db.archived.iter_mut().find(|a| a.id == t.id) {
+            *slot = t;
+            outcome.updated += 1;
+        } else {
+            if db.remove(t.id).is_some() {
+                outcome.updated += 1;
+            } else {
+                outcome.added += 1;
+            }
+            db.archived.push(t);
+        }
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock;
+    use crate::models::{test_task, TrashedTask};
+
+    fn task(title: &str) -> Task {
+        test_task(title, clock::local("2031-03-04 09:00"))
+    }
+
+    fn titles(tasks: &[Task]) -> Vec<&str> {
+        tasks.iter().map(|t| t.title.as_str()).collect()
+    }
+
+    fn fields(rejected: Rejected) -> Vec<String> {
+        match rejected {
+            Rejected::Invalid(errors) => errors.iter().map(|e| e["field"].as_str().unwrap().to_string()).collect(),
+            other => panic!("expected Invalid, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn merge_lets_the_incoming_copy_win_without_duplicating_ids() {
+        let (kept, edited, archiving, unarchiving) = (task("kept"), task("edited"), task("archiving"), task("unarchiving"));
+        let mut db = Db::default();
+        db.insert(kept.clone());
+        db.insert(edited.clone());
+        db.insert(archiving.clone());
+        db.archived.push(unarchiving.clone());
+        db.trash.push(TrashedTask { task: task("trashed"), deleted_at: clock::local("2031-03-01 09:00") });
+
+        let mut incoming = Db::default();
+        incoming.tasks = vec![
+            Task { title: "edited elsewhere".to_string(), ..edited.clone() },
+            Task { title: "back from the archive".to_string(), ..unarchiving.clone() },
+            task("new"),
+        ];
+        incoming.archived = vec![Task { title: "now archived".to_string(), ..archiving.clone() }];
+        let back = db.trash[0].task.clone();
+        incoming.archived.push(back.clone());
+        let outcome = merge(&mut db, incoming);
+
+        assert_eq!((outcome.added, outcome.updated, outcome.removed), (2, 3, 0));
+        assert_eq!(titles(&db.tasks), ["kept", "edited elsewhere", "back from the archive", "new"]);
+        assert_eq!(titles(&db.archived), ["now archived", "trashed"]);
+        assert!(db.trash.is_empty());
+        let mut ids: Vec<Uuid> = db.tasks.iter().chain(&db.archived).map(|t| t.id).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), db.tasks.len() + db.archived.len());
+        assert_eq!(db.get(unarchiving.id).map(|t| t.title.as_str()), Some("back from the archive"));
+        assert!(db.get(archiving.id).is_none());
+        assert!(db.get(back.id).is_none());
+    }
+
+    #[test]
+    fn replace_counts_added_updated_and_removed() {
+        let (a, b, c) = (task("a"), task("b"), task("c"));
+        let mut db = Db::default();
+        db.insert(a.clone());
+        db.insert(b.clone());
+        db.archived.push(c.clone());
+        db.settings.day_start = "06:00".to_string();
+
+        let mut incoming = Db::default();
+        incoming.tasks = vec![Task { title: "b again".to_string(), ..b }, task("d"), task("e")];
+        let outcome = replace(&mut db, incoming);
+
+        assert_eq!((outcome.added, outcome.updated, outcome.removed), (2, 1, 2));
+        assert_eq!(titles(&db.tasks), ["b again", "d", "e"]);
+        assert!(db.archived.is_empty());
+        assert_eq!(db.settings.day_start, DaySettings::default().day_start);
+        assert_eq!(db.schema_version, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn a_bad_task_is_named_by_its_index() {
+        let mut doc = serde_json::to_value(Db::default()).unwrap();
+        let mut bad = serde_json::to_value(task("bad priority")).unwrap();
+        bad["priority"] = json!(9);
+        doc["tasks"] = json!([serde_json::to_value(task("fine")).unwrap(), bad]);
+        assert_eq!(fields(parse(&doc.to_string(), Lang::En).err().unwrap()), ["tasks[1].priority"]);
+    }
+
+    #[test]
+    fn a_duplicate_id_is_named_with_the_first_use() {
+        let twice = task("twice");
+        let mut doc = serde_json::to_value(Db::default()).unwrap();
+        doc["tasks"] = json!([serde_json::to_value(&twice).unwrap(), serde_json::to_value(task("other")).unwrap()]);
+        doc["archived"] = json!([serde_json::to_value(&twice).unwrap()]);
+        let Err(Rejected::Invalid(errors)) = parse(&doc.to_string(), Lang::En) else { panic!("expected Invalid") };
+        assert_eq!(errors.len(), 1);
+        assert_eq!((&errors[0]["field"], &errors[0]["code"]), (&json!("archived[0].id"), &json!("duplicate")));
+        assert!(errors[0]["message"].as_str().unwrap().ends_with("tasks[0]"));
+    }
+}
@@ -0,0 +1,505 @@
+// --------------------------------------------------
+// Input validation shared by every entry point that
+// stores user data (task API, settings, imports).
+//
+// Each validate_* function runs every check and returns
+// all violations at once as FieldErrors, so a form with
+// three mistakes gets three messages in one response.
+// Message text is produced per language by i18n.rs.
+//
+// Sanitizing:
+// - Titles: trimmed, every run of control characters or
+//   ANSI escape sequences collapsed to one space
+// - Notes: same, but newlines and tabs are kept
+// - Length limits from config (limits.max_title_len,
+//   limits.max_notes_len), counted in characters
+// - Tags: trimmed, inner whitespace turned into "-",
+//   lowercased unless tags.preserve_case, at most
+//   tags.max_len characters of letters, digits, "-", "_"
+//   and "/", no duplicates (ignoring case). The API
+//   rejects a tag it cannot use; imports and stored data
+//   are coerced into shape instead (coerce_tags)
+// --------------------------------------------------
+
+use chrono::{DateTime, FixedOffset, NaiveTime};
+use serde_json::{json, Value};
+
+use crate::collate;
+use crate::config;
+use crate::i18n::{self, Lang};
+use crate::models::DaySettings;
+
+// What is wrong with a field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    Required,
+    TooLong { max: usize },                   // characters
+    OutOfRange { min: i64, max: Option<i64> }, // inclusive; None = no upper bound
+    InvalidFormat { expected: &'static str },
+    NotOneOf { value: String, allowed: &'static [&'static str] },
+}
+
+impl Problem {
+    // Stable, machine-readable code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Problem::Required => "required",
+            Problem::TooLong { .. } => "too_long",
+            Problem::OutOfRange { .. } => "out_of_range",
+            Problem::InvalidFormat { .. } => "invalid_format",
+            Problem::NotOneOf { .. } => "not_one_of",
+        }
+    }
+}
+
+// One violation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub problem: Problem,
+}
+
+impl FieldError {
+    pub fn new(field: &'static str, problem: Problem) -> Self {
+        Self { field, problem }
+    }
+
+    pub fn message(&self, lang: Lang) -> String {
+        i18n::field_problem(lang, self.field, &self.problem)
+    }
+
+    // {"field", "code", "message"} as returned by the API.
+    pub fn to_json(&self, lang: Lang) -> Value {
+        json!({ "field": self.field, "code": self.problem.code(), "message": self.message(lang) })
+    }
+}
+
+// Joined English messages, for places that report errors as text (import warnings).
+pub fn summary(errors: &[FieldError]) -> String {
+    errors.iter().map(|e| e.message(Lang::En)).collect::<Vec<_>>().join("; ")
+}
+
+// Raw task fields as submitted.
+#[derive(Debug, Clone, Copy)]
+pub struct TaskDraft<'a> {
+    pub title: &'a str,
+    pub due_at: &'a str, // RFC3339
+    pub duration_min: i64,
+    pub priority: i64,
+    pub notes: Option<&'a str>,
+}
+
+// Task fields after validation and sanitizing.
+#[derive(Debug, Clone)]
+pub struct ValidTask {
+    pub title: String,
+    pub due_at: DateTime<FixedOffset>,
+    pub duration_min: i64,
+    pub priority: i64,
+    pub notes: Option<String>,
+}
+
+// Replace control characters and ANSI escape sequences with
+// single spaces; `keep` lists control characters to leave alone.
+fn collapse_controls(raw: &str, keep: &[char]) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+    let mut in_gap = false;
+
+    while let Some(c) = chars.next() {
+        if keep.contains(&c) || !c.is_control() {
+            out.push(c);
+            in_gap = false;
+            continue;
+        }
+        // ESC [ params final: drop the whole CSI sequence
+        if c == '\u{1b}' && chars.peek() == Some(&'[') {
+            chars.next();
+            while let Some(&p) = chars.peek() {
+                chars.next();
+                if ('\u{40}'..='\u{7e}').contains(&p) {
+                    break;
+                }
+            }
+        }
+        if !in_gap {
+            out.push(' ');
+            in_gap = true;
+        }
+    }
+    out
+}
+
+fn clean_title(raw: &str) -> Result<String, FieldError> {
+    let max = config::get().limits.max_title_len;
+    let title = collapse_controls(raw, &[]).trim().to_string();
+    if title.is_empty() {
+        return Err(FieldError::new("title", Problem::Required));
+    }
+    if title.chars().count() > max {
+        return Err(FieldError::new("title", Problem::TooLong { max }));
+    }
+    Ok(title)
+}
+
+// Blank notes become None.
+fn clean_notes(raw: Option<&str>) -> Result<Option<String>, FieldError> {
+    let max = config::get().limits.max_notes_len;
+    let Some(raw) = raw else {
+        return Ok(None);
+    };
+    let notes = collapse_controls(&raw.replace("\r\n", "\n"), &['\n', '\t'])
+        .trim()
+        .to_string();
+    if notes.is_empty() {
+        return Ok(None);
+    }
+    if notes.chars().count() > max {
+        return Err(FieldError::new("notes", Problem::TooLong { max }));
+    }
+    Ok(Some(notes))
+}
+
+// Characters a tag may contain besides letters and digits.
+const TAG_PUNCTUATION: &[char] = &['-', '_', '/'];
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || TAG_PUNCTUATION.contains(&c)
+}
+
+// Join runs of whitespace and "-" into one "-", drop them at
+// both ends, and lowercase unless tags.preserve_case.
+pub fn normalize_tag(raw: &str) -> String {
+    let tag = collapse_controls(raw, &[])
+        .split(|c: char| c.is_whitespace() || c == '-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if config::get().tags.preserve_case { tag } else { tag.to_lowercase() }
+}
+
+// What two tags are compared by: the normalized form, lowercased even
+// with tags.preserve_case ("Über" and "über" are the same tag).
+pub fn tag_key(tag: &str) -> String {
+    normalize_tag(tag).to_lowercase()
+}
+
+// Non-empty tags, first spelling of each kept (ignoring case); empty -> None.
+fn dedupe_tags(tags: impl IntoIterator<Item = String>) -> Option<Vec<String>> {
+    let mut out: Vec<String> = Vec::new();
+    for t in tags {
+        if !t.is_empty() && !out.iter().any(|o| o.to_lowercase() == t.to_lowercase()) {
+            out.push(t);
+        }
+    }
+    (!out.is_empty()).then_some(out)
+}
+
+// Normalize and check tags from the API; `field` names them in errors.
+// Blank entries are dropped.
+pub fn clean_tags(field: &'static str, raw: &[String]) -> Result<Option<Vec<String>>, Vec<FieldError>> {
+    let max = config::get().tags.max_len;
+    let mut errors = Vec::new();
+    let tags: Vec<String> = raw.iter().map(|t| normalize_tag(t)).collect();
+    for t in &tags {
+        if t.chars().count() > max {
+            errors.push(FieldError::new(field, Problem::TooLong { max }));
+        } else if !t.chars().all(is_tag_char) {
+            let problem = Problem::InvalidFormat { expected: "letters, digits, -, _ or /" };
+            errors.push(FieldError::new(field, problem));
+        }
+    }
+    if errors.is_empty() { Ok(dedupe_tags(tags)) } else { Err(errors) }
+}
+
+// Parse a comma-separated list of names from `allowed` (e.g. ?fields=).
+// Blank entries and repeats are dropped; None when nothing is left.
+pub fn parse_names(
+    field: &'static str,
+    raw: &str,
+    allowed: &'static [&'static str],
+) -> Result<Option<Vec<&'static str>>, Vec<FieldError>> {
+    let mut names: Vec<&'static str> = Vec::new();
+    let mut errors = Vec::new();
+    for part in raw.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        match allowed.iter().find(|a| **a == part) {
+            Some(name) if !names.contains(name) => names.push(name),
+            Some(_) => {}
+            None => errors.push(FieldError::new(
+                field,
+                Problem::NotOneOf { value: part.to_string(), allowed },
+            )),
+        }
+    }
+    if !errors.is_empty() {
+        Err(errors)
+    } else {
+        Ok((!names.is_empty()).then_some(names))
+    }
+}
+
+// Force tags into shape without rejecting any (imports, stored data):
+// other characters become "-" and long tags are cut to tags.max_len.
+// Tags clean_tags accepts come back unchanged.
+pub fn coerce_tags(raw: &[String]) -> Option<Vec<String>> {
+    let max = config::get().tags.max_len;
+    let coerce = |raw: &String| {
+        let replaced: String =
+            normalize_tag(raw).chars().map(|c| if is_tag_char(c) { c } else { '-' }).collect();
+        let cut: String = normalize_tag(&replaced).chars().take(max).collect();
+        cut.trim_end_matches('-').to_string()
+    };
+    dedupe_tags(raw.iter().map(coerce))
+}
+
+// Validate and sanitize task fields (create, update, import).
+pub fn validate_task(d: TaskDraft) -> Result<ValidTask, Vec<FieldError>> {
+    let mut errors = Vec::new();
+
+    let title = clean_title(d.title).map_err(|e| errors.push(e)).ok();
+    let notes = clean_notes(d.notes).map_err(|e| errors.push(e)).ok();
+
+    let due_at = DateTime::parse_from_rfc3339(d.due_at).ok();
+    if due_at.is_none() {
+        let problem = Problem::InvalidFormat { expected: "RFC3339" };
+        errors.push(FieldError::new("due_at", problem));
+    }
+
+    let max_duration = config::get().limits.max_duration_min;
+    if !(1..=max_duration).contains(&d.duration_min) {
+        let problem = Problem::OutOfRange { min: 1, max: Some(max_duration) };
+        errors.push(FieldError::new("duration_min", problem));
+    }
+    if !(1..=5).contains(&d.priority) {
+        let problem = Problem::OutOfRange { min: 1, max: Some(5) };
+        errors.push(FieldError::new("priority", problem));
+    }
+
+    match (title, notes, due_at) {
+        (Some(title), Some(notes), Some(due_at)) if errors.is_empty() => Ok(ValidTask {
+            title,
+            due_at,
+            duration_min: d.duration_min,
+            priority: d.priority,
+            notes,
+        }),
+        _ => Err(errors),
+    }
+}
+
+// Longest priority label, in characters.
+const MAX_PRIORITY_LABEL_LEN: usize = 40;
+
+// "#rrggbb" (either case).
+fn is_hex_color(s: &str) -> bool {
+    s.len() == 7 && s.starts_with('#') && s[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// Validate day settings (PUT /api/settings).
+pub fn validate_settings(s: &DaySettings) -> Result<(), Vec<FieldError>> {
+    let mut errors = Vec::new();
+
+    for (field, value) in [
+        ("day_start", &s.day_start),
+        ("day_end", &s.day_end),
+        ("default_due_time", &s.default_due_time),
+    ] {
+        if NaiveTime::parse_from_str(value, "%H:%M").is_err() {
+            errors.push(FieldError::new(field, Problem::InvalidFormat { expected: "HH:MM" }));
+        }
+    }
+    if s.focus_block_min < 1 {
+        let problem = Problem::OutOfRange { min: 1, max: None };
+        errors.push(FieldError::new("focus_block_min", problem));
+    }
+    if s.overdue_grace_min < 0 {
+        let problem = Problem::OutOfRange { min: 0, max: None };
+        errors.push(FieldError::new("overdue_grace_min", problem));
+    }
+    if s.auto_archive_days.is_some_and(|d| d < 0) {
+        let problem = Problem::OutOfRange { min: 0, max: None };
+        errors.push(FieldError::new("auto_archive_days", problem));
+    }
+    if s.wip_limit.is_some_and(|n| n < 1) {
+        let problem = Problem::OutOfRange { min: 1, max: None };
+        errors.push(FieldError::new("wip_limit", problem));
+    }
+    for meta in s.priority_labels.iter().flatten() {
+        let label = meta.label.trim();
+        if label.is_empty() {
+            errors.push(FieldError::new("priority_labels.label", Problem::Required));
+        } else if label.chars().count() > MAX_PRIORITY_LABEL_LEN {
+            let problem = Problem::TooLong { max: MAX_PRIORITY_LABEL_LEN };
+            errors.push(FieldError::new("priority_labels.label", problem));
+        }
+        if !is_hex_color(&meta.color) {
+            let problem = Problem::InvalidFormat { expected: "#rrggbb" };
+            errors.push(FieldError::new("priority_labels.color", problem));
+        }
+    }
+    if collate::parse_locale(&s.sort_locale).is_err() {
+        let problem = Problem::InvalidFormat { expected: "BCP 47" };
+        errors.push(FieldError::new("sort_locale", problem));
+    }
+
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draft<'a>(title: &'a str, notes: Option<&'a str>) -> TaskDraft<'a> {
+        TaskDraft { title, due_at: "2024-06-03T12:00:00-04:00", duration_min: 30, priority: 3, notes }
+    }
+
+    fn problems(result: Result<ValidTask, Vec<FieldError>>) -> Vec<(&'static str, Problem)> {
+        result.err().unwrap_or_default().into_iter().map(|e| (e.field, e.problem)).collect()
+    }
+
+    #[test]
+    fn title_length_boundary_counts_characters() {
+        let max = "가".repeat(500);
+        assert_eq!(validate_task(draft(&max, None)).unwrap().title, max);
+        let over = "가".repeat(501);
+        assert_eq!(problems(validate_task(draft(&over, None))), [("title", Problem::TooLong { max: 500 })]);
+        // Trimmed before counting
+        let padded = format!("  {max}\n");
+        assert!(validate_task(draft(&padded, None)).is_ok());
+    }
+
+    #[test]
+    fn notes_length_boundary() {
+        let max = "n".repeat(20_000);
+        assert_eq!(validate_task(draft("ok", Some(&max))).unwrap().notes.as_deref(), Some(max.as_str()));
+        let over = "n".repeat(20_001);
+        assert_eq!(problems(validate_task(draft("ok", Some(&over)))), [("notes", Problem::TooLong { max: 20_000 })]);
+    }
+
+    #[test]
+    fn whitespace_only_title_is_required() {
+        for title in ["", "   ", "\t\n", "\u{1b}[31m\u{7}"] {
+            assert_eq!(problems(validate_task(draft(title, None))), [("title", Problem::Required)], "{title:?}");
+        }
+    }
+
+    #[test]
+    fn control_characters_and_ansi_escapes_are_collapsed() {
+        let v = validate_task(draft("Pay\u{0}\u{7}rent\n\nnow\u{1b}[1;31mred\u{1b}[0m", None)).unwrap();
+        assert_eq!(v.title, "Pay rent now red");
+        let v = validate_task(draft("ok", Some("line one\r\n\tindented\u{1b}[2K\u{0}end\n\n  "))).unwrap();
+        assert_eq!(v.notes.as_deref(), Some("line one\n\tindented end"));
+        assert_eq!(validate_task(draft("ok", Some(" \n "))).unwrap().notes, None);
+    }
+
+    #[test]
+    fn imports_go_through_the_same_checks() {
+        use crate::import::{merge_into, ParsedImport};
+        use crate::models::test_task;
+        let due = crate::clock::local("2024-06-03 12:00");
+        let mut long = test_task(&"x".repeat(501), due);
+        long.notes = Some("fine".to_string());
+        let dirty = test_task("Call\u{1b}[0m\u{7}bank", due);
+        let parsed = ParsedImport { tasks: vec![long, dirty], skipped: 0, warnings: Vec::new() };
+        let mut existing = Vec::new();
+        let report = merge_into(&mut existing, parsed, false, false);
+        assert_eq!((report.imported, report.skipped), (1, 1));
+        assert_eq!(existing[0].title, "Call bank");
+        assert!(report.warnings[0].contains("exceeds 500"), "{:?}", report.warnings);
+    }
+
+    #[test]
+    fn every_task_problem_is_reported_at_once() {
+        let d = TaskDraft { title: " ", due_at: "tomorrow", duration_min: 0, priority: 9, notes: None };
+        assert_eq!(
+            problems(validate_task(d)),
+            [
+                ("title", Problem::Required),
+                ("due_at", Problem::InvalidFormat { expected: "RFC3339" }),
+                ("duration_min", Problem::OutOfRange { min: 1, max: Some(1440) }),
+                ("priority", Problem::OutOfRange { min: 1, max: Some(5) }),
+            ]
+        );
+    }
+
+    #[test]
+    fn every_settings_problem_is_reported_at_once() {
+        let s = DaySettings {
+            day_start: "9am".to_string(),
+            focus_block_min: 0,
+            overdue_grace_min: -5,
+            sort_locale: "not a locale!".to_string(),
+            ..DaySettings::default()
+        };
+        let fields: Vec<&str> = validate_settings(&s).unwrap_err().iter().map(|e| e.field).collect();
+        assert_eq!(fields, ["day_start", "focus_block_min", "overdue_grace_min", "sort_locale"]);
+    }
+
+    #[test]
+    fn priority_labels_need_a_label_and_a_hex_color() {
+        use crate::models::PriorityMeta;
+        let with = |label: &str, color: &str| {
+            let mut labels: [PriorityMeta; 5] =
+                std::array::from_fn(|i| PriorityMeta { label: format!("P{i}"), color: "#a0B1c2".to_string() });
+            labels[4] = PriorityMeta { label: label.to_string(), color: color.to_string() };
+            let s = DaySettings { priority_labels: Some(labels), ..DaySettings::default() };
+            validate_settings(&s).err().unwrap_or_default().into_iter().map(|e| e.field).collect::<Vec<_>>()
+        };
+        assert!(with("Critical", "#ff0000").is_empty());
+        for color in ["#fff", "red", "#12345g", "ff00000", "#ff00001", ""] {
+            assert_eq!(with("Critical", color), ["priority_labels.color"], "{color:?}");
+        }
+        assert_eq!(with("  ", "#ff0000"), ["priority_labels.label"]);
+        assert!(with(&"x".repeat(MAX_PRIORITY_LABEL_LEN), "#ff0000").is_empty());
+        assert_eq!(with(&"x".repeat(MAX_PRIORITY_LABEL_LEN + 1), "nope"), ["priority_labels.label", "priority_labels.color"]);
+    }
+
+    fn tags(list: &[&str]) -> Vec<String> {
+        list.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn tag_normalization_rules() {
+        let cases: [(&str, &str); 6] = [
+            ("  work ", "work"),
+            ("Deep   Work", "deep-work"),
+            ("deep - work", "deep-work"),
+            ("--side-project--", "side-project"),
+            ("ÜBER\tALLES", "über-alles"),
+            ("Q3/Планы_2024", "q3/планы_2024"),
+        ];
+        for (raw, normalized) in cases {
+            assert_eq!(normalize_tag(raw), normalized, "{raw:?}");
+            assert_eq!(clean_tags("tags", &tags(&[raw])).unwrap(), Some(tags(&[normalized])), "{raw:?}");
+        }
+    }
+
+    #[test]
+    fn tags_are_deduplicated_within_a_task() {
+        let cleaned = clean_tags("tags", &tags(&["Work", " work ", "home", "WORK", "", "  "])).unwrap();
+        assert_eq!(cleaned, Some(tags(&["work", "home"])));
+        assert_eq!(clean_tags("tags", &tags(&["", " - "])).unwrap(), None);
+    }
+
+    #[test]
+    fn tags_past_the_limits_are_refused_by_the_api() {
+        let max = "t".repeat(40);
+        assert_eq!(clean_tags("tags", std::slice::from_ref(&max)).unwrap(), Some(vec![max.clone()]));
+        let over = format!("{max}x");
+        let problems = |raw: &[String]| clean_tags("tags", raw).unwrap_err().into_iter().map(|e| e.problem).collect::<Vec<_>>();
+        assert_eq!(problems(&[over]), [Problem::TooLong { max: 40 }]);
+        let charset = Problem::InvalidFormat { expected: "letters, digits, -, _ or /" };
+        assert_eq!(problems(&tags(&["c++", "ok", "a.b"])), [charset.clone(), charset]);
+    }
+
+    #[test]
+    fn tags_are_coerced_where_nothing_may_be_refused() {
+        let long = format!("{}-tail", "x".repeat(39));
+        let coerced = coerce_tags(&tags(&["C++", "a.b.c", &long, "Work", "work!", "work"])).unwrap();
+        assert_eq!(coerced, tags(&["c", "a-b-c", &"x".repeat(39), "work"]));
+        assert_eq!(coerce_tags(&tags(&["!!!", "  "])), None);
+        // What the API accepts comes back unchanged
+        let accepted = clean_tags("tags", &tags(&["deep-work", "q3/plans", "über"])).unwrap().unwrap();
+        assert_eq!(coerce_tags(&accepted), Some(accepted));
+    }
+}
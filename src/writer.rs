@@ -0,0 +1,1215 @@
+// --------------------------------------------------
+// Single writer for the database.
+//
+// All mutations go through one tokio task. Handlers send a
+// Command over a channel and wait for the reply, so writes
+// are applied and persisted strictly one after another (no
+// lost updates).
+//
+// db.json is loaded once at startup (main.rs) and kept in
+// memory as the committed Db. For each command the writer:
+// 1. Applies the mutation to a copy of the committed Db (the
+//    previous committed Db, updated in place; see Buffers)
+// 2. For commands that changed something, appends the result to
+//    db.journal (store::append_journal; the whole Db for an Apply
+//    job), so a crash before the save completes is replayed on the
+//    next start
+// 3. Saves the copy (store::save_db, async) when something changed,
+//    then drops the journal
+// 4. Makes the saved copy the committed Db
+// 5. For task and settings commands, and for the changes an Apply
+//    job reports (apply_audited), appends the task or settings before
+//    and after to audit.log (store::append_audit)
+// 6. Replies through a oneshot channel
+//
+// Reads take `snapshot()` and never touch the file. A copy
+// that could not be saved is dropped, so readers only ever
+// see what is on disk.
+//
+// A db.json edited outside the server (by hand, while it runs)
+// is not overwritten: before each command the writer asks the
+// store whether the file changed, and if so loads it again and
+// applies the command on top of it. A task command whose task
+// was removed in that edit fails with WriteError::Removed.
+// --------------------------------------------------
+
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, OnceLock, RwLock};
+
+use chrono::{DateTime, FixedOffset};
+use tokio::sync::{mpsc, oneshot};
+use uuid::Uuid;
+
+use crate::clock;
+use crate::logic;
+use crate::models::{AuditEntry, AuditOp, DaySettings, Db, JournalOp, Task, TaskStatus, TrashedTask};
+use crate::request_id;
+use crate::store::{self, StoreError};
+
+// Commands waiting for the writer before senders block.
+const QUEUE_LEN: usize = 256;
+
+static WRITER: OnceLock<mpsc::Sender<Envelope>> = OnceLock::new();
+
+// Committed Db. Replaced as a whole after each saved change, so a
+// reader's snapshot stays consistent while it is being used.
+static COMMITTED: OnceLock<RwLock<Arc<Db>>> = OnceLock::new();
+
+// A command plus the id of the request that sent it, for log lines.
+struct Envelope {
+    request_id: Option<String>,
+    cmd: Command,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteError {
+    Save(StoreError),   // db.json could not be written; nothing was applied
+    Reload(StoreError), // db.json changed outside the server and does not load; nothing was applied
+    Removed(Uuid),      // the task was removed from db.json outside the server
+    Stopped,            // writer task is not running
+}
+
+impl WriteError {
+    // Text for logs and the HTTP error body.
+    pub fn message(&self) -> String {
+        match self {
+            WriteError::Save(e) => format!("failed to save db: {}", e.describe()),
+            WriteError::Reload(e) => format!("db changed outside the server, failed to reload it: {}", e.describe()),
+            WriteError::Removed(id) => format!("task {id} was removed from the db outside the server"),
+            WriteError::Stopped => "db writer is not running".to_string(),
+        }
+    }
+}
+
+type Reply<T> = oneshot::Sender<Result<T, WriteError>>;
+type AnyValue = Box<dyn Any + Send>;
+type Job = Box<dyn FnOnce(&mut Db) -> (AnyValue, bool, Vec<Audited>) + Send>;
+
+// A change for audit.log: what was done, and the task it was done to
+// (None for settings).
+pub type Audited = (AuditOp, Option<Uuid>);
+
+// New values for every editable task field (PUT /api/tasks/:id).
+#[derive(Debug, Clone)]
+pub struct TaskChanges {
+    pub title: String,
+    pub due_at: DateTime<FixedOffset>,
+    pub duration_min: i64,
+    pub priority: i64,
+    pub status: TaskStatus,
+    pub tags: Option<Vec<String>>,
+    pub notes: Option<String>,
+}
+
+// Whether CreateTask checks for a duplicate open task (logic::find_duplicate).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupeCheck {
+    Setting, // when settings.dedupe_on_create is on
+    Always,
+    Never,
+}
+
+// Whether a move into InProgress is checked against settings.wip_limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WipCheck {
+    Setting, // refuse when the limit is set and already reached
+    Force,
+}
+
+// What DeleteTask does with the task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Removal {
+    Trash, // move it to db.trash (restorable)
+    Purge, // erase it
+}
+
+// If-Match condition sent with a task write.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IfMatch {
+    Any,            // "*": any current revision
+    Revs(Vec<u64>), // one of these revisions (from strong ETags)
+}
+
+// Result of a conditional task write (update, delete, toggle).
+#[derive(Debug, Clone)]
+pub enum Guarded<T> {
+    Applied(T),
+    NotFound,
+    Stale(Box<Task>),  // If-Match did not match; the current task, nothing saved
+    ConditionRequired, // strict_concurrency is on and no If-Match was sent
+    OverWipLimit(Vec<Task>), // would exceed wip_limit; the tasks in progress, nothing saved
+}
+
+// Why a conditional write may not run, if it may not.
+fn precondition<T>(db: &Db, id: Uuid, cond: &Option<IfMatch>) -> Option<Guarded<T>> {
+    let Some(t) = db.get(id) else {
+        return Some(Guarded::NotFound);
+    };
+    match cond {
+        None if db.settings.strict_concurrency => Some(Guarded::ConditionRequired),
+        Some(IfMatch::Revs(revs)) if !revs.contains(&t.rev) => Some(Guarded::Stale(Box::new(t.clone()))),
+        _ => None,
+    }
+}
+
+// Whether moving task `id` to `next` would exceed the WIP limit.
+// Tasks already in progress, and moves out of InProgress, always pass.
+fn wip_refusal<T>(db: &Db, id: Uuid, next: &TaskStatus, check: WipCheck) -> Option<Guarded<T>> {
+    let limit = db.settings.wip_limit?;
+    if check == WipCheck::Force || *next != TaskStatus::InProgress {
+        return None;
+    }
+    if db.get(id).is_some_and(|t| t.status == TaskStatus::InProgress) {
+        return None;
+    }
+    let active: Vec<Task> = db.tasks.iter().filter(|t| t.status == TaskStatus::InProgress).cloned().collect();
+    (active.len() as i64 >= limit).then_some(Guarded::OverWipLimit(active))
+}
+
+// Result of CreateTask.
+#[derive(Debug, Clone)]
+pub enum Created {
+    New(Task),
+    Duplicate(Task), // existing open task it would duplicate; nothing saved
+}
+
+pub enum Command {
+    CreateTask(Task, DedupeCheck, Reply<Created>),
+    UpdateTask(Uuid, Option<IfMatch>, WipCheck, TaskChanges, Reply<Guarded<Task>>),
+    DeleteTask(Uuid, Option<IfMatch>, Removal, Reply<Guarded<()>>),
+    ToggleTask(Uuid, Option<IfMatch>, WipCheck, Reply<Guarded<Task>>),
+    PutSettings(DaySettings, Reply<DaySettings>),
+    // Compound operation; the job returns (value, changed, audited)
+    Apply(Job, Reply<AnyValue>),
+}
+
+impl Command {
+    // The task a task command acts on.
+    fn target(&self) -> Option<Uuid> {
+        match self {
+            Command::UpdateTask(id, ..) | Command::DeleteTask(id, ..) | Command::ToggleTask(id, ..) => Some(*id),
+            Command::CreateTask(..) | Command::PutSettings(..) | Command::Apply(..) => None,
+        }
+    }
+
+    // What the audit log records for the command, and the task it is about.
+    fn audit(&self) -> Option<Audited> {
+        match self {
+            Command::CreateTask(task, ..) => Some((AuditOp::Create, Some(task.id))),
+            Command::UpdateTask(id, ..) => Some((AuditOp::Update, Some(*id))),
+            Command::DeleteTask(id, _, Removal::Trash, _) => Some((AuditOp::Delete, Some(*id))),
+            Command::DeleteTask(id, _, Removal::Purge, _) => Some((AuditOp::Purge, Some(*id))),
+            Command::ToggleTask(id, ..) => Some((AuditOp::Toggle, Some(*id))),
+            Command::PutSettings(..) => Some((AuditOp::Settings, None)),
+            Command::Apply(..) => None,
+        }
+    }
+
+    // Answer without running.
+    fn fail(self, e: WriteError) {
+        match self {
+            Command::CreateTask(_, _, reply) => drop(reply.send(Err(e))),
+            Command::UpdateTask(.., reply) => drop(reply.send(Err(e))),
+            Command::DeleteTask(.., reply) => drop(reply.send(Err(e))),
+            Command::ToggleTask(.., reply) => drop(reply.send(Err(e))),
+            Command::PutSettings(_, reply) => drop(reply.send(Err(e))),
+            Command::Apply(_, reply) => drop(reply.send(Err(e))),
+        }
+    }
+}
+
+// Start the writer task with the db loaded at startup.
+// Called once from main.rs, before the server and background loops.
+pub fn spawn(db: Db) {
+    let (tx, rx) = mpsc::channel(QUEUE_LEN);
+    if COMMITTED.set(RwLock::new(Arc::new(db))).is_ok() && WRITER.set(tx).is_ok() {
+        tokio::spawn(run(rx));
+    }
+}
+
+// Tests: start the writer once, with an empty db, on a runtime of its
+// own so it outlives the #[tokio::test] that first needs it.
+#[cfg(test)]
+pub fn spawn_for_tests() {
+    static STARTED: std::sync::Once = std::sync::Once::new();
+    STARTED.call_once(|| {
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            rt.block_on(async {
+                spawn(Db::default());
+                let _ = ready_tx.send(());
+                std::future::pending::<()>().await
+            })
+        });
+        let _ = ready_rx.recv();
+    });
+}
+
+// Tests: db.json saves made for each request id, so a test can
+// tell how many saves its own request caused.
+#[cfg(test)]
+static SAVES: std::sync::Mutex<Option<HashMap<String, usize>>> = std::sync::Mutex::new(None);
+
+#[cfg(test)]
+fn count_save(request_id: Option<&str>) {
+    let mut saves = SAVES.lock().unwrap_or_else(|e| e.into_inner());
+    *saves.get_or_insert_default().entry(request_id.unwrap_or_default().to_string()).or_default() += 1;
+}
+
+#[cfg(test)]
+pub fn saves_for(request_id: &str) -> usize {
+    let saves = SAVES.lock().unwrap_or_else(|e| e.into_inner());
+    saves.as_ref().and_then(|s| s.get(request_id)).copied().unwrap_or(0)
+}
+
+// The committed Db, for reads. Cheap: no file access, no copy.
+pub fn snapshot() -> Arc<Db> {
+    let committed = COMMITTED.get().expect("writer::spawn runs before any read");
+    committed.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+fn publish(db: Arc<Db>) {
+    if let Some(committed) = COMMITTED.get() {
+        *committed.write().unwrap_or_else(|e| e.into_inner()) = db;
+    }
+}
+
+// The committed Db and the writer's copy that the next write goes into.
+//
+// The copy is the previous committed Db, one write behind: `lag` is
+// that write, replayed onto it before the next command runs. Readers
+// drop their snapshots quickly, so Arc::make_mut finds the copy
+// unshared and the write is applied in place instead of to a clone of
+// the whole Db. Without a replayable write (Apply jobs, a reload, a
+// failed save) the copy is the committed Db itself, and the next
+// write clones it once.
+struct Buffers {
+    live: Arc<Db>,
+    spare: Arc<Db>,
+    lag: Option<JournalOp>,
+}
+
+impl Buffers {
+    fn new(live: Arc<Db>) -> Self {
+        Buffers { spare: live.clone(), live, lag: None }
+    }
+
+    // Start over from `live`, dropping whatever the copy holds.
+    fn reset(&mut self, live: Arc<Db>) {
+        *self = Buffers::new(live);
+    }
+
+    // The copy, brought up to date with `live`.
+    fn next(&mut self) -> &mut Db {
+        let db = Arc::make_mut(&mut self.spare);
+        if let Some(op) = self.lag.take() {
+            store::replay_journal(db, vec![op]);
+        }
+        db
+    }
+
+    // The copy was saved: it becomes `live` and the old `live` becomes
+    // the copy, `op` behind. Returns the old `live`.
+    fn commit(&mut self, op: Option<JournalOp>) -> Arc<Db> {
+        let before = std::mem::replace(&mut self.live, self.spare.clone());
+        self.spare = match op {
+            Some(_) => before.clone(),
+            None => self.live.clone(),
+        };
+        self.lag = op;
+        before
+    }
+}
+
+async fn run(mut rx: mpsc::Receiver<Envelope>) {
+    let mut buffers = Buffers::new(snapshot());
+    while let Some(Envelope { request_id, cmd }) = rx.recv().await {
+        if let Err(e) = request_id::scope(request_id.clone(), reload_if_changed(cmd.target())).await {
+            cmd.fail(e);
+            continue;
+        }
+        // Reloaded from db.json above
+        let committed = snapshot();
+        if !Arc::ptr_eq(&committed, &buffers.live) {
+            buffers.reset(committed);
+        }
+        let audit = cmd.audit();
+        let replayable = !matches!(cmd, Command::Apply(..));
+        let db = buffers.next();
+        let mut done = execute(db, cmd);
+        if !done.changed {
+            // An Apply job may have edited the copy before deciding not to save
+            if !replayable {
+                buffers.reset(buffers.live.clone());
+            }
+            done.answer(None);
+            continue;
+        }
+        let journal = journal_for(audit, db);
+        // store.rs logs errors tagged with the sender's request id
+        if let Some(entry) = &journal
+            && let Err(e) = request_id::scope(request_id.clone(), store::append_journal(entry)).await
+        {
+            buffers.reset(buffers.live.clone());
+            done.answer(Some(WriteError::Save(e)));
+            continue;
+        }
+        let saved = request_id::scope(request_id.clone(), store::save_db(buffers.spare.clone())).await;
+        #[cfg(test)]
+        count_save(request_id.as_deref());
+        // Saved, or failed and never applied: either way nothing to replay
+        if journal.is_some() {
+            store::clear_journal().await;
+        }
+        match saved {
+            Ok(()) => {
+                // A snapshot is not worth replaying onto the copy: it
+                // is the committed Db, cloned again on the next write
+                let before = buffers.commit(journal.filter(|_| replayable));
+                // Committed before the reply, so the sender's next read sees it
+                publish(buffers.live.clone());
+                for (op, task_id) in audit.into_iter().chain(std::mem::take(&mut done.audited)) {
+                    record(audit_entry(op, task_id, &before, &buffers.live, request_id.clone())).await;
+                }
+                done.answer(None);
+            }
+            Err(e) => {
+                buffers.reset(buffers.live.clone());
+                done.answer(Some(WriteError::Save(e)));
+            }
+        }
+    }
+}
+
+// The db.journal line for a command: the task or settings write
+// the audit log records (journal_op), or, for an Apply job, the
+// whole Db about to be saved.
+fn journal_for(audit: Option<Audited>, after: &Db) -> Option<JournalOp> {
+    match audit {
+        Some((op, task_id)) => journal_op(op, task_id, after),
+        None => Some(JournalOp::Snapshot { db: Box::new(after.clone()) }),
+    }
+}
+
+// The db.journal line for a task or settings write: its result in
+// `after`, the Db about to be saved.
+fn journal_op(op: AuditOp, task_id: Option<Uuid>, after: &Db) -> Option<JournalOp> {
+    let task = || task_id.and_then(|id| after.get(id)).cloned();
+    match op {
+        AuditOp::Create => task().map(|task| JournalOp::CreateTask { task }),
+        AuditOp::Update => task().map(|task| JournalOp::UpdateTask { task }),
+        AuditOp::Toggle => task().map(|task| JournalOp::Toggle { task }),
+        AuditOp::Delete => task_id.map(|id| JournalOp::DeleteTask {
+            id,
+            trashed: after.trash.iter().rev().find(|t| t.task.id == id).cloned(),
+        }),
+        AuditOp::Purge => task_id.map(|id| JournalOp::DeleteTask { id, trashed: None }),
+        AuditOp::Settings => Some(JournalOp::PutSettings { settings: after.settings.clone() }),
+        AuditOp::Restore => None, // only Apply jobs restore, and they are journaled whole
+    }
+}
+
+// The audit entry for a saved write: the task (or the settings)
+// before and after it. A task is looked up in tasks and archived,
+// and before the write also in the trash (a purge or a restore).
+fn audit_entry(
+    op: AuditOp,
+    task_id: Option<Uuid>,
+    before: &Db,
+    after: &Db,
+    request_id: Option<String>,
+) -> AuditEntry {
+    let (old, new) = match task_id {
+        Some(id) => {
+            let trashed = || before.trash.iter().rev().find(|t| t.task.id == id).map(|t| &t.task);
+            let old = find(before, id).or_else(trashed).and_then(to_value);
+            (old, find(after, id).and_then(to_value))
+        }
+        None => (to_value(&before.settings), to_value(&after.settings)),
+    };
+    AuditEntry { at: clock::now(), op, task_id, before: old, after: new, request_id }
+}
+
+fn find(db: &Db, id: Uuid) -> Option<&Task> {
+    db.get(id).or_else(|| db.archived.iter().find(|t| t.id == id))
+}
+
+// What a compound write did to the tasks and settings, from the Db
+// before and after it: a create for each new id, an update for each
+// task that differs, a purge for each id gone (tasks and archived
+// together), and settings when they changed.
+pub fn changes(before: &Db, after: &Db) -> Vec<Audited> {
+    let old: HashMap<Uuid, &Task> = before.tasks.iter().chain(&before.archived).map(|t| (t.id, t)).collect();
+    let mut out = Vec::new();
+    let mut kept = HashSet::new();
+    for t in after.tasks.iter().chain(&after.archived) {
+        match old.get(&t.id) {
+            None => out.push((AuditOp::Create, Some(t.id))),
+            Some(prev) if to_value(*prev) != to_value(t) => out.push((AuditOp::Update, Some(t.id))),
+            Some(_) => {}
+        }
+        kept.insert(t.id);
+    }
+    let gone = before.tasks.iter().chain(&before.archived).filter(|t| !kept.contains(&t.id));
+    out.extend(gone.map(|t| (AuditOp::Purge, Some(t.id))));
+    if to_value(&before.settings) != to_value(&after.settings) {
+        out.push((AuditOp::Settings, None));
+    }
+    out
+}
+
+fn to_value<T: serde::Serialize>(value: &T) -> Option<serde_json::Value> {
+    serde_json::to_value(value).ok()
+}
+
+// Append to audit.log. The write is already saved, so a failure
+// here is logged and the command still succeeds.
+async fn record(entry: AuditEntry) {
+    if let Err(e) = store::append_audit(&entry).await {
+        let tag = entry.request_id.as_deref().map(|id| format!("[{id}] ")).unwrap_or_default();
+        eprintln!("  {tag}failed to append to {}: {e}", store::audit_path());
+    }
+}
+
+// Make a db.json changed outside the server the committed Db.
+// Err when it does not load, or no longer has the `target` task
+// of the command about to run (which then does not run).
+async fn reload_if_changed(target: Option<Uuid>) -> Result<(), WriteError> {
+    let store = store::backend();
+    if !store.changed_externally().await.unwrap_or(false) {
+        return Ok(());
+    }
+    let db = store.load().await.map_err(WriteError::Reload)?;
+    eprintln!("  {}{} changed outside the server; reloaded it", request_id::log_tag(), store::db_label());
+    let before = snapshot();
+    let removed = target.filter(|&id| before.get(id).is_some() && db.get(id).is_none());
+    publish(Arc::new(db));
+    removed.map_or(Ok(()), |id| Err(WriteError::Removed(id)))
+}
+
+// A command applied in memory, waiting for its save before the reply.
+struct Done {
+    changed: bool,
+    audited: Vec<Audited>, // an Apply job's changes, for audit.log
+    reply: Box<dyn FnOnce(Option<WriteError>) + Send>,
+}
+
+impl Done {
+    fn answer(self, error: Option<WriteError>) {
+        (self.reply)(error)
+    }
+}
+
+// Apply one command to `db`.
+fn execute(db: &mut Db, cmd: Command) -> Done {
+    match cmd {
+        Command::CreateTask(mut task, check, reply) => commit(db, reply, |db| {
+            task.updated_at = task.created_at;
+            // Checked here so two racing creates cannot both pass
+            let dedupe = match check {
+                DedupeCheck::Setting => db.settings.dedupe_on_create,
+                DedupeCheck::Always => true,
+                DedupeCheck::Never => false,
+            };
+            if dedupe
+                && let Some(existing) = logic::find_duplicate(&db.tasks, &task.title, task.due_at)
+            {
+                return (Created::Duplicate(existing.clone()), false);
+            }
+            db.insert(task.clone());
+            (Created::New(task), true)
+        }),
+        Command::UpdateTask(id, cond, wip, c, reply) => commit(db, reply, |db| {
+            if let Some(refused) = precondition(db, id, &cond) {
+                return (refused, false);
+            }
+            if let Some(refused) = wip_refusal(db, id, &c.status, wip) {
+                return (refused, false);
+            }
+            let Some(t) = db.get_mut(id) else {
+                return (Guarded::NotFound, false);
+            };
+            // A new due date gets a fresh deadline notification
+            if t.due_at != c.due_at {
+                t.deadline_notified_at = None;
+            }
+            if c.due_at > t.due_at {
+                t.postpone_count = t.postpone_count.saturating_add(1);
+            }
+            t.title = c.title;
+            t.due_at = c.due_at;
+            t.duration_min = c.duration_min;
+            t.priority = c.priority;
+            set_status(t, c.status);
+            t.tags = c.tags;
+            t.notes = c.notes;
+            // Every PUT counts as an edit, even one that changes nothing
+            t.rev += 1;
+            t.updated_at = clock::now();
+            (Guarded::Applied(t.clone()), true)
+        }),
+        Command::DeleteTask(id, cond, removal, reply) => commit(db, reply, |db| {
+            if let Some(refused) = precondition(db, id, &cond) {
+                return (refused, false);
+            }
+            match db.remove(id) {
+                Some(mut task) => {
+                    if removal == Removal::Trash {
+                        // A restored task comes back unplanned, not pinned to an old slot
+                        task.locked = None;
+                        db.trash.push(TrashedTask { task, deleted_at: clock::now() });
+                    }
+                    (Guarded::Applied(()), true)
+                }
+                None => (Guarded::NotFound, false),
+            }
+        }),
+        Command::ToggleTask(id, cond, wip, reply) => commit(db, reply, |db| {
+            if let Some(refused) = precondition(db, id, &cond) {
+                return (refused, false);
+            }
+            let next = match db.get(id).map(|t| &t.status) {
+                Some(TaskStatus::Todo) => TaskStatus::InProgress,
+                Some(TaskStatus::InProgress) => TaskStatus::Done,
+                Some(TaskStatus::Done) | None => TaskStatus::Todo,
+            };
+            if let Some(refused) = wip_refusal(db, id, &next, wip) {
+                return (refused, false);
+            }
+            let Some(t) = db.get_mut(id) else {
+                return (Guarded::NotFound, false);
+            };
+            set_status(t, next);
+            t.rev += 1;
+            t.updated_at = clock::now();
+            (Guarded::Applied(t.clone()), true)
+        }),
+        Command::PutSettings(s, reply) => commit(db, reply, |db| {
+            // Keys the client cannot know about stay as stored
+            db.settings = s.with_extra_of(&db.settings);
+            (db.settings.clone(), true)
+        }),
+        Command::Apply(job, reply) => {
+            let mut audited = Vec::new();
+            let mut done = commit(db, reply, |db| {
+                let (value, changed, audit) = job(db);
+                audited = audit;
+                (value, changed)
+            });
+            done.audited = audited;
+            done
+        }
+    }
+}
+
+// Change a task's status, keeping completed_at in step.
+// Completing a task releases its plan lock.
+fn set_status(t: &mut Task, status: TaskStatus) {
+    if status == TaskStatus::Done {
+        t.locked = None;
+    }
+    if status != TaskStatus::Done {
+        t.completed_at = None;
+    } else if t.status != TaskStatus::Done {
+        t.completed_at = Some(clock::now());
+    }
+    t.status = status;
+}
+
+// Run a mutation. The reply carries its value, or the save error
+// when the change could not be persisted.
+fn commit<T: Send + 'static>(db: &mut Db, reply: Reply<T>, f: impl FnOnce(&mut Db) -> (T, bool)) -> Done {
+    let (value, changed) = f(db);
+    Done {
+        changed,
+        audited: Vec::new(),
+        reply: Box::new(move |error| {
+            let _ = reply.send(match error {
+                Some(e) => Err(e),
+                None => Ok(value),
+            });
+        }),
+    }
+}
+
+// Send a command and wait for its reply.
+pub async fn send<T>(make: impl FnOnce(Reply<T>) -> Command) -> Result<T, WriteError> {
+    let tx = WRITER.get().ok_or(WriteError::Stopped)?;
+    let (reply, rx) = oneshot::channel();
+    let envelope = Envelope { request_id: request_id::current(), cmd: make(reply) };
+    tx.send(envelope).await.map_err(|_| WriteError::Stopped)?;
+    rx.await.map_err(|_| WriteError::Stopped)?
+}
+
+// Run a compound mutation inside the writer.
+//
+// `f` returns (value, changed); the db is saved only when changed.
+// Nothing is added to audit.log; see apply_audited.
+pub async fn apply<R, F>(f: F) -> Result<R, WriteError>
+where
+    R: Send + 'static,
+    F: FnOnce(&mut Db) -> (R, bool) + Send + 'static,
+{
+    apply_audited(move |db| {
+        let (value, changed) = f(db);
+        (value, changed, Vec::new())
+    })
+    .await
+}
+
+// Like apply, for a job that creates, edits or deletes tasks or
+// settings: `f` also returns what it did, and once the db is saved
+// each change is appended to audit.log like a task command's.
+pub async fn apply_audited<R, F>(f: F) -> Result<R, WriteError>
+where
+    R: Send + 'static,
+    F: FnOnce(&mut Db) -> (R, bool, Vec<Audited>) + Send + 'static,
+{
+    let job: Job = Box::new(move |db| {
+        let (value, changed, audited) = f(db);
+        (Box::new(value) as AnyValue, changed, audited)
+    });
+    let value = send(|reply| Command::Apply(job, reply)).await?;
+    Ok(*value.downcast::<R>().expect("writer returned a value of another type"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_task;
+    use std::time::{Duration, Instant};
+
+    fn db_with(n: usize) -> Db {
+        let due = clock::local("2024-06-03 12:00");
+        let mut db = Db::default();
+        db.tasks.extend((0..n).map(|i| test_task(&format!("task {i}"), due)));
+        db.reindex();
+        db
+    }
+
+    fn changes(task: &Task, title: &str) -> TaskChanges {
+        TaskChanges {
+            title: title.to_string(),
+            due_at: task.due_at,
+            duration_min: task.duration_min,
+            priority: task.priority,
+            status: task.status.clone(),
+            tags: task.tags.clone(),
+            notes: task.notes.clone(),
+        }
+    }
+
+    fn update(id: Uuid, c: TaskChanges) -> Command {
+        Command::UpdateTask(id, None, WipCheck::Setting, c, oneshot::channel().0)
+    }
+
+    // What run() does with a command, minus the files.
+    fn write(buffers: &mut Buffers, cmd: Command) {
+        let audit = cmd.audit();
+        let replayable = !matches!(cmd, Command::Apply(..));
+        let db = buffers.next();
+        let done = execute(db, cmd);
+        assert!(done.changed);
+        let journal = journal_for(audit, db);
+        buffers.commit(journal.filter(|_| replayable));
+        done.answer(None);
+    }
+
+    fn json(db: &Db) -> serde_json::Value {
+        serde_json::to_value(db).unwrap()
+    }
+
+    #[test]
+    fn writes_alternate_between_two_dbs_instead_of_cloning() {
+        let db = db_with(3);
+        let id = db.tasks[0].id;
+        let mut buffers = Buffers::new(Arc::new(db));
+        let mut seen = Vec::new();
+        for i in 0..6 {
+            let c = changes(buffers.live.get(id).unwrap(), &format!("edit {i}"));
+            write(&mut buffers, update(id, c));
+            seen.push(Arc::as_ptr(&buffers.live));
+        }
+        // The first write copies the loaded db once; after that the
+        // previous committed db is reused in place
+        assert_ne!(seen[0], seen[1]);
+        assert!(seen.iter().skip(2).zip(&seen).all(|(a, b)| a == b), "{seen:?}");
+        assert_eq!(buffers.live.get(id).unwrap().title, "edit 5");
+    }
+
+    #[test]
+    fn the_copy_catches_up_after_every_kind_of_write() {
+        let mut buffers = Buffers::new(Arc::new(db_with(4)));
+        let ids: Vec<Uuid> = buffers.live.tasks.iter().map(|t| t.id).collect();
+        let new = test_task("created", clock::local("2024-06-04 09:00"));
+        let settings = DaySettings { day_start: "08:00".to_string(), ..DaySettings::default() };
+        let commands = vec![
+            Command::CreateTask(new.clone(), DedupeCheck::Never, oneshot::channel().0),
+            update(ids[0], changes(buffers.live.get(ids[0]).unwrap(), "renamed")),
+            Command::ToggleTask(ids[1], None, WipCheck::Setting, oneshot::channel().0),
+            Command::DeleteTask(ids[2], None, Removal::Trash, oneshot::channel().0),
+            Command::DeleteTask(ids[3], None, Removal::Purge, oneshot::channel().0),
+            Command::PutSettings(settings, oneshot::channel().0),
+            Command::ToggleTask(new.id, None, WipCheck::Setting, oneshot::channel().0),
+        ];
+        for cmd in commands {
+            write(&mut buffers, cmd);
+            let live = json(&buffers.live);
+            assert_eq!(json(buffers.next()), live);
+            let copy = buffers.next();
+            assert!(copy.tasks.iter().all(|t| std::ptr::eq(copy.get(t.id).unwrap(), t)));
+        }
+        assert_eq!(buffers.live.tasks.len(), 3);
+        assert_eq!(buffers.live.trash.len(), 1);
+        assert_eq!(buffers.live.settings.day_start, "08:00");
+    }
+
+    #[test]
+    fn readers_keep_their_snapshot() {
+        let db = db_with(2);
+        let id = db.tasks[0].id;
+        let mut buffers = Buffers::new(Arc::new(db));
+        let c = changes(buffers.live.get(id).unwrap(), "first");
+        write(&mut buffers, update(id, c));
+        let reader = buffers.live.clone();
+        for title in ["second", "third"] {
+            let c = changes(buffers.live.get(id).unwrap(), title);
+            write(&mut buffers, update(id, c));
+        }
+        assert_eq!(reader.get(id).unwrap().title, "first");
+        assert_eq!(buffers.live.get(id).unwrap().title, "third");
+    }
+
+    #[test]
+    fn reset_drops_an_unsaved_write() {
+        let db = db_with(1);
+        let id = db.tasks[0].id;
+        let mut buffers = Buffers::new(Arc::new(db));
+        let c = changes(buffers.live.get(id).unwrap(), "never saved");
+        let _ = execute(buffers.next(), update(id, c));
+        buffers.reset(buffers.live.clone());
+        assert_eq!(buffers.next().get(id).unwrap().title, "task 0");
+    }
+
+    // Time for `n` updates after one warm-up write (which copies the db).
+    fn time_updates(tasks: usize, n: usize) -> Duration {
+        let db = db_with(tasks);
+        let ids: Vec<Uuid> = db.tasks.iter().map(|t| t.id).collect();
+        let mut buffers = Buffers::new(Arc::new(db));
+        let c = changes(buffers.live.get(ids[0]).unwrap(), "warm-up");
+        write(&mut buffers, update(ids[0], c));
+        let started = Instant::now();
+        for i in 0..n {
+            let id = ids[i * 7919 % ids.len()];
+            let c = changes(buffers.live.get(id).unwrap(), "edited");
+            write(&mut buffers, update(id, c));
+        }
+        started.elapsed()
+    }
+
+    #[test]
+    fn update_cost_does_not_scale_with_the_task_count() {
+        let small = (0..3).map(|_| time_updates(1_000, 500)).min().unwrap();
+        let large = (0..3).map(|_| time_updates(50_000, 500)).min().unwrap();
+        // Cloning or scanning 50k tasks per update would be ~50x slower
+        assert!(large < small * 10 + Duration::from_millis(20), "1k: {small:?}, 50k: {large:?}");
+    }
+
+    // Through the running writer and the temp db of the test config.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn interleaved_writes_leave_exactly_the_expected_tasks_on_disk() {
+        spawn_for_tests();
+        let due = clock::local("2024-06-03 12:00");
+        let mut handles = Vec::new();
+        for i in 0..660 {
+            handles.push(tokio::spawn(async move {
+                let task = test_task(&format!("stress-430 {i}"), due);
+                let id = task.id;
+                let created = send(|reply| Command::CreateTask(task.clone(), DedupeCheck::Never, reply)).await.unwrap();
+                assert!(matches!(created, Created::New(_)));
+                if i % 3 == 0 {
+                    let c = changes(&task, &format!("stress-430 {i} edited"));
+                    let updated = send(|reply| Command::UpdateTask(id, None, WipCheck::Force, c, reply)).await.unwrap();
+                    assert!(matches!(updated, Guarded::Applied(_)));
+                }
+                if i % 5 == 0 {
+                    let deleted = send(|reply| Command::DeleteTask(id, None, Removal::Purge, reply)).await.unwrap();
+                    assert!(matches!(deleted, Guarded::Applied(())));
+                }
+            }));
+        }
+        for h in handles {
+            h.await.unwrap();
+        }
+
+        let expected: std::collections::BTreeSet<String> = (0..660)
+            .filter(|i| i % 5 != 0)
+            .map(|i| if i % 3 == 0 { format!("stress-430 {i} edited") } else { format!("stress-430 {i}") })
+            .collect();
+        let ours = |db: &Db| -> std::collections::BTreeSet<String> {
+            db.tasks.iter().filter(|t| t.title.starts_with("stress-430 ")).map(|t| t.title.clone()).collect()
+        };
+        let text = tokio::fs::read_to_string(store::db_path()).await.unwrap();
+        let on_disk: Db = serde_json::from_str(&text).unwrap();
+        assert_eq!(on_disk.tasks.iter().filter(|t| t.title.starts_with("stress-430 ")).count(), expected.len());
+        assert_eq!(ours(&on_disk), expected);
+        assert_eq!(ours(&snapshot()), expected);
+    }
+
+    #[test]
+    fn strict_concurrency_refuses_unconditional_writes() {
+        let mut db = db_with(1);
+        db.settings.strict_concurrency = true;
+        let task = db.tasks[0].clone();
+        let id = task.id;
+
+        let (reply, mut rx) = oneshot::channel();
+        execute(&mut db, Command::UpdateTask(id, None, WipCheck::Setting, changes(&task, "unguarded"), reply)).answer(None);
+        assert!(matches!(rx.try_recv(), Ok(Ok(Guarded::ConditionRequired))));
+        let (reply, mut rx) = oneshot::channel();
+        execute(&mut db, Command::ToggleTask(id, None, WipCheck::Setting, reply)).answer(None);
+        assert!(matches!(rx.try_recv(), Ok(Ok(Guarded::ConditionRequired))));
+        let (reply, mut rx) = oneshot::channel();
+        execute(&mut db, Command::DeleteTask(id, None, Removal::Trash, reply)).answer(None);
+        assert!(matches!(rx.try_recv(), Ok(Ok(Guarded::ConditionRequired))));
+        assert_eq!(db.tasks[0].title, task.title);
+
+        let cond = Some(IfMatch::Revs(vec![task.rev]));
+        let (reply, mut rx) = oneshot::channel();
+        execute(&mut db, Command::UpdateTask(id, cond, WipCheck::Setting, changes(&task, "guarded"), reply)).answer(None);
+        assert!(matches!(rx.try_recv(), Ok(Ok(Guarded::Applied(_)))));
+        let (reply, mut rx) = oneshot::channel();
+        execute(&mut db, Command::DeleteTask(id, Some(IfMatch::Any), Removal::Trash, reply)).answer(None);
+        assert!(matches!(rx.try_recv(), Ok(Ok(Guarded::Applied(())))));
+        assert!(db.tasks.is_empty());
+    }
+
+    #[test]
+    fn only_moving_due_at_later_counts_as_a_postponement() {
+        let mut db = db_with(1);
+        let id = db.tasks[0].id;
+        let run = |db: &mut Db, cmd: Command| execute(db, cmd).answer(None);
+        let moved = |db: &Db, minutes: i64| {
+            let t = db.get(id).unwrap();
+            TaskChanges { due_at: t.due_at + chrono::Duration::minutes(minutes), ..changes(t, &t.title) }
+        };
+
+        let steps: [(&str, i64, i64); 6] = [
+            ("a day later", 24 * 60, 1),
+            ("a minute later", 1, 2),
+            ("unchanged", 0, 2),
+            ("earlier", -3 * 60, 2),
+            ("back later again", 60, 3),
+            ("other fields only", 0, 3),
+        ];
+        for (label, minutes, expected) in steps {
+            let mut c = moved(&db, minutes);
+            c.title = format!("edited: {label}");
+            run(&mut db, update(id, c));
+            assert_eq!(db.get(id).unwrap().postpone_count, expected, "{label}");
+        }
+
+        run(&mut db, Command::ToggleTask(id, None, WipCheck::Setting, oneshot::channel().0));
+        assert_eq!(db.get(id).unwrap().postpone_count, 3, "toggle");
+
+        let created = test_task("new", clock::local("2024-06-03 12:00"));
+        run(&mut db, Command::CreateTask(created.clone(), DedupeCheck::Never, oneshot::channel().0));
+        assert_eq!(db.get(created.id).unwrap().postpone_count, 0, "create");
+    }
+
+    // Run a guarded command against `db` and return its answer.
+    fn guarded(db: &mut Db, cmd: impl FnOnce(Reply<Guarded<Task>>) -> Command) -> Guarded<Task> {
+        let (reply, mut rx) = oneshot::channel();
+        execute(db, cmd(reply)).answer(None);
+        rx.try_recv().unwrap().unwrap()
+    }
+
+    fn with_status(task: &Task, status: TaskStatus) -> TaskChanges {
+        TaskChanges { status, ..changes(task, &task.title) }
+    }
+
+    #[test]
+    fn wip_limit_refuses_the_move_past_the_limit() {
+        let mut db = db_with(3);
+        db.settings.wip_limit = Some(2);
+        let ids: Vec<Uuid> = db.tasks.iter().map(|t| t.id).collect();
+
+        // Zero and one in progress: room for another
+        for id in &ids[..2] {
+            let toggled = guarded(&mut db, |r| Command::ToggleTask(*id, None, WipCheck::Setting, r));
+            assert!(matches!(toggled, Guarded::Applied(t) if t.status == TaskStatus::InProgress));
+        }
+
+        // Two in progress: the third is refused, by toggle and by update
+        let third = db.get(ids[2]).unwrap().clone();
+        let toggled = guarded(&mut db, |r| Command::ToggleTask(third.id, None, WipCheck::Setting, r));
+        let Guarded::OverWipLimit(active) = toggled else { panic!("{toggled:?}") };
+        let mut active: Vec<Uuid> = active.iter().map(|t| t.id).collect();
+        active.sort();
+        let mut expected = ids[..2].to_vec();
+        expected.sort();
+        assert_eq!(active, expected);
+        let c = with_status(&third, TaskStatus::InProgress);
+        let updated = guarded(&mut db, |r| Command::UpdateTask(third.id, None, WipCheck::Setting, c, r));
+        assert!(matches!(updated, Guarded::OverWipLimit(ref a) if a.len() == 2));
+        assert_eq!(db.get(third.id).unwrap().status, TaskStatus::Todo);
+        assert_eq!(db.get(third.id).unwrap().rev, third.rev);
+
+        // Editing a task already in progress is not a new start
+        let second = db.get(ids[1]).unwrap().clone();
+        let c = TaskChanges { title: "still going".to_string(), ..changes(&second, "") };
+        assert!(matches!(
+            guarded(&mut db, |r| Command::UpdateTask(second.id, None, WipCheck::Setting, c, r)),
+            Guarded::Applied(_)
+        ));
+    }
+
+    #[test]
+    fn force_overrides_the_wip_limit() {
+        let mut db = db_with(3);
+        db.settings.wip_limit = Some(1);
+        let ids: Vec<Uuid> = db.tasks.iter().map(|t| t.id).collect();
+        assert!(matches!(guarded(&mut db, |r| Command::ToggleTask(ids[0], None, WipCheck::Setting, r)), Guarded::Applied(_)));
+
+        let forced = guarded(&mut db, |r| Command::ToggleTask(ids[1], None, WipCheck::Force, r));
+        assert!(matches!(forced, Guarded::Applied(t) if t.status == TaskStatus::InProgress));
+        let c = with_status(db.get(ids[2]).unwrap(), TaskStatus::InProgress);
+        let forced = guarded(&mut db, |r| Command::UpdateTask(ids[2], None, WipCheck::Force, c, r));
+        assert!(matches!(forced, Guarded::Applied(t) if t.status == TaskStatus::InProgress));
+        assert_eq!(db.tasks.iter().filter(|t| t.status == TaskStatus::InProgress).count(), 3);
+    }
+
+    #[test]
+    fn moving_out_of_in_progress_always_succeeds() {
+        let mut db = db_with(3);
+        for t in &mut db.tasks {
+            t.status = TaskStatus::InProgress;
+        }
+        // Already over a limit lowered after the fact
+        db.settings.wip_limit = Some(1);
+        let ids: Vec<Uuid> = db.tasks.iter().map(|t| t.id).collect();
+
+        let toggled = guarded(&mut db, |r| Command::ToggleTask(ids[0], None, WipCheck::Setting, r));
+        assert!(matches!(toggled, Guarded::Applied(t) if t.status == TaskStatus::Done));
+        for (id, status) in [(ids[1], TaskStatus::Todo), (ids[2], TaskStatus::Done)] {
+            let c = with_status(db.get(id).unwrap(), status.clone());
+            let updated = guarded(&mut db, |r| Command::UpdateTask(id, None, WipCheck::Setting, c, r));
+            assert!(matches!(updated, Guarded::Applied(t) if t.status == status));
+        }
+        assert!(db.tasks.iter().all(|t| t.status != TaskStatus::InProgress));
+    }
+
+    #[test]
+    fn unknown_keys_survive_an_update_and_a_save() {
+        use serde_json::json;
+        let mut doc = json(&db_with(1));
+        doc["from_a_newer_version"] = json!({ "enabled": true });
+        doc["settings"]["theme"] = "dark".into();
+        doc["tasks"][0]["subtasks"] = json!([{ "title": "step", "done": false }]);
+        let mut db = store::decode(&doc.to_string()).unwrap().db;
+        db.reindex();
+        let id = db.tasks[0].id;
+
+        let c = changes(db.get(id).unwrap(), "renamed");
+        execute(&mut db, update(id, c)).answer(None);
+        // Settings from a client never carry the stored unknown keys
+        let settings = DaySettings { day_start: "08:00".to_string(), ..DaySettings::default() };
+        execute(&mut db, Command::PutSettings(settings, oneshot::channel().0)).answer(None);
+
+        // Written the way a save writes it, then loaded again
+        let saved = json(&store::decode(&serde_json::to_string_pretty(&db).unwrap()).unwrap().db);
+        assert_eq!(saved["from_a_newer_version"], json!({ "enabled": true }));
+        assert_eq!((&saved["settings"]["theme"], &saved["settings"]["day_start"]), (&json!("dark"), &json!("08:00")));
+        assert_eq!(saved["tasks"][0]["subtasks"], json!([{ "title": "step", "done": false }]));
+        assert_eq!(saved["tasks"][0]["title"], "renamed");
+    }
+
+    #[test]
+    fn a_trashed_task_loses_its_plan_lock() {
+        let mut db = db_with(1);
+        let id = db.tasks[0].id;
+        let at = clock::local("2024-06-03 10:00");
+        db.get_mut(id).unwrap().locked = Some(crate::models::PlanLock { start: at, end: at + chrono::Duration::minutes(60) });
+
+        let (reply, mut rx) = oneshot::channel();
+        execute(&mut db, Command::DeleteTask(id, None, Removal::Trash, reply)).answer(None);
+        assert!(matches!(rx.try_recv(), Ok(Ok(Guarded::Applied(())))));
+        assert_eq!(db.trash.len(), 1);
+        assert_eq!(db.trash[0].task.locked, None);
+    }
+
+    #[test]
+    fn audit_entries_hold_the_task_or_settings_on_both_sides() {
+        let mut buffers = Buffers::new(Arc::new(db_with(3)));
+        let ids: Vec<Uuid> = buffers.live.tasks.iter().map(|t| t.id).collect();
+        let new = test_task("created", clock::local("2024-06-04 09:00"));
+        let settings = DaySettings { day_start: "08:00".to_string(), ..DaySettings::default() };
+        let commands = vec![
+            Command::CreateTask(new.clone(), DedupeCheck::Never, oneshot::channel().0),
+            update(ids[0], changes(buffers.live.get(ids[0]).unwrap(), "renamed")),
+            Command::ToggleTask(ids[1], None, WipCheck::Setting, oneshot::channel().0),
+            Command::DeleteTask(ids[1], None, Removal::Trash, oneshot::channel().0),
+            Command::DeleteTask(ids[2], None, Removal::Purge, oneshot::channel().0),
+            Command::PutSettings(settings, oneshot::channel().0),
+        ];
+        let mut entries = Vec::new();
+        for cmd in commands {
+            let (op, task_id) = cmd.audit().unwrap();
+            let before = buffers.live.clone();
+            write(&mut buffers, cmd);
+            entries.push(audit_entry(op, task_id, &before, &buffers.live, Some("req-512".to_string())));
+        }
+
+        let field = |v: &Option<serde_json::Value>, key: &str| v.as_ref().map(|v| v[key].clone());
+        let ops: Vec<AuditOp> = entries.iter().map(|e| e.op).collect();
+        assert_eq!(ops, [AuditOp::Create, AuditOp::Update, AuditOp::Toggle, AuditOp::Delete, AuditOp::Purge, AuditOp::Settings]);
+        assert_eq!((entries[0].task_id, &entries[0].before), (Some(new.id), &None));
+        assert_eq!(field(&entries[0].after, "title"), Some("created".into()));
+        assert_eq!(field(&entries[1].before, "title"), Some("task 0".into()));
+        assert_eq!(field(&entries[1].after, "title"), Some("renamed".into()));
+        assert_eq!(field(&entries[2].before, "status"), Some("todo".into()));
+        assert_eq!(field(&entries[2].after, "status"), Some("in_progress".into()));
+        for gone in &entries[3..5] {
+            assert!(gone.before.is_some() && gone.after.is_none(), "{gone:?}");
+        }
+        assert_eq!(entries[5].task_id, None);
+        assert_eq!(field(&entries[5].before, "day_start"), Some(DaySettings::default().day_start.into()));
+        assert_eq!(field(&entries[5].after, "day_start"), Some("08:00".into()));
+
+        // Each entry is one line of JSON and reads back as written
+        for entry in &entries {
+            let line = serde_json::to_string(entry).unwrap();
+            assert!(!line.contains('\n'));
+            let back: AuditEntry = serde_json::from_str(&line).unwrap();
+            assert_eq!(serde_json::to_string(&back).unwrap(), line);
+        }
+    }
+
+    #[test]
+    fn changes_itemizes_a_compound_write() {
+        let before = db_with(3);
+        let mut after = before.clone();
+        let (kept, edited, dropped) = (after.tasks[0].id, after.tasks[1].id, after.tasks[2].id);
+        after.get_mut(edited).unwrap().title = "edited".to_string();
+        after.remove(dropped);
+        let new = test_task("new", clock::local("2024-06-04 09:00"));
+        after.archived.push(new.clone());
+        after.settings.day_start = "07:00".to_string();
+
+        let audited = super::changes(&before, &after);
+        assert_eq!(
+            audited,
+            [
+                (AuditOp::Update, Some(edited)),
+                (AuditOp::Create, Some(new.id)),
+                (AuditOp::Purge, Some(dropped)),
+                (AuditOp::Settings, None),
+            ]
+        );
+        assert!(!audited.iter().any(|(_, id)| *id == Some(kept)));
+        assert!(super::changes(&before, &before.clone()).is_empty());
+
+        // A task archived by the write is still found on either side
+        let entry = audit_entry(AuditOp::Create, Some(new.id), &before, &after, None);
+        assert_eq!(entry.after.unwrap()["title"], "new");
+    }
+
+    fn job(f: impl FnOnce(&mut Db) + Send + 'static) -> Command {
+        Command::Apply(Box::new(move |db| {
+            f(db);
+            (Box::new(()) as AnyValue, true, Vec::new())
+        }), oneshot::channel().0)
+    }
+
+    #[test]
+    fn apply_jobs_are_journaled_as_the_whole_db() {
+        let mut db = db_with(2);
+        let cmd = job(|db| {
+            db.tasks.retain(|t| t.title != "task 0");
+            db.reindex();
+            db.insert(test_task("imported", clock::local("2024-06-04 09:00")));
+        });
+        let audit = cmd.audit();
+        let mut restarted = db.clone();
+        execute(&mut db, cmd).answer(None);
+        let Some(JournalOp::Snapshot { db: saved }) = journal_for(audit, &db) else {
+            panic!("expected a snapshot");
+        };
+        assert_eq!(json(&saved), json(&db));
+
+        // Replayed from its line over the db as it was before the job
+        let line = serde_json::to_string(&JournalOp::Snapshot { db: saved }).unwrap();
+        assert_eq!(store::replay_journal(&mut restarted, vec![serde_json::from_str(&line).unwrap()]), 1);
+        assert_eq!(json(&restarted), json(&db));
+        let imported = db.tasks.iter().find(|t| t.title == "imported").unwrap().id;
+        assert_eq!(restarted.get(imported).map(|t| t.title.as_str()), Some("imported"));
+    }
+
+    #[tokio::test]
+    async fn a_write_journaled_but_never_saved_is_replayed_by_load_db() {
+        spawn_for_tests();
+        let task = test_task("Journaled only 517", clock::local("2031-08-09 12:00"));
+        let id = task.id;
+
+        // Run inside the writer, so no save or journal of another write
+        // lands in between; on a thread of its own, as load_db is async
+        let loaded = apply(move |db| {
+            let mut after = db.clone();
+            after.insert(task);
+            let op = JournalOp::Snapshot { db: Box::new(after) };
+            let loaded = std::thread::spawn(move || {
+                let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+                rt.block_on(async {
+                    // The crash: journaled, then the process died before saving
+                    store::append_journal(&op).await.unwrap();
+                    let mut cut = std::fs::OpenOptions::new().append(true).open(store::journal_path()).unwrap();
+                    std::io::Write::write_all(&mut cut, b"{\"op\":\"create_ta").unwrap();
+                    store::load_db().await
+                })
+            })
+            .join()
+            .unwrap();
+            let loaded = loaded.unwrap();
+            *db = loaded.clone();
+            (loaded, true)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(loaded.get(id).map(|t| t.title.as_str()), Some("Journaled only 517"));
+        assert!(!std::path::Path::new(&store::journal_path()).exists());
+        let saved = store::decode(&std::fs::read_to_string(store::db_path()).unwrap()).unwrap().db;
+        assert!(saved.tasks.iter().any(|t| t.id == id));
+        assert!(snapshot().get(id).is_some());
+    }
+
+    #[test]
+    fn a_put_that_changes_nothing_still_counts_as_an_edit() {
+        let mut db = db_with(1);
+        let id = db.tasks[0].id;
+        let long_ago = clock::local("2024-06-01 09:00");
+        db.get_mut(id).unwrap().updated_at = long_ago;
+        let before = db.tasks[0].clone();
+
+        execute(&mut db, update(id, changes(&before, &before.title))).answer(None);
+        let after = db.get(id).unwrap();
+        assert_eq!(after.rev, before.rev + 1);
+        assert!(after.updated_at > long_ago);
+        let unchanged = |t: &Task| (t.title.clone(), t.due_at, t.duration_min, t.priority, t.tags.clone(), t.notes.clone());
+        assert_eq!(unchanged(after), unchanged(&before));
+        assert_eq!(after.postpone_count, before.postpone_count);
+
+        // Toggling moves it too; a create sets it to created_at
+        let stamped = after.updated_at;
+        execute(&mut db, Command::ToggleTask(id, None, WipCheck::Setting, oneshot::channel().0)).answer(None);
+        assert_eq!(db.get(id).unwrap().rev, before.rev + 2);
+        assert!(db.get(id).unwrap().updated_at >= stamped);
+        let mut new = test_task("new", clock::local("2024-06-04 09:00"));
+        new.updated_at = long_ago;
+        execute(&mut db, Command::CreateTask(new.clone(), DedupeCheck::Never, oneshot::channel().0)).answer(None);
+        let created = db.get(new.id).unwrap();
+        assert_eq!(created.updated_at, created.created_at);
+    }
+}
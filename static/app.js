@@ -10,6 +10,9 @@
  * All scheduling and scoring logic lives in the backend.
 */
 
+// API URLs are relative ("api/...") so the app also works
+// when served under a path prefix such as /scheduler/.
+
 // Shortcut for document.getElementById
 const $ = (id) => document.getElementById(id);
 
@@ -65,13 +68,28 @@ async function apiGet(url) {
   return await r.json();
 }
 
-async function apiSend(url, method, bodyObj) {
+// 422 bodies list every invalid field: {errors: [{field, code, message}]}
+function validationMessages(text) {
+  try {
+    const body = JSON.parse(text);
+    return Array.isArray(body.errors) ? body.errors.map(e => e.message).join("; ") : null;
+  } catch {
+    return null;
+  }
+}
+
+async function apiSend(url, method, bodyObj, extraHeaders) {
   const r = await fetch(url, {
     method,
-    headers: { "Content-Type": "application/json" },
+    headers: { "Content-Type": "application/json", ...extraHeaders },
     body: bodyObj ? JSON.stringify(bodyObj) : undefined,
   });
-  if (!r.ok) throw new Error(await r.text());
+  if (!r.ok) {
+    const text = await r.text();
+    const err = new Error(validationMessages(text) || text);
+    err.status = r.status;
+    throw err;
+  }
   // some endpoints return {ok:true}
   const text = await r.text();
   return text ? JSON.parse(text) : null;
@@ -86,6 +104,13 @@ function fmtRFC3339ToLocal(rfc) {
   }
 }
 
+// Priority badge in the configured label/color (settings.priority_labels)
+function priorityBadge(item) {
+  if (!item.priority_label) return "";
+  const color = escapeHtml(item.priority_color || "");
+  return `<span class="badge" style="border-color:${color}; color:${color}">${escapeHtml(item.priority_label)}</span>`;
+}
+
 // Render task list (left panel)
 function renderTasks(tasks, nowRFC) {
   $("taskCount").textContent = String(tasks.length);
@@ -100,12 +125,18 @@ function renderTasks(tasks, nowRFC) {
   }
 
   for (const t of tasks) {
-    const isOverdue = nowRFC && new Date(nowRFC) > new Date(t.due_at);
-    const badgeOver = isOverdue ? `<span class="badge overdue">overdue</span>` : "";
+    // Same rule as the server: overdue only after the grace window
+    const graceMs = ((currentSettings && currentSettings.overdue_grace_min) || 0) * 60000;
+    const pastDue = nowRFC && new Date(nowRFC) > new Date(t.due_at);
+    const isOverdue = pastDue && new Date(nowRFC) > new Date(new Date(t.due_at).getTime() + graceMs);
+    const badgeOver = isOverdue
+      ? `<span class="badge overdue">overdue</span>`
+      : pastDue ? `<span class="badge">due now</span>` : "";
     const badgeStatus = `<span class="badge">${t.status}</span>`;
 
     const div = document.createElement("div");
     div.className = "item";
+    div.id = `task-${t.id}`;
     div.innerHTML = `
       <div class="left">
         <div class="title">${escapeHtml(t.title)}</div>
@@ -114,6 +145,7 @@ function renderTasks(tasks, nowRFC) {
         </div>
         <div class="row" style="gap:8px; align-items:center;">
           ${badgeStatus}
+          ${priorityBadge(t)}
           ${badgeOver}
           ${t.tags && t.tags.length ? `<span class="badge">${escapeHtml(t.tags.join(", "))}</span>` : ""}
         </div>
@@ -125,11 +157,21 @@ function renderTasks(tasks, nowRFC) {
     `;
 
     div.querySelector('[data-act="toggle"]').onclick = async () => {
-      await apiSend(`/api/tasks/${t.id}/toggle`, "POST");
+      const ifMatch = { "If-Match": `"${t.rev || 0}"` };
+      try {
+        await apiSend(`api/tasks/${t.id}/toggle`, "POST", null, ifMatch);
+      } catch (err) {
+        // 409: wip_limit reached; list what is in progress and offer to start anyway
+        if (err.status !== 409) throw err;
+        const busy = (JSON.parse(err.message).in_progress || []).map((x) => `- ${x.title}`).join("\n");
+        if (confirm(`Already in progress:\n${busy}\n\nStart this task anyway?`)) {
+          await apiSend(`api/tasks/${t.id}/toggle?force=true`, "POST", null, ifMatch);
+        }
+      }
       await refreshAll();
     };
     div.querySelector('[data-act="del"]').onclick = async () => {
-      await apiSend(`/api/tasks/${t.id}`, "DELETE");
+      await apiSend(`api/tasks/${t.id}`, "DELETE", null, { "If-Match": `"${t.rev || 0}"` });
       await refreshAll();
     };
 
@@ -140,6 +182,14 @@ function renderTasks(tasks, nowRFC) {
 // Render today's plan (right panel)
 function renderPlan(resp) {
   $("planNowText").textContent = resp.now ? fmtRFC3339ToLocal(resp.now) : "-";
+  $("planFinishText").textContent = resp.finish_at
+    ? `${fmtRFC3339ToLocal(resp.finish_at)} (${resp.slack_min} min slack, ${resp.slack_after_unplanned_min} incl. unplanned)`
+    : "-";
+
+  const warnWrap = $("planWarnings");
+  warnWrap.innerHTML = (resp.warnings || [])
+    .map((w) => `<div class="msg err">${escapeHtml(w.message)}</div>`)
+    .join("");
 
   const planWrap = $("planList");
   const unWrap = $("unplannedList");
@@ -152,7 +202,9 @@ function renderPlan(resp) {
     for (const p of resp.plan) {
       const div = document.createElement("div");
       div.className = "item";
-      const badgeOver = p.is_overdue ? `<span class="badge overdue">overdue</span>` : "";
+      const badgeOver = p.is_overdue
+        ? `<span class="badge overdue">overdue</span>`
+        : p.is_due_now ? `<span class="badge">due now</span>` : "";
       div.innerHTML = `
         <div class="left">
           <div class="title">${escapeHtml(p.title)}</div>
@@ -162,7 +214,9 @@ function renderPlan(resp) {
             <span class="badge">u:${p.score_breakdown.urgency}</span>
             <span class="badge">p:${p.score_breakdown.priority}</span>
             <span class="badge">d:${p.score_breakdown.duration_score}</span>
+            ${priorityBadge(p)}
             ${badgeOver}
+            ${p.placed_by === "deadline_pass" ? `<span class="badge">due today</span>` : ""}
           </div>
         </div>
       `;
@@ -176,24 +230,49 @@ function renderPlan(resp) {
     for (const u of resp.unplanned) {
       const div = document.createElement("div");
       div.className = "item";
+      const short = u.minutes_short ? ` · ${u.minutes_short}m short` : "";
       div.innerHTML = `
         <div class="left">
-          <div class="title">${escapeHtml(u.task_id)}</div>
-          <div class="small">reason: ${escapeHtml(u.reason)}</div>
+          <div class="title">${escapeHtml(u.title || u.task_id)}</div>
+          <div class="small">${escapeHtml(u.message || u.reason)} · ${u.duration_min}m${short}</div>
+          ${u.score_breakdown ? `<div class="row" style="gap:8px; align-items:center;">
+            <span class="badge">score ${u.score_breakdown.total}</span>
+            <span class="badge">u:${u.score_breakdown.urgency}</span>
+            <span class="badge">p:${u.score_breakdown.priority}</span>
+            <span class="badge">d:${u.score_breakdown.duration_score}</span>
+          </div>` : ""}
         </div>
       `;
       unWrap.appendChild(div);
     }
+    const more = (resp.unplanned_total || 0) - resp.unplanned.length;
+    if (more > 0) {
+      const div = document.createElement("div");
+      div.className = "small";
+      div.textContent = `…and ${more} more`;
+      unWrap.appendChild(div);
+    }
   }
 }
 
+// Last settings loaded from backend.
+// Fields without a form input are sent back unchanged on save.
+let currentSettings = {};
+
 // Load day-level settings from backend
 async function loadSettings() {
-  const s = await apiGet("/api/settings");
+  const s = await apiGet("api/settings");
+  currentSettings = s;
   // s: {day_start, day_end, focus_block_min}
   $("dayStartInput").value = s.day_start;
   $("dayEndInput").value = s.day_end;
   $("focusBlockInput").value = String(s.focus_block_min);
+  $("urgencyModeInput").value = s.urgency_mode || "rolling";
+  $("scorerInput").value = s.scorer || "default";
+  $("sortLocaleInput").value = s.sort_locale || "und";
+  $("timeFormatInput").value = s.time_format || "24h";
+  $("weekStartInput").value = s.week_start || "Mon";
+  $("wipLimitInput").value = s.wip_limit == null ? "" : String(s.wip_limit);
 }
 
 // Save updated settings to backend
@@ -203,12 +282,20 @@ async function saveSettings(e) {
   try {
     setMsg(msg, "Saving...", null);
     const body = {
+      ...currentSettings,
       day_start: hhmmFromTimeInput($("dayStartInput").value),
       day_end: hhmmFromTimeInput($("dayEndInput").value),
       focus_block_min: Number($("focusBlockInput").value),
+      urgency_mode: $("urgencyModeInput").value,
+      scorer: $("scorerInput").value,
+      sort_locale: $("sortLocaleInput").value.trim() || "und",
+      time_format: $("timeFormatInput").value,
+      week_start: $("weekStartInput").value,
+      wip_limit: $("wipLimitInput").value ? Number($("wipLimitInput").value) : null,
     };
-    await apiSend("/api/settings", "PUT", body);
-    setMsg(msg, "Saved.", "ok");
+    const { warnings, ...saved } = await apiSend("api/settings", "PUT", body);
+    currentSettings = saved;
+    setMsg(msg, warnings && warnings.length ? `Saved. ${warnings.join(" ")}` : "Saved.", "ok");
   } catch (err) {
     setMsg(msg, String(err.message || err), "err");
   }
@@ -217,7 +304,7 @@ async function saveSettings(e) {
 // Fetch tasks for selected date
 async function refreshTasks() {
   const date = $("dateInput").value;
-  const resp = await apiGet(`/api/tasks?date=${encodeURIComponent(date)}`);
+  const resp = await apiGet(`api/tasks?date=${encodeURIComponent(date)}`);
   renderTasks(resp.tasks || [], resp.now);
 }
 
@@ -225,7 +312,7 @@ async function refreshTasks() {
 async function generatePlan() {
   const date = $("dateInput").value;
   const available = Number($("availInput").value);
-  const resp = await apiGet(`/api/plan/today?date=${encodeURIComponent(date)}&available_min=${available}`);
+  const resp = await apiGet(`api/plan/today?date=${encodeURIComponent(date)}&available_min=${available}`);
   renderPlan(resp);
 }
 
@@ -265,8 +352,21 @@ async function createTask(e) {
       notes: $("notesInput").value.trim() || null,
     };
 
-    await apiSend("/api/tasks", "POST", body);
-    setMsg(msg, "Added.", "ok");
+    let saved;
+    try {
+      saved = await apiSend("api/tasks", "POST", body);
+    } catch (err) {
+      // 409: an open task with the same title is due that day
+      if (err.status !== 409) throw err;
+      const existing = JSON.parse(err.message);
+      if (!confirm(`"${existing.title}" is already due that day. Add anyway?`)) {
+        setMsg(msg, "Not added (duplicate).", null);
+        return;
+      }
+      saved = await apiSend("api/tasks", "POST", { ...body, force: true });
+    }
+    const warnings = saved && saved.warnings;
+    setMsg(msg, warnings && warnings.length ? `Added. ${warnings.join(" ")}` : "Added.", "ok");
 
     // reset some fields
     $("titleInput").value = "";
@@ -279,6 +379,24 @@ async function createTask(e) {
   }
 }
 
+// Deep link "#/task/<id>": show the task's due date and highlight it
+async function openTaskLink() {
+  const m = location.hash.match(/^#\/task\/([0-9a-f-]{36})$/i);
+  if (!m) return;
+  try {
+    const t = await apiGet(`api/tasks/${m[1]}`);
+    $("dateInput").value = toDateInputValue(new Date(t.due_at));
+    await refreshAll();
+    const el = $(`task-${t.id}`);
+    if (el) {
+      el.classList.add("linked");
+      el.scrollIntoView({ block: "center" });
+    }
+  } catch {
+    // unknown or archived task: keep the default view
+  }
+}
+
 function initDefaults() {
   const today = new Date();
   $("dateInput").value = toDateInputValue(today);
@@ -305,4 +423,6 @@ window.addEventListener("DOMContentLoaded", async () => {
 
   await loadSettings();
   await refreshAll();
+  await openTaskLink();
+  window.addEventListener("hashchange", openTaskLink);
 });